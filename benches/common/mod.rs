@@ -0,0 +1,61 @@
+//! Fixtures shared by the benchmarks. Each bench uses a different subset.
+#![allow(dead_code)]
+
+use nebula_tools::player::{NblHeader, Particle, PlayerState};
+use std::path::PathBuf;
+
+/// `count` particles scattered through a 20-block cube with a fixed seed,
+/// so every run measures the same data.
+pub fn scattered_particles(count: usize) -> Vec<Particle> {
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let mut next = move || {
+        // xorshift64
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state >> 40) as f32 / (1u64 << 24) as f32
+    };
+    (0..count)
+        .map(|i| Particle {
+            id: i as i32,
+            pos: [next() * 20.0, next() * 20.0, next() * 20.0],
+            color: [255, 200, 80, 255],
+            size: 0.2,
+            tex_id: 0,
+            seq_index: 0,
+        })
+        .collect()
+}
+
+/// `frames` frames of `count` particles drifting upwards.
+pub fn drifting_frames(frames: usize, count: usize) -> Vec<Vec<Particle>> {
+    let base = scattered_particles(count);
+    (0..frames)
+        .map(|f| {
+            base.iter()
+                .map(|p| Particle {
+                    pos: [p.pos[0], p.pos[1] + f as f32 * 0.05, p.pos[2]],
+                    ..p.clone()
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Writes `frames` to `name` in the temp directory as a keyframe-only NBL.
+pub fn write_fixture(name: &str, frames: &[Vec<Particle>]) -> PathBuf {
+    let path = std::env::temp_dir().join(name);
+    let header = NblHeader {
+        version: 1,
+        target_fps: 60,
+        total_frames: frames.len() as u32,
+        texture_count: 0,
+        attributes: 0x03,
+        bbox_min: [0.0; 3],
+        bbox_max: [20.0; 3],
+    };
+    PlayerState::default()
+        .save_file(&path, &header, &[], frames)
+        .expect("write benchmark fixture");
+    path
+}
@@ -0,0 +1,54 @@
+//! Deflating 100,000 particles with the spatial hash against the brute-force
+//! O(n²) neighbour search it replaced, plus `recalculate_bbox` over a
+//! 30-frame, 3-million-particle animation.
+mod common;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use nebula_tools::player::{self, DeflateState, Particle};
+
+const PARTICLES: usize = 100_000;
+const RADIUS: f32 = 0.2;
+
+/// The deflate pass without the spatial hash: every candidate is checked
+/// against every particle kept so far.
+fn deflate_brute_force(particles: &[Particle], radius: f32) -> Vec<Particle> {
+    let radius_sq = radius * radius;
+    let mut kept: Vec<Particle> = Vec::new();
+    for p in particles {
+        let crowded = kept.iter().any(|q| {
+            let dx = q.pos[0] - p.pos[0];
+            let dy = q.pos[1] - p.pos[1];
+            let dz = q.pos[2] - p.pos[2];
+            dx * dx + dy * dy + dz * dz < radius_sq
+        });
+        if !crowded {
+            kept.push(p.clone());
+        }
+    }
+    kept
+}
+
+fn deflate(c: &mut Criterion) {
+    let particles = common::scattered_particles(PARTICLES);
+    let mut group = c.benchmark_group("deflate_100k");
+    group.sample_size(10);
+    group.bench_function("spatial_hash", |b| {
+        b.iter(|| {
+            player::edit_deflate_particles(particles.clone(), RADIUS, &mut DeflateState::default())
+        })
+    });
+    group.bench_function("brute_force", |b| {
+        b.iter(|| deflate_brute_force(&particles, RADIUS))
+    });
+    group.finish();
+}
+
+fn bbox(c: &mut Criterion) {
+    let frames = common::drifting_frames(30, PARTICLES);
+    c.bench_function("recalculate_bbox_30x100k", |b| {
+        b.iter(|| player::recalculate_bbox(&frames))
+    });
+}
+
+criterion_group!(benches, deflate, bbox);
+criterion_main!(benches);
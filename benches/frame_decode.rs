@@ -0,0 +1,62 @@
+//! Sequential decode of a 100-frame file with Zstd and with LZ4 frame blocks.
+mod common;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use nebula_tools::player::{self, CompressAlgorithm, CompressProgress, EditAction, PlayerState};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+const FRAMES: u32 = 100;
+const PARTICLES: usize = 10_000;
+
+/// Re-encodes `source` the way the compress export does.
+fn compress(source: &Path, name: &str, algorithm: CompressAlgorithm) -> PathBuf {
+    let output = std::env::temp_dir().join(name);
+    let progress = Arc::new(Mutex::new(CompressProgress {
+        total_frames: 0,
+        current_frame: 0,
+        is_done: false,
+        error: None,
+        start_time: std::time::Instant::now(),
+        removed_particles: 0,
+    }));
+    player::streaming_edit(
+        source.to_path_buf(),
+        output.clone(),
+        EditAction::Compress(30, 0.0),
+        algorithm,
+        progress,
+    )
+    .expect("compress benchmark fixture");
+    output
+}
+
+fn decode(c: &mut Criterion) {
+    let frames = common::drifting_frames(FRAMES as usize, PARTICLES);
+    let source = common::write_fixture("nebula_bench_decode_src.nbl", &frames);
+    let mut group = c.benchmark_group("decode_100_frames");
+    for (label, algorithm) in [
+        ("zstd", CompressAlgorithm::Zstd(3)),
+        ("lz4", CompressAlgorithm::Lz4),
+    ] {
+        let path = compress(
+            &source,
+            &format!("nebula_bench_decode_{}.nbl", label),
+            algorithm,
+        );
+        let mut player = PlayerState::default();
+        player.prefetch_threads = 0;
+        player.load_file_mmap(&path).unwrap();
+        group.bench_function(label, |b| {
+            b.iter(|| {
+                for f in 0..FRAMES {
+                    player.seek_to(f).unwrap();
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, decode);
+criterion_main!(benches);
@@ -0,0 +1,39 @@
+//! Random-access seek latency with the file memory-mapped and read through
+//! `File`.
+mod common;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use nebula_tools::player::PlayerState;
+
+const FRAMES: usize = 100;
+const PARTICLES: usize = 10_000;
+
+fn seek(c: &mut Criterion) {
+    let frames = common::drifting_frames(FRAMES, PARTICLES);
+    let path = common::write_fixture("nebula_bench_seek.nbl", &frames);
+    // Fixed jump order so both loaders decode the same frames.
+    let order: Vec<u32> = (0..FRAMES as u32)
+        .map(|i| (i * 37) % FRAMES as u32)
+        .collect();
+    let mut group = c.benchmark_group("seek_random_100_frames");
+    for (label, mapped) in [("mmap", true), ("file", false)] {
+        let mut player = PlayerState::default();
+        player.prefetch_threads = 0;
+        if mapped {
+            player.load_file_mmap(&path).unwrap();
+        } else {
+            player.load_file(path.clone()).unwrap();
+        }
+        group.bench_function(label, |b| {
+            b.iter(|| {
+                for &f in &order {
+                    player.seek_to(f).unwrap();
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, seek);
+criterion_main!(benches);
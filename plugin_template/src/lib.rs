@@ -0,0 +1,95 @@
+//! Example NebulaTools plugin. Build with `cargo build --release`, then copy
+//! the library from `target/release/` (`.so`, `.dll` or `.dylib`) into the
+//! `plugins/` folder next to the NebulaTools executable. The functions become
+//! callable in velocity and colour expressions, e.g.
+//! `vy = smoothstep(0, 1, t) * 0.01`.
+//!
+//! Only C-compatible types cross into NebulaTools, so the plugin does not
+//! need to be built with the same Rust toolchain.
+
+use std::ffi::c_char;
+
+/// Layout version of [`PluginApi`]. NebulaTools refuses any other value.
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+
+/// Called with a pointer to `len` arguments.
+pub type PluginFn = extern "C" fn(args: *const f64, len: usize) -> f64;
+
+/// C layout shared with NebulaTools' `src/plugins.rs` (ABI version 1).
+#[repr(C)]
+pub struct PluginFunction {
+    pub name: *const c_char,
+    pub func: PluginFn,
+}
+
+/// C layout shared with NebulaTools' `src/plugins.rs` (ABI version 1).
+#[repr(C)]
+pub struct PluginApi {
+    pub abi_version: u32,
+    pub name: *const c_char,
+    pub functions: *const PluginFunction,
+    pub function_count: usize,
+}
+
+// SAFETY: the tables below only point at immutable `'static` data.
+unsafe impl Sync for PluginFunction {}
+unsafe impl Sync for PluginApi {}
+
+/// The arguments NebulaTools passed in.
+fn args<'a>(ptr: *const f64, len: usize) -> &'a [f64] {
+    if ptr.is_null() || len == 0 {
+        return &[];
+    }
+    // SAFETY: NebulaTools passes a pointer to `len` initialised values that
+    // outlive the call.
+    unsafe { std::slice::from_raw_parts(ptr, len) }
+}
+
+fn arg(args: &[f64], i: usize) -> f64 {
+    args.get(i).copied().unwrap_or(0.0)
+}
+
+/// `smoothstep(edge0, edge1, x)`
+extern "C" fn smoothstep(ptr: *const f64, len: usize) -> f64 {
+    let args = args(ptr, len);
+    let (e0, e1, x) = (arg(args, 0), arg(args, 1), arg(args, 2));
+    if e1 == e0 {
+        return if x < e0 { 0.0 } else { 1.0 };
+    }
+    let t = ((x - e0) / (e1 - e0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// `remap(x, in_min, in_max, out_min, out_max)`
+extern "C" fn remap(ptr: *const f64, len: usize) -> f64 {
+    let args = args(ptr, len);
+    let (x, in_min, in_max) = (arg(args, 0), arg(args, 1), arg(args, 2));
+    let (out_min, out_max) = (arg(args, 3), arg(args, 4));
+    if in_max == in_min {
+        return out_min;
+    }
+    out_min + (x - in_min) / (in_max - in_min) * (out_max - out_min)
+}
+
+static FUNCTIONS: [PluginFunction; 2] = [
+    PluginFunction {
+        name: c"smoothstep".as_ptr(),
+        func: smoothstep,
+    },
+    PluginFunction {
+        name: c"remap".as_ptr(),
+        func: remap,
+    },
+];
+
+static API: PluginApi = PluginApi {
+    abi_version: PLUGIN_ABI_VERSION,
+    name: c"Template Plugin".as_ptr(),
+    functions: FUNCTIONS.as_ptr(),
+    function_count: FUNCTIONS.len(),
+};
+
+#[no_mangle]
+pub extern "C" fn nebula_plugin_api() -> *const PluginApi {
+    &API
+}
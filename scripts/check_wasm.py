@@ -0,0 +1,24 @@
+import shutil
+import subprocess
+import sys
+
+# Checks that the library (expression engine + NBL codec) still builds for
+# the browser with the `wasm` feature. Needs the target
+# (`rustup target add wasm32-unknown-unknown`) and clang, which zstd-sys
+# uses to compile zstd for wasm32.
+TARGET = "wasm32-unknown-unknown"
+CMD = ["cargo", "check", "--target", TARGET, "--features", "wasm", "--lib"]
+
+
+if __name__ == "__main__":
+    if shutil.which("clang") is None:
+        print("Error: clang is required to build zstd-sys for wasm32.")
+        sys.exit(1)
+
+    # Assuming script is run from project root
+    print(f"Running: {' '.join(CMD)}")
+    result = subprocess.run(CMD)
+    if result.returncode != 0:
+        print(f"❌ wasm check failed (exit code {result.returncode})")
+        sys.exit(result.returncode)
+    print("✅ wasm check passed.")
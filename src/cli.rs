@@ -0,0 +1,262 @@
+use crate::particleex::{self, CompileEntry, ExprContext, Value};
+use crate::player::{self, NblHeader, PlayerState, NBL_VERSION};
+use crate::ui::app::{build_texture_entries, NebulaToolsApp};
+use anyhow::{anyhow, bail, Context, Result};
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{BufRead, Write as _};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+const USAGE: &str = "usage: nebula_tools --compile <commands.txt> --output <out.nbl> [--subdivisions <n>] [--metrics-output <metrics.txt>]
+       nebula_tools --repl";
+
+/// Headless batch mode: compile a particleex command file straight to NBL.
+pub struct CompileArgs {
+    pub input: PathBuf,
+    pub output: PathBuf,
+    /// Prometheus text-format metrics, written after a successful compile.
+    pub metrics_output: Option<PathBuf>,
+    /// Interpolated frames per 60 fps step; see `particleex::compile_entries`.
+    pub subdivisions: u32,
+}
+
+impl CompileArgs {
+    /// `None` when `--compile` is absent and the GUI should start instead.
+    pub fn parse(args: &[String]) -> Result<Option<Self>> {
+        if !args.iter().any(|a| a == "--compile") {
+            return Ok(None);
+        }
+        let mut input = None;
+        let mut output = None;
+        let mut metrics_output = None;
+        let mut subdivisions = 1;
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if arg == "--subdivisions" {
+                subdivisions = iter
+                    .next()
+                    .and_then(|v| v.parse().ok())
+                    .filter(|n| (1..=particleex::MAX_SUBDIVISIONS).contains(n))
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "`--subdivisions` needs a number from 1 to {}\n{}",
+                            particleex::MAX_SUBDIVISIONS,
+                            USAGE
+                        )
+                    })?;
+                continue;
+            }
+            let slot = match arg.as_str() {
+                "--compile" => &mut input,
+                "--output" => &mut output,
+                "--metrics-output" => &mut metrics_output,
+                other => bail!("unknown argument `{}`\n{}", other, USAGE),
+            };
+            let value = iter
+                .next()
+                .ok_or_else(|| anyhow!("`{}` needs a path\n{}", arg, USAGE))?;
+            *slot = Some(PathBuf::from(value));
+        }
+        Ok(Some(Self {
+            input: input.ok_or_else(|| anyhow!(USAGE))?,
+            output: output.ok_or_else(|| anyhow!(USAGE))?,
+            metrics_output,
+            subdivisions,
+        }))
+    }
+}
+
+pub fn run_compile(args: &CompileArgs) -> Result<()> {
+    let started = Instant::now();
+    let commands = fs::read_to_string(&args.input)
+        .with_context(|| format!("reading {}", args.input.display()))?;
+    let entries = [CompileEntry {
+        command: commands,
+        start_tick: 0.0,
+        position: [0.0; 3],
+        duration_override: 0.0,
+        textures: vec![],
+        texture_interval: 20,
+        time_scale_override: None,
+    }];
+    let (frames, fps, raw_textures) =
+        particleex::compile_entries(&entries, args.subdivisions).map_err(|e| anyhow!(e))?;
+
+    let textures = build_texture_entries(&raw_textures);
+    let (bbox_min, bbox_max) = player::recalculate_bbox(&frames);
+    let header = NblHeader {
+        version: NBL_VERSION,
+        target_fps: fps,
+        total_frames: frames.len() as u32,
+        texture_count: textures.len() as u16,
+        attributes: 0x03,
+        bbox_min,
+        bbox_max,
+    };
+    PlayerState::default().save_file(&args.output, &header, &textures, &frames)?;
+    let duration = started.elapsed().as_secs_f64();
+
+    if let Some(path) = &args.metrics_output {
+        let total_particles: usize = frames.iter().map(Vec::len).sum();
+        let text = metrics_text(duration, total_particles, frames.len(), peak_memory_bytes());
+        fs::write(path, text).with_context(|| format!("writing {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// `--repl` starts `run_repl` instead of the GUI or a compile.
+pub fn is_repl(args: &[String]) -> bool {
+    args.iter().any(|a| a == "--repl")
+}
+
+const REPL_HELP: &str = "\
+Enter expressions; a line ending in `;` continues on the next line.
+  .vars         print every variable
+  .reset        start again from a fresh context
+  .load <file>  run a .pex expression file
+Ctrl-D exits.";
+
+/// Interactive expression evaluator on stdin/stdout. Variables persist
+/// between inputs until `.reset`.
+pub fn run_repl() -> Result<()> {
+    println!("{}", REPL_HELP);
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let new_context = || {
+        let mut ctx = ExprContext::new();
+        ctx.log = Some(log.clone());
+        ctx
+    };
+    let mut ctx = new_context();
+    let mut buffer = String::new();
+    let stdin = std::io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("{}", if buffer.is_empty() { ">> " } else { ".. " });
+        std::io::stdout().flush()?;
+        let Some(line) = lines.next() else {
+            println!();
+            return Ok(());
+        };
+        let line = line?;
+        let trimmed = line.trim();
+
+        if buffer.is_empty() && trimmed.starts_with('.') {
+            let (command, arg) = trimmed.split_once(' ').unwrap_or((trimmed, ""));
+            match command {
+                ".vars" => {
+                    let mut vars: Vec<_> = ctx.vars.iter().collect();
+                    vars.sort_by(|a, b| a.0.cmp(b.0));
+                    for (name, value) in vars {
+                        println!("{} = {}", name, format_value(value));
+                    }
+                }
+                ".reset" => ctx = new_context(),
+                ".load" => match load_recipe(Path::new(arg.trim())) {
+                    Ok(src) => eval_and_print(&src, &mut ctx),
+                    Err(e) => eprintln!("error: {:#}", e),
+                },
+                _ => println!("{}", REPL_HELP),
+            }
+            continue;
+        }
+
+        buffer.push_str(&line);
+        buffer.push('\n');
+        if trimmed.ends_with(';') {
+            continue;
+        }
+        eval_and_print(&buffer, &mut ctx);
+        buffer.clear();
+    }
+}
+
+fn load_recipe(path: &Path) -> Result<String> {
+    let recipe = NebulaToolsApp::read_expression_recipe(path)
+        .with_context(|| format!("reading {}", path.display()))?;
+    Ok(particleex::expand_macros(
+        &recipe.velocity_expr,
+        &recipe.macros,
+    ))
+}
+
+/// Runs `src` in `ctx` and prints its last value after anything it `print`ed.
+fn eval_and_print(src: &str, ctx: &mut ExprContext) {
+    if let Err(e) = particleex::check_expr(src) {
+        eprintln!("error: {}", e);
+        return;
+    }
+    let Some(stmts) = particleex::compile_expr(src) else {
+        return;
+    };
+    let value = particleex::exec_stmts(&stmts, ctx);
+    if let Some(log) = &ctx.log {
+        for event in log.lock().unwrap().drain(..) {
+            println!("{}", event);
+        }
+    }
+    println!("{}", format_value(&value));
+}
+
+fn format_value(value: &Value) -> String {
+    match value {
+        Value::Num(n) => n.to_string(),
+        Value::Matrix(rows) => {
+            let rows: Vec<String> = rows
+                .iter()
+                .map(|row| {
+                    let cells: Vec<String> = row.iter().map(f64::to_string).collect();
+                    format!("[{}]", cells.join(", "))
+                })
+                .collect();
+            format!("[{}]", rows.join(", "))
+        }
+    }
+}
+
+fn metrics_text(
+    duration_secs: f64,
+    total_particles: usize,
+    total_frames: usize,
+    peak_memory: Option<u64>,
+) -> String {
+    let mut out = String::new();
+    let mut gauge = |name: &str, help: &str, value: String| {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} gauge", name);
+        let _ = writeln!(out, "{} {}", name, value);
+    };
+    gauge(
+        "nebula_compile_duration_seconds",
+        "Wall-clock time of the compile, including writing the NBL.",
+        format!("{:.6}", duration_secs),
+    );
+    gauge(
+        "nebula_total_particles",
+        "Particle count summed over all frames.",
+        total_particles.to_string(),
+    );
+    gauge(
+        "nebula_total_frames",
+        "Number of frames written.",
+        total_frames.to_string(),
+    );
+    if let Some(bytes) = peak_memory {
+        gauge(
+            "nebula_peak_memory_bytes",
+            "Peak resident set size of the process.",
+            bytes.to_string(),
+        );
+    }
+    out
+}
+
+/// Peak resident set size from `/proc/self/status`; `None` where that is
+/// unavailable, in which case the metric is left out.
+fn peak_memory_bytes() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmHWM:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
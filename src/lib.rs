@@ -0,0 +1,13 @@
+//! NBL reading and writing, the particleex expression engine and plugin
+//! loading, without the GUI. The `nebula_tools` binary is built on top of
+//! this, and the benchmarks in `benches/` link against it. With the `wasm`
+//! feature the expression engine is also exported to JavaScript.
+pub mod math;
+pub mod particleex;
+pub mod player;
+pub mod plugins;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+#[cfg(all(target_arch = "wasm32", not(feature = "wasm")))]
+compile_error!("building for wasm32 needs the `wasm` feature");
@@ -1,13 +1,38 @@
+mod cli;
 mod i18n;
-mod math;
-mod particleex;
-mod player;
 mod renderer;
 mod ui;
 
+use nebula_tools::{math, particleex, player, plugins};
 use ui::app::NebulaToolsApp;
 
 fn main() -> eframe::Result<()> {
+    if let Some(dir) = plugins::plugin_dir() {
+        plugins::load_plugins(&dir);
+    }
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if cli::is_repl(&args) {
+        if let Err(e) = cli::run_repl() {
+            eprintln!("error: {:#}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    match cli::CompileArgs::parse(&args) {
+        Ok(Some(compile)) => {
+            if let Err(e) = cli::run_compile(&compile) {
+                eprintln!("error: {:#}", e);
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Ok(None) => {}
+        Err(e) => {
+            eprintln!("error: {:#}", e);
+            std::process::exit(2);
+        }
+    }
+
     let version = env!("CARGO_PKG_VERSION");
     let options = eframe::NativeOptions {
         viewport: eframe::egui::ViewportBuilder::default()
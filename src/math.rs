@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 pub fn look_at(eye: [f32; 3], center: [f32; 3], up: [f32; 3]) -> [f32; 16] {
     let f = normalize([center[0] - eye[0], center[1] - eye[1], center[2] - eye[2]]);
     let s = normalize(cross(f, up));
@@ -44,6 +46,34 @@ pub fn perspective(fov: f32, aspect: f32, near: f32, far: f32) -> [f32; 16] {
     ]
 }
 
+pub fn orthographic(
+    left: f32,
+    right: f32,
+    bottom: f32,
+    top: f32,
+    near: f32,
+    far: f32,
+) -> [f32; 16] {
+    [
+        2.0 / (right - left),
+        0.0,
+        0.0,
+        0.0,
+        0.0,
+        2.0 / (top - bottom),
+        0.0,
+        0.0,
+        0.0,
+        0.0,
+        -2.0 / (far - near),
+        0.0,
+        -(right + left) / (right - left),
+        -(top + bottom) / (top - bottom),
+        -(far + near) / (far - near),
+        1.0,
+    ]
+}
+
 pub fn multiply_matrices(a: [f32; 16], b: [f32; 16]) -> [f32; 16] {
     let mut out = [0.0; 16];
     for r in 0..4 {
@@ -56,6 +86,19 @@ pub fn multiply_matrices(a: [f32; 16], b: [f32; 16]) -> [f32; 16] {
     out
 }
 
+/// Transform a world-space point by `mvp` and return normalized device
+/// coordinates, or `None` if the point is behind the camera.
+pub fn project_to_ndc(mvp: [f32; 16], p: [f32; 3]) -> Option<[f32; 3]> {
+    let x = mvp[0] * p[0] + mvp[4] * p[1] + mvp[8] * p[2] + mvp[12];
+    let y = mvp[1] * p[0] + mvp[5] * p[1] + mvp[9] * p[2] + mvp[13];
+    let z = mvp[2] * p[0] + mvp[6] * p[1] + mvp[10] * p[2] + mvp[14];
+    let w = mvp[3] * p[0] + mvp[7] * p[1] + mvp[11] * p[2] + mvp[15];
+    if w <= 0.0 {
+        return None;
+    }
+    Some([x / w, y / w, z / w])
+}
+
 pub fn normalize(v: [f32; 3]) -> [f32; 3] {
     let len = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
     if len > 0.0 {
@@ -76,3 +119,74 @@ pub fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
 pub fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
     a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
 }
+
+/// Point at `t` in `[0, 1]` on a uniform Catmull-Rom spline through all of
+/// `points`, with the end points repeated so the curve starts and ends on them.
+pub fn catmull_rom(points: &[[f32; 3]], t: f32) -> [f32; 3] {
+    match points.len() {
+        0 => return [0.0; 3],
+        1 => return points[0],
+        _ => {}
+    }
+    let segments = points.len() - 1;
+    let scaled = t.clamp(0.0, 1.0) * segments as f32;
+    let i = (scaled as usize).min(segments - 1);
+    let u = scaled - i as f32;
+    let p0 = points[i.saturating_sub(1)];
+    let p1 = points[i];
+    let p2 = points[i + 1];
+    let p3 = points[(i + 2).min(segments)];
+    let (u2, u3) = (u * u, u * u * u);
+    std::array::from_fn(|k| {
+        0.5 * (2.0 * p1[k]
+            + (p2[k] - p0[k]) * u
+            + (2.0 * p0[k] - 5.0 * p1[k] + 4.0 * p2[k] - p3[k]) * u2
+            + (3.0 * p1[k] - p0[k] - 3.0 * p2[k] + p3[k]) * u3)
+    })
+}
+
+/// Uniform grid bucketing of points for fast neighbour lookups.
+pub struct SpatialHash {
+    cell_size: f32,
+    cells: HashMap<(i32, i32, i32), Vec<usize>>,
+}
+
+impl SpatialHash {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size: cell_size.max(f32::EPSILON),
+            cells: HashMap::new(),
+        }
+    }
+
+    pub fn cell_of(&self, p: [f32; 3]) -> (i32, i32, i32) {
+        (
+            (p[0] / self.cell_size).floor() as i32,
+            (p[1] / self.cell_size).floor() as i32,
+            (p[2] / self.cell_size).floor() as i32,
+        )
+    }
+
+    pub fn insert(&mut self, p: [f32; 3], index: usize) {
+        let cell = self.cell_of(p);
+        self.cells.entry(cell).or_default().push(index);
+    }
+
+    /// Indices stored in the cell containing `p` and its 26 neighbours.
+    /// Callers still need to check the exact distance. Cell coordinates
+    /// saturate for huge positions, so neighbours past `i32` are skipped.
+    pub fn query_neighbours(&self, p: [f32; 3]) -> impl Iterator<Item = usize> + '_ {
+        let (cx, cy, cz) = self.cell_of(p);
+        (-1..=1)
+            .flat_map(move |dx| (-1..=1).flat_map(move |dy| (-1..=1).map(move |dz| (dx, dy, dz))))
+            .filter_map(move |(dx, dy, dz)| {
+                let cell = (
+                    cx.checked_add(dx)?,
+                    cy.checked_add(dy)?,
+                    cz.checked_add(dz)?,
+                );
+                self.cells.get(&cell)
+            })
+            .flat_map(|bucket| bucket.iter().copied())
+    }
+}
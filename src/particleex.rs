@@ -11,11 +11,19 @@ use pest_derive::Parser;
 use rand::Rng;
 use std::collections::HashMap;
 use std::f64::consts::{E, PI};
+use std::sync::{Arc, Mutex};
 
 // ─────────────────────── Constants ───────────────────────
 
+/// Output frames per command tick: 60 fps over Minecraft's 20 ticks/s.
 const TIME_SCALE: f64 = 3.0;
 
+/// Frame rate of the keyframe timeline, before any sub-tick subdivision.
+const TRACK_FPS: u32 = 60;
+
+/// Upper bound for `compile_entries`' `subdivisions`.
+pub const MAX_SUBDIVISIONS: u32 = 8;
+
 // ─────────────────────── Pest Grammar ───────────────────────
 
 #[derive(Parser)]
@@ -35,6 +43,8 @@ pub enum Expr {
     #[allow(dead_code)]
     Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
     MatrixBuilder(Vec<Vec<Expr>>),
+    /// `m[i]`: a row of a multi-row matrix, or an element of a single row.
+    Index(Box<Expr>, Box<Expr>),
     Assign(String, Box<Expr>),
     MultiAssign(Vec<String>, Vec<Expr>),
 }
@@ -128,6 +138,15 @@ fn build_expr(pair: Pair<Rule>) -> Expr {
                 Expr::MultiAssign(names, exprs)
             }
         }
+        Rule::postfix => {
+            let mut inner = pair.into_inner();
+            let mut base = build_expr(inner.next().unwrap());
+            for index in inner {
+                let idx = build_expr(index.into_inner().next().unwrap());
+                base = Expr::Index(Box::new(base), Box::new(idx));
+            }
+            base
+        }
         Rule::neg => {
             let inner = pair.into_inner().next().unwrap();
             Expr::UnaryNeg(Box::new(build_expr(inner)))
@@ -230,9 +249,80 @@ impl Value {
 
 // ─── Evaluator context ───
 
+/// Something an expression reported while it was evaluated, or a migration
+/// applied to it when a project was loaded.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprLogEvent {
+    Migrated {
+        from: u32,
+        to: u32,
+    },
+    Print {
+        frame: i64,
+        value: f64,
+    },
+    ExprAssertionFailed {
+        frame: i64,
+        id: i64,
+        a: f64,
+        b: f64,
+        tol: f64,
+    },
+}
+
+impl std::fmt::Display for ExprLogEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExprLogEvent::Migrated { from, to } => {
+                write!(f, "expression migrated v{} -> v{}", from, to)
+            }
+            ExprLogEvent::Print { frame, value } => write!(f, "frame={}: {}", frame, value),
+            ExprLogEvent::ExprAssertionFailed {
+                frame,
+                id,
+                a,
+                b,
+                tol,
+            } => write!(f, "frame={} id={}: |{} - {}| > {}", frame, id, a, b, tol),
+        }
+    }
+}
+
+/// Events from `print` and `assert_eq`, shared with the expression editor.
+pub type DebugLog = Arc<Mutex<Vec<ExprLogEvent>>>;
+
+/// Oldest `print` lines are dropped beyond this many; assertion failures
+/// past this many are not recorded.
+pub const DEBUG_LOG_LINES: usize = 100;
+
+/// Frames kept by `record_path(n)` when no capacity argument is given.
+pub const PATH_CAPACITY: usize = 64;
+const MAX_PATH_CAPACITY: usize = 4096;
+
+/// Copy of an `ExprContext`'s variables, taken by `save_state()`.
+#[derive(Clone)]
+pub struct ExprContextSnapshot {
+    vars: HashMap<String, Value>,
+}
+
 #[derive(Clone)]
 pub struct ExprContext {
     pub vars: HashMap<String, Value>,
+    /// Frame index used by `print` and `record_path`; set through
+    /// `set_frame` so expressions can also read it as `frame`.
+    pub frame: i64,
+    /// Where `print` writes; `None` makes it a no-op.
+    pub log: Option<DebugLog>,
+    /// `(frame, id)` of the last `print`, so each particle logs once per frame.
+    last_print: Option<(i64, i64)>,
+    /// Taken by `save_state()` and restored by `load_state()`.
+    saved: Option<ExprContextSnapshot>,
+}
+
+impl Default for ExprContext {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ExprContext {
@@ -240,7 +330,13 @@ impl ExprContext {
         let mut vars = HashMap::new();
         vars.insert("PI".into(), Value::Num(PI));
         vars.insert("E".into(), Value::Num(E));
-        Self { vars }
+        Self {
+            vars,
+            frame: 0,
+            log: None,
+            last_print: None,
+            saved: None,
+        }
     }
 
     pub fn get(&self, name: &str) -> Value {
@@ -250,6 +346,21 @@ impl ExprContext {
     pub fn set(&mut self, name: &str, val: Value) {
         self.vars.insert(name.to_string(), val);
     }
+
+    pub fn set_frame(&mut self, frame: i64) {
+        self.frame = frame;
+        self.set("frame", Value::Num(frame as f64));
+    }
+
+    pub fn snapshot(&self) -> ExprContextSnapshot {
+        ExprContextSnapshot {
+            vars: self.vars.clone(),
+        }
+    }
+
+    pub fn restore(&mut self, snap: ExprContextSnapshot) {
+        self.vars = snap.vars;
+    }
 }
 
 pub fn eval_expr(expr: &Expr, ctx: &mut ExprContext) -> Value {
@@ -415,7 +526,6 @@ pub fn eval_expr(expr: &Expr, ctx: &mut ExprContext) -> Value {
             1.0
         }),
         Expr::Call(name, args) => {
-            let mut rng = rand::thread_rng();
             let a: Vec<Value> = args.iter().map(|e| eval_expr(e, ctx)).collect();
             let nums: Vec<f64> = a.iter().map(|v| v.as_num()).collect();
 
@@ -472,7 +582,82 @@ pub fn eval_expr(expr: &Expr, ctx: &mut ExprContext) -> Value {
                         Value::Num(v.signum())
                     }
                 }
-                "random" => Value::Num(rng.gen::<f64>()),
+                "random" => Value::Num(random_unit()),
+                "print" => {
+                    let val = nums.first().copied().unwrap_or(0.0);
+                    let key = (ctx.frame, ctx.get("id").as_num() as i64);
+                    if let Some(log) = &ctx.log {
+                        if ctx.last_print != Some(key) {
+                            let mut events = log.lock().unwrap();
+                            let is_print =
+                                |e: &ExprLogEvent| matches!(e, ExprLogEvent::Print { .. });
+                            if events.iter().filter(|e| is_print(e)).count() >= DEBUG_LOG_LINES {
+                                if let Some(oldest) = events.iter().position(is_print) {
+                                    events.remove(oldest);
+                                }
+                            }
+                            events.push(ExprLogEvent::Print {
+                                frame: ctx.frame,
+                                value: val,
+                            });
+                            ctx.last_print = Some(key);
+                        }
+                    }
+                    Value::Num(val)
+                }
+                "record_path" => {
+                    let n = nums.first().copied().unwrap_or(0.0) as i64;
+                    let capacity = nums.get(1).copied().unwrap_or(PATH_CAPACITY as f64);
+                    let capacity = (capacity as usize).clamp(1, MAX_PATH_CAPACITY);
+                    let name = format!("path_{}", n);
+                    let mut rows = match ctx.get(&name) {
+                        Value::Matrix(m) if m.len() == capacity => m,
+                        _ => vec![vec![0.0; 3]; capacity],
+                    };
+                    rows[ctx.frame.rem_euclid(capacity as i64) as usize] = vec![
+                        ctx.get("x").as_num(),
+                        ctx.get("y").as_num(),
+                        ctx.get("z").as_num(),
+                    ];
+                    ctx.set(&name, Value::Matrix(rows));
+                    Value::Num(0.0)
+                }
+                "save_state" => {
+                    ctx.saved = Some(ctx.snapshot());
+                    Value::Num(0.0)
+                }
+                "load_state" => match ctx.saved.clone() {
+                    Some(snap) => {
+                        ctx.restore(snap);
+                        Value::Num(1.0)
+                    }
+                    None => Value::Num(0.0),
+                },
+                "assert_eq" => {
+                    let a = nums.first().copied().unwrap_or(0.0);
+                    let b = nums.get(1).copied().unwrap_or(0.0);
+                    let tol = nums.get(2).copied().unwrap_or(0.0);
+                    if (a - b).abs() <= tol {
+                        return Value::Num(0.0);
+                    }
+                    if let Some(log) = &ctx.log {
+                        let mut events = log.lock().unwrap();
+                        let failures = events
+                            .iter()
+                            .filter(|e| matches!(e, ExprLogEvent::ExprAssertionFailed { .. }))
+                            .count();
+                        if failures < DEBUG_LOG_LINES {
+                            events.push(ExprLogEvent::ExprAssertionFailed {
+                                frame: ctx.frame,
+                                id: ctx.get("id").as_num() as i64,
+                                a,
+                                b,
+                                tol,
+                            });
+                        }
+                    }
+                    Value::Num(1.0)
+                }
                 "toRadians" => Value::Num(nums.first().copied().unwrap_or(0.0).to_radians()),
                 "toDegrees" => Value::Num(nums.first().copied().unwrap_or(0.0).to_degrees()),
                 "clamp" => {
@@ -595,7 +780,7 @@ pub fn eval_expr(expr: &Expr, ctx: &mut ExprContext) -> Value {
                         Value::Num(0.0)
                     }
                 }
-                _ => Value::Num(0.0),
+                _ => Value::Num(crate::plugins::call(name, &nums).unwrap_or(0.0)),
             }
         }
         Expr::Conditional(cond, then, else_) => {
@@ -616,6 +801,22 @@ pub fn eval_expr(expr: &Expr, ctx: &mut ExprContext) -> Value {
             }
             Value::Matrix(res_rows)
         }
+        Expr::Index(base, idx) => {
+            let base = eval_expr(base, ctx);
+            let i = eval_expr(idx, ctx).as_num().floor();
+            match base {
+                Value::Matrix(m) if i >= 0.0 => {
+                    let i = i as usize;
+                    if m.len() == 1 {
+                        Value::Num(m[0].get(i).copied().unwrap_or(0.0))
+                    } else {
+                        m.get(i)
+                            .map_or(Value::Num(0.0), |row| Value::Matrix(vec![row.clone()]))
+                    }
+                }
+                _ => Value::Num(0.0),
+            }
+        }
         Expr::Assign(name, val_expr) => {
             let val = eval_expr(val_expr, ctx);
             ctx.set(name, val.clone());
@@ -650,6 +851,35 @@ pub fn eval_expr(expr: &Expr, ctx: &mut ExprContext) -> Value {
     }
 }
 
+/// Uniform sample in `[0, 1)` for `random()`.
+#[cfg(not(target_arch = "wasm32"))]
+fn random_unit() -> f64 {
+    rand::random()
+}
+
+/// Uniform sample in `[0, 1)` for `random()`, drawn from the page's or the
+/// worker's `crypto.getRandomValues`.
+#[cfg(target_arch = "wasm32")]
+fn random_unit() -> f64 {
+    use web_sys::js_sys;
+    use web_sys::wasm_bindgen::JsCast;
+    let crypto = match web_sys::window() {
+        Some(window) => window.crypto().ok(),
+        None => js_sys::global()
+            .dyn_into::<web_sys::WorkerGlobalScope>()
+            .ok()
+            .and_then(|scope| scope.crypto().ok()),
+    };
+    let mut bytes = [0u8; 8];
+    match crypto {
+        Some(crypto) if crypto.get_random_values_with_u8_array(&mut bytes).is_ok() => {
+            // 53 random mantissa bits.
+            (u64::from_le_bytes(bytes) >> 11) as f64 / (1u64 << 53) as f64
+        }
+        _ => js_sys::Math::random(),
+    }
+}
+
 pub fn exec_stmts(stmts: &[Stmt], ctx: &mut ExprContext) -> Value {
     let mut last_val = Value::Num(0.0);
     for stmt in stmts {
@@ -673,6 +903,480 @@ pub fn compile_expr(src: &str) -> Option<Vec<Stmt>> {
     Some(stmts)
 }
 
+/// Parse `src` against the expression grammar without building statements,
+/// returning the parser's message on failure.
+pub fn check_expr(src: &str) -> Result<(), String> {
+    ExprParser::parse(Rule::program, src.trim())
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintSeverity {
+    /// Works, but there is a cheaper way to write it.
+    Performance,
+    /// Almost certainly not what was meant.
+    Warning,
+}
+
+/// What `lint` found. The editor turns it into a translated message.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LintKind {
+    /// Multiplying by a literal diagonal matrix of this number.
+    ScaledIdentity(f64),
+    /// Statements after a constant `destroy = 1`.
+    AfterDestroy,
+}
+
+/// Something `lint` found; `stmts` are the `;`-separated top-level
+/// statements it applies to, for the editor to underline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LintWarning {
+    pub severity: LintSeverity,
+    pub kind: LintKind,
+    pub stmts: std::ops::Range<usize>,
+}
+
+/// Static checks for expressions that run, but slower or differently than
+/// they read: multiplying by a scaled identity matrix instead of a number,
+/// and statements after a constant `destroy = 1`, which are still evaluated
+/// but whose results are discarded with the particle. A later assignment to
+/// `destroy` ends that range, since it may keep the particle after all.
+pub fn lint(stmts: &[Stmt]) -> Vec<LintWarning> {
+    let mut warnings = Vec::new();
+    for (i, Stmt::ExprStmt(expr)) in stmts.iter().enumerate() {
+        lint_expr(expr, i, &mut warnings);
+    }
+    let mut discarded_from = None;
+    for (i, Stmt::ExprStmt(expr)) in stmts.iter().enumerate() {
+        let Some(value) = destroy_assignment(expr) else {
+            continue;
+        };
+        if !is_nonzero_const(value) {
+            discarded_from = None;
+        } else if discarded_from.is_none() {
+            discarded_from = Some(i + 1);
+        }
+    }
+    if let Some(start) = discarded_from.filter(|&start| start < stmts.len()) {
+        warnings.push(LintWarning {
+            severity: LintSeverity::Warning,
+            kind: LintKind::AfterDestroy,
+            stmts: start..stmts.len(),
+        });
+    }
+    warnings
+}
+
+/// The value a statement assigns to `destroy`, the last one if it does so
+/// more than once.
+fn destroy_assignment(expr: &Expr) -> Option<&Expr> {
+    match expr {
+        Expr::Assign(name, value) => (name == "destroy").then_some(&**value),
+        Expr::MultiAssign(names, values) => names
+            .iter()
+            .zip(values)
+            .rev()
+            .find(|(name, _)| *name == "destroy")
+            .map(|(_, value)| value),
+        _ => None,
+    }
+}
+
+fn lint_expr(expr: &Expr, stmt: usize, warnings: &mut Vec<LintWarning>) {
+    match expr {
+        Expr::BinOp(l, op, r) => {
+            if matches!(op, BinOp::Mul) {
+                if let Some(k) = scaled_identity(l).or_else(|| scaled_identity(r)) {
+                    warnings.push(LintWarning {
+                        severity: LintSeverity::Performance,
+                        kind: LintKind::ScaledIdentity(k),
+                        stmts: stmt..stmt + 1,
+                    });
+                }
+            }
+            lint_expr(l, stmt, warnings);
+            lint_expr(r, stmt, warnings);
+        }
+        Expr::UnaryNeg(e) | Expr::UnaryNot(e) | Expr::Assign(_, e) => lint_expr(e, stmt, warnings),
+        Expr::Call(_, args) | Expr::MultiAssign(_, args) => {
+            for arg in args {
+                lint_expr(arg, stmt, warnings);
+            }
+        }
+        Expr::Conditional(c, a, b) => {
+            for e in [c, a, b] {
+                lint_expr(e, stmt, warnings);
+            }
+        }
+        Expr::MatrixBuilder(rows) => {
+            for e in rows.iter().flatten() {
+                lint_expr(e, stmt, warnings);
+            }
+        }
+        Expr::Index(m, i) => {
+            lint_expr(m, stmt, warnings);
+            lint_expr(i, stmt, warnings);
+        }
+        Expr::Num(_) | Expr::Var(_) => {}
+    }
+}
+
+fn const_num(expr: &Expr) -> Option<f64> {
+    match expr {
+        Expr::Num(n) => Some(*n),
+        Expr::UnaryNeg(e) => const_num(e).map(|n| -n),
+        _ => None,
+    }
+}
+
+fn is_nonzero_const(expr: &Expr) -> bool {
+    const_num(expr).is_some_and(|n| n != 0.0)
+}
+
+/// `k` when `expr` is a literal square matrix with `k` on the diagonal and
+/// zeros elsewhere.
+fn scaled_identity(expr: &Expr) -> Option<f64> {
+    let Expr::MatrixBuilder(rows) = expr else {
+        return None;
+    };
+    let k = const_num(rows.first()?.first()?)?;
+    for (i, row) in rows.iter().enumerate() {
+        if row.len() != rows.len() {
+            return None;
+        }
+        for (j, e) in row.iter().enumerate() {
+            let expected = if i == j { k } else { 0.0 };
+            if const_num(e) != Some(expected) {
+                return None;
+            }
+        }
+    }
+    Some(k)
+}
+
+const FORMAT_INDENT: &str = "  ";
+
+/// Reprint `src` from its parse tree: one statement per line, spaces around
+/// binary operators, only the parentheses precedence needs, and the rows
+/// of multi-column matrix literals on their own indented lines. Source that
+/// does not parse is returned unchanged.
+pub fn format_expr(src: &str) -> String {
+    if check_expr(src).is_err() {
+        return src.to_string();
+    }
+    let Some(stmts) = compile_expr(src) else {
+        return src.to_string();
+    };
+    stmts
+        .iter()
+        .map(|Stmt::ExprStmt(expr)| format_node(expr, 0))
+        .collect::<Vec<_>>()
+        .join(";\n")
+}
+
+pub fn op_symbol(op: BinOp) -> &'static str {
+    match op {
+        BinOp::Add => "+",
+        BinOp::Sub => "-",
+        BinOp::Mul => "*",
+        BinOp::Div => "/",
+        BinOp::Mod => "%",
+        BinOp::Pow => "^",
+        BinOp::Eq => "==",
+        BinOp::Ne => "!=",
+        BinOp::Lt => "<",
+        BinOp::Gt => ">",
+        BinOp::Le => "<=",
+        BinOp::Ge => ">=",
+        BinOp::And => "&&",
+        BinOp::Or => "||",
+    }
+}
+
+/// Binding strength of a binary operator, loosest first, as in the grammar.
+fn op_precedence(op: BinOp) -> u8 {
+    match op {
+        BinOp::Or => 1,
+        BinOp::And => 2,
+        BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Gt | BinOp::Le | BinOp::Ge => 3,
+        BinOp::Add | BinOp::Sub => 4,
+        BinOp::Mul | BinOp::Div | BinOp::Mod => 5,
+        BinOp::Pow => 6,
+    }
+}
+
+/// `expr` formatted, in parentheses when it is a binary operation binding
+/// looser than `min_precedence`. Assignments are never wrapped: the grammar
+/// only accepts them bare, where they swallow everything to their right.
+fn format_operand(expr: &Expr, min_precedence: u8, level: usize) -> String {
+    match expr {
+        Expr::BinOp(_, op, _) if op_precedence(*op) < min_precedence => {
+            format!("({})", format_node(expr, level))
+        }
+        _ => format_node(expr, level),
+    }
+}
+
+fn format_node(expr: &Expr, level: usize) -> String {
+    let list = |exprs: &[Expr]| -> String {
+        exprs
+            .iter()
+            .map(|e| format_node(e, level))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    match expr {
+        Expr::Num(n) => n.to_string(),
+        Expr::Var(name) => name.clone(),
+        Expr::BinOp(l, op, r) => {
+            let p = op_precedence(*op);
+            // `^` takes unary operands on both sides; the other operators
+            // chain to the left, so an equal-precedence right side is grouped.
+            let (lp, rp) = if matches!(op, BinOp::Pow) {
+                (u8::MAX, u8::MAX)
+            } else {
+                (p, p + 1)
+            };
+            format!(
+                "{} {} {}",
+                format_operand(l, lp, level),
+                op_symbol(*op),
+                format_operand(r, rp, level)
+            )
+        }
+        Expr::UnaryNeg(e) => format!("-{}", format_operand(e, u8::MAX, level)),
+        Expr::UnaryNot(e) => format!("!{}", format_operand(e, u8::MAX, level)),
+        Expr::Call(name, args) => format!("{}({})", name, list(args)),
+        // Never built by the parser; printed in call form for completeness.
+        Expr::Conditional(c, a, b) => format!(
+            "conditional({}, {}, {})",
+            format_node(c, level),
+            format_node(a, level),
+            format_node(b, level)
+        ),
+        Expr::MatrixBuilder(rows) => {
+            if rows.len() > 1 && rows.iter().any(|row| row.len() > 1) {
+                let inner = FORMAT_INDENT.repeat(level + 1);
+                let rows: Vec<String> = rows
+                    .iter()
+                    .map(|row| {
+                        let cells: Vec<String> =
+                            row.iter().map(|e| format_node(e, level + 1)).collect();
+                        format!("{}{}", inner, cells.join(", "))
+                    })
+                    .collect();
+                format!("(\n{}\n{})", rows.join(",,\n"), FORMAT_INDENT.repeat(level))
+            } else {
+                let rows: Vec<String> = rows.iter().map(|row| list(row)).collect();
+                format!("({})", rows.join(",, "))
+            }
+        }
+        Expr::Index(base, idx) => {
+            let base = match **base {
+                Expr::Index(..) | Expr::Var(_) | Expr::Call(..) | Expr::MatrixBuilder(_) => {
+                    format_node(base, level)
+                }
+                _ => format!("({})", format_node(base, level)),
+            };
+            format!("{}[{}]", base, format_node(idx, level))
+        }
+        Expr::Assign(name, value) => format!("{} = {}", name, format_node(value, level)),
+        Expr::MultiAssign(names, values) => format!("{} = {}", names.join(", "), list(values)),
+    }
+}
+
+/// Expression language version written to project files.
+pub const EXPR_VERSION: u32 = 2;
+
+/// Rewrites expression source across one breaking language change.
+pub type ExprMigration = fn(&str) -> String;
+
+/// `(from_version, migration)`: each entry upgrades source written for
+/// `from_version` to `from_version + 1`.
+pub const MIGRATIONS: &[(u32, ExprMigration)] = &[(1, migrate_expression_v1_v2)];
+
+/// v2 made `$name` a macro call, so `$` inside v1 identifiers is renamed to
+/// `_` to keep them plain variables. `$name(` is left alone: v1 had no call
+/// syntax for it, and projects saved after macros were added but before
+/// `expr_version` was written already use it.
+pub fn migrate_expression_v1_v2(src: &str) -> String {
+    let chars: Vec<char> = src.chars().collect();
+    let mut out = String::with_capacity(src.len());
+    for (i, &c) in chars.iter().enumerate() {
+        if c != '$' {
+            out.push(c);
+            continue;
+        }
+        let name_len = chars[i + 1..]
+            .iter()
+            .take_while(|c| c.is_ascii_alphanumeric() || **c == '_')
+            .count();
+        let is_call = name_len > 0 && chars.get(i + 1 + name_len) == Some(&'(');
+        out.push(if is_call { '$' } else { '_' });
+    }
+    out
+}
+
+/// Runs every migration from `version` up to `EXPR_VERSION`, returning the
+/// upgraded source and the events for the ones applied.
+pub fn migrate_expression(src: &str, version: u32) -> (String, Vec<ExprLogEvent>) {
+    let mut src = src.to_string();
+    let mut applied = Vec::new();
+    for (from, migrate) in MIGRATIONS {
+        if *from >= version && *from < EXPR_VERSION {
+            src = migrate(&src);
+            applied.push(ExprLogEvent::Migrated {
+                from: *from,
+                to: from + 1,
+            });
+        }
+    }
+    (src, applied)
+}
+
+/// Text-level expansion of `$name` and `$name(arg, ...)` macro calls, run on
+/// expression source before `compile_expr`. In a macro body `$1`, `$2`, ...
+/// are replaced verbatim by the positional arguments (so they can also name
+/// assignment targets); bodies should parenthesise them where precedence
+/// matters. Bodies are expanded again so macros can use other macros, up to a
+/// fixed depth; unknown names are left as-is.
+pub fn expand_macros(src: &str, macros: &HashMap<String, String>) -> String {
+    expand_macros_depth(src, macros, 8)
+}
+
+/// Number of positional arguments a macro body takes: its highest `$N`.
+pub fn macro_param_count(body: &str) -> usize {
+    body.split('$')
+        .skip(1)
+        .filter_map(|rest| {
+            let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            rest[..digits].parse::<usize>().ok()
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+fn expand_macros_depth(src: &str, macros: &HashMap<String, String>, depth: u32) -> String {
+    if depth == 0 || !src.contains('$') {
+        return src.to_string();
+    }
+    let chars: Vec<char> = src.chars().collect();
+    let mut out = String::with_capacity(src.len());
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] != '$' {
+            out.push(chars[i]);
+            i += 1;
+            continue;
+        }
+        let name_end = chars[i + 1..]
+            .iter()
+            .position(|c| !(c.is_ascii_alphanumeric() || *c == '_'))
+            .map_or(chars.len(), |p| i + 1 + p);
+        let name: String = chars[i + 1..name_end].iter().collect();
+        let Some(body) = macros.get(&name) else {
+            out.extend(&chars[i..name_end]);
+            i = name_end;
+            continue;
+        };
+
+        // Positional arguments split on top-level commas.
+        let mut args: Vec<String> = Vec::new();
+        let mut end = name_end;
+        if chars.get(name_end) == Some(&'(') {
+            let mut level = 0;
+            let mut current = String::new();
+            for (j, &c) in chars.iter().enumerate().skip(name_end + 1) {
+                match c {
+                    '(' => level += 1,
+                    ')' if level == 0 => {
+                        end = j + 1;
+                        break;
+                    }
+                    ')' => level -= 1,
+                    ',' if level == 0 => {
+                        args.push(current.trim().to_string());
+                        current.clear();
+                        continue;
+                    }
+                    _ => {}
+                }
+                current.push(c);
+            }
+            if end == name_end {
+                // Unbalanced call: leave the rest untouched.
+                out.extend(&chars[i..]);
+                break;
+            }
+            if !current.trim().is_empty() || !args.is_empty() {
+                args.push(current.trim().to_string());
+            }
+        }
+
+        // Substitute from the highest index down so `$1` does not eat `$10`.
+        let mut expanded = body.clone();
+        for (k, arg) in args.iter().enumerate().rev() {
+            expanded = expanded.replace(&format!("${}", k + 1), arg);
+        }
+        out.push_str(&expand_macros_depth(&expanded, macros, depth - 1));
+        i = end;
+    }
+    out
+}
+
+/// Trace a single particle spawned at the origin through `frames` frames of
+/// `speed_expr`, advancing it the same way normal-mode commands do. Used by
+/// the expression editor's live test.
+pub fn simulate_test_particle(speed_expr: &str, frames: u32) -> Result<Vec<[f64; 3]>, String> {
+    if !speed_expr.trim().is_empty() {
+        ExprParser::parse(Rule::program, speed_expr.trim()).map_err(|e| e.to_string())?;
+    }
+    let stmts = compile_expr(speed_expr);
+
+    let mut ctx = ExprContext::new();
+    for name in ["x", "y", "z", "vx", "vy", "vz", "age", "t", "destroy"] {
+        ctx.set(name, Value::Num(0.0));
+    }
+    for name in ["cr", "cg", "cb", "alpha"] {
+        ctx.set(name, Value::Num(1.0));
+    }
+    ctx.set("mpsize", Value::Num(0.1));
+
+    let (mut x, mut y, mut z) = (0.0, 0.0, 0.0);
+    let (mut vx, mut vy, mut vz) = (0.0, 0.0, 0.0);
+    let mut trace = Vec::with_capacity(frames as usize);
+    for f in 0..frames {
+        ctx.set("age", Value::Num(f as f64 / TIME_SCALE));
+        ctx.set("t", Value::Num(f as f64 / TIME_SCALE));
+        ctx.set("x", Value::Num(x));
+        ctx.set("y", Value::Num(y));
+        ctx.set("z", Value::Num(z));
+        ctx.set("vx", Value::Num(vx));
+        ctx.set("vy", Value::Num(vy));
+        ctx.set("vz", Value::Num(vz));
+
+        if let Some(ref stmts) = stmts {
+            exec_stmts(stmts, &mut ctx);
+            vx = ctx.get("vx").as_num();
+            vy = ctx.get("vy").as_num();
+            vz = ctx.get("vz").as_num();
+            x = ctx.get("x").as_num();
+            y = ctx.get("y").as_num();
+            z = ctx.get("z").as_num();
+        }
+        if ctx.get("destroy").is_true() {
+            break;
+        }
+        trace.push([x, y, z]);
+
+        x += vx / TIME_SCALE;
+        y += vy / TIME_SCALE;
+        z += vz / TIME_SCALE;
+    }
+    Ok(trace)
+}
+
 // ─────────────────────── Command Types ───────────────────────
 
 #[derive(Debug, Clone, Copy)]
@@ -1373,7 +2077,12 @@ fn parse_command(line: &str) -> Option<ParsedCommand> {
         .map(|cmd| parsed_from_model(&cmd))
 }
 
-pub fn validate_command_model(cmd: &ParticleexCommand) -> Result<String, String> {
+/// `time_scale_override` is the entry's `CompileEntry::time_scale_override`;
+/// the effective tick rate is appended to the description.
+pub fn validate_command_model(
+    cmd: &ParticleexCommand,
+    time_scale_override: Option<f64>,
+) -> Result<String, String> {
     let parsed = parsed_from_model(cmd);
     let mode = &parsed.type_name;
     let lifespan = parsed.lifespan;
@@ -1396,7 +2105,8 @@ pub fn validate_command_model(cmd: &ParticleexCommand) -> Result<String, String>
             mode, parsed.t_begin, parsed.t_end, total, lifespan
         )
     };
-    Ok(info)
+    let tick_rate = 60.0 / time_scale_override.unwrap_or(TIME_SCALE);
+    Ok(format!("{} tick_rate={}/s", info, tick_rate))
 }
 
 // ─────────────────────── Track ───────────────────────
@@ -1416,8 +2126,30 @@ struct Keyframe {
     seq_index: u8,
 }
 
+impl Keyframe {
+    /// State `t` of the way from `self` to `next`, keeping `self.tick`.
+    fn lerp(&self, next: &Keyframe, t: f64) -> Keyframe {
+        let mix_u8 = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+        Keyframe {
+            tick: self.tick,
+            x: self.x + (next.x - self.x) * t,
+            y: self.y + (next.y - self.y) * t,
+            z: self.z + (next.z - self.z) * t,
+            r: mix_u8(self.r, next.r),
+            g: mix_u8(self.g, next.g),
+            b: mix_u8(self.b, next.b),
+            a: mix_u8(self.a, next.a),
+            size: self.size + (next.size - self.size) * t,
+            tex_id: self.tex_id,
+            seq_index: self.seq_index,
+        }
+    }
+}
+
 struct Track {
     id: i32,
+    /// In tick order. Ticks may be skipped; `tracks_to_frames_interpolated`
+    /// fills them in linearly.
     keyframes: Vec<Keyframe>,
 }
 
@@ -1428,6 +2160,7 @@ fn generate_tracks(
     start_id: i32,
     global_tex_ids: &[u8],
     texture_interval: u32,
+    time_scale: f64,
 ) -> (Vec<Track>, i32) {
     let mut tracks = Vec::new();
     let mut current_id = start_id;
@@ -1437,7 +2170,7 @@ fn generate_tracks(
         if global_tex_ids.is_empty() {
             0
         } else {
-            let frame_interval = (texture_interval as f64 * TIME_SCALE).max(1.0);
+            let frame_interval = (texture_interval as f64 * time_scale).max(1.0);
             let idx = ((f as f64) / frame_interval).floor() as usize % global_tex_ids.len();
             global_tex_ids[idx]
         }
@@ -1488,7 +2221,7 @@ fn generate_tracks(
                         let mut cur_vy = cmd.base_velocity[1];
                         let mut cur_vz = cmd.base_velocity[2];
 
-                        let total_frames = (cmd.lifespan as f64 * TIME_SCALE).floor() as u32;
+                        let total_frames = (cmd.lifespan as f64 * time_scale).floor() as u32;
                         let mut sctx = ExprContext::new();
                         sctx.set("x", Value::Num(cx));
                         sctx.set("y", Value::Num(cy));
@@ -1506,8 +2239,8 @@ fn generate_tracks(
                         sctx.set("destroy", Value::Num(0.0));
 
                         for f in 0..total_frames {
-                            sctx.set("age", Value::Num(f as f64 / TIME_SCALE));
-                            sctx.set("t", Value::Num(f as f64 / TIME_SCALE));
+                            sctx.set("age", Value::Num(f as f64 / time_scale));
+                            sctx.set("t", Value::Num(f as f64 / time_scale));
                             sctx.set("x", Value::Num(cur_x - cmd.center[0]));
                             sctx.set("y", Value::Num(cur_y - cmd.center[1]));
                             sctx.set("z", Value::Num(cur_z - cmd.center[2]));
@@ -1548,9 +2281,9 @@ fn generate_tracks(
                                 seq_index: 0,
                             });
 
-                            cur_x += cur_vx / TIME_SCALE;
-                            cur_y += cur_vy / TIME_SCALE;
-                            cur_z += cur_vz / TIME_SCALE;
+                            cur_x += cur_vx / time_scale;
+                            cur_y += cur_vy / time_scale;
+                            cur_z += cur_vz / time_scale;
                         }
 
                         if !track.keyframes.is_empty() {
@@ -1617,11 +2350,11 @@ fn generate_tracks(
             ctx.set("t", Value::Num(0.0));
             ctx.set("destroy", Value::Num(0.0));
 
-            let total_frames = (cmd.lifespan as f64 * TIME_SCALE).floor() as u32;
+            let total_frames = (cmd.lifespan as f64 * time_scale).floor() as u32;
 
             for f in 0..total_frames {
-                ctx.set("age", Value::Num(f as f64 / TIME_SCALE));
-                ctx.set("t", Value::Num(f as f64 / TIME_SCALE));
+                ctx.set("age", Value::Num(f as f64 / time_scale));
+                ctx.set("t", Value::Num(f as f64 / time_scale));
                 ctx.set("x", Value::Num(cur_x - cmd.center[0]));
                 ctx.set("y", Value::Num(cur_y - cmd.center[1]));
                 ctx.set("z", Value::Num(cur_z - cmd.center[2]));
@@ -1662,9 +2395,9 @@ fn generate_tracks(
                     seq_index: 0,
                 });
 
-                cur_x += cur_vx / TIME_SCALE;
-                cur_y += cur_vy / TIME_SCALE;
-                cur_z += cur_vz / TIME_SCALE;
+                cur_x += cur_vx / time_scale;
+                cur_y += cur_vy / time_scale;
+                cur_z += cur_vz / time_scale;
             }
 
             if !track.keyframes.is_empty() {
@@ -1744,11 +2477,11 @@ fn generate_tracks(
         };
         particle_index += 1;
 
-        let total_frames = (cmd.lifespan as f64 * TIME_SCALE).floor() as u32;
+        let total_frames = (cmd.lifespan as f64 * time_scale).floor() as u32;
 
         for f in 0..total_frames {
-            ctx.set("age", Value::Num(f as f64 / TIME_SCALE));
-            ctx.set("t", Value::Num(f as f64 / TIME_SCALE));
+            ctx.set("age", Value::Num(f as f64 / time_scale));
+            ctx.set("t", Value::Num(f as f64 / time_scale));
             ctx.set("x", Value::Num(cur_x - cmd.center[0]));
             ctx.set("y", Value::Num(cur_y - cmd.center[1]));
             ctx.set("z", Value::Num(cur_z - cmd.center[2]));
@@ -1789,9 +2522,9 @@ fn generate_tracks(
                 seq_index: 0,
             });
 
-            cur_x += cur_vx / TIME_SCALE;
-            cur_y += cur_vy / TIME_SCALE;
-            cur_z += cur_vz / TIME_SCALE;
+            cur_x += cur_vx / time_scale;
+            cur_y += cur_vy / time_scale;
+            cur_z += cur_vz / time_scale;
         }
 
         if !track.keyframes.is_empty() {
@@ -1806,39 +2539,57 @@ fn generate_tracks(
 
 // ─────────────────────── Tracks → Frame Snapshots ───────────────────────
 
-fn tracks_to_frames(tracks: &[Track]) -> Vec<Vec<Particle>> {
-    if tracks.is_empty() {
+/// Frame snapshots at `target_fps` for tracks whose keyframe ticks run at
+/// `tick_rate` per second. Frames falling between two keyframes, whether
+/// sub-tick frames when `target_fps > tick_rate` or ticks skipped by sparse
+/// keyframes, interpolate position, colour and size linearly; texture and
+/// sequence index hold the earlier keyframe's value.
+fn tracks_to_frames_interpolated(
+    tracks: &[Track],
+    target_fps: u32,
+    tick_rate: u32,
+) -> Vec<Vec<Particle>> {
+    let Some(max_tick) = tracks
+        .iter()
+        .filter_map(|t| t.keyframes.last())
+        .map(|k| k.tick)
+        .max()
+    else {
         return vec![];
-    }
-
-    let mut max_tick: u32 = 0;
-    for t in tracks {
-        if let Some(last) = t.keyframes.last() {
-            if last.tick > max_tick {
-                max_tick = last.tick;
-            }
-        }
-    }
-
-    let total = (max_tick + 1) as usize;
-    let mut frames: Vec<Vec<Particle>> = Vec::with_capacity(total);
-    for _ in 0..total {
-        frames.push(Vec::new());
-    }
+    };
+    let frames_per_tick = target_fps.max(1) as f64 / tick_rate.max(1) as f64;
+    let total = (max_tick as f64 * frames_per_tick).floor() as usize + 1;
+    let mut frames: Vec<Vec<Particle>> = vec![Vec::new(); total];
 
     for t in tracks {
-        for k in &t.keyframes {
-            let fi = k.tick as usize;
-            if fi < frames.len() {
-                frames[fi].push(Particle {
-                    id: t.id,
-                    pos: [k.x as f32, k.y as f32, k.z as f32],
-                    color: [k.r, k.g, k.b, k.a],
-                    size: k.size as f32,
-                    tex_id: k.tex_id,
-                    seq_index: k.seq_index,
-                });
+        let (Some(first), Some(last)) = (t.keyframes.first(), t.keyframes.last()) else {
+            continue;
+        };
+        let start = (first.tick as f64 * frames_per_tick).ceil() as usize;
+        let end = (last.tick as f64 * frames_per_tick).floor() as usize;
+        let mut seg = 0;
+        for (fi, frame) in frames.iter_mut().enumerate().take(end + 1).skip(start) {
+            let time = fi as f64 / frames_per_tick;
+            while t
+                .keyframes
+                .get(seg + 1)
+                .is_some_and(|next| next.tick as f64 <= time)
+            {
+                seg += 1;
             }
+            let k = &t.keyframes[seg];
+            let state = match t.keyframes.get(seg + 1) {
+                Some(next) => k.lerp(next, (time - k.tick as f64) / (next.tick - k.tick) as f64),
+                None => k.clone(),
+            };
+            frame.push(Particle {
+                id: t.id,
+                pos: [state.x as f32, state.y as f32, state.z as f32],
+                color: [state.r, state.g, state.b, state.a],
+                size: state.size as f32,
+                tex_id: state.tex_id,
+                seq_index: state.seq_index,
+            });
         }
     }
 
@@ -1858,8 +2609,9 @@ pub fn compile(commands_text: &str) -> Result<(Vec<Vec<Particle>>, u16), String>
         duration_override: 0.0,
         textures: vec![],
         texture_interval: 20,
+        time_scale_override: None,
     }];
-    compile_entries(&entries).map(|(f, fps, _)| (f, fps))
+    compile_entries(&entries, 1).map(|(f, fps, _)| (f, fps))
 }
 
 /// A single compilable entry with optional overrides.
@@ -1870,13 +2622,19 @@ pub struct CompileEntry {
     pub duration_override: f64, // 0 = use command's own value
     pub textures: Vec<String>,
     pub texture_interval: u32,
+    /// Output frames per command tick; `None` uses `TIME_SCALE` (20 ticks/s).
+    /// Lets commands written for other tick rates share one animation.
+    pub time_scale_override: Option<f64>,
 }
 
 /// Compile multiple entries into merged frame snapshots.
 /// Each entry can have its own start time, position, and duration override.
+/// `subdivisions` frames are emitted per 60 fps step, interpolated between
+/// keyframes, and the returned frame rate is raised to match.
 /// Returns (frames, target_fps, global_textures).
 pub fn compile_entries(
     entries: &[CompileEntry],
+    subdivisions: u32,
 ) -> Result<(Vec<Vec<Particle>>, u16, Vec<String>), String> {
     let mut all_tracks: Vec<Track> = Vec::new();
     let mut p_id: i32 = 0;
@@ -1923,9 +2681,15 @@ pub fn compile_entries(
                         cmd.lifespan = entry.duration_override as u32;
                     }
 
-                    let (mut tracks, next_id) =
-                        generate_tracks(&cmd, p_id, &entry_global_ids, entry.texture_interval);
+                    let (mut tracks, next_id) = generate_tracks(
+                        &cmd,
+                        p_id,
+                        &entry_global_ids,
+                        entry.texture_interval,
+                        entry.time_scale_override.unwrap_or(TIME_SCALE),
+                    );
 
+                    // Start ticks are on the shared 20 ticks/s timeline
                     let offset = (entry.start_tick * TIME_SCALE).floor() as u32;
                     if offset > 0 {
                         for track in &mut tracks {
@@ -1953,18 +2717,20 @@ pub fn compile_entries(
         });
     }
 
-    let frames = tracks_to_frames(&all_tracks);
-    Ok((frames, 60, global_textures))
+    let subdivisions = subdivisions.clamp(1, MAX_SUBDIVISIONS);
+    let target_fps = TRACK_FPS * subdivisions;
+    let frames = tracks_to_frames_interpolated(&all_tracks, target_fps, TRACK_FPS);
+    Ok((frames, target_fps as u16, global_textures))
 }
 
 /// Validate a command line. Returns Ok(description) or Err(error message).
-pub fn validate_command(line: &str) -> Result<String, String> {
+pub fn validate_command(line: &str, time_scale_override: Option<f64>) -> Result<String, String> {
     let trimmed = line.trim();
     if trimmed.is_empty() {
         return Err("Empty command".into());
     }
     let model = parse_command_model(trimmed)?;
-    validate_command_model(&model)
+    validate_command_model(&model, time_scale_override)
 }
 
 #[cfg(test)]
@@ -1976,7 +2742,7 @@ mod tests {
         let formatted = format_command_model(&model);
         let reparsed = parse_command_model(&formatted).expect("reparse should succeed");
         assert_eq!(model, reparsed, "roundtrip mismatch for {input}");
-        validate_command_model(&model).expect("model validation should succeed");
+        validate_command_model(&model, None).expect("model validation should succeed");
     }
 
     #[test]
@@ -2021,9 +2787,194 @@ mod tests {
             duration_override: 0.0,
             textures: vec![],
             texture_interval: 20,
+            time_scale_override: None,
         }];
-        let (frames, fps, _) = compile_entries(&entries).expect("compile should succeed");
+        let (frames, fps, _) = compile_entries(&entries, 1).expect("compile should succeed");
         assert!(!frames.is_empty());
         assert_eq!(fps, 60);
+
+        let (subdivided, fps, _) = compile_entries(&entries, 2).expect("compile should succeed");
+        assert_eq!(fps, 120);
+        assert_eq!(subdivided.len(), (frames.len() - 1) * 2 + 1);
+    }
+
+    #[test]
+    fn test_particle_follows_constant_velocity() {
+        let trace = simulate_test_particle("vx=1;vy=0;vz=0", 4).expect("simulate should succeed");
+        assert_eq!(trace.len(), 4);
+        assert!((trace[3][0] - 1.0).abs() < 1e-9);
+        assert!(simulate_test_particle("vx=(", 4).is_err());
+    }
+
+    #[test]
+    fn expands_macros_with_arguments() {
+        let macros = HashMap::from([
+            ("half".to_string(), "($1) / 2".to_string()),
+            ("spin".to_string(), "vx = $half($1 + 1)".to_string()),
+        ]);
+        assert_eq!(
+            expand_macros("$spin(sin(t), 3)", &macros),
+            "vx = (sin(t) + 1) / 2"
+        );
+        assert_eq!(expand_macros("vy = $unknown", &macros), "vy = $unknown");
+    }
+
+    #[test]
+    fn load_state_rolls_back_to_save_state() {
+        let stmts = compile_expr("vx = 5; save_state(); vx = 99; load_state()").unwrap();
+        let mut ctx = ExprContext::new();
+        exec_stmts(&stmts, &mut ctx);
+        assert_eq!(ctx.get("vx").as_num(), 5.0);
+    }
+
+    #[test]
+    fn record_path_wraps_and_indexes() {
+        let stmts = compile_expr("record_path(1, 2); px = path_1[frame % 2][0]").unwrap();
+        let mut ctx = ExprContext::new();
+        for frame in 0..3 {
+            ctx.set_frame(frame);
+            ctx.set("x", Value::Num(10.0 + frame as f64));
+            exec_stmts(&stmts, &mut ctx);
+            assert_eq!(ctx.get("px").as_num(), 10.0 + frame as f64);
+        }
+        let Value::Matrix(rows) = ctx.get("path_1") else {
+            panic!("path_1 should be a matrix");
+        };
+        assert_eq!(rows, vec![vec![12.0, 0.0, 0.0], vec![11.0, 0.0, 0.0]]);
+    }
+
+    #[test]
+    fn sub_tick_frames_interpolate_between_ticks() {
+        let keyframe = |tick: u32, x: f64, a: u8, size: f64| Keyframe {
+            tick,
+            x,
+            y: 0.0,
+            z: 0.0,
+            r: 0,
+            g: 0,
+            b: 0,
+            a,
+            size,
+            tex_id: 0,
+            seq_index: 0,
+        };
+        let track = Track {
+            id: 1,
+            keyframes: vec![keyframe(0, 0.0, 0, 0.1), keyframe(1, 3.0, 255, 0.4)],
+        };
+        let frames = tracks_to_frames_interpolated(&[track], 60, 20);
+        assert_eq!(frames.len(), 4);
+        let xs: Vec<f32> = frames.iter().map(|f| f[0].pos[0]).collect();
+        assert_eq!(xs, vec![0.0, 1.0, 2.0, 3.0]);
+        assert_eq!(frames[1][0].color[3], 85);
+        assert!((frames[2][0].size - 0.3).abs() < 1e-6);
+    }
+
+    #[test]
+    fn linear_keyframes_compress_and_expand() {
+        // Straight line for 6 ticks, then a turn; alpha drops at tick 7.
+        let frames: Vec<Vec<Particle>> = (0..9)
+            .map(|f: u32| {
+                let x = f.min(5) as f32 * 0.5;
+                let y = f.saturating_sub(5) as f32;
+                vec![Particle {
+                    id: 3,
+                    pos: [x, y, 1.0],
+                    color: [10, 20, 30, if f < 7 { 255 } else { 100 }],
+                    size: 0.2,
+                    tex_id: 0,
+                    seq_index: 0,
+                }]
+            })
+            .collect();
+        let sparse = crate::player::compress_linear_keyframes(&frames, 1e-4);
+        let kept: Vec<usize> = (0..sparse.len())
+            .filter(|&f| !sparse[f].is_empty())
+            .collect();
+        assert_eq!(kept, vec![0, 5, 6, 7, 8]);
+
+        let track = Track {
+            id: 3,
+            keyframes: kept
+                .iter()
+                .map(|&f| {
+                    let p = &sparse[f][0];
+                    Keyframe {
+                        tick: f as u32,
+                        x: p.pos[0] as f64,
+                        y: p.pos[1] as f64,
+                        z: p.pos[2] as f64,
+                        r: p.color[0],
+                        g: p.color[1],
+                        b: p.color[2],
+                        a: p.color[3],
+                        size: p.size as f64,
+                        tex_id: p.tex_id,
+                        seq_index: p.seq_index,
+                    }
+                })
+                .collect(),
+        };
+        let expanded = tracks_to_frames_interpolated(&[track], 1, 1);
+        assert_eq!(expanded.len(), frames.len());
+        for (a, b) in expanded.iter().zip(&frames) {
+            assert_eq!(a.len(), 1);
+            assert_eq!(a[0].color, b[0].color);
+            assert!((0..3).all(|k| (a[0].pos[k] - b[0].pos[k]).abs() < 1e-5));
+        }
+    }
+
+    #[test]
+    fn lint_flags_scaled_identity_and_code_after_destroy() {
+        let stmts =
+            compile_expr("m = (2, 0,, 0, 2) * (x,, y); destroy = 1; vx = 0; vy = 0").unwrap();
+        let warnings = lint(&stmts);
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[0].severity, LintSeverity::Performance);
+        assert_eq!(warnings[0].kind, LintKind::ScaledIdentity(2.0));
+        assert_eq!(warnings[0].stmts, 0..1);
+        assert_eq!(warnings[1].severity, LintSeverity::Warning);
+        assert_eq!(warnings[1].stmts, 2..4);
+
+        let stmts =
+            compile_expr("m = (2, 0,, 0, 3) * (x,, y); destroy = age > 10; vx = 0").unwrap();
+        assert!(lint(&stmts).is_empty());
+
+        let stmts = compile_expr("destroy = 1; vx = 0; destroy = age > 10; vy = 0").unwrap();
+        assert!(lint(&stmts).is_empty());
+    }
+
+    #[test]
+    fn format_expr_reprints_with_minimal_parentheses() {
+        let src = "vx=a+b*c;vy=-(a+b)^2;vz=a-(b-c);m=(1,0,,0,1)*(x,,y)";
+        let formatted = format_expr(src);
+        assert_eq!(
+            formatted,
+            "vx = a + b * c;\nvy = -(a + b) ^ 2;\nvz = a - (b - c);\nm = (\n  1, 0,,\n  0, 1\n) * (x,, y)"
+        );
+        assert_eq!(format_expr(&formatted), formatted);
+        assert_eq!(format_expr("vx = (1"), "vx = (1");
+    }
+
+    #[test]
+    fn macro_buttons_fill_every_parameter() {
+        let body = "($2, $3) = ($2) * cos($1) - ($3) * sin($1), ($2) * sin($1) + ($3) * cos($1)";
+        assert_eq!(macro_param_count(body), 3);
+        assert_eq!(macro_param_count("vx = $10 + $2"), 10);
+        assert_eq!(macro_param_count("vx = 1"), 0);
+        let macros = HashMap::from([("rotate".to_string(), body.to_string())]);
+        let expanded = expand_macros("$rotate(arg1, arg2, arg3)", &macros);
+        assert!(check_expr(&expanded).is_ok());
+    }
+
+    #[test]
+    fn v1_migration_keeps_macro_calls() {
+        assert_eq!(
+            migrate_expression_v1_v2("$a = $rotate($b, 2); vx = $a + $"),
+            "_a = $rotate(_b, 2); vx = _a + _"
+        );
+        let (src, events) = migrate_expression("vy = $k", 1);
+        assert_eq!(src, "vy = _k");
+        assert_eq!(events.len(), 1);
     }
 }
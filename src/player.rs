@@ -1,14 +1,90 @@
+use crate::math::SpatialHash;
 use anyhow::{anyhow, Result};
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::{BufWriter, Cursor, Read, Seek, SeekFrom, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 
 const MAGIC: &[u8; 8] = b"NEBULAFX";
 
+/// Newest NBL format version the reader understands. v2 adds the guarantee
+/// that every sequence index fits its texture's grid.
+pub const NBL_VERSION: u16 = 2;
+
+/// Attribute bit marking frame blocks as LZ4 (size-prepended) instead of Zstd.
+pub const ATTR_LZ4: u16 = 0x04;
+
+/// Block compression used when writing frame data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CompressAlgorithm {
+    Zstd(u8),
+    Lz4,
+}
+
+impl CompressAlgorithm {
+    fn compress(self, raw: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            CompressAlgorithm::Zstd(level) => Ok(zstd::encode_all(raw, level as i32)?),
+            CompressAlgorithm::Lz4 => Ok(lz4_flex::compress_prepend_size(raw)),
+        }
+    }
+}
+
+/// Canonical paths of every file currently memory-mapped by a `PlayerState`,
+/// one entry per live mapping. Truncating a mapped file makes later reads
+/// through the mapping fault with SIGBUS, so writers check this list first.
+static MAPPED_PATHS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// A read-only file mapping that stays listed in `MAPPED_PATHS` until dropped.
+pub struct MappedFile {
+    map: memmap2::Mmap,
+    path: PathBuf,
+}
+
+impl MappedFile {
+    fn new(map: memmap2::Mmap, path: &Path) -> Self {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        MAPPED_PATHS.lock().unwrap().push(path.clone());
+        Self { map, path }
+    }
+}
+
+impl std::ops::Deref for MappedFile {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.map
+    }
+}
+
+impl Drop for MappedFile {
+    fn drop(&mut self) {
+        let mut mapped = MAPPED_PATHS.lock().unwrap();
+        if let Some(i) = mapped.iter().position(|p| *p == self.path) {
+            mapped.swap_remove(i);
+        }
+    }
+}
+
+/// Refuse to write `path` while any player has it memory-mapped.
+pub fn ensure_not_mapped(path: &Path) -> Result<()> {
+    // A file that does not exist yet cannot be mapped.
+    let Ok(path) = path.canonicalize() else {
+        return Ok(());
+    };
+    if MAPPED_PATHS.lock().unwrap().contains(&path) {
+        return Err(anyhow!(
+            "{} is open for playback; choose a different output file",
+            path.display()
+        ));
+    }
+    Ok(())
+}
+
 #[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub struct NblHeader {
@@ -29,6 +105,18 @@ pub struct TextureEntry {
     pub cols: u8,
 }
 
+/// Free-form metadata kept in a `.nbmeta` JSON sidecar next to an NBL file,
+/// since the format itself has no room for it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NblMeta {
+    pub author: String,
+    pub description: String,
+    pub tags: Vec<String>,
+    /// UTC, `YYYY-MM-DDTHH:MM:SSZ`.
+    pub created_at: String,
+}
+
 #[allow(dead_code)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Particle {
@@ -42,6 +130,8 @@ pub struct Particle {
 
 pub struct PlayerState {
     pub file: Option<File>,
+    /// Read-only mapping of `file`, set by `load_file_mmap`.
+    pub mmap: Option<MappedFile>,
     pub file_path: Option<PathBuf>,
     pub header: Option<NblHeader>,
     pub textures: Vec<TextureEntry>,
@@ -52,6 +142,165 @@ pub struct PlayerState {
     pub particles: HashMap<i32, Particle>,
     pub is_playing: bool,
     pub frame_timer: f32,
+
+    /// Frames decoded ahead of time by `prefetch_frames`, keyed by frame index.
+    pub prefetch_cache: Arc<Mutex<HashMap<u32, Vec<Particle>>>>,
+    /// Number of worker threads used by `prefetch_frames` (0 disables prefetching).
+    pub prefetch_threads: u8,
+    prefetch_workers: Arc<AtomicUsize>,
+}
+
+/// Summary of a single frame's particles.
+#[derive(Debug, Clone, Default)]
+pub struct FrameStats {
+    pub particle_count: usize,
+    pub bbox_min: [f32; 3],
+    pub bbox_max: [f32; 3],
+    pub mean_pos: [f32; 3],
+    pub mean_size: f32,
+    pub unique_colors: usize,
+}
+
+impl FrameStats {
+    /// Single pass over `particles`; an empty frame yields all zeros.
+    pub fn from_particles<'a>(particles: impl IntoIterator<Item = &'a Particle>) -> Self {
+        let mut count = 0usize;
+        let mut bbox_min = [f32::MAX; 3];
+        let mut bbox_max = [f32::MIN; 3];
+        let mut pos_sum = [0.0f64; 3];
+        let mut size_sum = 0.0f64;
+        let mut colors = HashSet::new();
+        for p in particles {
+            count += 1;
+            for i in 0..3 {
+                bbox_min[i] = bbox_min[i].min(p.pos[i]);
+                bbox_max[i] = bbox_max[i].max(p.pos[i]);
+                pos_sum[i] += p.pos[i] as f64;
+            }
+            size_sum += p.size as f64;
+            colors.insert(p.color);
+        }
+        if count == 0 {
+            return Self::default();
+        }
+        let n = count as f64;
+        Self {
+            particle_count: count,
+            bbox_min,
+            bbox_max,
+            mean_pos: pos_sum.map(|v| (v / n) as f32),
+            mean_size: (size_sum / n) as f32,
+            unique_colors: colors.len(),
+        }
+    }
+}
+
+/// Path of one particle id over the whole animation. Speeds are in units
+/// per frame.
+#[derive(Debug, Clone, Default)]
+pub struct ParticleMotionStats {
+    pub id: i32,
+    pub total_distance: f32,
+    pub max_speed: f32,
+    pub avg_speed: f32,
+    /// Frames the id appears in.
+    pub lifetime_frames: usize,
+}
+
+/// Per-id running totals for `compute_motion_stats`, fed one frame at a time
+/// so files can be analysed without holding every frame.
+#[derive(Default)]
+struct MotionAccumulator {
+    frame: usize,
+    /// Stats so far, last position and last frame seen, frames spent moving.
+    tracks: HashMap<i32, (ParticleMotionStats, [f32; 3], usize, usize)>,
+}
+
+impl MotionAccumulator {
+    fn push_frame<'a>(&mut self, particles: impl IntoIterator<Item = &'a Particle>) {
+        for p in particles {
+            let (stats, last_pos, last_frame, moving) =
+                self.tracks.entry(p.id).or_insert_with(|| {
+                    let stats = ParticleMotionStats {
+                        id: p.id,
+                        ..Default::default()
+                    };
+                    (stats, p.pos, self.frame, 0)
+                });
+            // A track that disappears and comes back spreads the jump over
+            // the missing frames.
+            let gap = self.frame - *last_frame;
+            if gap > 0 {
+                let d = (0..3)
+                    .map(|k| (p.pos[k] - last_pos[k]).powi(2))
+                    .sum::<f32>()
+                    .sqrt();
+                stats.total_distance += d;
+                stats.max_speed = stats.max_speed.max(d / gap as f32);
+                *moving += gap;
+            }
+            stats.lifetime_frames += 1;
+            *last_pos = p.pos;
+            *last_frame = self.frame;
+        }
+        self.frame += 1;
+    }
+
+    /// Longest path first.
+    fn finish(self) -> Vec<ParticleMotionStats> {
+        let mut out: Vec<ParticleMotionStats> = self
+            .tracks
+            .into_values()
+            .map(|(mut stats, _, _, moving)| {
+                if moving > 0 {
+                    stats.avg_speed = stats.total_distance / moving as f32;
+                }
+                stats
+            })
+            .collect();
+        out.sort_by(|a, b| {
+            b.total_distance
+                .total_cmp(&a.total_distance)
+                .then(a.id.cmp(&b.id))
+        });
+        out
+    }
+}
+
+/// Path length and speed of every particle id across `frames`, sorted by
+/// `total_distance`, longest first.
+pub fn compute_motion_stats(frames: &[Vec<Particle>]) -> Vec<ParticleMotionStats> {
+    let mut acc = MotionAccumulator::default();
+    for frame in frames {
+        acc.push_frame(frame);
+    }
+    acc.finish()
+}
+
+/// `compute_motion_stats` over every frame of the file at `path`, streamed
+/// through its own reader so it can run on a worker thread. `progress` is set
+/// to the fraction of frames read.
+pub fn file_motion_stats(path: &Path, progress: &Mutex<f32>) -> Vec<ParticleMotionStats> {
+    let mut reader = PlayerState {
+        prefetch_threads: 0,
+        ..Default::default()
+    };
+    if reader.load_file_mmap(path).is_err() {
+        return Vec::new();
+    }
+    let total = reader.header.as_ref().map_or(0, |h| h.total_frames);
+    reader.particles.clear();
+    let mut acc = MotionAccumulator::default();
+    for f in 0..total {
+        if reader.process_frame(f).is_err() {
+            break;
+        }
+        acc.push_frame(reader.particles.values());
+        if let Ok(mut p) = progress.lock() {
+            *p = (f + 1) as f32 / total as f32;
+        }
+    }
+    acc.finish()
 }
 
 #[derive(Debug)]
@@ -116,6 +365,7 @@ impl Default for PlayerState {
     fn default() -> Self {
         Self {
             file: None,
+            mmap: None,
             file_path: None,
             header: None,
             textures: Vec::new(),
@@ -125,6 +375,9 @@ impl Default for PlayerState {
             particles: HashMap::new(),
             is_playing: false,
             frame_timer: 0.0,
+            prefetch_cache: Arc::new(Mutex::new(HashMap::new())),
+            prefetch_threads: 2,
+            prefetch_workers: Arc::new(AtomicUsize::new(0)),
         }
     }
 }
@@ -132,7 +385,30 @@ impl Default for PlayerState {
 impl PlayerState {
     pub fn load_file(&mut self, path: PathBuf) -> Result<()> {
         let mut f = File::open(&path)?;
+        self.read_index(&mut f)?;
+        self.mmap = None;
+        self.finish_load(f, path)
+    }
 
+    /// Memory-map the file instead of reading frames through `File`. Only the
+    /// header and index tables are parsed up front; frame data is paged in by
+    /// the OS as `seek_to` touches it. Falls back to `load_file` when the file
+    /// cannot be mapped.
+    pub fn load_file_mmap(&mut self, path: &Path) -> Result<()> {
+        let f = File::open(path)?;
+        // SAFETY: the mapping is read-only, writers in this process refuse
+        // mapped paths (`ensure_not_mapped`), and NBL files are not expected
+        // to be modified by other processes while open.
+        let mmap = match unsafe { memmap2::Mmap::map(&f) } {
+            Ok(m) => m,
+            Err(_) => return self.load_file(path.to_path_buf()),
+        };
+        self.read_index(&mut Cursor::new(&mmap[..]))?;
+        self.mmap = Some(MappedFile::new(mmap, path));
+        self.finish_load(f, path.to_path_buf())
+    }
+
+    fn read_index<R: Read + Seek>(&mut self, f: &mut R) -> Result<()> {
         let mut magic = [0u8; 8];
         f.read_exact(&mut magic)?;
         if &magic != MAGIC {
@@ -140,6 +416,9 @@ impl PlayerState {
         }
 
         let version = f.read_u16::<LittleEndian>()?;
+        if version == 0 || version > NBL_VERSION {
+            return Err(anyhow!("Unsupported NBL version: {}", version));
+        }
         let target_fps = f.read_u16::<LittleEndian>()?;
         let total_frames = f.read_u32::<LittleEndian>()?;
         let texture_count = f.read_u16::<LittleEndian>()?;
@@ -186,10 +465,19 @@ impl PlayerState {
             self.keyframe_indices.push(f.read_u32::<LittleEndian>()?);
         }
 
+        Ok(())
+    }
+
+    fn finish_load(&mut self, f: File, path: PathBuf) -> Result<()> {
+        let total_frames = self.header.as_ref().map_or(0, |h| h.total_frames);
         self.file = Some(f);
         self.file_path = Some(path);
         self.current_frame_idx = -1;
         self.particles.clear();
+        // Workers still decoding the previous file keep the old cache and
+        // counter, so they can neither pollute this file nor block prefetching.
+        self.prefetch_cache = Arc::new(Mutex::new(HashMap::new()));
+        self.prefetch_workers = Arc::new(AtomicUsize::new(0));
         self.is_playing = false;
 
         if total_frames > 0 {
@@ -208,6 +496,17 @@ impl PlayerState {
             return Ok(());
         }
 
+        let cached = self
+            .prefetch_cache
+            .lock()
+            .ok()
+            .and_then(|mut cache| cache.remove(&target_frame));
+        if let Some(frame) = cached {
+            self.particles = frame.into_iter().map(|p| (p.id, p)).collect();
+            self.current_frame_idx = target_frame as i32;
+            return Ok(());
+        }
+
         let mut start_frame = 0;
         for &kf in &self.keyframe_indices {
             if kf <= target_frame {
@@ -230,25 +529,214 @@ impl PlayerState {
         Ok(())
     }
 
+    /// Decode only frames `[start, end)`, replaying from the nearest keyframe at
+    /// or before `start`. Uses a separate reader, so playback state is untouched.
+    pub fn decode_frame_range(&self, start: u32, end: u32) -> Result<Vec<Vec<Particle>>> {
+        let path = self
+            .file_path
+            .as_ref()
+            .ok_or_else(|| anyhow!("No file loaded"))?;
+        let end = end.min(self.header.as_ref().map_or(0, |h| h.total_frames));
+        if start >= end {
+            return Ok(Vec::new());
+        }
+        let keyframe = self
+            .keyframe_indices
+            .iter()
+            .copied()
+            .take_while(|&kf| kf <= start)
+            .last()
+            .unwrap_or(0);
+
+        let mut reader = PlayerState {
+            prefetch_threads: 0,
+            ..Default::default()
+        };
+        reader.load_file_mmap(path)?;
+        reader.particles.clear();
+        let mut frames = Vec::with_capacity((end - start) as usize);
+        for f in keyframe..end {
+            reader.process_frame(f)?;
+            if f >= start {
+                let mut frame: Vec<Particle> = reader.particles.values().cloned().collect();
+                frame.sort_unstable_by_key(|p| p.id);
+                frames.push(frame);
+            }
+        }
+        Ok(frames)
+    }
+
+    /// Particles of `frame_idx` without moving the playhead: served from the
+    /// prefetch cache when present, otherwise decoded with `decode_frame_range`.
+    pub fn peek_frame(&self, frame_idx: u32) -> Result<Vec<Particle>> {
+        let cached = self
+            .prefetch_cache
+            .lock()
+            .ok()
+            .and_then(|cache| cache.get(&frame_idx).cloned());
+        if let Some(frame) = cached {
+            return Ok(frame);
+        }
+        self.decode_frame_range(frame_idx, frame_idx + 1)?
+            .pop()
+            .ok_or_else(|| anyhow!("Frame {} out of range", frame_idx))
+    }
+
+    /// Statistics for one frame. The current frame is read from memory; any
+    /// other frame is decoded with `decode_frame_range`.
+    pub fn particle_statistics(&self, frame_idx: u32) -> Result<FrameStats> {
+        if frame_idx as i32 == self.current_frame_idx {
+            return Ok(FrameStats::from_particles(self.particles.values()));
+        }
+        let frames = self.decode_frame_range(frame_idx, frame_idx + 1)?;
+        let frame = frames
+            .first()
+            .ok_or_else(|| anyhow!("Frame {} out of range", frame_idx))?;
+        Ok(FrameStats::from_particles(frame))
+    }
+
+    /// Statistics for every frame, decoded sequentially with a separate
+    /// reader. Stops early at the first frame that fails to decode.
+    pub fn animation_statistics(&self) -> Vec<FrameStats> {
+        let (Some(path), Some(header)) = (&self.file_path, &self.header) else {
+            return Vec::new();
+        };
+        let mut reader = PlayerState {
+            prefetch_threads: 0,
+            ..Default::default()
+        };
+        if reader.load_file_mmap(path).is_err() {
+            return Vec::new();
+        }
+        reader.particles.clear();
+        let mut stats = Vec::with_capacity(header.total_frames as usize);
+        for f in 0..header.total_frames {
+            if reader.process_frame(f).is_err() {
+                break;
+            }
+            stats.push(FrameStats::from_particles(reader.particles.values()));
+        }
+        stats
+    }
+
+    /// Decode frames `current+1..=current+lookahead` on background threads so
+    /// that upcoming `seek_to` calls can be served from `prefetch_cache`.
+    /// Does nothing while a previous batch is still being decoded.
+    pub fn prefetch_frames(&mut self, current: u32, lookahead: u32) {
+        let (Some(path), Some(header)) = (self.file_path.clone(), self.header.as_ref()) else {
+            return;
+        };
+        if self.prefetch_threads == 0 || self.prefetch_workers.load(Ordering::Acquire) > 0 {
+            return;
+        }
+
+        let last = current
+            .saturating_add(lookahead)
+            .min(header.total_frames.saturating_sub(1));
+        let wanted: Vec<u32> = {
+            let Ok(mut cache) = self.prefetch_cache.lock() else {
+                return;
+            };
+            cache.retain(|&k, _| k > current && k <= last);
+            (current + 1..=last)
+                .filter(|f| !cache.contains_key(f))
+                .collect()
+        };
+        if wanted.is_empty() {
+            return;
+        }
+
+        let threads = (self.prefetch_threads as usize).min(wanted.len());
+        let chunk_len = wanted.len().div_ceil(threads);
+        for chunk in wanted.chunks(chunk_len) {
+            let (first, end) = (chunk[0], chunk[chunk.len() - 1]);
+            let path = path.clone();
+            let cache = self.prefetch_cache.clone();
+            let workers = self.prefetch_workers.clone();
+            workers.fetch_add(1, Ordering::AcqRel);
+            std::thread::spawn(move || {
+                let mut reader = PlayerState {
+                    prefetch_threads: 0,
+                    ..Default::default()
+                };
+                if reader.load_file_mmap(&path).is_ok() {
+                    for f in first..=end {
+                        if reader.seek_to(f).is_err() {
+                            break;
+                        }
+                        let snapshot: Vec<Particle> = reader.particles.values().cloned().collect();
+                        if let Ok(mut c) = cache.lock() {
+                            c.insert(f, snapshot);
+                        }
+                    }
+                }
+                workers.fetch_sub(1, Ordering::AcqRel);
+            });
+        }
+    }
+
     pub(crate) fn process_frame(&mut self, frame_idx: u32) -> Result<()> {
         let (offset, size) = self.frame_indices[frame_idx as usize];
-        let file = self.file.as_mut().unwrap();
 
-        file.seek(SeekFrom::Start(offset))?;
-        let mut compressed = vec![0u8; size as usize];
-        file.read_exact(&mut compressed)?;
+        let is_lz4 = self
+            .header
+            .as_ref()
+            .is_some_and(|h| h.attributes & ATTR_LZ4 != 0);
+
+        // Decompress straight from the source so the compressed frame is never
+        // buffered in full alongside its decoded payload.
+        let mut raw_data = Vec::new();
+        if let Some(mmap) = &self.mmap {
+            let start = offset as usize;
+            let end = start + size as usize;
+            let compressed = mmap
+                .get(start..end)
+                .ok_or_else(|| anyhow!("Frame {} lies outside the file", frame_idx))?;
+            if is_lz4 {
+                raw_data = lz4_flex::decompress_size_prepended(compressed)?;
+            } else {
+                zstd::stream::Decoder::new(compressed)?.read_to_end(&mut raw_data)?;
+            }
+        } else {
+            let file = self.file.as_mut().unwrap();
+            file.seek(SeekFrom::Start(offset))?;
+            if is_lz4 {
+                let mut compressed = vec![0u8; size as usize];
+                file.read_exact(&mut compressed)?;
+                raw_data = lz4_flex::decompress_size_prepended(&compressed)?;
+            } else {
+                zstd::stream::Decoder::new(file.take(size as u64))?.read_to_end(&mut raw_data)?;
+            }
+        }
 
-        let raw_data = zstd::decode_all(Cursor::new(compressed))?;
         let mut cursor = Cursor::new(raw_data);
 
         let frame_type = cursor.read_u8()?;
         let particle_count = cursor.read_u32::<LittleEndian>()? as usize;
 
         match frame_type {
-            0 => self.parse_i_frame(&mut cursor, particle_count),
-            1 => self.parse_p_frame(&mut cursor, particle_count),
-            _ => Err(anyhow!("Unknown frame type: {}", frame_type)),
+            0 => self.parse_i_frame(&mut cursor, particle_count)?,
+            1 => self.parse_p_frame(&mut cursor, particle_count)?,
+            _ => return Err(anyhow!("Unknown frame type: {}", frame_type)),
         }
+
+        // v1 files predate the sequence range guarantee and are taken as is
+        if self.header.as_ref().is_some_and(|h| h.version >= 2) {
+            let textures = &self.textures;
+            if let Some(p) = self
+                .particles
+                .values()
+                .find(|p| p.seq_index as usize >= seq_frame_count(textures, p.tex_id))
+            {
+                return Err(anyhow!(
+                    "Frame {}: sequence index {} out of range for texture {}",
+                    frame_idx,
+                    p.seq_index,
+                    p.tex_id
+                ));
+            }
+        }
+        Ok(())
     }
 
     fn parse_i_frame(&mut self, r: &mut Cursor<Vec<u8>>, count: usize) -> Result<()> {
@@ -378,6 +866,7 @@ impl PlayerState {
         textures: &[TextureEntry],
         frames: &[Vec<Particle>],
     ) -> Result<()> {
+        ensure_not_mapped(path)?;
         let mut f = File::create(path)?;
 
         // 1. Header (48 bytes)
@@ -527,6 +1016,7 @@ impl PlayerState {
     ) -> Result<()> {
         chunks.sort_unstable_by_key(|chunk| chunk.start_frame);
 
+        ensure_not_mapped(path)?;
         let file = File::create(path)?;
         let mut writer = BufWriter::new(file);
 
@@ -718,23 +1208,1221 @@ fn lerp_u8(a: u8, b: u8, t: f32) -> u8 {
         .clamp(0.0, 255.0) as u8
 }
 
-/// Recalculate the AABB bounding box from frame data.
+/// Overlay several NBL files into one animation. Particle and texture ids are
+/// offset per layer so they stay unique. The result uses the first layer's
+/// FPS and runs as long as the longest layer.
+pub fn merge_nbl_layers(paths: &[PathBuf]) -> Result<(u16, Vec<TextureEntry>, Vec<Vec<Particle>>)> {
+    let mut fps = None;
+    let mut textures: Vec<TextureEntry> = Vec::new();
+    let mut merged: Vec<Vec<Particle>> = Vec::new();
+    let mut id_offset = 0i32;
+    for path in paths {
+        let mut player = PlayerState {
+            prefetch_threads: 0,
+            ..Default::default()
+        };
+        player.load_file_mmap(path)?;
+        let header = player.header.clone().ok_or(anyhow!("No header"))?;
+        fps.get_or_insert(header.target_fps);
+        let frames = player.decode_frame_range(0, header.total_frames)?;
+
+        let tex_offset = textures.len() as u8;
+        let mut max_id = -1;
+        if merged.len() < frames.len() {
+            merged.resize_with(frames.len(), Vec::new);
+        }
+        for (out, frame) in merged.iter_mut().zip(frames) {
+            for mut p in frame {
+                max_id = max_id.max(p.id);
+                p.id += id_offset;
+                p.tex_id = p.tex_id.saturating_add(tex_offset);
+                out.push(p);
+            }
+        }
+        id_offset += max_id + 1;
+        textures.extend(player.textures.iter().cloned());
+    }
+    let fps = fps.ok_or(anyhow!("No layers to merge"))?;
+    Ok((fps, textures, merged))
+}
+
+/// Sequence frames available to particles using `tex_id`: the texture's
+/// `rows * cols`, or 1 when `tex_id` has no texture entry.
+fn seq_frame_count(textures: &[TextureEntry], tex_id: u8) -> usize {
+    textures
+        .get(tex_id as usize)
+        .map_or(1, |t| (t.rows as usize * t.cols as usize).max(1))
+}
+
+/// Read only the format version from an NBL header.
+pub fn check_nbl_version(path: &Path) -> Result<u16> {
+    let mut f = File::open(path)?;
+    let mut magic = [0u8; 8];
+    f.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(anyhow!("Invalid NBL file: Magic mismatch"));
+    }
+    Ok(f.read_u16::<LittleEndian>()?)
+}
+
+/// Rewrite a v1 NBL as v2 with every sequence index set to 0, since v1 never
+/// guaranteed they match the texture grid. Frames are re-encoded as
+/// keyframes; textures and bbox are carried over.
+pub fn migrate_v1_to_v2(path: &Path, output: &Path) -> Result<()> {
+    let version = check_nbl_version(path)?;
+    if version != 1 {
+        return Err(anyhow!("Expected a v1 file, found v{}", version));
+    }
+    let mut player = PlayerState {
+        prefetch_threads: 0,
+        ..Default::default()
+    };
+    player.load_file_mmap(path)?;
+    let mut header = player
+        .header
+        .clone()
+        .ok_or_else(|| anyhow!("Missing header"))?;
+    let mut frames = player.decode_frame_range(0, header.total_frames)?;
+    for p in frames.iter_mut().flatten() {
+        p.seq_index = 0;
+    }
+    header.version = 2;
+    // save_file writes Zstd blocks
+    header.attributes &= !ATTR_LZ4;
+    player.save_file(&output.to_path_buf(), &header, &player.textures, &frames)
+}
+
+/// `anim.nbl` → `anim.nbl.nbmeta`.
+fn meta_path(nbl_path: &Path) -> PathBuf {
+    let mut name = nbl_path.as_os_str().to_owned();
+    name.push(".nbmeta");
+    PathBuf::from(name)
+}
+
+/// Sidecar metadata of `nbl_path`; `None` if there is none or it is invalid.
+pub fn load_meta(nbl_path: &Path) -> Option<NblMeta> {
+    let text = std::fs::read_to_string(meta_path(nbl_path)).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+pub fn save_meta(nbl_path: &Path, meta: &NblMeta) -> Result<()> {
+    std::fs::write(meta_path(nbl_path), serde_json::to_string_pretty(meta)?)?;
+    Ok(())
+}
+
+/// Current time as `YYYY-MM-DDTHH:MM:SSZ`, for `NblMeta::created_at`.
+pub fn utc_timestamp_now() -> String {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let (days, rem) = (secs / 86400, secs % 86400);
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+const E57_PAGE_SIZE: usize = 1024;
+/// Page payload; the last 4 bytes of every page hold its CRC-32C.
+const E57_PAGE_DATA: usize = E57_PAGE_SIZE - 4;
+/// Points per data packet: a 20-byte header plus 16 bytes per point keeps
+/// each packet under the 64 KiB limit.
+const E57_POINTS_PER_PACKET: usize = 4000;
+
+fn crc32c(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0x82F6_3B78
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn e57_physical_offset(logical: usize) -> u64 {
+    ((logical / E57_PAGE_DATA) * E57_PAGE_SIZE + logical % E57_PAGE_DATA) as u64
+}
+
+fn e57_guid() -> String {
+    let b: [u8; 16] = rand::random();
+    format!(
+        "{{{:02X}{:02X}{:02X}{:02X}-{:02X}{:02X}-{:02X}{:02X}-{:02X}{:02X}-{:02X}{:02X}{:02X}{:02X}{:02X}{:02X}}}",
+        b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7], b[8], b[9], b[10], b[11], b[12], b[13],
+        b[14], b[15]
+    )
+}
+
+/// Write one frame as an ASTM E57 point cloud: a single scan whose points
+/// carry `cartesianX/Y/Z` as single floats and `colorRed/Green/Blue` as 8-bit
+/// integers. E57 has no alpha field, so alpha is stored as `intensity`.
+pub fn export_e57_frame(frame: &[Particle], path: &Path) -> Result<()> {
+    // Logical byte stream; split into checksummed pages at the end.
+    let mut data: Vec<u8> = vec![0; 48];
+
+    // Binary section: CompressedVector header, then data packets.
+    let section_start = data.len();
+    data.extend_from_slice(&[0u8; 32]);
+    let packets_start = data.len();
+    for chunk in frame.chunks(E57_POINTS_PER_PACKET) {
+        let n = chunk.len();
+        let stream_lens = [4 * n, 4 * n, 4 * n, n, n, n, n];
+        let packet_len = 6 + 2 * stream_lens.len() + stream_lens.iter().sum::<usize>();
+        data.write_u8(1)?; // data packet
+        data.write_u8(0)?;
+        data.write_u16::<LittleEndian>((packet_len - 1) as u16)?;
+        data.write_u16::<LittleEndian>(stream_lens.len() as u16)?;
+        for len in stream_lens {
+            data.write_u16::<LittleEndian>(len as u16)?;
+        }
+        for axis in 0..3 {
+            for p in chunk {
+                data.write_f32::<LittleEndian>(p.pos[axis])?;
+            }
+        }
+        for channel in 0..4 {
+            data.extend(chunk.iter().map(|p| p.color[channel]));
+        }
+    }
+    let section_len = data.len() - section_start;
+    {
+        let mut header = &mut data[section_start..packets_start];
+        header.write_u8(1)?; // compressed vector section
+        header.write_all(&[0u8; 7])?;
+        header.write_u64::<LittleEndian>(section_len as u64)?;
+        header.write_u64::<LittleEndian>(e57_physical_offset(packets_start))?;
+        header.write_u64::<LittleEndian>(0)?; // no index packets
+    }
+
+    let limit = |name: &str| {
+        format!(
+            "<{n}Minimum type=\"Integer\">0</{n}Minimum><{n}Maximum type=\"Integer\">255</{n}Maximum>",
+            n = name
+        )
+    };
+    let integer =
+        |name: &str| format!("<{} type=\"Integer\" minimum=\"0\" maximum=\"255\"/>", name);
+    let float = |name: &str| format!("<{} type=\"Float\" precision=\"single\"/>", name);
+    let xml = format!(
+        concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<e57Root type=\"Structure\" xmlns=\"http://www.astm.org/COMMIT/E57/2010-e57-v1.0\">",
+            "<formatName type=\"String\"><![CDATA[ASTM E57 3D Imaging Data File]]></formatName>",
+            "<guid type=\"String\"><![CDATA[{root_guid}]]></guid>",
+            "<versionMajor type=\"Integer\">1</versionMajor>",
+            "<versionMinor type=\"Integer\">0</versionMinor>",
+            "<coordinateMetadata type=\"String\"></coordinateMetadata>",
+            "<data3D type=\"Vector\" allowHeterogeneousChildren=\"1\">",
+            "<vectorChild type=\"Structure\">",
+            "<guid type=\"String\"><![CDATA[{scan_guid}]]></guid>",
+            "<name type=\"String\"><![CDATA[NebulaTools frame]]></name>",
+            "<colorLimits type=\"Structure\">{red}{green}{blue}</colorLimits>",
+            "<intensityLimits type=\"Structure\">{intensity}</intensityLimits>",
+            "<points type=\"CompressedVector\" fileOffset=\"{offset}\" recordCount=\"{count}\">",
+            "<prototype type=\"Structure\">{x}{y}{z}{r}{g}{b}{i}</prototype>",
+            "<codecs type=\"Vector\" allowHeterogeneousChildren=\"1\"></codecs>",
+            "</points></vectorChild></data3D>",
+            "<images2D type=\"Vector\" allowHeterogeneousChildren=\"1\"></images2D>",
+            "</e57Root>\n"
+        ),
+        root_guid = e57_guid(),
+        scan_guid = e57_guid(),
+        red = limit("colorRed"),
+        green = limit("colorGreen"),
+        blue = limit("colorBlue"),
+        intensity = limit("intensity"),
+        offset = e57_physical_offset(section_start),
+        count = frame.len(),
+        x = float("cartesianX"),
+        y = float("cartesianY"),
+        z = float("cartesianZ"),
+        r = integer("colorRed"),
+        g = integer("colorGreen"),
+        b = integer("colorBlue"),
+        i = integer("intensity"),
+    );
+    let xml_start = data.len();
+    data.extend_from_slice(xml.as_bytes());
+
+    let page_count = data.len().div_ceil(E57_PAGE_DATA);
+    {
+        let mut header = &mut data[..48];
+        header.write_all(b"ASTM-E57")?;
+        header.write_u32::<LittleEndian>(1)?;
+        header.write_u32::<LittleEndian>(0)?;
+        header.write_u64::<LittleEndian>((page_count * E57_PAGE_SIZE) as u64)?;
+        header.write_u64::<LittleEndian>(e57_physical_offset(xml_start))?;
+        header.write_u64::<LittleEndian>(xml.len() as u64)?;
+        header.write_u64::<LittleEndian>(E57_PAGE_SIZE as u64)?;
+    }
+    data.resize(page_count * E57_PAGE_DATA, 0);
+
+    ensure_not_mapped(path)?;
+    let mut writer = BufWriter::new(File::create(path)?);
+    for page in data.chunks(E57_PAGE_DATA) {
+        writer.write_all(page)?;
+        writer.write_u32::<byteorder::BigEndian>(crc32c(page))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// `s` as a YAML double-quoted scalar, so names containing `:`, `#`, quotes
+/// or leading spaces cannot break the document.
+fn yaml_quoted(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04X}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Write one frame as a Unity prefab (YAML, Unity 2021.3 and later): a
+/// GameObject with a non-emitting `ParticleSystem` sized to the frame and a
+/// `ParticleSystemRenderer`. The frame itself is stored in the component's
+/// `m_Particles` list with position, colour and size per particle; Unity has
+/// no built-in serialized particle state, so a loader script must read the
+/// list and apply it with `ParticleSystem.SetParticles`. Z is negated to go
+/// from NBL's right-handed space to Unity's left-handed one.
+pub fn export_unity_prefab_frame(frame: &[Particle], path: &Path) -> Result<()> {
+    ensure_not_mapped(path)?;
+    let mut w = BufWriter::new(File::create(path)?);
+    let name = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "NebulaFrame".into());
+    write!(
+        w,
+        "%YAML 1.1
+%TAG !u! tag:unity3d.com,2011:
+--- !u!1 &100000
+GameObject:
+  m_ObjectHideFlags: 0
+  serializedVersion: 6
+  m_Component:
+  - component: {{fileID: 400000}}
+  - component: {{fileID: 19800000}}
+  - component: {{fileID: 19900000}}
+  m_Layer: 0
+  m_Name: {name}
+  m_TagString: Untagged
+  m_IsActive: 1
+--- !u!4 &400000
+Transform:
+  m_ObjectHideFlags: 0
+  m_GameObject: {{fileID: 100000}}
+  serializedVersion: 2
+  m_LocalRotation: {{x: 0, y: 0, z: 0, w: 1}}
+  m_LocalPosition: {{x: 0, y: 0, z: 0}}
+  m_LocalScale: {{x: 1, y: 1, z: 1}}
+  m_Children: []
+  m_Father: {{fileID: 0}}
+--- !u!198 &19800000
+ParticleSystem:
+  m_ObjectHideFlags: 0
+  m_GameObject: {{fileID: 100000}}
+  serializedVersion: 8
+  lengthInSec: 5
+  looping: 0
+  prewarm: 0
+  playOnAwake: 0
+  moveWithTransform: 0
+  InitialModule:
+    serializedVersion: 3
+    enabled: 1
+    maxNumParticles: {count}
+  EmissionModule:
+    enabled: 0
+  m_Particles:
+",
+        name = yaml_quoted(&name),
+        count = frame.len().max(1),
+    )?;
+    for p in frame {
+        writeln!(
+            w,
+            "  - m_Position: {{x: {}, y: {}, z: {}}}",
+            p.pos[0], p.pos[1], -p.pos[2]
+        )?;
+        writeln!(
+            w,
+            "    m_Color: {{r: {}, g: {}, b: {}, a: {}}}",
+            p.color[0], p.color[1], p.color[2], p.color[3]
+        )?;
+        writeln!(w, "    m_Size: {}", p.size)?;
+    }
+    write!(
+        w,
+        "--- !u!199 &19900000
+ParticleSystemRenderer:
+  m_ObjectHideFlags: 0
+  m_GameObject: {{fileID: 100000}}
+  m_Enabled: 1
+  m_RenderMode: 0
+"
+    )?;
+    w.flush()?;
+    Ok(())
+}
+
+const PLY_DEFAULT_SIZE: f32 = 0.1;
+
+#[derive(Clone, Copy)]
+enum PlyScalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl PlyScalar {
+    fn parse(name: &str) -> Result<Self> {
+        Ok(match name {
+            "char" | "int8" => PlyScalar::I8,
+            "uchar" | "uint8" => PlyScalar::U8,
+            "short" | "int16" => PlyScalar::I16,
+            "ushort" | "uint16" => PlyScalar::U16,
+            "int" | "int32" => PlyScalar::I32,
+            "uint" | "uint32" => PlyScalar::U32,
+            "float" | "float32" => PlyScalar::F32,
+            "double" | "float64" => PlyScalar::F64,
+            other => return Err(anyhow!("Unknown PLY property type `{}`", other)),
+        })
+    }
+
+    fn read_binary<R: Read>(self, r: &mut R) -> Result<f64> {
+        Ok(match self {
+            PlyScalar::I8 => r.read_i8()? as f64,
+            PlyScalar::U8 => r.read_u8()? as f64,
+            PlyScalar::I16 => r.read_i16::<LittleEndian>()? as f64,
+            PlyScalar::U16 => r.read_u16::<LittleEndian>()? as f64,
+            PlyScalar::I32 => r.read_i32::<LittleEndian>()? as f64,
+            PlyScalar::U32 => r.read_u32::<LittleEndian>()? as f64,
+            PlyScalar::F32 => r.read_f32::<LittleEndian>()? as f64,
+            PlyScalar::F64 => r.read_f64::<LittleEndian>()?,
+        })
+    }
+
+    fn is_float(self) -> bool {
+        matches!(self, PlyScalar::F32 | PlyScalar::F64)
+    }
+}
+
+struct PlyProperty {
+    name: String,
+    ty: PlyScalar,
+    /// Count type of a `property list`; list values are read and dropped.
+    list_count: Option<PlyScalar>,
+}
+
+struct PlyElement {
+    name: String,
+    count: usize,
+    properties: Vec<PlyProperty>,
+}
+
+/// Parse an ASCII or binary little-endian PLY file into one particle per
+/// vertex. `x/y/z` become `pos`, `red/green/blue/alpha` become `color`
+/// (floats are taken as 0–1) and `scalar_intensity` becomes `size`.
+pub fn import_ply_frame(path: &Path) -> Result<Vec<Particle>> {
+    let bytes = std::fs::read(path)?;
+    let mut cursor = Cursor::new(&bytes[..]);
+
+    let mut binary = false;
+    let mut elements: Vec<PlyElement> = Vec::new();
+    let mut line = String::new();
+    let mut first = true;
+    loop {
+        line.clear();
+        if std::io::BufRead::read_line(&mut cursor, &mut line)? == 0 {
+            return Err(anyhow!("PLY header has no end_header"));
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if first {
+            if words != ["ply"] {
+                return Err(anyhow!("Not a PLY file"));
+            }
+            first = false;
+            continue;
+        }
+        match words.as_slice() {
+            ["format", "ascii", _] => binary = false,
+            ["format", "binary_little_endian", _] => binary = true,
+            ["format", other, _] => return Err(anyhow!("Unsupported PLY format `{}`", other)),
+            ["element", name, count] => elements.push(PlyElement {
+                name: name.to_string(),
+                count: count.parse()?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count_ty, ty, name] => elements
+                .last_mut()
+                .ok_or_else(|| anyhow!("PLY property outside an element"))?
+                .properties
+                .push(PlyProperty {
+                    name: name.to_string(),
+                    ty: PlyScalar::parse(ty)?,
+                    list_count: Some(PlyScalar::parse(count_ty)?),
+                }),
+            ["property", ty, name] => elements
+                .last_mut()
+                .ok_or_else(|| anyhow!("PLY property outside an element"))?
+                .properties
+                .push(PlyProperty {
+                    name: name.to_string(),
+                    ty: PlyScalar::parse(ty)?,
+                    list_count: None,
+                }),
+            ["end_header"] => break,
+            _ => {}
+        }
+    }
+
+    let body = &bytes[cursor.position() as usize..];
+    let mut binary_body = Cursor::new(body);
+    let text = if binary {
+        String::new()
+    } else {
+        String::from_utf8_lossy(body).into_owned()
+    };
+    let mut tokens = text.split_whitespace();
+    let mut next_ascii = || -> Result<f64> {
+        Ok(tokens
+            .next()
+            .ok_or_else(|| anyhow!("PLY data ended early"))?
+            .parse()?)
+    };
+
+    let mut frame = Vec::new();
+    for element in &elements {
+        let is_vertex = element.name == "vertex";
+        for index in 0..element.count {
+            let mut particle = Particle {
+                id: index as i32,
+                pos: [0.0; 3],
+                color: [255; 4],
+                size: PLY_DEFAULT_SIZE,
+                tex_id: 0,
+                seq_index: 0,
+            };
+            for prop in &element.properties {
+                let mut read = |ty: PlyScalar| {
+                    if binary {
+                        ty.read_binary(&mut binary_body)
+                    } else {
+                        next_ascii()
+                    }
+                };
+                if let Some(count_ty) = prop.list_count {
+                    let n = read(count_ty)? as usize;
+                    for _ in 0..n {
+                        read(prop.ty)?;
+                    }
+                    continue;
+                }
+                let value = read(prop.ty)?;
+                let channel = |v: f64| {
+                    let v = if prop.ty.is_float() { v * 255.0 } else { v };
+                    v.round().clamp(0.0, 255.0) as u8
+                };
+                match prop.name.as_str() {
+                    "x" => particle.pos[0] = value as f32,
+                    "y" => particle.pos[1] = value as f32,
+                    "z" => particle.pos[2] = value as f32,
+                    "red" => particle.color[0] = channel(value),
+                    "green" => particle.color[1] = channel(value),
+                    "blue" => particle.color[2] = channel(value),
+                    "alpha" => particle.color[3] = channel(value),
+                    "scalar_intensity" => particle.size = value as f32,
+                    _ => {}
+                }
+            }
+            if is_vertex {
+                frame.push(particle);
+            }
+        }
+        if is_vertex {
+            break;
+        }
+    }
+    Ok(frame)
+}
+
+/// Running bounding box fed one particle at a time, so callers that stream
+/// frames never need to hold them all.
+pub struct BboxAccumulator {
+    min: [f32; 3],
+    max: [f32; 3],
+}
+
+impl Default for BboxAccumulator {
+    fn default() -> Self {
+        Self {
+            min: [f32::MAX; 3],
+            max: [f32::MIN; 3],
+        }
+    }
+}
+
+impl BboxAccumulator {
+    pub fn update(&mut self, particle: &Particle) {
+        for i in 0..3 {
+            self.min[i] = self.min[i].min(particle.pos[i]);
+            self.max[i] = self.max[i].max(particle.pos[i]);
+        }
+    }
+
+    /// Combine the extents seen by two accumulators.
+    pub fn merge(mut self, other: Self) -> Self {
+        for i in 0..3 {
+            self.min[i] = self.min[i].min(other.min[i]);
+            self.max[i] = self.max[i].max(other.max[i]);
+        }
+        self
+    }
+
+    /// The accumulated `(min, max)`; all zeros if no particle was seen.
+    pub fn finish(self) -> ([f32; 3], [f32; 3]) {
+        if self.min[0] == f32::MAX {
+            ([0.0; 3], [0.0; 3])
+        } else {
+            (self.min, self.max)
+        }
+    }
+}
+
+/// Particle count below which `recalculate_bbox` stays on the calling thread.
+const BBOX_PARALLEL_MIN: usize = 1 << 20;
+
+/// Recalculate the AABB bounding box from frame data. Large inputs are split
+/// into runs of frames that are scanned on separate threads.
 pub fn recalculate_bbox(frames: &[Vec<Particle>]) -> ([f32; 3], [f32; 3]) {
-    let mut bbox_min = [f32::MAX; 3];
-    let mut bbox_max = [f32::MIN; 3];
-    for frame in frames {
+    let scan = |run: &[Vec<Particle>]| {
+        let mut bbox = BboxAccumulator::default();
+        for p in run.iter().flatten() {
+            bbox.update(p);
+        }
+        bbox
+    };
+    let total: usize = frames.iter().map(Vec::len).sum();
+    let threads = std::thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(frames.len());
+    if total < BBOX_PARALLEL_MIN || threads < 2 {
+        return scan(frames).finish();
+    }
+    let run_len = frames.len().div_ceil(threads);
+    std::thread::scope(|s| {
+        let handles: Vec<_> = frames
+            .chunks(run_len)
+            .map(|run| s.spawn(move || scan(run)))
+            .collect();
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .fold(BboxAccumulator::default(), BboxAccumulator::merge)
+    })
+    .finish()
+}
+
+/// Translate, rotate (Euler XYZ, degrees) and scale only the particles whose
+/// ids are in `ids`. Rotation and scale pivot on the selection's centroid in
+/// each frame.
+pub fn transform_selection(
+    frames: &mut [Vec<Particle>],
+    ids: &HashSet<i32>,
+    translate: [f32; 3],
+    rotate_deg: [f32; 3],
+    scale: f32,
+) {
+    let [rx, ry, rz] = rotate_deg.map(f32::to_radians);
+    let (sx, cx) = rx.sin_cos();
+    let (sy, cy) = ry.sin_cos();
+    let (sz, cz) = rz.sin_cos();
+    for frame in frames.iter_mut() {
+        let mut centroid = [0.0f32; 3];
+        let mut count = 0usize;
+        for p in frame.iter().filter(|p| ids.contains(&p.id)) {
+            for (c, v) in centroid.iter_mut().zip(p.pos) {
+                *c += v;
+            }
+            count += 1;
+        }
+        if count == 0 {
+            continue;
+        }
+        centroid = centroid.map(|c| c / count as f32);
+
+        for p in frame.iter_mut().filter(|p| ids.contains(&p.id)) {
+            let [x, y, z] = [
+                (p.pos[0] - centroid[0]) * scale,
+                (p.pos[1] - centroid[1]) * scale,
+                (p.pos[2] - centroid[2]) * scale,
+            ];
+            // X
+            let (y, z) = (y * cx - z * sx, y * sx + z * cx);
+            // Y
+            let (x, z) = (x * cy + z * sy, -x * sy + z * cy);
+            // Z
+            let (x, y) = (x * cz - y * sz, x * sz + y * cz);
+            p.pos = [
+                x + centroid[0] + translate[0],
+                y + centroid[1] + translate[1],
+                z + centroid[2] + translate[2],
+            ];
+        }
+    }
+}
+
+/// Keep/drop decisions of `edit_deflate_particles`, carried across frames.
+#[derive(Default)]
+pub struct DeflateState {
+    kept: HashSet<i32>,
+    dropped: HashSet<i32>,
+}
+
+/// Thin out particles that sit closer than `radius` to a kept one. Each id is
+/// decided in the first frame it appears in and keeps that decision after,
+/// so thinned areas don't flicker as particles move. New ids are visited in
+/// slice order and tested against every kept particle of the frame. The
+/// result is sorted by id.
+pub fn edit_deflate_particles(
+    particles: Vec<Particle>,
+    radius: f32,
+    state: &mut DeflateState,
+) -> Vec<Particle> {
+    if radius <= 0.0 {
+        return particles;
+    }
+    let radius_sq = radius * radius;
+    let mut grid = SpatialHash::new(radius);
+    let mut kept: Vec<Particle> = Vec::with_capacity(particles.len());
+    let mut undecided = Vec::new();
+    for p in particles {
+        if state.kept.contains(&p.id) {
+            grid.insert(p.pos, kept.len());
+            kept.push(p);
+        } else if !state.dropped.contains(&p.id) {
+            undecided.push(p);
+        }
+    }
+
+    for p in undecided {
+        let crowded = grid.query_neighbours(p.pos).any(|i| {
+            let q = &kept[i];
+            let dx = q.pos[0] - p.pos[0];
+            let dy = q.pos[1] - p.pos[1];
+            let dz = q.pos[2] - p.pos[2];
+            dx * dx + dy * dy + dz * dz < radius_sq
+        });
+        if crowded {
+            state.dropped.insert(p.id);
+        } else {
+            state.kept.insert(p.id);
+            grid.insert(p.pos, kept.len());
+            kept.push(p);
+        }
+    }
+    kept.sort_unstable_by_key(|p| p.id);
+    kept
+}
+
+/// Snap every particle position to the nearest multiple of `grid_size` for a
+/// voxel look. A non-positive grid size leaves the frames unchanged.
+pub fn edit_quantise_positions(frames: &mut [Vec<Particle>], grid_size: f32) {
+    if grid_size <= 0.0 {
+        return;
+    }
+    for p in frames.iter_mut().flatten() {
+        for v in &mut p.pos {
+            *v = (*v / grid_size).round() * grid_size;
+        }
+    }
+}
+
+/// Keep only the first particle (in slice order) at each grid cell.
+pub fn merge_voxel_duplicates(particles: Vec<Particle>, grid_size: f32) -> Vec<Particle> {
+    if grid_size <= 0.0 {
+        return particles;
+    }
+    let mut seen = HashSet::new();
+    particles
+        .into_iter()
+        .filter(|p| seen.insert(p.pos.map(|v| (v / grid_size).round() as i64)))
+        .collect()
+}
+
+/// Particle-level difference between two frames, matched by id.
+#[derive(Debug, Clone, Default)]
+pub struct CompareResult {
+    /// In `b` but not `a`.
+    pub added: Vec<Particle>,
+    /// In `a` but not `b`.
+    pub removed: Vec<Particle>,
+    /// `(old, new)` for ids whose position changed beyond P-frame precision.
+    pub moved: Vec<(Particle, Particle)>,
+}
+
+pub fn compare_frames(a: &[Particle], b: &[Particle]) -> CompareResult {
+    let quantize = |pos: [f32; 3]| pos.map(|v| (v * 1000.0).round() as i32);
+    let old: HashMap<i32, &Particle> = a.iter().map(|p| (p.id, p)).collect();
+    let new_ids: HashSet<i32> = b.iter().map(|p| p.id).collect();
+    let mut result = CompareResult::default();
+    for p in b {
+        match old.get(&p.id) {
+            None => result.added.push(p.clone()),
+            Some(prev) if quantize(prev.pos) != quantize(p.pos) => {
+                result.moved.push(((*prev).clone(), p.clone()))
+            }
+            Some(_) => {}
+        }
+    }
+    result.removed = a
+        .iter()
+        .filter(|p| !new_ids.contains(&p.id))
+        .cloned()
+        .collect();
+    result
+}
+
+/// Drop the in-between keyframes of constant-velocity stretches. Along each
+/// id's run of consecutive frames, a particle is left out where its colour,
+/// size and texture match the run's start and its position lies within
+/// `tolerance` (per axis) of the straight line between the run's endpoints.
+/// Run endpoints are always kept, so linear interpolation between the
+/// remaining keyframes, as `tracks_to_frames_interpolated` does for sparse
+/// tracks, gives back the dropped ones to within `tolerance`.
+///
+/// Ids are assumed to live from their first to their last appearance; a gap
+/// in the input is filled in by the interpolation as well.
+pub fn compress_linear_keyframes(frames: &[Vec<Particle>], tolerance: f32) -> Vec<Vec<Particle>> {
+    let mut tracks: HashMap<i32, Vec<(usize, &Particle)>> = HashMap::new();
+    for (fi, frame) in frames.iter().enumerate() {
         for p in frame {
-            for i in 0..3 {
-                bbox_min[i] = bbox_min[i].min(p.pos[i]);
-                bbox_max[i] = bbox_max[i].max(p.pos[i]);
+            tracks.entry(p.id).or_default().push((fi, p));
+        }
+    }
+    let same_attributes = |a: &Particle, b: &Particle| {
+        a.color == b.color && a.size == b.size && a.tex_id == b.tex_id && a.seq_index == b.seq_index
+    };
+
+    let mut dropped: HashSet<(usize, i32)> = HashSet::new();
+    for (id, track) in &tracks {
+        let mut start = 0;
+        while start + 1 < track.len() {
+            let (f0, a) = track[start];
+            let (f1, b) = track[start + 1];
+            if f1 != f0 + 1 || !same_attributes(a, b) {
+                start += 1;
+                continue;
+            }
+            // Every interior point bounds the per-tick velocity from `a` to
+            // the end point: `|a + v * t - p| <= tolerance` on each axis.
+            // A candidate end is accepted while its own velocity stays
+            // inside all of those bounds.
+            let mut lo = [f32::NEG_INFINITY; 3];
+            let mut hi = [f32::INFINITY; 3];
+            let mut end = start + 1;
+            while let Some(&(fi, p)) = track.get(end + 1) {
+                let (fe, e) = track[end];
+                if fi != fe + 1 || !same_attributes(a, p) {
+                    break;
+                }
+                let te = (fe - f0) as f32;
+                let next_lo: [f32; 3] =
+                    std::array::from_fn(|k| lo[k].max((e.pos[k] - a.pos[k] - tolerance) / te));
+                let next_hi: [f32; 3] =
+                    std::array::from_fn(|k| hi[k].min((e.pos[k] - a.pos[k] + tolerance) / te));
+                let ticks = (fi - f0) as f32;
+                let on_line = (0..3).all(|k| {
+                    let v = (p.pos[k] - a.pos[k]) / ticks;
+                    next_lo[k] <= v && v <= next_hi[k]
+                });
+                if !on_line {
+                    break;
+                }
+                (lo, hi) = (next_lo, next_hi);
+                end += 1;
+            }
+            dropped.extend(track[start + 1..end].iter().map(|&(fi, _)| (fi, *id)));
+            start = end;
+        }
+    }
+
+    frames
+        .iter()
+        .enumerate()
+        .map(|(fi, frame)| {
+            frame
+                .iter()
+                .filter(|p| !dropped.contains(&(fi, p.id)))
+                .cloned()
+                .collect()
+        })
+        .collect()
+}
+
+/// Move every particle `compress_linear_keyframes` would drop onto the line
+/// between its run's endpoints. The frames keep all their particles, but each
+/// near-linear run now has one constant per-tick step, which the P-frame delta
+/// codec stores as repeated deltas that compress well.
+pub fn straighten_linear_runs(frames: &[Vec<Particle>], tolerance: f32) -> Vec<Vec<Particle>> {
+    let sparse = compress_linear_keyframes(frames, tolerance);
+    let mut kept: HashMap<i32, Vec<(usize, [f32; 3])>> = HashMap::new();
+    for (fi, frame) in sparse.iter().enumerate() {
+        for p in frame {
+            kept.entry(p.id).or_default().push((fi, p.pos));
+        }
+    }
+
+    frames
+        .iter()
+        .enumerate()
+        .map(|(fi, frame)| {
+            frame
+                .iter()
+                .map(|p| {
+                    let track = &kept[&p.id];
+                    match track.binary_search_by_key(&fi, |&(f, _)| f) {
+                        Ok(_) => p.clone(),
+                        // Dropped particles always sit strictly inside a run.
+                        Err(next) => {
+                            let (f0, a) = track[next - 1];
+                            let (f1, b) = track[next];
+                            let t = (fi - f0) as f32 / (f1 - f0) as f32;
+                            Particle {
+                                pos: std::array::from_fn(|k| a[k] + (b[k] - a[k]) * t),
+                                ..p.clone()
+                            }
+                        }
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Remove particles that share a position within a frame, keeping the first
+/// occurrence. Positions are compared at the P-frame precision (1/1000 unit).
+/// Returns the number of particles removed, or an error if a position is too
+/// large (or not finite) to be compared at that precision.
+pub fn edit_deduplicate_particles(frames: &mut [Vec<Particle>]) -> Result<usize> {
+    const LIMIT: f32 = i32::MAX as f32 / 1000.0;
+    let mut removed = 0;
+    for frame in frames.iter_mut() {
+        if let Some(p) = frame
+            .iter()
+            .find(|p| p.pos.iter().any(|v| !v.is_finite() || v.abs() >= LIMIT))
+        {
+            return Err(anyhow!(
+                "Particle {} at {:?} is outside the deduplicate range (±{})",
+                p.id,
+                p.pos,
+                LIMIT
+            ));
+        }
+        let before = frame.len();
+        let mut seen = HashSet::with_capacity(before);
+        frame.retain(|p| seen.insert(p.pos.map(|v| (v * 1000.0).round() as i32)));
+        removed += before - frame.len();
+    }
+    Ok(removed)
+}
+
+/// Offset every particle by Gaussian noise with standard deviation
+/// `amplitude` on each axis. The noise comes from an RNG seeded with
+/// `seed ^ id`, so a particle keeps the same offset in every frame and the
+/// same seed always gives the same result.
+pub fn edit_jitter_positions(frames: &mut [Vec<Particle>], amplitude: f32, seed: u64) {
+    use rand::{rngs::SmallRng, Rng, SeedableRng};
+    for p in frames.iter_mut().flatten() {
+        let mut rng = SmallRng::seed_from_u64(seed ^ p.id as u64);
+        for v in &mut p.pos {
+            // Box-Muller; the lower bound keeps ln() finite
+            let u1: f32 = rng.gen_range(f32::EPSILON..1.0);
+            let u2: f32 = rng.gen();
+            let gauss = (-2.0 * u1.ln()).sqrt() * (std::f32::consts::TAU * u2).cos();
+            *v += gauss * amplitude;
+        }
+    }
+}
+
+/// Horizontal displacement the wind adds in frame `frame_idx` of
+/// `total_frames`: `direction * strength`, varied by up to `turbulence` times
+/// itself over one sine period across the animation.
+fn wind_step(
+    frame_idx: usize,
+    total_frames: usize,
+    direction: [f32; 2],
+    strength: f32,
+    turbulence: f32,
+) -> [f32; 2] {
+    let wind_t = frame_idx as f32 / total_frames.max(1) as f32;
+    let gust = strength * (1.0 + turbulence * (wind_t * std::f32::consts::TAU).sin());
+    direction.map(|d| d * gust)
+}
+
+/// Push particles along X (`direction[0]`) and Z (`direction[1]`) as if
+/// blown by wind. Each frame's `wind_step` is added to the displacement of
+/// all earlier frames, so particles drift further over time.
+pub fn edit_apply_wind(
+    frames: &mut [Vec<Particle>],
+    direction: [f32; 2],
+    strength: f32,
+    turbulence: f32,
+) {
+    let total = frames.len();
+    let mut offset = [0.0f32; 2];
+    for (i, frame) in frames.iter_mut().enumerate() {
+        let step = wind_step(i, total, direction, strength, turbulence);
+        offset = [offset[0] + step[0], offset[1] + step[1]];
+        for p in frame {
+            p.pos[0] += offset[0];
+            p.pos[2] += offset[1];
+        }
+    }
+}
+
+/// Pull particles towards `center`. In every frame each particle (by id)
+/// takes a step of `strength / dist^falloff` towards the centre, measured
+/// from where earlier steps have already moved it, and the steps add up
+/// over time. A step never carries a particle past the centre; a negative
+/// strength pushes particles away instead.
+pub fn edit_gravity_well(
+    frames: &mut [Vec<Particle>],
+    center: [f32; 3],
+    strength: f32,
+    falloff: f32,
+) {
+    let mut offsets: HashMap<i32, [f32; 3]> = HashMap::new();
+    for p in frames.iter_mut().flatten() {
+        let offset = offsets.entry(p.id).or_insert([0.0; 3]);
+        let to_center: [f32; 3] = std::array::from_fn(|k| center[k] - (p.pos[k] + offset[k]));
+        let dist = to_center.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if dist > 1e-6 {
+            let step = (strength / dist.powf(falloff)).min(dist);
+            for (o, v) in offset.iter_mut().zip(to_center) {
+                *o += v / dist * step;
+            }
+        }
+        for (v, o) in p.pos.iter_mut().zip(*offset) {
+            *v += o;
+        }
+    }
+}
+
+/// Pick `total_out` frames from `frames` by formula. `expr` is evaluated
+/// with `t` at evenly spaced points from 0 to 1 and its value, also 0 to 1
+/// (clamped), selects the source frame; `t*t` eases in. An expression that
+/// does not compile samples linearly, as if it were `t`.
+pub fn edit_resample_frames(
+    frames: &[Vec<Particle>],
+    expr: &str,
+    total_out: usize,
+) -> Vec<Vec<Particle>> {
+    if frames.is_empty() {
+        return Vec::new();
+    }
+    let stmts = crate::particleex::compile_expr(expr);
+    let mut ctx = crate::particleex::ExprContext::new();
+    let last = frames.len() - 1;
+    (0..total_out)
+        .map(|i| {
+            let t = if total_out > 1 {
+                i as f64 / (total_out - 1) as f64
+            } else {
+                0.0
+            };
+            ctx.set("t", crate::particleex::Value::Num(t));
+            let u = match &stmts {
+                Some(stmts) => crate::particleex::exec_stmts(stmts, &mut ctx).as_num(),
+                None => t,
+            };
+            let u = if u.is_finite() {
+                u.clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            frames[(u * last as f64).round() as usize].clone()
+        })
+        .collect()
+}
+
+/// Colour temperature at which `kelvin_to_rgb` is white, so tinting by it
+/// leaves colours unchanged.
+pub const NEUTRAL_KELVIN: f32 = 6600.0;
+
+/// Approximate RGB of black-body light at `kelvin` (clamped to 1000–12000 K),
+/// after Tanner Helland's curve fit.
+pub fn kelvin_to_rgb(kelvin: f32) -> [u8; 3] {
+    let t = kelvin.clamp(1000.0, 12000.0) as f64 / 100.0;
+    let r = if t <= 66.0 {
+        255.0
+    } else {
+        329.698727446 * (t - 60.0).powf(-0.1332047592)
+    };
+    let g = if t <= 66.0 {
+        99.4708025861 * t.ln() - 161.1195681661
+    } else {
+        288.1221695283 * (t - 60.0).powf(-0.0755148492)
+    };
+    let b = if t >= 66.0 {
+        255.0
+    } else if t <= 19.0 {
+        0.0
+    } else {
+        138.5177312231 * (t - 10.0).ln() - 305.0447927307
+    };
+    [r, g, b].map(|c| c.round().clamp(0.0, 255.0) as u8)
+}
+
+/// Tint every particle's RGB by the `kelvin_to_rgb` colour of `kelvin`:
+/// below `NEUTRAL_KELVIN` warms, above it cools. Alpha is unchanged.
+pub fn edit_color_temperature(frames: &mut [Vec<Particle>], kelvin: f32) {
+    let tint = kelvin_to_rgb(kelvin).map(|c| c as f32 / 255.0);
+    for p in frames.iter_mut().flatten() {
+        for (c, t) in p.color.iter_mut().zip(tint) {
+            *c = (*c as f32 * t).round() as u8;
+        }
+    }
+}
+
+/// HSV hue of an RGB colour in degrees, `0..360`. Greys count as 0.
+pub fn color_hue(color: [u8; 4]) -> f32 {
+    let [r, g, b] = [color[0], color[1], color[2]].map(|c| c as f32 / 255.0);
+    let max = r.max(g).max(b);
+    let delta = max - r.min(g).min(b);
+    if delta <= 0.0 {
+        return 0.0;
+    }
+    let h = if max == r {
+        ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        (b - r) / delta + 2.0
+    } else {
+        (r - g) / delta + 4.0
+    };
+    h * 60.0
+}
+
+/// Rec. 601 luma of an RGB colour, `0..255`.
+pub fn color_brightness(color: [u8; 4]) -> f32 {
+    0.299 * color[0] as f32 + 0.587 * color[1] as f32 + 0.114 * color[2] as f32
+}
+
+/// Indices of `particles` in ascending `key` order, each key computed once.
+/// Equal keys keep the file's order.
+pub fn order_by_key(particles: &[Particle], key: impl Fn(&Particle) -> f32) -> Vec<u32> {
+    let mut keyed: Vec<(f32, u32)> = particles.iter().map(key).zip(0..).collect();
+    keyed.sort_by(|a, b| a.0.total_cmp(&b.0));
+    keyed.into_iter().map(|(_, i)| i).collect()
+}
+
+/// Temporal Laplacian smoothing: each pass moves every particle towards the
+/// midpoint of its positions (matched by id) in the previous and next frames,
+/// `pos = weight * (prev + next) / 2 + (1 - weight) * cur`. A missing
+/// neighbour counts as the current position.
+pub fn edit_smooth_positions(frames: &mut [Vec<Particle>], passes: u32, weight: f32) {
+    let positions = |frame: &[Particle]| -> HashMap<i32, [f32; 3]> {
+        frame.iter().map(|p| (p.id, p.pos)).collect()
+    };
+    for _ in 0..passes {
+        // Neighbours are read from this pass's input, not the partly smoothed frames
+        let mut prev: HashMap<i32, [f32; 3]> = HashMap::new();
+        let mut cur = frames.first().map(|f| positions(f)).unwrap_or_default();
+        for i in 0..frames.len() {
+            let next = frames.get(i + 1).map(|f| positions(f)).unwrap_or_default();
+            for p in frames[i].iter_mut() {
+                let a = prev.get(&p.id).copied().unwrap_or(p.pos);
+                let b = next.get(&p.id).copied().unwrap_or(p.pos);
+                for k in 0..3 {
+                    p.pos[k] = weight * (a[k] + b[k]) / 2.0 + (1.0 - weight) * p.pos[k];
+                }
             }
+            prev = std::mem::replace(&mut cur, next);
         }
     }
-    if bbox_min[0] == f32::MAX {
-        bbox_min = [0.0; 3];
-        bbox_max = [0.0; 3];
+}
+
+/// Decode a whole file, apply `edit_smooth_positions` and write the result.
+/// Unlike `streaming_edit` this keeps every frame in memory, since each pass
+/// needs both temporal neighbours.
+pub fn smooth_nbl_file(
+    source_path: PathBuf,
+    output_path: PathBuf,
+    passes: u32,
+    weight: f32,
+    progress: Arc<Mutex<CompressProgress>>,
+) -> Result<()> {
+    rewrite_nbl_file(source_path, output_path, progress, |frames| {
+        edit_smooth_positions(frames, passes, weight)
+    })
+}
+
+/// Decode a whole file into memory, run `edit` over all frames and write the
+/// result, for edits where a frame depends on the ones around it.
+pub fn rewrite_nbl_file(
+    source_path: PathBuf,
+    output_path: PathBuf,
+    progress: Arc<Mutex<CompressProgress>>,
+    edit: impl FnOnce(&mut Vec<Vec<Particle>>),
+) -> Result<()> {
+    let mut player = PlayerState {
+        prefetch_threads: 0,
+        ..Default::default()
+    };
+    player.load_file_mmap(&source_path)?;
+    let mut header = player.header.clone().ok_or(anyhow!("No header"))?;
+    if let Ok(mut p) = progress.lock() {
+        p.total_frames = header.total_frames;
     }
-    (bbox_min, bbox_max)
+    let mut frames = player.decode_frame_range(0, header.total_frames)?;
+    edit(&mut frames);
+    header.total_frames = frames.len() as u32;
+
+    let (bbox_min, bbox_max) = recalculate_bbox(&frames);
+    header.bbox_min = bbox_min;
+    header.bbox_max = bbox_max;
+    // save_file writes Zstd blocks
+    header.attributes &= !ATTR_LZ4;
+    player.save_file(&output_path, &header, &player.textures, &frames)?;
+
+    if let Ok(mut p) = progress.lock() {
+        p.current_frame = header.total_frames;
+        p.is_done = true;
+    }
+    Ok(())
 }
 
 /// Encode a P-Frame: delta between prev_particles and cur_particles.
@@ -842,6 +2530,8 @@ pub struct CompressProgress {
     pub is_done: bool,
     pub error: Option<String>,
     pub start_time: std::time::Instant,
+    /// Particles dropped by the edit so far (deduplication).
+    pub removed_particles: usize,
 }
 
 /// Helper function to detect if particle movement exceeds the P-Frame delta limit (int16 * 1000 = 32.767).
@@ -875,32 +2565,81 @@ pub enum EditAction {
     InterpolateAndFps(f32, u16),
     ScaleSize(f32),
     UniformSize(f32),
-    AdjustColor(f32, f32),
+    /// Brightness and opacity factors, then a colour temperature tint in
+    /// kelvin.
+    AdjustColor(f32, f32, f32),
     Transform([f32; 3], f32),
     Trim(u32, u32),
-    Compress(u32),
+    /// Keyframe interval, then the tolerance for `straighten_linear_runs`
+    /// (0 leaves positions untouched).
+    Compress(u32, f32),
+    Deflate(f32),
+    Voxelise(f32),
+    Deduplicate,
+    /// Temporal smoothing (passes, weight); handled by `smooth_nbl_file`.
+    Smooth(u32, f32),
+    /// Per-particle position noise (amplitude, seed).
+    Jitter(f32, u64),
+    /// Cumulative wind drift (direction on XZ, strength, turbulence);
+    /// handled by `rewrite_nbl_file` with `edit_apply_wind`.
+    Wind([f32; 2], f32, f32),
+    /// Cumulative pull towards a point (center, strength, falloff); handled
+    /// by `rewrite_nbl_file` with `edit_gravity_well`.
+    GravityWell([f32; 3], f32, f32),
+    /// Output frame count for `edit_resample_frames`; the formula is
+    /// `EditState::resample_expr`.
+    Resample(usize),
 }
 
+/// Re-encode an NBL file with LZ4 frame blocks, which decode faster than Zstd
+/// at the cost of a larger file.
+pub fn streaming_compress_lz4(
+    source_path: PathBuf,
+    output_path: PathBuf,
+    keyframe_interval: u32,
+    progress: Arc<Mutex<CompressProgress>>,
+) -> Result<()> {
+    streaming_edit(
+        source_path,
+        output_path,
+        EditAction::Compress(keyframe_interval, 0.0),
+        CompressAlgorithm::Lz4,
+        progress,
+    )
+}
+
+/// Source frames `streaming_edit` straightens at a time for
+/// `EditAction::Compress` with a tolerance. Runs are split at window
+/// boundaries, which bounds memory at the cost of a little compression.
+const STRAIGHTEN_WINDOW: u32 = 256;
+
 /// Stream-process an NBL file applying an EditAction.
 pub fn streaming_edit(
     source_path: PathBuf,
     output_path: PathBuf,
     action: EditAction,
-    zstd_level: i32,
+    algorithm: CompressAlgorithm,
     progress: Arc<Mutex<CompressProgress>>,
 ) -> Result<()> {
-    // 1. Initial load for metadata
+    // 1. Initial load for metadata. Mapping the source also makes
+    // `ensure_not_mapped` refuse an output path equal to it.
     let mut player = PlayerState::default();
-    player.load_file(source_path)?;
+    player.load_file_mmap(&source_path)?;
     let mut header = player.header.as_ref().ok_or(anyhow!("No header"))?.clone();
     let textures = player.textures.clone();
     let old_total_frames = header.total_frames;
 
+    match algorithm {
+        CompressAlgorithm::Lz4 => header.attributes |= ATTR_LZ4,
+        CompressAlgorithm::Zstd(_) => header.attributes &= !ATTR_LZ4,
+    }
+
     // 2. Determine new header parameters
     let mut new_total_frames = old_total_frames;
 
     // Default compression interval (0 = auto)
     let mut keyframe_interval = 0;
+    let mut straighten_tolerance = 0.0;
 
     match action {
         EditAction::ChangeFps(fps) => {
@@ -922,8 +2661,9 @@ pub fn streaming_edit(
             let end = end.min(old_total_frames.saturating_sub(1)).max(start);
             new_total_frames = end - start + 1;
         }
-        EditAction::Compress(interval) => {
+        EditAction::Compress(interval, tolerance) => {
             keyframe_interval = interval;
+            straighten_tolerance = tolerance;
         }
         _ => {}
     }
@@ -934,6 +2674,7 @@ pub fn streaming_edit(
         p.error = None;
     }
 
+    ensure_not_mapped(&output_path)?;
     let file = File::create(&output_path)?;
     let mut writer = BufWriter::new(file);
 
@@ -994,10 +2735,17 @@ pub fn streaming_edit(
     // Cache for frame interpolation
     let mut source_cache: HashMap<u32, Vec<Particle>> = HashMap::new();
     let mut next_needed_source_frame: u32 = 0;
+    // Source frames straightened one window at a time, starting at `window_start`
+    let mut window: Vec<Vec<Particle>> = Vec::new();
+    let mut window_start: u32 = 0;
 
     let mut index_entries: Vec<(u64, u32)> = Vec::with_capacity(new_total_frames as usize);
     let mut real_keyframe_list: Vec<u32> = Vec::new();
     let mut current_data_offset = data_start_offset as u64;
+    // Transforms and trims change the extent, so the header bbox is rebuilt
+    // from the written particles and patched in at the end.
+    let mut bbox = BboxAccumulator::default();
+    let mut deflate_state = DeflateState::default();
 
     for output_frame_idx in 0..new_total_frames {
         // 1. Determine which source frame(s) we need
@@ -1027,9 +2775,23 @@ pub fn streaming_edit(
         let max_needed = src_idx_b.max(src_idx_a);
         while next_needed_source_frame <= max_needed && next_needed_source_frame < old_total_frames
         {
-            player.process_frame(next_needed_source_frame)?;
-            // Current state of player.particles is now next_needed_source_frame
-            let mut snapshot: Vec<Particle> = player.particles.values().cloned().collect();
+            let mut snapshot: Vec<Particle> = if straighten_tolerance > 0.0 {
+                if next_needed_source_frame >= window_start + window.len() as u32 {
+                    let end = (next_needed_source_frame + STRAIGHTEN_WINDOW).min(old_total_frames);
+                    let mut raw = Vec::with_capacity((end - next_needed_source_frame) as usize);
+                    for f in next_needed_source_frame..end {
+                        player.process_frame(f)?;
+                        raw.push(player.particles.values().cloned().collect());
+                    }
+                    window = straighten_linear_runs(&raw, straighten_tolerance);
+                    window_start = next_needed_source_frame;
+                }
+                std::mem::take(&mut window[(next_needed_source_frame - window_start) as usize])
+            } else {
+                player.process_frame(next_needed_source_frame)?;
+                // Current state of player.particles is now next_needed_source_frame
+                player.particles.values().cloned().collect()
+            };
             // Sort to ensure stable interpolation
             snapshot.sort_unstable_by_key(|p| p.id);
             source_cache.insert(next_needed_source_frame, snapshot);
@@ -1061,7 +2823,7 @@ pub fn streaming_edit(
                     p.size = s;
                 }
             }
-            EditAction::AdjustColor(b, o) => {
+            EditAction::AdjustColor(b, o, kelvin) => {
                 for p in &mut particles {
                     let c0 = (p.color[0] as f32 * b).round().clamp(0.0, 255.0) as u8;
                     let c1 = (p.color[1] as f32 * b).round().clamp(0.0, 255.0) as u8;
@@ -1069,6 +2831,7 @@ pub fn streaming_edit(
                     let c3 = (p.color[3] as f32 * o).round().clamp(0.0, 255.0) as u8;
                     p.color = [c0, c1, c2, c3];
                 }
+                edit_color_temperature(std::slice::from_mut(&mut particles), kelvin);
             }
             EditAction::Transform(trans, scale) => {
                 for p in &mut particles {
@@ -1077,12 +2840,34 @@ pub fn streaming_edit(
                     p.pos[2] = p.pos[2] * scale + trans[2];
                 }
             }
+            EditAction::Jitter(amplitude, seed) => {
+                edit_jitter_positions(std::slice::from_mut(&mut particles), amplitude, seed);
+            }
+            EditAction::Deflate(radius) => {
+                particles.sort_unstable_by_key(|p| p.id);
+                particles = edit_deflate_particles(particles, radius, &mut deflate_state);
+            }
+            EditAction::Voxelise(grid_size) => {
+                edit_quantise_positions(std::slice::from_mut(&mut particles), grid_size);
+                particles.sort_unstable_by_key(|p| p.id);
+                particles = merge_voxel_duplicates(particles, grid_size);
+            }
+            EditAction::Deduplicate => {
+                particles.sort_unstable_by_key(|p| p.id);
+                let removed = edit_deduplicate_particles(std::slice::from_mut(&mut particles))?;
+                if let Ok(mut p) = progress.lock() {
+                    p.removed_particles += removed;
+                }
+            }
             _ => {}
         }
 
         // 5. Compression / Encoding
         // Sort for consistent encoding
         particles.sort_unstable_by_key(|p| p.id);
+        for p in &particles {
+            bbox.update(p);
+        }
         let current_written_snapshot = particles;
 
         let mut force_iframe = false;
@@ -1105,7 +2890,7 @@ pub fn streaming_edit(
             encode_p_frame(&previous_written_snapshot, &current_written_snapshot)
         };
 
-        let compressed = zstd::encode_all(Cursor::new(&raw_packet), zstd_level)?;
+        let compressed = algorithm.compress(&raw_packet)?;
         writer.write_all(&compressed)?;
 
         index_entries.push((current_data_offset, compressed.len() as u32));
@@ -1136,6 +2921,13 @@ pub fn streaming_edit(
         writer.write_u32::<LittleEndian>(kf_idx)?;
     }
 
+    // bbox follows magic (8) + version, fps, frames, textures, attributes (12)
+    let (bbox_min, bbox_max) = bbox.finish();
+    writer.seek(SeekFrom::Start(20))?;
+    for v in bbox_min.iter().chain(&bbox_max) {
+        writer.write_f32::<LittleEndian>(*v)?;
+    }
+
     writer.flush()?;
 
     if let Ok(mut p) = progress.lock() {
@@ -1144,3 +2936,393 @@ pub fn streaming_edit(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn imports_ascii_and_binary_ply() {
+        let dir = std::env::temp_dir();
+        let ascii = dir.join("nebula_import_test_ascii.ply");
+        std::fs::write(
+            &ascii,
+            "ply\nformat ascii 1.0\ncomment fixture\nelement vertex 2\n\
+             property float x\nproperty float y\nproperty float z\n\
+             property uchar red\nproperty uchar green\nproperty uchar blue\n\
+             property uchar alpha\nproperty float scalar_intensity\n\
+             element face 1\nproperty list uchar int vertex_indices\nend_header\n\
+             1 2 3 255 0 0 128 0.5\n-1 0 0.25 0 255 0 255 2\n3 0 1 1\n",
+        )
+        .unwrap();
+        let frame = import_ply_frame(&ascii).unwrap();
+        assert_eq!(frame.len(), 2);
+        assert_eq!(frame[0].pos, [1.0, 2.0, 3.0]);
+        assert_eq!(frame[0].color, [255, 0, 0, 128]);
+        assert_eq!(frame[0].size, 0.5);
+        assert_eq!(frame[1].pos, [-1.0, 0.0, 0.25]);
+
+        let binary = dir.join("nebula_import_test_binary.ply");
+        let mut bytes = b"ply\nformat binary_little_endian 1.0\nelement vertex 1\n\
+            property float x\nproperty float y\nproperty float z\n\
+            property float red\nend_header\n"
+            .to_vec();
+        for v in [4.0f32, 5.0, 6.0, 0.5] {
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+        std::fs::write(&binary, bytes).unwrap();
+        let frame = import_ply_frame(&binary).unwrap();
+        assert_eq!(frame[0].pos, [4.0, 5.0, 6.0]);
+        assert_eq!(frame[0].color, [128, 255, 255, 255]);
+        assert_eq!(frame[0].size, PLY_DEFAULT_SIZE);
+    }
+
+    #[test]
+    fn resamples_frames_by_formula() {
+        let frames: Vec<Vec<Particle>> = (0..11)
+            .map(|i| {
+                vec![Particle {
+                    id: i,
+                    pos: [0.0; 3],
+                    color: [255; 4],
+                    size: 1.0,
+                    tex_id: 0,
+                    seq_index: 0,
+                }]
+            })
+            .collect();
+        let picked = |out: &[Vec<Particle>]| out.iter().map(|f| f[0].id).collect::<Vec<_>>();
+        assert_eq!(
+            picked(&edit_resample_frames(&frames, "t*t", 5)),
+            [0, 1, 3, 6, 10]
+        );
+        assert_eq!(
+            picked(&edit_resample_frames(&frames, "2 - t", 3)),
+            [10, 10, 10]
+        );
+        assert_eq!(picked(&edit_resample_frames(&frames, "", 3)), [0, 5, 10]);
+    }
+
+    #[test]
+    fn motion_stats_follow_ids_across_gaps() {
+        let particle = |id, x| Particle {
+            id,
+            pos: [x, 0.0, 0.0],
+            color: [255; 4],
+            size: 1.0,
+            tex_id: 0,
+            seq_index: 0,
+        };
+        let frames = vec![
+            vec![particle(1, 0.0), particle(2, 0.0)],
+            vec![particle(1, 1.0)],
+            vec![particle(1, 4.0), particle(2, 2.0)],
+        ];
+        let stats = compute_motion_stats(&frames);
+        assert_eq!(stats.iter().map(|s| s.id).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(stats[0].total_distance, 4.0);
+        assert_eq!(stats[0].max_speed, 3.0);
+        assert_eq!(stats[0].avg_speed, 2.0);
+        assert_eq!(stats[0].lifetime_frames, 3);
+        assert_eq!(stats[1].max_speed, 1.0);
+        assert_eq!(stats[1].lifetime_frames, 2);
+    }
+
+    #[test]
+    fn straightened_runs_keep_their_endpoints() {
+        // Near-linear drift with noise well inside the tolerance, then a turn.
+        let frames: Vec<Vec<Particle>> = (0..8)
+            .map(|f: u32| {
+                let noise = [0.0005, -0.0005][f as usize % 2];
+                let x = f.min(5) as f32 * 0.5 + if f < 5 { noise } else { 0.0 };
+                vec![Particle {
+                    id: 7,
+                    pos: [x, f.saturating_sub(5) as f32, 0.0],
+                    color: [255; 4],
+                    size: 0.2,
+                    tex_id: 0,
+                    seq_index: 0,
+                }]
+            })
+            .collect();
+        let straight = straighten_linear_runs(&frames, 0.01);
+        assert_eq!(straight.len(), frames.len());
+        assert_eq!(straight[0][0].pos, frames[0][0].pos);
+        assert_eq!(straight[7][0].pos, frames[7][0].pos);
+        let steps: Vec<f32> = (1..5)
+            .map(|f| straight[f][0].pos[0] - straight[f - 1][0].pos[0])
+            .collect();
+        assert!(steps.iter().all(|s| (s - steps[0]).abs() < 1e-5));
+        for (a, b) in frames.iter().zip(&straight) {
+            assert!((a[0].pos[0] - b[0].pos[0]).abs() <= 0.01);
+        }
+    }
+
+    #[test]
+    fn straightening_stays_within_tolerance() {
+        use rand::{rngs::SmallRng, Rng, SeedableRng};
+        let tolerance = 0.05;
+        let mut rng = SmallRng::seed_from_u64(3);
+        let frames: Vec<Vec<Particle>> = (0..200)
+            .map(|f| {
+                let t = f as f32;
+                vec![Particle {
+                    id: 1,
+                    pos: [0.3 * t, -0.1 * t, 2.0].map(|v| v + rng.gen_range(-0.5..0.5) * tolerance),
+                    color: [255; 4],
+                    size: 0.2,
+                    tex_id: 0,
+                    seq_index: 0,
+                }]
+            })
+            .collect();
+        let kept: usize = compress_linear_keyframes(&frames, tolerance)
+            .iter()
+            .map(Vec::len)
+            .sum();
+        assert!(kept < frames.len() / 2, "kept {}", kept);
+        let straight = straighten_linear_runs(&frames, tolerance);
+        let moved = frames
+            .iter()
+            .zip(&straight)
+            .flat_map(|(a, b)| (0..3).map(move |k| (a[0].pos[k] - b[0].pos[k]).abs()))
+            .fold(0.0f32, f32::max);
+        assert!(moved <= tolerance, "moved {} > {}", moved, tolerance);
+    }
+
+    #[test]
+    fn streaming_straightening_covers_every_window() {
+        let dir = std::env::temp_dir();
+        let (src, dst) = (
+            dir.join("nebula_straighten_src.nbl"),
+            dir.join("nebula_straighten_dst.nbl"),
+        );
+        let total = STRAIGHTEN_WINDOW + 44;
+        let frames: Vec<Vec<Particle>> = (0..total)
+            .map(|f| {
+                let noise = [0.01, -0.01][f as usize % 2];
+                vec![Particle {
+                    id: 1,
+                    pos: [0.02 * f as f32 + noise, 1.0, 0.0],
+                    color: [255; 4],
+                    size: 0.2,
+                    tex_id: 0,
+                    seq_index: 0,
+                }]
+            })
+            .collect();
+        let header = NblHeader {
+            version: NBL_VERSION,
+            target_fps: 30,
+            total_frames: total,
+            texture_count: 0,
+            attributes: 0x03,
+            bbox_min: [0.0; 3],
+            bbox_max: [0.0; 3],
+        };
+        PlayerState::default()
+            .save_file(&src, &header, &[], &frames)
+            .unwrap();
+        let progress = Arc::new(Mutex::new(CompressProgress {
+            total_frames: 0,
+            current_frame: 0,
+            is_done: false,
+            error: None,
+            start_time: std::time::Instant::now(),
+            removed_particles: 0,
+        }));
+        streaming_edit(
+            src,
+            dst.clone(),
+            EditAction::Compress(0, 0.05),
+            CompressAlgorithm::Zstd(3),
+            progress,
+        )
+        .unwrap();
+
+        let mut reader = PlayerState {
+            prefetch_threads: 0,
+            ..Default::default()
+        };
+        reader.load_file(dst).unwrap();
+        let out = reader.decode_frame_range(0, total).unwrap();
+        assert_eq!(out.len(), frames.len());
+        for (a, b) in frames.iter().zip(&out) {
+            // Straightening moves by at most the tolerance, the P-frame codec by 1/1000.
+            assert!((a[0].pos[0] - b[0].pos[0]).abs() <= 0.05 + 0.001);
+        }
+    }
+
+    #[test]
+    fn yaml_names_are_quoted_and_escaped() {
+        assert_eq!(yaml_quoted("fx: 1 #a"), "\"fx: 1 #a\"");
+        assert_eq!(yaml_quoted("a\"b\\c\n"), "\"a\\\"b\\\\c\\n\"");
+    }
+
+    #[test]
+    fn spatial_hash_survives_saturated_cells() {
+        let mut hash = SpatialHash::new(1.0);
+        hash.insert([1e30, -1e30, 0.0], 0);
+        hash.insert([f32::INFINITY, 0.0, 0.0], 1);
+        assert_eq!(
+            hash.query_neighbours([1e30, -1e30, 0.0])
+                .collect::<Vec<_>>(),
+            [0]
+        );
+        assert_eq!(
+            hash.query_neighbours([f32::MAX, 0.0, 0.0])
+                .collect::<Vec<_>>(),
+            [1]
+        );
+    }
+
+    #[test]
+    fn deflate_decisions_stick_to_ids() {
+        let particle = |id, x| Particle {
+            id,
+            pos: [x, 0.0, 0.0],
+            color: [255; 4],
+            size: 1.0,
+            tex_id: 0,
+            seq_index: 0,
+        };
+        let ids = |frame: &[Particle]| frame.iter().map(|p| p.id).collect::<Vec<_>>();
+        let mut state = DeflateState::default();
+        let first = edit_deflate_particles(
+            vec![particle(1, 0.0), particle(2, 5.0), particle(3, 0.5)],
+            1.0,
+            &mut state,
+        );
+        assert_eq!(ids(&first), [1, 2]);
+        // 1 and 2 now overlap and 3 has moved clear, but nothing changes
+        let second = edit_deflate_particles(
+            vec![particle(1, 0.0), particle(2, 0.2), particle(3, 9.0)],
+            1.0,
+            &mut state,
+        );
+        assert_eq!(ids(&second), [1, 2]);
+        // A new id is tested against the kept particles of its frame
+        let third = edit_deflate_particles(
+            vec![particle(1, 0.0), particle(4, 0.5), particle(5, 3.0)],
+            1.0,
+            &mut state,
+        );
+        assert_eq!(ids(&third), [1, 5]);
+    }
+
+    #[test]
+    fn migration_resets_sequence_indices() {
+        let dir = std::env::temp_dir();
+        let v1 = dir.join("nebula_migrate_test_v1.nbl");
+        let v2 = dir.join("nebula_migrate_test_v2.nbl");
+        let particle = |id, tex_id, seq_index| Particle {
+            id,
+            pos: [0.0; 3],
+            color: [255; 4],
+            size: 1.0,
+            tex_id,
+            seq_index,
+        };
+        let textures = [TextureEntry {
+            path: "minecraft:textures/particle/flame.png".into(),
+            rows: 2,
+            cols: 2,
+        }];
+        let header = NblHeader {
+            version: 1,
+            target_fps: 30,
+            total_frames: 1,
+            texture_count: 1,
+            attributes: 0x03,
+            bbox_min: [0.0; 3],
+            bbox_max: [0.0; 3],
+        };
+        let frame = vec![particle(1, 0, 3), particle(2, 0, 4), particle(3, 1, 2)];
+        let player = PlayerState::default();
+        player.save_file(&v1, &header, &textures, &[frame]).unwrap();
+
+        migrate_v1_to_v2(&v1, &v2).unwrap();
+        assert_eq!(check_nbl_version(&v2).unwrap(), 2);
+        let mut reader = PlayerState {
+            prefetch_threads: 0,
+            ..Default::default()
+        };
+        reader.load_file_mmap(&v2).unwrap();
+        let frames = reader.decode_frame_range(0, 1).unwrap();
+        let seq: Vec<u8> = frames[0].iter().map(|p| p.seq_index).collect();
+        assert_eq!(seq, [0, 0, 0]);
+    }
+
+    #[test]
+    fn deduplicate_rejects_out_of_range_positions() {
+        let at = |id, x| Particle {
+            id,
+            pos: [x, 0.0, 0.0],
+            color: [255; 4],
+            size: 1.0,
+            tex_id: 0,
+            seq_index: 0,
+        };
+        let mut frames = vec![vec![at(1, 1.0), at(2, 1.0), at(3, 2.0)]];
+        assert_eq!(edit_deduplicate_particles(&mut frames).unwrap(), 1);
+
+        let mut frames = vec![vec![at(1, 3.0e6), at(2, 4.0e6)]];
+        assert!(edit_deduplicate_particles(&mut frames).is_err());
+        assert_eq!(frames[0].len(), 2);
+    }
+
+    #[test]
+    fn orders_by_hue_brightness_and_z() {
+        let particle = |id, z, color| Particle {
+            id,
+            pos: [0.0, 0.0, z],
+            color,
+            size: 1.0,
+            tex_id: 0,
+            seq_index: 0,
+        };
+        let frame = [
+            particle(0, 2.0, [0, 0, 255, 255]),
+            particle(1, -1.0, [255, 0, 0, 255]),
+            particle(2, 0.5, [0, 255, 0, 255]),
+            particle(3, -1.0, [10, 10, 10, 255]),
+        ];
+        assert_eq!(order_by_key(&frame, |p| p.pos[2]), [1, 3, 2, 0]);
+        assert_eq!(order_by_key(&frame, |p| color_hue(p.color)), [1, 3, 2, 0]);
+        assert_eq!(
+            order_by_key(&frame, |p| color_brightness(p.color)),
+            [3, 0, 1, 2]
+        );
+    }
+
+    #[test]
+    fn refuses_to_overwrite_a_mapped_file() {
+        let path = std::env::temp_dir().join("nebula_mapped_write_test.nbl");
+        let header = NblHeader {
+            version: 2,
+            target_fps: 30,
+            total_frames: 1,
+            texture_count: 0,
+            attributes: 0x03,
+            bbox_min: [0.0; 3],
+            bbox_max: [0.0; 3],
+        };
+        let writer = PlayerState::default();
+        writer
+            .save_file(&path, &header, &[], &[Vec::new()])
+            .unwrap();
+
+        let mut reader = PlayerState {
+            prefetch_threads: 0,
+            ..Default::default()
+        };
+        reader.load_file_mmap(&path).unwrap();
+        assert!(writer
+            .save_file(&path, &header, &[], &[Vec::new()])
+            .is_err());
+        reader.mmap = None;
+        writer
+            .save_file(&path, &header, &[], &[Vec::new()])
+            .unwrap();
+    }
+}
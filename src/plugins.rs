@@ -0,0 +1,178 @@
+//! Native expression functions loaded from shared libraries in the
+//! `plugins/` folder next to the executable.
+//!
+//! A plugin is a `cdylib` exporting
+//! `#[no_mangle] pub extern "C" fn nebula_plugin_api() -> *const PluginApi`,
+//! which returns a table that lives as long as the library. Only `#[repr(C)]`
+//! data and `extern "C"` functions cross the library boundary, and the
+//! table's leading `abi_version` is checked before anything else in it is
+//! read; see `plugin_template/`. There is no dynamic loading on wasm32, so
+//! the registry stays empty there.
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+use std::ffi::c_char;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Layout version of [`PluginApi`]. Bump on any change to the types below.
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+
+/// Called with a pointer to `len` arguments.
+pub type PluginFn = extern "C" fn(args: *const f64, len: usize) -> f64;
+
+/// One expression function. `name` is a NUL-terminated UTF-8 string.
+#[repr(C)]
+pub struct PluginFunction {
+    pub name: *const c_char,
+    pub func: PluginFn,
+}
+
+/// Returned by `nebula_plugin_api`. Must stay valid while the library is
+/// loaded.
+#[repr(C)]
+pub struct PluginApi {
+    pub abi_version: u32,
+    pub name: *const c_char,
+    pub functions: *const PluginFunction,
+    pub function_count: usize,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+type ApiFn = unsafe extern "C" fn() -> *const PluginApi;
+#[cfg(not(target_arch = "wasm32"))]
+const API_SYMBOL: &[u8] = b"nebula_plugin_api";
+
+/// What the Plugins panel shows about one loaded library.
+pub struct PluginInfo {
+    pub name: String,
+    pub path: PathBuf,
+    pub functions: Vec<String>,
+}
+
+#[derive(Default)]
+pub struct PluginRegistry {
+    pub plugins: Vec<PluginInfo>,
+    /// One message per library that failed to load.
+    pub errors: Vec<String>,
+    functions: HashMap<String, PluginFn>,
+    // Never unloaded: the function pointers above point into these.
+    #[cfg(not(target_arch = "wasm32"))]
+    _libraries: Vec<libloading::Library>,
+}
+
+static REGISTRY: OnceLock<PluginRegistry> = OnceLock::new();
+
+/// `plugins/` next to the running executable, so the same plugins are found
+/// whatever directory NebulaTools is started from.
+pub fn plugin_dir() -> Option<PathBuf> {
+    let exe = std::env::current_exe().ok()?;
+    Some(exe.parent()?.join("plugins"))
+}
+
+/// Load every shared library in `dir`. Only the first call has any effect.
+pub fn load_plugins(dir: &Path) {
+    REGISTRY.get_or_init(|| {
+        let mut registry = PluginRegistry::default();
+        let Ok(read_dir) = std::fs::read_dir(dir) else {
+            return registry;
+        };
+        let mut paths: Vec<PathBuf> = read_dir
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| {
+                p.extension()
+                    .is_some_and(|ext| ext == std::env::consts::DLL_EXTENSION)
+            })
+            .collect();
+        paths.sort();
+        for path in paths {
+            if let Err(e) = registry.load(&path) {
+                registry.errors.push(format!("{}: {}", path.display(), e));
+            }
+        }
+        registry
+    });
+}
+
+/// Copies a plugin-owned C string, rejecting null and non-UTF-8 data.
+///
+/// # Safety
+/// `ptr` must be null or point at a NUL-terminated string.
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn plugin_str(ptr: *const c_char) -> Result<String> {
+    if ptr.is_null() {
+        bail!("null string in plugin table");
+    }
+    Ok(std::ffi::CStr::from_ptr(ptr).to_str()?.to_string())
+}
+
+impl PluginRegistry {
+    #[cfg(target_arch = "wasm32")]
+    fn load(&mut self, _path: &Path) -> Result<()> {
+        bail!("native plugins are not supported on wasm32")
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn load(&mut self, path: &Path) -> Result<()> {
+        // SAFETY: loading runs the library's initialisers and trusts its
+        // `nebula_plugin_api` to have the expected signature; plugins are
+        // user-installed native code by design. The table is only read past
+        // `abi_version` once that matches ours, so a plugin built against a
+        // different layout is rejected rather than misread.
+        let (library, name, entries) = unsafe {
+            let library = libloading::Library::new(path)?;
+            let api_fn = *library
+                .get::<ApiFn>(API_SYMBOL)
+                .map_err(|e| anyhow::anyhow!("missing nebula_plugin_api: {}", e))?;
+            let api = api_fn();
+            if api.is_null() {
+                bail!("nebula_plugin_api returned null");
+            }
+            let version = std::ptr::addr_of!((*api).abi_version).read();
+            if version != PLUGIN_ABI_VERSION {
+                bail!(
+                    "plugin ABI version {} is not supported (expected {})",
+                    version,
+                    PLUGIN_ABI_VERSION
+                );
+            }
+            let api = &*api;
+            let name = plugin_str(api.name)?;
+            let mut entries = Vec::with_capacity(api.function_count);
+            if api.function_count > 0 {
+                if api.functions.is_null() {
+                    bail!("null function table");
+                }
+                let table = std::slice::from_raw_parts(api.functions, api.function_count);
+                for f in table {
+                    entries.push((plugin_str(f.name)?, f.func));
+                }
+            }
+            (library, name, entries)
+        };
+        let functions: Vec<String> = entries
+            .into_iter()
+            .map(|(name, func)| {
+                self.functions.entry(name.clone()).or_insert(func);
+                name
+            })
+            .collect();
+        self.plugins.push(PluginInfo {
+            name,
+            path: path.to_path_buf(),
+            functions,
+        });
+        self._libraries.push(library);
+        Ok(())
+    }
+}
+
+pub fn registry() -> Option<&'static PluginRegistry> {
+    REGISTRY.get()
+}
+
+/// Calls the plugin function registered under `name`, if any.
+pub fn call(name: &str, args: &[f64]) -> Option<f64> {
+    let func = registry()?.functions.get(name)?;
+    Some(func(args.as_ptr(), args.len()))
+}
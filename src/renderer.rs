@@ -1,87 +1,569 @@
+use crate::math::SpatialHash;
+use crate::player::TextureEntry;
 use eframe::glow::{self, HasContext};
+use std::collections::HashMap;
+
+/// Floats per particle in render data: pos (3), color (4), size, tex_id, seq_index.
+pub const FLOATS_PER_PARTICLE: usize = 10;
+
+/// A sprite sheet uploaded from `TextureEntry::path`, split into
+/// `rows` x `cols` equally sized cells indexed by `Particle::seq_index`.
+pub struct TextureAtlas {
+    texture: glow::Texture,
+    rows: u8,
+    cols: u8,
+}
+
+impl TextureAtlas {
+    pub fn load(gl: &glow::Context, entry: &TextureEntry) -> anyhow::Result<Self> {
+        let img = image::open(&entry.path)?.to_rgba8();
+        let (w, h) = img.dimensions();
+        unsafe {
+            let texture = gl.create_texture().map_err(|e| anyhow::anyhow!(e))?;
+            gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+            // Pixel-art particle sprites: keep texels sharp.
+            gl.tex_parameter_i32(
+                glow::TEXTURE_2D,
+                glow::TEXTURE_MIN_FILTER,
+                glow::NEAREST as i32,
+            );
+            gl.tex_parameter_i32(
+                glow::TEXTURE_2D,
+                glow::TEXTURE_MAG_FILTER,
+                glow::NEAREST as i32,
+            );
+            gl.tex_parameter_i32(
+                glow::TEXTURE_2D,
+                glow::TEXTURE_WRAP_S,
+                glow::CLAMP_TO_EDGE as i32,
+            );
+            gl.tex_parameter_i32(
+                glow::TEXTURE_2D,
+                glow::TEXTURE_WRAP_T,
+                glow::CLAMP_TO_EDGE as i32,
+            );
+            gl.tex_image_2d(
+                glow::TEXTURE_2D,
+                0,
+                glow::RGBA as i32,
+                w as i32,
+                h as i32,
+                0,
+                glow::RGBA,
+                glow::UNSIGNED_BYTE,
+                Some(img.as_raw()),
+            );
+            gl.bind_texture(glow::TEXTURE_2D, None);
+            Ok(Self {
+                texture,
+                rows: entry.rows.max(1),
+                cols: entry.cols.max(1),
+            })
+        }
+    }
+
+    pub fn destroy(&self, gl: &glow::Context) {
+        unsafe { gl.delete_texture(self.texture) };
+    }
+}
+
+/// Off-screen multisample colour target the viewport draws into before it is
+/// resolved onto egui's framebuffer. There is no depth attachment, matching
+/// the default framebuffer egui paints into.
+struct MsaaTarget {
+    fbo: glow::Framebuffer,
+    color: glow::Renderbuffer,
+    size: (i32, i32),
+    samples: i32,
+}
+
+impl MsaaTarget {
+    unsafe fn new(gl: &glow::Context, width: i32, height: i32, samples: i32) -> Option<Self> {
+        let fbo = gl.create_framebuffer().ok()?;
+        let color = gl.create_renderbuffer().ok()?;
+        gl.bind_renderbuffer(glow::RENDERBUFFER, Some(color));
+        gl.renderbuffer_storage_multisample(
+            glow::RENDERBUFFER,
+            samples,
+            glow::RGBA8,
+            width,
+            height,
+        );
+        gl.bind_renderbuffer(glow::RENDERBUFFER, None);
+        gl.bind_framebuffer(glow::FRAMEBUFFER, Some(fbo));
+        gl.framebuffer_renderbuffer(
+            glow::FRAMEBUFFER,
+            glow::COLOR_ATTACHMENT0,
+            glow::RENDERBUFFER,
+            Some(color),
+        );
+        let complete = gl.check_framebuffer_status(glow::FRAMEBUFFER) == glow::FRAMEBUFFER_COMPLETE;
+        let target = Self {
+            fbo,
+            color,
+            size: (width, height),
+            samples,
+        };
+        if !complete {
+            target.destroy(gl);
+            return None;
+        }
+        Some(target)
+    }
+
+    fn destroy(&self, gl: &glow::Context) {
+        unsafe {
+            gl.delete_framebuffer(self.fbo);
+            gl.delete_renderbuffer(self.color);
+        }
+    }
+}
+
+/// Number of frames `ParticleRenderer::measure_render_time` averages over.
+#[cfg(debug_assertions)]
+const GPU_TIMER_WINDOW: usize = 60;
+
+/// `TIME_ELAPSED` query wrapped around `ParticleRenderer::paint`. The result
+/// is read on a later frame so the CPU never waits for the GPU.
+#[cfg(debug_assertions)]
+struct GpuTimer {
+    query: glow::Query,
+    /// A paint has been timed but its result not yet read back.
+    pending: bool,
+    /// Recent paint durations in microseconds, oldest first.
+    samples: std::collections::VecDeque<f64>,
+}
+
+#[cfg(debug_assertions)]
+impl GpuTimer {
+    unsafe fn new(gl: &glow::Context) -> Option<Self> {
+        Some(Self {
+            query: gl.create_query().ok()?,
+            pending: false,
+            samples: std::collections::VecDeque::with_capacity(GPU_TIMER_WINDOW),
+        })
+    }
+
+    fn destroy(&self, gl: &glow::Context) {
+        unsafe {
+            gl.delete_query(self.query);
+        }
+    }
+}
+
+const PARTICLE_VERTEX_SHADER: &str = r#"#version 330 core
+        layout (location = 0) in vec3 a_pos;
+        layout (location = 1) in vec4 a_color;
+        layout (location = 2) in float a_size;
+        layout (location = 3) in float a_seq;
+        layout (location = 4) in float a_density;
+        uniform mat4 u_mvp;
+        uniform float u_scaling;
+        uniform float u_size_scale;
+        uniform vec2 u_atlas_grid;
+        out vec4 v_color;
+        out vec2 v_uv_offset;
+        out float v_density;
+        void main() {
+            gl_Position = u_mvp * vec4(a_pos, 1.0);
+            // 核心修复：确保计算出的点大小至少为 1.2 像素
+            gl_PointSize = max((a_size * u_scaling) / gl_Position.w, 1.2) * u_size_scale;
+            v_color = a_color;
+            v_density = a_density;
+            float cell = mod(a_seq, u_atlas_grid.x * u_atlas_grid.y);
+            v_uv_offset = vec2(mod(cell, u_atlas_grid.x), floor(cell / u_atlas_grid.x)) / u_atlas_grid;
+        }"#;
+
+const LINE_VERTEX_SHADER: &str = r#"#version 330 core
+        layout (location = 0) in vec3 a_pos;
+        layout (location = 1) in vec4 a_color;
+        uniform mat4 u_mvp;
+        out vec4 v_color;
+        void main() {
+            gl_Position = u_mvp * vec4(a_pos, 1.0);
+            v_color = a_color;
+        }"#;
+
+const LINE_FRAGMENT_SHADER: &str = r#"#version 330 core
+        in vec4 v_color;
+        out vec4 f_color;
+        void main() {
+            f_color = v_color;
+        }"#;
+
+/// Untextured points are cut out with `SHAPE_DISTANCE`, replaced per
+/// `PointShape` by an expression that reaches 0.5 on the shape's boundary.
+const PARTICLE_FRAGMENT_SHADER: &str = r#"#version 330 core
+        in vec4 v_color;
+        in vec2 v_uv_offset;
+        in float v_density;
+        uniform bool u_textured;
+        uniform bool u_heatmap;
+        uniform vec4 u_heat_low;
+        uniform vec4 u_heat_high;
+        uniform bool u_soft_edges;
+        uniform sampler2D u_atlas;
+        uniform vec2 u_atlas_grid;
+        out vec4 f_color;
+        void main() {
+            vec4 color = u_heatmap ? mix(u_heat_low, u_heat_high, v_density) : v_color;
+            if (u_textured) {
+                vec4 texel = texture(u_atlas, v_uv_offset + gl_PointCoord / u_atlas_grid);
+                if (texel.a < 0.01) discard;
+                f_color = texel * color;
+                return;
+            }
+            float dist = SHAPE_DISTANCE;
+            float coverage = step(dist, 0.5);
+            if (u_soft_edges) {
+                coverage = 1.0 - smoothstep(0.5 - fwidth(dist), 0.5, dist);
+            }
+            if (coverage <= 0.0) discard;
+            f_color = vec4(color.rgb, color.a * coverage);
+        }"#;
+
+/// Outline used for untextured point sprites.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum PointShape {
+    #[default]
+    Circle,
+    Square,
+    Diamond,
+    /// Star with the given number of points (clamped to 3..=12).
+    Star(u8),
+    Cross,
+}
+
+impl PointShape {
+    /// GLSL expression for the normalised distance from the sprite centre,
+    /// where 0.5 lies on the outline.
+    fn distance_glsl(self) -> String {
+        let c = "(gl_PointCoord - vec2(0.5))";
+        match self {
+            PointShape::Circle => "distance(gl_PointCoord, vec2(0.5))".to_string(),
+            PointShape::Square => format!("max(abs({c}.x), abs({c}.y))"),
+            PointShape::Diamond => format!("abs({c}.x) + abs({c}.y)"),
+            // Two bars 0.3 wide; the short axis is scaled so its edge is at 0.5.
+            PointShape::Cross => {
+                format!("max(max(abs({c}.x), abs({c}.y)), min(abs({c}.x), abs({c}.y)) / 0.3)")
+            }
+            // The outline radius runs linearly from 0.5 at each tip to 0.2
+            // between them.
+            PointShape::Star(points) => {
+                let n = points.clamp(3, 12) as f32;
+                format!(
+                    "0.5 * length({c}) / mix(0.2, 0.5, abs(fract(atan({c}.y, {c}.x) * {n:.1} / 6.2831853) * 2.0 - 1.0))"
+                )
+            }
+        }
+    }
+}
+
+/// How particle colours are chosen when drawing.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum RenderMode {
+    /// Each particle's stored colour.
+    #[default]
+    Normal,
+    /// Colour by neighbour count within `radius`, from `low_color` at the
+    /// sparsest particle to `high_color` at the densest.
+    HeatMap {
+        low_color: [f32; 4],
+        high_color: [f32; 4],
+        radius: f32,
+    },
+    /// Stored colours, plus a line from each particle back through its
+    /// positions in the previous `history_frames` frames (matched by id).
+    /// Each step into the past multiplies the line's alpha by
+    /// `tail_alpha_factor`.
+    Trail {
+        history_frames: u8,
+        tail_alpha_factor: f32,
+    },
+}
+
+/// Neighbour count within `radius` of each particle in `particles` (render
+/// data layout), normalised so the densest particle maps to 1.0.
+fn heat_densities(particles: &[f32], radius: f32) -> Vec<f32> {
+    let positions: Vec<[f32; 3]> = particles
+        .chunks_exact(FLOATS_PER_PARTICLE)
+        .map(|p| [p[0], p[1], p[2]])
+        .collect();
+    let mut grid = SpatialHash::new(radius);
+    for (i, &p) in positions.iter().enumerate() {
+        grid.insert(p, i);
+    }
+    let r2 = radius * radius;
+    let counts: Vec<f32> = positions
+        .iter()
+        .map(|&p| {
+            grid.query_neighbours(p)
+                .filter(|&j| {
+                    let q = positions[j];
+                    let d = [p[0] - q[0], p[1] - q[1], p[2] - q[2]];
+                    d[0] * d[0] + d[1] * d[1] + d[2] * d[2] <= r2
+                })
+                .count() as f32
+        })
+        .collect();
+    let max = counts.iter().copied().fold(1.0f32, f32::max);
+    counts.into_iter().map(|c| c / max).collect()
+}
+
+unsafe fn create_particle_program(gl: &glow::Context, shape: PointShape) -> glow::Program {
+    let fragment = PARTICLE_FRAGMENT_SHADER.replace("SHAPE_DISTANCE", &shape.distance_glsl());
+    create_program(gl, PARTICLE_VERTEX_SHADER, &fragment)
+}
+
+/// Minimal line-list renderer for small auxiliary viewports. Vertices are
+/// 7 floats: position (3) and colour (4).
+pub struct WireframeRenderer {
+    program: glow::Program,
+    vbo: glow::Buffer,
+    vao: glow::VertexArray,
+}
+
+impl WireframeRenderer {
+    pub fn new(gl: &glow::Context) -> Self {
+        unsafe {
+            Self {
+                program: create_program(gl, LINE_VERTEX_SHADER, LINE_FRAGMENT_SHADER),
+                vbo: gl.create_buffer().unwrap(),
+                vao: gl.create_vertex_array().unwrap(),
+            }
+        }
+    }
+
+    pub unsafe fn paint(&self, gl: &glow::Context, mvp: [f32; 16], lines: &[f32]) {
+        gl.disable(glow::DEPTH_TEST);
+        gl.enable(glow::BLEND);
+        gl.blend_func(glow::SRC_ALPHA, glow::ONE_MINUS_SRC_ALPHA);
+        gl.use_program(Some(self.program));
+        let mvp_loc = gl.get_uniform_location(self.program, "u_mvp");
+        gl.uniform_matrix_4_f32_slice(mvp_loc.as_ref(), false, &mvp);
+
+        gl.bind_vertex_array(Some(self.vao));
+        gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.vbo));
+        gl.buffer_data_u8_slice(
+            glow::ARRAY_BUFFER,
+            bytemuck::cast_slice(lines),
+            glow::STREAM_DRAW,
+        );
+        gl.enable_vertex_attrib_array(0);
+        gl.vertex_attrib_pointer_f32(0, 3, glow::FLOAT, false, 7 * 4, 0);
+        gl.enable_vertex_attrib_array(1);
+        gl.vertex_attrib_pointer_f32(1, 4, glow::FLOAT, false, 7 * 4, 3 * 4);
+        gl.draw_arrays(glow::LINES, 0, (lines.len() / 7) as i32);
+    }
+
+    pub fn destroy(&self, gl: &glow::Context) {
+        unsafe {
+            gl.delete_program(self.program);
+            gl.delete_buffer(self.vbo);
+            gl.delete_vertex_array(self.vao);
+        }
+    }
+}
 
 pub struct ParticleRenderer {
+    /// Particle program for `point_shape`, taken from `shape_programs`.
     program: glow::Program,
+    point_shape: PointShape,
+    /// Particle programs compiled so far, one per point shape.
+    shape_programs: HashMap<PointShape, glow::Program>,
     line_program: glow::Program,
     vbo: glow::Buffer,
+    /// Per-particle densities for `RenderMode::HeatMap` (attribute 4).
+    density_vbo: glow::Buffer,
+    render_mode: RenderMode,
     vao: glow::VertexArray,
     line_vbo: glow::Buffer,
     line_vao: glow::VertexArray,
+    /// Historical positions for `RenderMode::Trail`, in the line layout.
+    trail_vbo: glow::Buffer,
+    trail_vao: glow::VertexArray,
+    /// Atlas per `tex_id`; `None` where the image could not be loaded, in
+    /// which case those particles fall back to plain round points.
+    atlases: Vec<Option<TextureAtlas>>,
+    atlas_entries: Vec<TextureEntry>,
+    msaa: Option<MsaaTarget>,
+    /// Set between `begin_msaa` and `end_msaa`: round points fade out over
+    /// their last pixel so alpha-to-coverage can smooth their edges.
+    soft_edges: bool,
+    #[cfg(debug_assertions)]
+    gpu_timer: Option<GpuTimer>,
 }
 
 impl ParticleRenderer {
     pub fn new(gl: &glow::Context) -> Self {
         unsafe {
             // --- Particle Shader ---
-            let program = create_program(
-                gl,
-                r#"#version 330 core
-                layout (location = 0) in vec3 a_pos;
-                layout (location = 1) in vec4 a_color;
-                layout (location = 2) in float a_size;
-                uniform mat4 u_mvp;
-                uniform float u_scaling;
-                out vec4 v_color;
-                void main() {
-                    gl_Position = u_mvp * vec4(a_pos, 1.0);
-                    // 核心修复：确保计算出的点大小至少为 1.2 像素
-                    gl_PointSize = max((a_size * u_scaling) / gl_Position.w, 1.2);
-                    v_color = a_color;
-                }"#,
-                r#"#version 330 core
-                in vec4 v_color;
-                out vec4 f_color;
-                void main() {
-                    float dist = distance(gl_PointCoord, vec2(0.5));
-                    if (dist > 0.5) discard;
-                    f_color = v_color;
-                }"#,
-            );
+            let program = create_particle_program(gl, PointShape::Circle);
 
             // --- Line Shader (for Grid/Axes) ---
-            let line_program = create_program(
-                gl,
-                r#"#version 330 core
-                layout (location = 0) in vec3 a_pos;
-                layout (location = 1) in vec4 a_color;
-                uniform mat4 u_mvp;
-                out vec4 v_color;
-                void main() {
-                    gl_Position = u_mvp * vec4(a_pos, 1.0);
-                    v_color = a_color;
-                }"#,
-                r#"#version 330 core
-                in vec4 v_color;
-                out vec4 f_color;
-                void main() {
-                    f_color = v_color;
-                }"#,
-            );
+            let line_program = create_program(gl, LINE_VERTEX_SHADER, LINE_FRAGMENT_SHADER);
 
             let vbo = gl.create_buffer().unwrap();
+            let density_vbo = gl.create_buffer().unwrap();
             let vao = gl.create_vertex_array().unwrap();
             let line_vbo = gl.create_buffer().unwrap();
             let line_vao = gl.create_vertex_array().unwrap();
+            let trail_vbo = gl.create_buffer().unwrap();
+            let trail_vao = gl.create_vertex_array().unwrap();
 
             Self {
                 program,
+                point_shape: PointShape::Circle,
+                shape_programs: HashMap::from([(PointShape::Circle, program)]),
                 line_program,
                 vbo,
+                density_vbo,
+                render_mode: RenderMode::Normal,
                 vao,
                 line_vbo,
                 line_vao,
+                trail_vbo,
+                trail_vao,
+                atlases: Vec::new(),
+                atlas_entries: Vec::new(),
+                msaa: None,
+                soft_edges: false,
+                #[cfg(debug_assertions)]
+                gpu_timer: GpuTimer::new(gl),
+            }
+        }
+    }
+
+    /// Upload the atlases for `entries` (indexed by `tex_id`), reusing the
+    /// current ones when the list is unchanged.
+    pub fn set_textures(&mut self, gl: &glow::Context, entries: &[TextureEntry]) {
+        let unchanged = self.atlas_entries.len() == entries.len()
+            && self
+                .atlas_entries
+                .iter()
+                .zip(entries)
+                .all(|(a, b)| a.path == b.path && a.rows == b.rows && a.cols == b.cols);
+        if unchanged {
+            return;
+        }
+        for atlas in self.atlases.drain(..).flatten() {
+            atlas.destroy(gl);
+        }
+        self.atlases = entries
+            .iter()
+            .map(|entry| TextureAtlas::load(gl, entry).ok())
+            .collect();
+        self.atlas_entries = entries.to_vec();
+    }
+
+    /// Redirect drawing into a `samples`-times multisampled target covering
+    /// `viewport` (`[x, y, width, height]` in physical pixels, as passed to
+    /// `glViewport`) and clear it to the current clear colour. The target is
+    /// recreated whenever the size or sample count changes. Returns `false`
+    /// if multisampling is unavailable, in which case drawing continues to
+    /// go straight to the bound framebuffer.
+    pub unsafe fn begin_msaa(
+        &mut self,
+        gl: &glow::Context,
+        samples: u8,
+        viewport: [i32; 4],
+    ) -> bool {
+        let [_, _, width, height] = viewport;
+        let samples = (samples as i32).min(gl.get_parameter_i32(glow::MAX_SAMPLES));
+        if samples <= 1 || width <= 0 || height <= 0 {
+            return false;
+        }
+        let reusable = self
+            .msaa
+            .as_ref()
+            .is_some_and(|t| t.size == (width, height) && t.samples == samples);
+        if !reusable {
+            if let Some(old) = self.msaa.take() {
+                old.destroy(gl);
             }
+            self.msaa = MsaaTarget::new(gl, width, height, samples);
+        }
+        let Some(target) = &self.msaa else {
+            return false;
+        };
+        gl.bind_framebuffer(glow::FRAMEBUFFER, Some(target.fbo));
+        gl.viewport(0, 0, width, height);
+        gl.disable(glow::SCISSOR_TEST);
+        gl.clear(glow::COLOR_BUFFER_BIT);
+        gl.enable(glow::SAMPLE_ALPHA_TO_COVERAGE);
+        self.soft_edges = true;
+        true
+    }
+
+    /// Resolve the multisampled target from `begin_msaa` onto `resolve_to`
+    /// at `viewport`, leaving that framebuffer bound.
+    pub unsafe fn end_msaa(
+        &mut self,
+        gl: &glow::Context,
+        resolve_to: Option<glow::Framebuffer>,
+        viewport: [i32; 4],
+    ) {
+        self.soft_edges = false;
+        gl.disable(glow::SAMPLE_ALPHA_TO_COVERAGE);
+        let [x, y, width, height] = viewport;
+        if let Some(target) = &self.msaa {
+            gl.bind_framebuffer(glow::READ_FRAMEBUFFER, Some(target.fbo));
+            gl.bind_framebuffer(glow::DRAW_FRAMEBUFFER, resolve_to);
+            // Re-enable the scissor so the resolve honours egui's clip rect.
+            gl.enable(glow::SCISSOR_TEST);
+            gl.blit_framebuffer(
+                0,
+                0,
+                width,
+                height,
+                x,
+                y,
+                x + width,
+                y + height,
+                glow::COLOR_BUFFER_BIT,
+                glow::NEAREST,
+            );
+        }
+        gl.bind_framebuffer(glow::FRAMEBUFFER, resolve_to);
+        gl.viewport(x, y, width, height);
+    }
+
+    pub fn set_render_mode(&mut self, mode: RenderMode) {
+        self.render_mode = mode;
+    }
+
+    /// Switch untextured point sprites to `shape`, compiling its program the
+    /// first time the shape is used.
+    pub fn set_point_shape(&mut self, gl: &glow::Context, shape: PointShape) {
+        if shape == self.point_shape {
+            return;
         }
+        self.program = *self
+            .shape_programs
+            .entry(shape)
+            .or_insert_with(|| unsafe { create_particle_program(gl, shape) });
+        self.point_shape = shape;
     }
 
     pub unsafe fn paint(
-        &self,
+        &mut self,
         gl: &glow::Context,
         mvp: [f32; 16],
         particles: &[f32],
+        highlight: &[f32],
         scaling: f32,
         grid_enabled: bool,
     ) {
+        // Skip timing while the previous measurement is still in flight
+        #[cfg(debug_assertions)]
+        let timed = match self.gpu_timer.as_ref() {
+            Some(t) if !t.pending => {
+                gl.begin_query(glow::TIME_ELAPSED, t.query);
+                true
+            }
+            _ => false,
+        };
+
         gl.enable(glow::DEPTH_TEST);
         gl.enable(glow::BLEND);
         gl.blend_func(glow::SRC_ALPHA, glow::ONE_MINUS_SRC_ALPHA);
@@ -92,37 +574,228 @@ impl ParticleRenderer {
             self.draw_grid_and_axes(gl, mvp);
         }
 
-        // 2. Draw Particles
-        if !particles.is_empty() {
-            gl.use_program(Some(self.program));
-            let mvp_loc = gl.get_uniform_location(self.program, "u_mvp");
-            gl.uniform_matrix_4_f32_slice(mvp_loc.as_ref(), false, &mvp);
-            let scale_loc = gl.get_uniform_location(self.program, "u_scaling");
-            gl.uniform_1_f32(scale_loc.as_ref(), scaling);
-
-            gl.bind_vertex_array(Some(self.vao));
-            gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.vbo));
-            gl.buffer_data_u8_slice(
-                glow::ARRAY_BUFFER,
-                bytemuck::cast_slice(particles),
-                glow::DYNAMIC_DRAW,
-            );
+        // 2. Draw selection halo (enlarged, without depth writes so the
+        //    particles themselves still draw on top of it)
+        if !highlight.is_empty() {
+            gl.depth_mask(false);
+            self.draw_particles(gl, mvp, highlight, scaling, 1.5, None);
+            gl.depth_mask(true);
+        }
 
-            // Pos
-            gl.enable_vertex_attrib_array(0);
-            gl.vertex_attrib_pointer_f32(0, 3, glow::FLOAT, false, 8 * 4, 0);
-            // Color
-            gl.enable_vertex_attrib_array(1);
-            gl.vertex_attrib_pointer_f32(1, 4, glow::FLOAT, false, 8 * 4, 3 * 4);
-            // Size
-            gl.enable_vertex_attrib_array(2);
-            gl.vertex_attrib_pointer_f32(2, 1, glow::FLOAT, false, 8 * 4, 7 * 4);
-
-            gl.draw_arrays(glow::POINTS, 0, (particles.len() / 8) as i32);
+        // 3. Draw Particles, one pass per atlas so each binds its own texture
+        if let RenderMode::HeatMap {
+            low_color,
+            high_color,
+            radius,
+        } = self.render_mode
+        {
+            self.draw_heatmap(gl, mvp, particles, scaling, [low_color, high_color], radius);
+        } else if self.atlases.iter().all(Option::is_none) {
+            self.draw_particles(gl, mvp, particles, scaling, 1.0, None);
+        } else {
+            let mut groups: Vec<Vec<f32>> = vec![Vec::new(); self.atlases.len() + 1];
+            for p in particles.chunks_exact(FLOATS_PER_PARTICLE) {
+                let tex_id = p[8] as usize;
+                let group = match self.atlases.get(tex_id) {
+                    Some(Some(_)) => tex_id + 1,
+                    _ => 0,
+                };
+                groups[group].extend_from_slice(p);
+            }
+            self.draw_particles(gl, mvp, &groups[0], scaling, 1.0, None);
+            for (atlas, group) in self.atlases.iter().zip(&groups[1..]) {
+                if let Some(atlas) = atlas {
+                    self.draw_particles(gl, mvp, group, scaling, 1.0, Some(atlas));
+                }
+            }
         }
 
-        // 3. Draw Compass (Direction Indicator) in corner
+        // 4. Draw Compass (Direction Indicator) in corner
         self.draw_compass(gl, mvp);
+
+        #[cfg(debug_assertions)]
+        if let Some(t) = self.gpu_timer.as_mut().filter(|_| timed) {
+            gl.end_query(glow::TIME_ELAPSED);
+            t.pending = true;
+        }
+    }
+
+    /// GPU time of `paint` in microseconds, averaged over the last
+    /// `GPU_TIMER_WINDOW` measurements that have been read back. Debug builds
+    /// only; always `None` in release builds and without timer queries.
+    pub fn measure_render_time(&mut self, gl: &glow::Context) -> Option<f64> {
+        #[cfg(debug_assertions)]
+        {
+            let t = self.gpu_timer.as_mut()?;
+            unsafe {
+                if t.pending
+                    && gl.get_query_parameter_u32(t.query, glow::QUERY_RESULT_AVAILABLE) != 0
+                {
+                    // Elapsed nanoseconds; a 32-bit read only saturates for
+                    // paints longer than four seconds.
+                    let elapsed = gl.get_query_parameter_u32(t.query, glow::QUERY_RESULT);
+                    t.pending = false;
+                    if t.samples.len() == GPU_TIMER_WINDOW {
+                        t.samples.pop_front();
+                    }
+                    t.samples.push_back(elapsed as f64 / 1000.0);
+                }
+            }
+            if t.samples.is_empty() {
+                return None;
+            }
+            Some(t.samples.iter().sum::<f64>() / t.samples.len() as f64)
+        }
+        #[cfg(not(debug_assertions))]
+        {
+            let _ = gl;
+            None
+        }
+    }
+
+    /// Draw an extra particle layer on top of the scene (e.g. a comparison overlay).
+    pub unsafe fn paint_overlay(
+        &self,
+        gl: &glow::Context,
+        mvp: [f32; 16],
+        particles: &[f32],
+        scaling: f32,
+    ) {
+        gl.enable(glow::DEPTH_TEST);
+        gl.enable(glow::BLEND);
+        gl.blend_func(glow::SRC_ALPHA, glow::ONE_MINUS_SRC_ALPHA);
+        gl.enable(glow::PROGRAM_POINT_SIZE);
+        self.draw_particles(gl, mvp, particles, scaling, 1.0, None);
+    }
+
+    /// Draw trail segments (pairs of `[x, y, z, r, g, b, a]` vertices) into
+    /// the depth-tested scene without hiding the particles behind them.
+    pub unsafe fn paint_trails(&self, gl: &glow::Context, mvp: [f32; 16], lines: &[f32]) {
+        if lines.is_empty() {
+            return;
+        }
+        gl.enable(glow::DEPTH_TEST);
+        gl.depth_mask(false);
+        gl.enable(glow::BLEND);
+        gl.blend_func(glow::SRC_ALPHA, glow::ONE_MINUS_SRC_ALPHA);
+        gl.use_program(Some(self.line_program));
+        let mvp_loc = gl.get_uniform_location(self.line_program, "u_mvp");
+        gl.uniform_matrix_4_f32_slice(mvp_loc.as_ref(), false, &mvp);
+
+        gl.bind_vertex_array(Some(self.trail_vao));
+        gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.trail_vbo));
+        gl.buffer_data_u8_slice(
+            glow::ARRAY_BUFFER,
+            bytemuck::cast_slice(lines),
+            glow::STREAM_DRAW,
+        );
+        gl.enable_vertex_attrib_array(0);
+        gl.vertex_attrib_pointer_f32(0, 3, glow::FLOAT, false, 7 * 4, 0);
+        gl.enable_vertex_attrib_array(1);
+        gl.vertex_attrib_pointer_f32(1, 4, glow::FLOAT, false, 7 * 4, 3 * 4);
+        gl.draw_arrays(glow::LINES, 0, (lines.len() / 7) as i32);
+        gl.depth_mask(true);
+    }
+
+    /// Draw `particles` as plain points coloured by local density, mapped
+    /// through the `[low, high]` gradient.
+    unsafe fn draw_heatmap(
+        &self,
+        gl: &glow::Context,
+        mvp: [f32; 16],
+        particles: &[f32],
+        scaling: f32,
+        [low, high]: [[f32; 4]; 2],
+        radius: f32,
+    ) {
+        let densities = heat_densities(particles, radius);
+        gl.bind_vertex_array(Some(self.vao));
+        gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.density_vbo));
+        gl.buffer_data_u8_slice(
+            glow::ARRAY_BUFFER,
+            bytemuck::cast_slice(&densities),
+            glow::DYNAMIC_DRAW,
+        );
+        gl.enable_vertex_attrib_array(4);
+        gl.vertex_attrib_pointer_f32(4, 1, glow::FLOAT, false, 4, 0);
+        gl.use_program(Some(self.program));
+        let heat_loc = gl.get_uniform_location(self.program, "u_heatmap");
+        gl.uniform_1_i32(heat_loc.as_ref(), 1);
+        let low_loc = gl.get_uniform_location(self.program, "u_heat_low");
+        gl.uniform_4_f32_slice(low_loc.as_ref(), &low);
+        let high_loc = gl.get_uniform_location(self.program, "u_heat_high");
+        gl.uniform_4_f32_slice(high_loc.as_ref(), &high);
+
+        self.draw_particles(gl, mvp, particles, scaling, 1.0, None);
+
+        gl.uniform_1_i32(heat_loc.as_ref(), 0);
+        gl.bind_vertex_array(Some(self.vao));
+        gl.disable_vertex_attrib_array(4);
+    }
+
+    unsafe fn draw_particles(
+        &self,
+        gl: &glow::Context,
+        mvp: [f32; 16],
+        particles: &[f32],
+        scaling: f32,
+        size_scale: f32,
+        atlas: Option<&TextureAtlas>,
+    ) {
+        if particles.is_empty() {
+            return;
+        }
+        gl.use_program(Some(self.program));
+        let mvp_loc = gl.get_uniform_location(self.program, "u_mvp");
+        gl.uniform_matrix_4_f32_slice(mvp_loc.as_ref(), false, &mvp);
+        let scale_loc = gl.get_uniform_location(self.program, "u_scaling");
+        gl.uniform_1_f32(scale_loc.as_ref(), scaling);
+        let size_scale_loc = gl.get_uniform_location(self.program, "u_size_scale");
+        gl.uniform_1_f32(size_scale_loc.as_ref(), size_scale);
+        let textured_loc = gl.get_uniform_location(self.program, "u_textured");
+        gl.uniform_1_i32(textured_loc.as_ref(), atlas.is_some() as i32);
+        let soft_loc = gl.get_uniform_location(self.program, "u_soft_edges");
+        gl.uniform_1_i32(soft_loc.as_ref(), self.soft_edges as i32);
+        let (cols, rows) = atlas.map_or((1.0, 1.0), |a| (a.cols as f32, a.rows as f32));
+        let grid_loc = gl.get_uniform_location(self.program, "u_atlas_grid");
+        gl.uniform_2_f32(grid_loc.as_ref(), cols, rows);
+        if let Some(atlas) = atlas {
+            gl.active_texture(glow::TEXTURE0);
+            gl.bind_texture(glow::TEXTURE_2D, Some(atlas.texture));
+            let atlas_loc = gl.get_uniform_location(self.program, "u_atlas");
+            gl.uniform_1_i32(atlas_loc.as_ref(), 0);
+        }
+
+        gl.bind_vertex_array(Some(self.vao));
+        gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.vbo));
+        gl.buffer_data_u8_slice(
+            glow::ARRAY_BUFFER,
+            bytemuck::cast_slice(particles),
+            glow::DYNAMIC_DRAW,
+        );
+
+        let stride = (FLOATS_PER_PARTICLE * 4) as i32;
+        // Pos
+        gl.enable_vertex_attrib_array(0);
+        gl.vertex_attrib_pointer_f32(0, 3, glow::FLOAT, false, stride, 0);
+        // Color
+        gl.enable_vertex_attrib_array(1);
+        gl.vertex_attrib_pointer_f32(1, 4, glow::FLOAT, false, stride, 3 * 4);
+        // Size
+        gl.enable_vertex_attrib_array(2);
+        gl.vertex_attrib_pointer_f32(2, 1, glow::FLOAT, false, stride, 7 * 4);
+        // Atlas cell (seq_index); tex_id at offset 8 only selects the pass
+        gl.enable_vertex_attrib_array(3);
+        gl.vertex_attrib_pointer_f32(3, 1, glow::FLOAT, false, stride, 9 * 4);
+
+        gl.draw_arrays(
+            glow::POINTS,
+            0,
+            (particles.len() / FLOATS_PER_PARTICLE) as i32,
+        );
+        if atlas.is_some() {
+            gl.bind_texture(glow::TEXTURE_2D, None);
+        }
     }
 
     unsafe fn draw_compass(&self, gl: &glow::Context, mvp: [f32; 16]) {
@@ -223,12 +896,27 @@ impl ParticleRenderer {
 
     pub fn destroy(&self, gl: &glow::Context) {
         unsafe {
-            gl.delete_program(self.program);
+            for &program in self.shape_programs.values() {
+                gl.delete_program(program);
+            }
             gl.delete_program(self.line_program);
             gl.delete_buffer(self.vbo);
+            gl.delete_buffer(self.density_vbo);
             gl.delete_vertex_array(self.vao);
             gl.delete_buffer(self.line_vbo);
             gl.delete_vertex_array(self.line_vao);
+            gl.delete_buffer(self.trail_vbo);
+            gl.delete_vertex_array(self.trail_vao);
+        }
+        for atlas in self.atlases.iter().flatten() {
+            atlas.destroy(gl);
+        }
+        if let Some(target) = &self.msaa {
+            target.destroy(gl);
+        }
+        #[cfg(debug_assertions)]
+        if let Some(timer) = &self.gpu_timer {
+            timer.destroy(gl);
         }
     }
 }
@@ -1,14 +1,20 @@
+use super::node_editor::ExprGraph;
 use crate::i18n::I18nManager;
 use crate::particleex::{ParticleexCommand, ParticleexCommandFormat, ParticleexEditorMode};
-use crate::player::{NblHeader, Particle, PlayerState, TextureEntry};
-use crate::renderer::ParticleRenderer;
+use crate::player::{self, NblHeader, Particle, PlayerState, TextureEntry, NBL_VERSION};
+use crate::renderer::{
+    ParticleRenderer, PointShape, RenderMode, WireframeRenderer, FLOATS_PER_PARTICLE,
+};
 use eframe::{
     egui, egui_glow,
     glow::{self, HasContext},
 };
 use serde::{self, Deserialize, Serialize};
+use std::collections::HashSet;
 use std::fs;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum MultimediaThreadStatus {
@@ -43,19 +49,72 @@ pub enum AppMode {
     Creator,
 }
 
+/// How the preview viewport presents the current frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PlaybackMode {
+    #[default]
+    Normal,
+    /// Ghost the previous `n` frames behind the current one as a cheap
+    /// motion blur.
+    FrameBlend(u8),
+}
+
+/// Order particles are drawn in. With alpha blending, far particles have to
+/// be drawn first to blend correctly; additive looks do not care.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SortMode {
+    /// Keep the file's order.
+    #[default]
+    None,
+    FrontToBack,
+    BackToFront,
+    /// A fixed pseudo-random order seeded by particle id, spreading blending
+    /// errors evenly without reshuffling between frames.
+    Random,
+    /// Red through violet, for rainbow layering under additive blending.
+    Hue,
+    /// Darkest first.
+    Brightness,
+    /// World Z, lowest first, whatever the camera angle.
+    ZDepth,
+}
+
+/// Identifies the frame and camera a depth sort was computed for.
+#[derive(Clone, Copy, PartialEq)]
+struct DepthSortKey {
+    mode: AppMode,
+    frame: usize,
+    len: usize,
+    eye: [f32; 3],
+    target: [f32; 3],
+}
+
+/// Draw order of the last depth sort, nearest first, as indices into the
+/// sorted frame.
+struct DepthSortCache {
+    key: DepthSortKey,
+    order: Vec<u32>,
+}
+
 #[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct AppConfig {
     pub lang: String,
+    /// Background decoder threads for preview playback (0 = off).
+    pub prefetch_threads: u8,
 }
 
 impl Default for AppConfig {
     fn default() -> Self {
         Self {
             lang: "en_US".into(),
+            prefetch_threads: 2,
         }
     }
 }
 
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct CameraState {
     pub yaw: f32,
     pub pitch: f32,
@@ -82,8 +141,13 @@ pub enum EditTool {
     Transform,
     Trim,
     Compress,
+    Deflate,
+    Voxelise,
+    Smooth,
 }
 
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct EditState {
     pub selected_tool: EditTool,
     pub new_fps: u16,
@@ -94,17 +158,61 @@ pub struct EditState {
     pub size_uniform: f32,
     pub brightness: f32,
     pub opacity: f32,
+    /// Tint for `EditAction::AdjustColor`, in kelvin.
+    pub color_temperature: f32,
     pub translate: [f32; 3],
     pub pos_scale: f32,
     pub trim_start: u32,
     pub trim_end: u32,
+    /// Frames of the current trim range, decoded for previewing before export.
+    #[serde(skip)]
+    pub trim_preview: Option<Vec<Vec<Particle>>>,
+    #[serde(skip)]
+    pub trim_preview_frame: usize,
     pub status_msg: Option<String>,
+    #[serde(skip)]
     pub decoded_frames: Option<Vec<Vec<Particle>>>,
+    #[serde(skip)]
     pub edited_header: Option<NblHeader>,
     pub compress_keyframe_interval: u32,
-    pub compress_zstd_level: i32,
+    pub compress_algorithm: crate::player::CompressAlgorithm,
+    /// Tolerance for straightening near-linear motion on compress; 0 is off.
+    pub compress_linear_tolerance: f32,
+    pub deflate_radius: f32,
+    pub voxel_grid_size: f32,
+    pub smooth_passes: u32,
+    pub smooth_weight: f32,
+    pub jitter_amplitude: f32,
+    pub jitter_seed: u64,
+    /// XZ direction of `EditAction::Wind`.
+    pub wind_direction: [f32; 2],
+    pub wind_strength: f32,
+    pub wind_turbulence: f32,
+    pub gravity_center: [f32; 3],
+    pub gravity_strength: f32,
+    pub gravity_falloff: f32,
+    /// Formula of `EditAction::Resample`, mapping output `t` to source time.
+    pub resample_expr: String,
+    pub resample_frames: usize,
+    pub selection_translate: [f32; 3],
+    pub selection_rotate: [f32; 3],
+    pub selection_scale: f32,
+    #[serde(skip)]
     pub compress_progress:
         Option<std::sync::Arc<std::sync::Mutex<crate::player::CompressProgress>>>,
+    /// Last Voxelise count estimate; recomputed when its inputs change.
+    #[serde(skip)]
+    pub voxel_estimate: Option<VoxelEstimate>,
+}
+
+/// Particles in one frame before and after `merge_voxel_duplicates`, with
+/// the grid size and frame they were counted for.
+#[derive(Clone, Copy, PartialEq)]
+pub struct VoxelEstimate {
+    pub grid_size: f32,
+    pub frame: i32,
+    pub before: usize,
+    pub after: usize,
 }
 
 impl Default for EditState {
@@ -119,20 +227,62 @@ impl Default for EditState {
             size_uniform: 0.5,
             brightness: 1.0,
             opacity: 1.0,
+            color_temperature: crate::player::NEUTRAL_KELVIN,
             translate: [0.0; 3],
             pos_scale: 1.0,
             trim_start: 0,
             trim_end: 0,
+            trim_preview: None,
+            trim_preview_frame: 0,
             status_msg: None,
             decoded_frames: None,
             edited_header: None,
             compress_keyframe_interval: 60,
-            compress_zstd_level: 1,
+            compress_algorithm: crate::player::CompressAlgorithm::Zstd(1),
+            compress_linear_tolerance: 0.0,
+            deflate_radius: 0.05,
+            voxel_grid_size: 0.1,
+            smooth_passes: 2,
+            smooth_weight: 0.5,
+            jitter_amplitude: 0.02,
+            jitter_seed: 0,
+            wind_direction: [1.0, 0.0],
+            wind_strength: 0.01,
+            wind_turbulence: 0.5,
+            gravity_center: [0.0; 3],
+            gravity_strength: 0.01,
+            gravity_falloff: 1.0,
+            resample_expr: "t*t".to_string(),
+            resample_frames: 0,
+            selection_translate: [0.0; 3],
+            selection_rotate: [0.0; 3],
+            selection_scale: 1.0,
             compress_progress: None,
+            voxel_estimate: None,
         }
     }
 }
 
+/// How multimedia particles start relative to their place in the source.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum AttractMode {
+    /// Start at the source position.
+    None,
+    /// Start scattered in a sphere and get pulled to the source position.
+    ToSource,
+}
+
+/// Which image/text particles appear first when their birth is staggered.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum BirthDelayMode {
+    None,
+    /// Left to right.
+    ByXPosition,
+    /// Outwards from the origin.
+    ByDistance,
+    Random,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum IntroPreset {
     None,
@@ -270,12 +420,138 @@ impl Default for TextureAnimationConfig {
     }
 }
 
+/// How text-mode particles are coloured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TextColorMode {
+    /// Every particle uses `color_start`.
+    Uniform,
+    /// `color_start` → `color_end` across the text's width.
+    GradientX,
+    /// `color_start` → `color_end` across the text's height.
+    GradientY,
+    /// Each character takes the next colour from `text_palette`.
+    PerChar,
+}
+
+/// Order in which text-mode glyphs are laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TextDirection {
+    LeftToRight,
+    /// Each line reversed and right-aligned.
+    RightToLeft,
+    /// Each line is a column read top to bottom; columns run right to left.
+    TopToBottom,
+}
+
+/// Which part of the rendered glyphs becomes particles. Stroke widths are in
+/// pixels at the text render size.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TextRenderMode {
+    Fill,
+    /// Only a band of the given width around the outside of each glyph.
+    Stroke(f32),
+    FillAndStroke(f32),
+}
+
+impl TextRenderMode {
+    pub fn stroke_width(self) -> Option<f32> {
+        match self {
+            TextRenderMode::Fill => None,
+            TextRenderMode::Stroke(w) | TextRenderMode::FillAndStroke(w) => Some(w),
+        }
+    }
+}
+
+/// One selectable face of the text-mode font.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FontVariant {
+    pub name: String,
+    /// Face index inside the font file or collection.
+    pub font_index: u32,
+    /// Axis values of a variable font's named instance; empty for a
+    /// plain face.
+    #[serde(default)]
+    pub coordinates: Vec<([u8; 4], f32)>,
+    /// File holding this face, when it differs from the selected font.
+    pub path: Option<PathBuf>,
+}
+
+/// How the source image is repeated in image mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TilingMode {
+    None,
+    Repeat(u32, u32),
+    /// Like `Repeat`, but every other tile is flipped.
+    Mirror(u32, u32),
+}
+
+impl TilingMode {
+    /// Number of tiles along x and y.
+    pub fn counts(self) -> (u32, u32) {
+        match self {
+            TilingMode::None => (1, 1),
+            TilingMode::Repeat(nx, ny) | TilingMode::Mirror(nx, ny) => (nx.max(1), ny.max(1)),
+        }
+    }
+
+    /// Map a pixel of the tiled canvas back onto the `width` × `height` source.
+    pub fn source_coord(self, x: u32, y: u32, width: u32, height: u32) -> (u32, u32) {
+        let mirror = matches!(self, TilingMode::Mirror(..));
+        let fold = |v: u32, size: u32| {
+            let local = v % size;
+            if mirror && (v / size) % 2 == 1 {
+                size - 1 - local
+            } else {
+                local
+            }
+        };
+        (fold(x, width), fold(y, height))
+    }
+}
+
+/// Progress fraction and final result of a background motion analysis.
+pub type MotionStatsJob = (
+    std::sync::Arc<std::sync::Mutex<f32>>,
+    std::sync::Arc<std::sync::Mutex<Option<Vec<crate::player::ParticleMotionStats>>>>,
+);
+
+/// Progress fraction and final result (output path or error) of a
+/// background image export.
+pub type ImageExportJob = (
+    std::sync::Arc<std::sync::Mutex<f32>>,
+    std::sync::Arc<std::sync::Mutex<Option<Result<String, String>>>>,
+);
+
+/// Contents of a shareable `.pex` expression file.
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct ExpressionRecipe {
+    pub velocity_expr: String,
+    pub macros: std::collections::HashMap<String, String>,
+}
+
+/// Colours before and after a palette reduction, most common first.
+pub type PalettePair = (Vec<[u8; 3]>, Vec<[u8; 3]>);
+
 #[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct MultimediaState {
     pub mode: usize,
     pub text_input: String,
     pub font_name: String,
     pub system_fonts: Vec<String>,
+    #[serde(skip)]
+    pub font_variants: Vec<FontVariant>,
+    pub font_variant: Option<FontVariant>,
+    pub text_color_mode: TextColorMode,
+    pub text_direction: TextDirection,
+    pub text_render_mode: TextRenderMode,
+    /// Keep only the one-pixel border of the rendered text bitmap, found by
+    /// erosion, whatever the font or render mode.
+    pub text_outline_only: bool,
+    pub color_start: [u8; 3],
+    pub color_end: [u8; 3],
+    pub text_palette: Vec<[u8; 3]>,
     pub media_path: Option<String>,
     pub target_fps: u16,
     pub duration_secs: f32,
@@ -285,13 +561,81 @@ pub struct MultimediaState {
     pub intro_params: [f32; 2],
     pub outro_preset: OutroPreset,
     pub outro_params: [f32; 2],
+    /// Copies of the last frame before the outro starts, added on top of
+    /// `duration_secs`.
+    pub outro_hold_frames: u32,
     pub velocity_expr: String,
+    /// Show the node graph instead of the text box in the expression editor.
+    #[serde(skip)]
+    pub expr_node_mode: bool,
+    #[serde(skip)]
+    pub expr_graph: ExprGraph,
+    /// Search text of the formula library panel.
+    #[serde(skip)]
+    pub formula_query: String,
+    /// Output of `print()` calls from the last compile.
+    #[serde(skip)]
+    pub expr_log: crate::particleex::DebugLog,
+    /// Expression file loaded from disk, shown for confirmation before use.
+    #[serde(skip)]
+    pub pending_recipe: Option<ExpressionRecipe>,
+    /// Run `particleex::format_expr` on the expression before saving a `.pex`.
+    pub format_on_save: bool,
     pub font_size: f32, // 文字渲染时的像素大小
+    /// Extra pixels after each glyph, at `font_size`.
+    pub letter_spacing: f32,
+    /// Extra pixels between lines, on top of the automatic gap.
+    pub extra_line_gap: f32,
     pub brightness_threshold: f32,
     pub particle_size: f32, // 粒子组成的整体空间尺寸 (原来的 particle_scale)
     pub point_size: f32,    // 粒子个体的大小 (Point Size)
+    /// Round image and text particle positions in the source plane to
+    /// multiples of `grid_snap`, for a mosaic look.
+    pub grid_snap_output: bool,
+    pub grid_snap: f32,
+    /// Distinct grid cells and particle count of the last snapped compile.
+    #[serde(skip)]
+    pub grid_snap_cells: Option<(usize, usize)>,
     pub density: f32,
+    /// Thin out image pixels in crowded regions so every region ends up
+    /// with about the same particle density.
+    pub density_equalise: bool,
+    /// 0 keeps every pixel, 1 matches the sparse regions fully.
+    pub equalise_strength: f32,
     pub rotation: [f32; 3],
+    pub tiling: TilingMode,
+    /// Grayscale image whose luminance pushes image particles along Z.
+    pub depth_map_path: Option<String>,
+    pub depth_scale: f32,
+    /// Image whose alpha (luminance, if it has none) must be non-zero for an
+    /// image pixel to spawn particles.
+    pub mask_path: Option<String>,
+    /// Remap image/text particle colours down to this many palette entries.
+    pub palette_limit: Option<usize>,
+    /// Quantise all exported frames to one shared `quantize_k`-colour palette.
+    pub quantize_on_export: bool,
+    pub quantize_k: usize,
+    /// Most common colours before and after the last export's quantisation.
+    #[serde(skip)]
+    pub quantize_palettes: Option<PalettePair>,
+    /// Colour image/text particles with `color_expr` instead of the source pixels.
+    pub color_from_expr: bool,
+    pub color_expr: String,
+    /// Place particles from the source but colour them with the velocity
+    /// expression's `cr, cg, cb, alpha` at frame 0.
+    pub override_color: bool,
+    /// Image whose R/G channels (centred at 128) add to each particle's
+    /// vx/vy every frame, sampled at its XY position over the source extent.
+    pub flow_field_path: Option<String>,
+    pub flow_field_strength: f32,
+    pub attract_mode: AttractMode,
+    /// Fraction of the remaining distance to the rest position added to the
+    /// velocity each frame.
+    pub attract_strength: f32,
+    pub birth_delay_mode: BirthDelayMode,
+    /// Fraction of the duration over which particles appear; the last one
+    /// is born at `particle_birth_delay * total_frames`.
+    pub particle_birth_delay: f32,
     pub texture_animation: TextureAnimationConfig,
     pub export_threads: usize,
     pub status_msg: Option<String>,
@@ -299,13 +643,52 @@ pub struct MultimediaState {
     pub is_processing: bool,
     #[serde(skip)]
     pub preview_frames: Option<Vec<Vec<crate::player::Particle>>>,
+    /// Visible particles per preview frame, for the count sparkline.
+    #[serde(skip)]
+    pub preview_counts: Vec<usize>,
     pub preview_playing: bool,
     pub preview_frame_idx: i32,
     pub preview_timer: f32,
+    /// Playback rate of the preview; compiled frames stay at `target_fps`.
+    pub preview_fps: u16,
+    /// Frames advanced per preview tick; negative plays backwards.
+    pub preview_step: i32,
+    /// Show the preview alone in a separate fullscreen window.
+    #[serde(skip)]
+    pub fullscreen_preview: bool,
+    /// Output size in pixels of rendered image exports (GIF, spritesheet cells).
+    pub export_image_size: [u32; 2],
+    pub spritesheet_cols: u32,
+    /// Running image export: progress and the final status message.
+    #[serde(skip)]
+    pub image_export: Option<ImageExportJob>,
     #[serde(skip)]
     pub source_image_preview: Option<egui::TextureHandle>,
     #[serde(skip)]
     pub last_source_size: Option<[u32; 2]>,
+    /// Images wider or taller than this are shrunk to fit before conversion,
+    /// keeping their aspect ratio. 0 disables the limit.
+    pub max_source_dimension: u32,
+    /// Original size of the last image that `max_source_dimension` shrank.
+    #[serde(skip)]
+    pub source_downscaled_from: Option<[u32; 2]>,
+    /// Part of the source video converted to particles, in seconds.
+    /// An end of 0 means the end of the video.
+    pub video_start_sec: f32,
+    pub video_end_sec: f32,
+    /// Length of the selected video, from ffprobe.
+    #[serde(skip)]
+    pub video_duration: Option<f32>,
+    /// Video frames are resized by this factor before becoming particles.
+    pub video_scale: f32,
+    /// Desaturate video frames before conversion; particles get the luminance
+    /// on all three channels.
+    pub video_grayscale: bool,
+    /// Negate video frames before conversion.
+    pub video_invert: bool,
+    /// Unscaled size of the selected video, from ffprobe.
+    #[serde(skip)]
+    pub video_native_size: Option<[u32; 2]>,
     #[serde(skip)]
     pub preview_output_path: Option<String>,
     #[serde(skip)]
@@ -340,6 +723,21 @@ impl Default for MultimediaState {
             text_input: "Nebula".to_string(),
             font_name: String::new(),
             system_fonts: Vec::new(),
+            font_variants: Vec::new(),
+            font_variant: None,
+            text_color_mode: TextColorMode::Uniform,
+            text_direction: TextDirection::LeftToRight,
+            text_render_mode: TextRenderMode::Fill,
+            text_outline_only: false,
+            color_start: [255, 255, 255],
+            color_end: [120, 180, 255],
+            text_palette: vec![
+                [255, 90, 90],
+                [255, 200, 80],
+                [110, 220, 120],
+                [90, 170, 255],
+                [200, 120, 255],
+            ],
             media_path: None,
             target_fps: 30,
             duration_secs: 5.0,
@@ -349,24 +747,71 @@ impl Default for MultimediaState {
             intro_params: [1.0, 0.0],
             outro_preset: OutroPreset::FadeScale,
             outro_params: [1.0, 0.0],
+            outro_hold_frames: 0,
             velocity_expr: "vx=0; vy=0; vz=0".to_string(),
+            expr_node_mode: false,
+            expr_graph: ExprGraph::default(),
+            formula_query: String::new(),
+            expr_log: Default::default(),
+            pending_recipe: None,
+            format_on_save: false,
             font_size: 128.0,
+            letter_spacing: 0.0,
+            extra_line_gap: 0.0,
             brightness_threshold: 0.1,
             particle_size: 0.1,
+            grid_snap_output: false,
+            grid_snap: 0.5,
+            grid_snap_cells: None,
             point_size: 0.05,
             density: 0.5,
+            density_equalise: false,
+            equalise_strength: 1.0,
             rotation: [0.0, 0.0, 0.0],
+            tiling: TilingMode::None,
+            depth_map_path: None,
+            depth_scale: 1.0,
+            mask_path: None,
+            palette_limit: None,
+            quantize_on_export: false,
+            quantize_k: 256,
+            quantize_palettes: None,
+            color_from_expr: false,
+            override_color: false,
+            flow_field_path: None,
+            flow_field_strength: 0.01,
+            attract_mode: AttractMode::None,
+            attract_strength: 0.05,
+            birth_delay_mode: BirthDelayMode::None,
+            particle_birth_delay: 0.5,
+            color_expr: "cr = 0.5 + 0.5 * sin(x); cg = 0.5 + 0.5 * sin(y); cb = 1".to_string(),
             texture_animation: TextureAnimationConfig::default(),
             export_threads: 4,
             status_msg: None,
             processing_progress: None,
             is_processing: false,
             preview_frames: None,
+            preview_counts: Vec::new(),
             preview_playing: false,
             preview_frame_idx: 0,
             preview_timer: 0.0,
+            preview_fps: 30,
+            preview_step: 1,
+            fullscreen_preview: false,
+            export_image_size: [480, 480],
+            spritesheet_cols: 8,
+            image_export: None,
             source_image_preview: None,
             last_source_size: None,
+            max_source_dimension: 1920,
+            source_downscaled_from: None,
+            video_start_sec: 0.0,
+            video_end_sec: 0.0,
+            video_duration: None,
+            video_scale: 0.25,
+            video_native_size: None,
+            video_grayscale: false,
+            video_invert: false,
             preview_output_path: None,
             thread_progress: Vec::new(),
             video_compile_shared: None,
@@ -385,6 +830,15 @@ pub struct PexCommandEntry {
     pub enabled: bool,
     pub textures: Vec<String>,
     pub texture_interval: u32,
+    /// Ticks per second the command was written for; `None` is Minecraft's 20.
+    pub tick_rate_override: Option<f32>,
+}
+
+impl PexCommandEntry {
+    /// `CompileEntry::time_scale_override` for `tick_rate_override`.
+    pub fn time_scale_override(&self) -> Option<f64> {
+        self.tick_rate_override.map(|rate| 60.0 / rate as f64)
+    }
 }
 
 impl Default for PexCommandEntry {
@@ -402,6 +856,7 @@ impl Default for PexCommandEntry {
             enabled: true,
             textures: default_particle_textures(),
             texture_interval: 20,
+            tick_rate_override: None,
         }
     }
 }
@@ -413,11 +868,31 @@ pub struct ParticleexState {
     pub preview_frame_idx: i32,
     pub preview_timer: f32,
     pub preview_fps: u16,
+    /// Interpolated frames per 60 fps step passed to `compile_entries`.
+    pub subdivisions: u32,
     pub status_msg: Option<String>,
     pub show_help: bool,
     pub fullscreen_entry: Option<usize>,
     pub confirm_delete: Option<usize>,
     pub preview_textures: Option<Vec<String>>,
+    /// Show the side panel as one plain-text command editor instead of the
+    /// per-entry cards.
+    pub text_view: bool,
+    /// Contents of the text view, one command per line; line `i` edits the
+    /// command of entry `i`.
+    pub raw_text: String,
+    pub live_test: LiveTestState,
+}
+
+/// Debounced single-particle trace shown by the expression editor's live test.
+#[derive(Default)]
+pub struct LiveTestState {
+    /// Expression the current `trace` was computed from.
+    pub source: String,
+    /// Latest edit and when it happened, waiting out the debounce.
+    pub pending: Option<(String, Instant)>,
+    pub trace: Vec<[f64; 3]>,
+    pub error: Option<String>,
 }
 impl Default for ParticleexState {
     fn default() -> Self {
@@ -428,11 +903,15 @@ impl Default for ParticleexState {
             preview_frame_idx: 0,
             preview_timer: 0.0,
             preview_fps: 60,
+            subdivisions: 1,
             status_msg: None,
             show_help: false,
             fullscreen_entry: None,
             confirm_delete: None,
             preview_textures: None,
+            text_view: false,
+            raw_text: String::new(),
+            live_test: LiveTestState::default(),
         }
     }
 }
@@ -440,9 +919,37 @@ impl Default for ParticleexState {
 #[derive(PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
 pub enum CreatorPreset {
     Butterfly,
+    /// Particles emitted from the surface of `CreatorState::mesh_path`.
+    Mesh,
+}
+
+/// Second NBL file drawn over the preview to spot differences.
+pub struct CompareState {
+    pub secondary_path: Option<PathBuf>,
+    pub secondary_player: Option<PlayerState>,
+    pub enabled: bool,
+    pub overlay_color: [u8; 4],
+    /// Highlight what changed since the previous frame of the primary file.
+    pub frame_diff: bool,
+    /// Frame index the cached diff was computed for.
+    pub frame_diff_cache: Option<(i32, crate::player::CompareResult)>,
+}
+
+impl Default for CompareState {
+    fn default() -> Self {
+        Self {
+            secondary_path: None,
+            secondary_player: None,
+            enabled: true,
+            overlay_color: [60, 120, 255, 140],
+            frame_diff: false,
+            frame_diff_cache: None,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct CreatorState {
     pub selected_preset: CreatorPreset,
     pub butterfly_count: u32,
@@ -462,6 +969,9 @@ pub struct CreatorState {
     pub trail_gravity: [f32; 3],
     pub trail_duration: f32,
     pub trail_opacity: f32,
+    /// Control points the butterfly travels along over the whole duration,
+    /// as a Catmull-Rom spline. `None` keeps it at the origin.
+    pub follow_path: Option<Vec<[f32; 3]>>,
     // Velocity expression
     pub velocity_expr: String,
     pub target_fps: u16,
@@ -470,15 +980,114 @@ pub struct CreatorState {
     pub status_msg: Option<String>,
     #[serde(skip)]
     pub preview_frames: Option<Vec<Vec<Particle>>>,
+    /// Extent of all `preview_frames`, computed when they are set.
+    #[serde(skip)]
+    pub preview_bbox: Option<([f32; 3], [f32; 3])>,
     pub preview_playing: bool,
     pub preview_frame_idx: i32,
     pub preview_timer: f32,
+    pub loop_mode: PlaybackLoopMode,
+    /// +1 or -1; only `PlaybackLoopMode::PingPong` plays backwards.
+    #[serde(skip)]
+    pub preview_direction: i32,
     // Flap mode: 0 = continuous (speed-based), 1 = schedule (imported time points)
     pub flap_mode: u8,
     #[serde(skip)]
     pub flap_schedule: Vec<f32>,
     #[serde(skip)]
     pub flap_schedule_status: Option<String>,
+    /// First particle id of the trail in `preview_frames`; ids below it belong
+    /// to the butterfly itself. Used to split the output into layers.
+    #[serde(skip)]
+    pub trail_id_start: i32,
+    /// Wavefront OBJ the Mesh preset emits from.
+    pub mesh_path: Option<String>,
+    pub mesh_emission: EmissionMode,
+    /// Particles spawned per frame in `EmissionMode::Continuous`.
+    pub mesh_emit_rate: u32,
+    /// Particles spawned per trigger in `EmissionMode::OnEvent`.
+    pub mesh_burst_count: u32,
+    /// Initial speed along the triangle normal, in units per second.
+    pub mesh_emit_speed: f32,
+    pub mesh_lifetime: f32,
+    pub mesh_color: [f32; 3],
+    /// Emitters that spawn from every live mesh particle, e.g. smoke off fire.
+    pub mesh_sub_emitters: Vec<SubEmitterConfig>,
+    /// No particles are spawned while this many are alive.
+    pub max_particles: usize,
+}
+
+/// What the creator preview does when it reaches an end of the animation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PlaybackLoopMode {
+    #[default]
+    Loop,
+    /// Stop on the last frame.
+    Once,
+    /// Play backwards from the last frame, then forwards from the first.
+    PingPong,
+}
+
+impl PlaybackLoopMode {
+    /// Frame after `idx` moving in `direction` (+1/-1), which `PingPong`
+    /// flips at each end. `None` once `Once` has reached the last frame.
+    pub fn advance(self, idx: i32, direction: &mut i32, len: i32) -> Option<i32> {
+        let last = (len - 1).max(0);
+        match self {
+            Self::Loop => Some((idx + 1) % len.max(1)),
+            Self::Once => (idx < last).then_some(idx + 1),
+            Self::PingPong => {
+                let next = idx + *direction;
+                if !(0..=last).contains(&next) {
+                    *direction = -*direction;
+                    return Some((idx + *direction).clamp(0, last));
+                }
+                Some(next)
+            }
+        }
+    }
+}
+
+/// When the mesh emitter spawns particles.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub enum EmissionMode {
+    /// `mesh_emit_rate` particles every tick.
+    Continuous,
+    /// `mesh_burst_count` particles on every tick where the expression,
+    /// run with `t` and the live particle `count`, sets `emit >= 1`.
+    OnEvent(String),
+}
+
+impl EmissionMode {
+    pub const DEFAULT_EVENT_EXPR: &'static str = "emit = sin(t * 12) > 0.98";
+}
+
+/// Spawns particles around each live particle of the parent emitter.
+/// Sub-emitted particles do not emit further.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SubEmitterConfig {
+    /// Particles per parent per second; fractions spawn stochastically.
+    pub rate: f32,
+    /// Speed in a random direction, in units per second.
+    pub speed: f32,
+    pub lifetime: f32,
+    pub color: [f32; 3],
+    pub gravity: [f32; 3],
+    pub point_size: f32,
+}
+
+impl Default for SubEmitterConfig {
+    fn default() -> Self {
+        Self {
+            rate: 2.0,
+            speed: 0.2,
+            lifetime: 1.0,
+            color: [0.5, 0.5, 0.5],
+            gravity: [0.0, 0.5, 0.0],
+            point_size: 0.08,
+        }
+    }
 }
 
 impl Default for CreatorState {
@@ -499,22 +1108,59 @@ impl Default for CreatorState {
             trail_gravity: [0.0, -0.5, 0.0],
             trail_duration: 0.5,
             trail_opacity: 0.5,
+            follow_path: None,
             velocity_expr: "vx=0; vy=0; vz=0".to_string(),
             target_fps: 30,
             duration_secs: 5.0,
             texture_animation: TextureAnimationConfig::default(),
             status_msg: None,
             preview_frames: None,
+            preview_bbox: None,
             preview_playing: false,
             preview_frame_idx: 0,
             preview_timer: 0.0,
+            loop_mode: PlaybackLoopMode::Loop,
+            preview_direction: 1,
             flap_mode: 0,
             flap_schedule: Vec::new(),
             flap_schedule_status: None,
+            trail_id_start: i32::MAX,
+            mesh_path: None,
+            mesh_emission: EmissionMode::Continuous,
+            mesh_emit_rate: 50,
+            mesh_burst_count: 500,
+            mesh_emit_speed: 1.0,
+            mesh_lifetime: 1.5,
+            mesh_color: [0.4, 0.8, 1.0],
+            mesh_sub_emitters: Vec::new(),
+            max_particles: 200_000,
         }
     }
 }
 
+/// Everything saved to a `.nbproj` file. Read as owned state; written by
+/// `NebulaToolsApp::project_json` with the type parameters set to references
+/// into the app, so saving never clones it.
+#[derive(Serialize, Deserialize)]
+pub struct NebulaProject<M = MultimediaState, C = CreatorState, K = CameraState, E = EditState> {
+    pub multimedia: M,
+    pub creator: C,
+    pub camera: K,
+    pub edit: E,
+    pub nbl_path: Option<PathBuf>,
+    /// Files saved before this field existed hold v1 expressions.
+    #[serde(default = "first_expr_version")]
+    pub expr_version: u32,
+}
+
+fn first_expr_version() -> u32 {
+    1
+}
+
+pub fn read_project(path: &std::path::Path) -> anyhow::Result<NebulaProject> {
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
 pub fn build_texture_entries(textures: &[String]) -> Vec<TextureEntry> {
     textures
         .iter()
@@ -527,6 +1173,52 @@ pub fn build_texture_entries(textures: &[String]) -> Vec<TextureEntry> {
         .collect()
 }
 
+/// Wall-clock cost in milliseconds of the latest run of each major subsystem,
+/// shown by the debug overlay. `render_ms` is the CPU side of the viewport
+/// paint callback only; the GPU work itself is not waited on.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DebugTimings {
+    pub compile_ms: f32,
+    pub render_ms: f32,
+    pub decode_ms: f32,
+    pub ui_ms: f32,
+    /// Averaged GPU time of the viewport paint, from
+    /// `ParticleRenderer::measure_render_time` (debug builds only).
+    pub gpu_render_us: Option<f64>,
+}
+
+pub type SharedTimings = Arc<Mutex<DebugTimings>>;
+
+/// Stores the time elapsed since its creation into one `DebugTimings` field
+/// when dropped, so early returns are still measured.
+pub struct TimingGuard {
+    timings: SharedTimings,
+    field: fn(&mut DebugTimings) -> &mut f32,
+    started: Instant,
+}
+
+impl TimingGuard {
+    pub fn new(timings: &SharedTimings, field: fn(&mut DebugTimings) -> &mut f32) -> Self {
+        Self {
+            timings: timings.clone(),
+            field,
+            started: Instant::now(),
+        }
+    }
+}
+
+impl Drop for TimingGuard {
+    fn drop(&mut self) {
+        let ms = self.started.elapsed().as_secs_f32() * 1000.0;
+        if let Ok(mut timings) = self.timings.lock() {
+            *(self.field)(&mut timings) = ms;
+        }
+    }
+}
+
+/// Writes one frame of particles to a file in some interchange format.
+pub type FrameWriter = fn(&[Particle], &std::path::Path) -> anyhow::Result<()>;
+
 pub struct NebulaToolsApp {
     pub player: PlayerState,
     pub config: AppConfig,
@@ -534,8 +1226,35 @@ pub struct NebulaToolsApp {
     pub error_msg: Option<String>,
     pub camera: CameraState,
     pub renderer: Arc<Mutex<Option<ParticleRenderer>>>,
+    /// Renderer for the creator's top-down extent view.
+    pub extent_renderer: Arc<Mutex<Option<WireframeRenderer>>>,
     pub show_grid: bool,
+    /// Unmultiplied RGBA clear colour of the 3D viewport; exports keep its alpha.
+    pub viewport_bg_color: [f32; 4],
+    /// Viewport multisample count (1, 2, 4 or 8); 1 draws without MSAA.
+    pub msaa_samples: u8,
+    /// Outline of untextured particles in the viewport.
+    pub point_shape: PointShape,
+    /// Draw order applied by `prepare_render_data_from`.
+    pub sort_mode: SortMode,
+    /// Reused by `draw_order` while the frame and camera are unchanged, so a
+    /// paused view does not re-sort every repaint.
+    depth_sort_cache: Mutex<Option<DepthSortCache>>,
+    pub render_mode: RenderMode,
+    /// Draw per-particle motion towards the next frame in the preview viewport.
+    pub show_velocity_arrows: bool,
+    pub velocity_scale: f32,
+    /// Positions by particle id of the frame after the one in the key, used
+    /// for the velocity arrows.
+    pub velocity_next: Option<(i32, std::collections::HashMap<i32, [f32; 3]>)>,
     pub mode: AppMode,
+    pub playback_mode: PlaybackMode,
+    /// Render data of the most recently shown preview frames, oldest first,
+    /// used by `PlaybackMode::FrameBlend`.
+    pub blend_history: std::collections::VecDeque<(i32, Vec<f32>)>,
+    /// Positions by particle id of the most recently shown preview frames,
+    /// oldest first, used by `RenderMode::Trail`.
+    pub trail_history: std::collections::VecDeque<(i32, std::collections::HashMap<i32, [f32; 3]>)>,
     pub scrub_frame: Option<u32>,
     pub fps_counter: f32,
     pub fps_display: f32,
@@ -544,6 +1263,44 @@ pub struct NebulaToolsApp {
     pub pex: ParticleexState,
     pub multimedia: MultimediaState,
     pub creator: CreatorState,
+    pub compare: CompareState,
+    /// Particle ids picked in the preview viewport.
+    pub selected_ids: HashSet<i32>,
+    /// Lasso tool toggle; while active, left-drag draws a lasso instead of orbiting.
+    pub lasso_active: bool,
+    pub lasso_points: Vec<egui::Pos2>,
+    /// Thumbnails for the preview Textures panel, keyed by texture path
+    /// (`None` when the image could not be loaded).
+    pub texture_thumbnails: std::collections::HashMap<String, Option<egui::TextureHandle>>,
+    /// Per-frame statistics of the whole animation, computed on request.
+    pub animation_stats: Vec<crate::player::FrameStats>,
+    /// Per-particle paths of the whole animation, computed on request.
+    pub motion_stats: Vec<crate::player::ParticleMotionStats>,
+    /// Running `motion_stats` analysis, started from the Motion section.
+    pub motion_job: Option<MotionStatsJob>,
+    /// Sidecar metadata of the open file, edited in the Properties section.
+    pub nbl_meta: crate::player::NblMeta,
+    /// `nbl_meta.tags` while being edited, comma separated.
+    pub nbl_meta_tags: String,
+    /// `$name` shortcuts expanded in velocity expressions before compiling.
+    pub expression_macros: std::collections::HashMap<String, String>,
+    /// Name and body being typed into the Macros panel.
+    pub macro_draft: (String, String),
+    pub last_autosave: Instant,
+    pub autosave_interval: Duration,
+    /// Autosave left behind by a previous session, offered on the welcome
+    /// screen. Autosaving is paused while this is set.
+    pub recoverable_autosave: Option<PathBuf>,
+    /// Subsystem timings window, toggled with Ctrl+D.
+    pub show_debug_overlay: bool,
+    pub debug_timings: SharedTimings,
+}
+
+/// Autosaves older than this are not offered for recovery.
+const AUTOSAVE_MAX_AGE: Duration = Duration::from_secs(60 * 60);
+
+pub fn autosave_path() -> PathBuf {
+    std::env::temp_dir().join("nebula_autosave.nbproj")
 }
 
 impl NebulaToolsApp {
@@ -557,15 +1314,39 @@ impl NebulaToolsApp {
 
         let i18n = I18nManager::new(config.lang.clone());
 
+        let mut player = PlayerState::default();
+        player.prefetch_threads = config.prefetch_threads;
+
+        let recoverable_autosave = Some(autosave_path()).filter(|path| {
+            fs::metadata(path)
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|t| t.elapsed().ok())
+                .is_some_and(|age| age < AUTOSAVE_MAX_AGE)
+        });
+
         Self {
-            player: PlayerState::default(),
+            player,
             config,
             i18n,
             error_msg: None,
             camera: CameraState::default(),
             renderer: Arc::new(Mutex::new(None)),
+            extent_renderer: Arc::new(Mutex::new(None)),
             show_grid: true,
+            viewport_bg_color: [0.0, 0.0, 0.0, 1.0],
+            msaa_samples: 1,
+            point_shape: PointShape::Circle,
+            sort_mode: SortMode::None,
+            depth_sort_cache: Mutex::new(None),
+            render_mode: RenderMode::Normal,
+            show_velocity_arrows: false,
+            velocity_scale: 1.0,
+            velocity_next: None,
             mode: AppMode::Preview,
+            playback_mode: PlaybackMode::Normal,
+            blend_history: std::collections::VecDeque::new(),
+            trail_history: std::collections::VecDeque::new(),
             scrub_frame: None,
             fps_counter: 0.0,
             fps_display: 0.0,
@@ -574,6 +1355,27 @@ impl NebulaToolsApp {
             pex: ParticleexState::default(),
             multimedia: MultimediaState::default(),
             creator: CreatorState::default(),
+            compare: CompareState::default(),
+            selected_ids: HashSet::new(),
+            lasso_active: false,
+            lasso_points: Vec::new(),
+            texture_thumbnails: std::collections::HashMap::new(),
+            animation_stats: Vec::new(),
+            motion_stats: Vec::new(),
+            motion_job: None,
+            nbl_meta: Default::default(),
+            nbl_meta_tags: String::new(),
+            expression_macros: std::collections::HashMap::from([(
+                "rotate".to_string(),
+                "($2, $3) = ($2) * cos($1) - ($3) * sin($1), ($2) * sin($1) + ($3) * cos($1)"
+                    .to_string(),
+            )]),
+            macro_draft: (String::new(), String::new()),
+            last_autosave: Instant::now(),
+            autosave_interval: Duration::from_secs(5 * 60),
+            recoverable_autosave,
+            show_debug_overlay: false,
+            debug_timings: SharedTimings::default(),
         }
     }
 
@@ -626,6 +1428,15 @@ impl NebulaToolsApp {
                             ui.horizontal(|ui| {
                                 ui.label(format!("[{}]", t_idx));
                                 ui.text_edit_singleline(&mut textures[t_idx]);
+                                // Image files on disk also render as sprites in the viewport.
+                                if ui.button("📂").clicked() {
+                                    if let Some(path) = rfd::FileDialog::new()
+                                        .add_filter("Image", &["png", "jpg", "jpeg"])
+                                        .pick_file()
+                                    {
+                                        textures[t_idx] = path.to_string_lossy().to_string();
+                                    }
+                                }
                                 if ui.button("❌").clicked() {
                                     to_remove = Some(t_idx);
                                 }
@@ -671,10 +1482,11 @@ impl NebulaToolsApp {
         }
     }
 
-    pub fn prepare_render_data(&self) -> Vec<f32> {
-        let count = self.player.particles.len();
-        let mut data = Vec::with_capacity(count * 8);
-        for p in self.player.particles.values() {
+    /// Build render data from an arbitrary particle slice (for creator preview).
+    /// `frame` is the slice's frame index, which keys the depth sort cache.
+    pub fn prepare_render_data_from(&self, particles: &[Particle], frame: usize) -> Vec<f32> {
+        let mut data = Vec::with_capacity(particles.len() * FLOATS_PER_PARTICLE);
+        let mut push = |p: &Particle| {
             data.push(p.pos[0]);
             data.push(p.pos[1]);
             data.push(p.pos[2]);
@@ -683,24 +1495,134 @@ impl NebulaToolsApp {
             data.push(p.color[2] as f32 / 255.0);
             data.push(p.color[3] as f32 / 255.0);
             data.push(p.size);
+            data.push(p.tex_id as f32);
+            data.push(p.seq_index as f32);
+        };
+        match self.draw_order(particles, frame) {
+            Some(order) => order.iter().for_each(|&i| push(&particles[i as usize])),
+            None => particles.iter().for_each(push),
         }
         data
     }
 
-    /// Build render data from an arbitrary particle slice (for creator preview).
-    pub fn prepare_render_data_from(&self, particles: &[Particle]) -> Vec<f32> {
-        let mut data = Vec::with_capacity(particles.len() * 8);
-        for p in particles {
-            data.push(p.pos[0]);
-            data.push(p.pos[1]);
-            data.push(p.pos[2]);
-            data.push(p.color[0] as f32 / 255.0);
-            data.push(p.color[1] as f32 / 255.0);
-            data.push(p.color[2] as f32 / 255.0);
-            data.push(p.color[3] as f32 / 255.0);
-            data.push(p.size);
+    /// Indices of `particles` in `sort_mode` order, or `None` to keep theirs.
+    /// Depth is the distance along the camera's view direction.
+    fn draw_order(&self, particles: &[Particle], frame: usize) -> Option<Vec<u32>> {
+        use rand::{rngs::SmallRng, Rng, SeedableRng};
+        let mut order: Vec<u32> = (0..particles.len() as u32).collect();
+        match self.sort_mode {
+            SortMode::None => return None,
+            SortMode::Random => {
+                order.sort_by_cached_key(|&i| {
+                    SmallRng::seed_from_u64(particles[i as usize].id as u64).gen::<u64>()
+                });
+            }
+            SortMode::Hue => {
+                order = player::order_by_key(particles, |p| player::color_hue(p.color))
+            }
+            SortMode::Brightness => {
+                order = player::order_by_key(particles, |p| player::color_brightness(p.color))
+            }
+            SortMode::ZDepth => order = player::order_by_key(particles, |p| p.pos[2]),
+            SortMode::FrontToBack | SortMode::BackToFront => {
+                let eye = self.camera_eye();
+                let target = self.camera.target;
+                let key = DepthSortKey {
+                    mode: self.mode,
+                    frame,
+                    len: particles.len(),
+                    eye,
+                    target,
+                };
+                let mut cache = self.depth_sort_cache.lock().unwrap();
+                match cache.as_ref().filter(|c| c.key == key) {
+                    Some(cached) => order.clone_from(&cached.order),
+                    None => {
+                        let depth = |p: &Particle| -> f32 {
+                            (0..3)
+                                .map(|k| (p.pos[k] - eye[k]) * (target[k] - eye[k]))
+                                .sum()
+                        };
+                        let mut keyed: Vec<(f32, u32)> =
+                            particles.iter().map(depth).zip(0..).collect();
+                        keyed.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+                        order = keyed.into_iter().map(|(_, i)| i).collect();
+                        *cache = Some(DepthSortCache {
+                            key,
+                            order: order.clone(),
+                        });
+                    }
+                }
+                if self.sort_mode == SortMode::BackToFront {
+                    order.reverse();
+                }
+            }
+        }
+        Some(order)
+    }
+
+    /// Pick a v1 NBL and write an upgraded v2 copy, after warning that older
+    /// players may not read it.
+    pub fn handle_upgrade_nbl(&mut self) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("Nebula", &["nbl"][..])
+            .pick_file()
+        else {
+            return;
+        };
+        match crate::player::check_nbl_version(&path) {
+            Ok(1) => {}
+            Ok(v) => {
+                self.error_msg = Some(format!("{} (v{})", self.i18n.tr("upgrade_not_v1"), v));
+                return;
+            }
+            Err(e) => {
+                self.error_msg = Some(format!("Load Failed: {}", e));
+                return;
+            }
+        }
+        let confirmed = rfd::MessageDialog::new()
+            .set_level(rfd::MessageLevel::Warning)
+            .set_title(self.i18n.tr("upgrade_nbl_version"))
+            .set_description(self.i18n.tr("upgrade_nbl_warning"))
+            .set_buttons(rfd::MessageButtons::OkCancel)
+            .show();
+        if confirmed != rfd::MessageDialogResult::Ok {
+            return;
+        }
+        let Some(output) = rfd::FileDialog::new()
+            .add_filter("Nebula", &["nbl"][..])
+            .set_file_name("upgraded.nbl")
+            .save_file()
+        else {
+            return;
+        };
+        match crate::player::migrate_v1_to_v2(&path, &output) {
+            Ok(_) => self.error_msg = None,
+            Err(e) => self.error_msg = Some(format!("Save Failed: {}", e)),
+        }
+    }
+
+    /// Write the current preview frame, sorted by id, with `write` to a
+    /// `.{ext}` file the user picks.
+    pub fn handle_export_frame(&mut self, filter: &str, ext: &str, write: FrameWriter) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter(filter, &[ext][..])
+            .set_file_name(format!(
+                "frame_{}.{}",
+                self.player.current_frame_idx.max(0),
+                ext
+            ))
+            .save_file()
+        else {
+            return;
+        };
+        let mut frame: Vec<Particle> = self.player.particles.values().cloned().collect();
+        frame.sort_unstable_by_key(|p| p.id);
+        match write(&frame, &path) {
+            Ok(_) => self.error_msg = None,
+            Err(e) => self.error_msg = Some(format!("Save Failed: {}", e)),
         }
-        data
     }
 
     pub fn handle_import(&mut self) {
@@ -708,12 +1630,165 @@ impl NebulaToolsApp {
             .add_filter("Nebula", &["nbl"][..])
             .pick_file()
         {
-            match self.player.load_file(path) {
-                Ok(_) => {
+            self.open_nbl(&path);
+        }
+    }
+
+    fn open_nbl(&mut self, path: &std::path::Path) {
+        match self.player.load_file_mmap(path) {
+            Ok(_) => {
+                self.error_msg = None;
+                self.selected_ids.clear();
+                self.edit.decoded_frames = None;
+                self.edit.edited_header = None;
+                self.edit.trim_preview = None;
+                self.edit.voxel_estimate = None;
+                self.animation_stats.clear();
+                self.motion_stats.clear();
+                self.motion_job = None;
+                self.nbl_meta = crate::player::load_meta(path).unwrap_or_default();
+                self.nbl_meta_tags = self.nbl_meta.tags.join(", ");
+                self.velocity_next = None;
+                self.compare.frame_diff_cache = None;
+                self.mode = AppMode::Preview;
+            }
+            Err(e) => self.error_msg = Some(format!("Load Failed: {}", e)),
+        }
+    }
+
+    /// Convert a PLY point cloud into a single-frame NBL at a path the user
+    /// picks, then open it.
+    pub fn handle_import_ply(&mut self) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("PLY", &["ply"][..])
+            .pick_file()
+        else {
+            return;
+        };
+        let frame = match crate::player::import_ply_frame(&path) {
+            Ok(frame) => frame,
+            Err(e) => {
+                self.error_msg = Some(format!("Load Failed: {}", e));
+                return;
+            }
+        };
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let Some(output) = rfd::FileDialog::new()
+            .add_filter("Nebula", &["nbl"][..])
+            .set_file_name(format!("{}.nbl", stem))
+            .save_file()
+        else {
+            return;
+        };
+        let frames = vec![frame];
+        let (bbox_min, bbox_max) = crate::player::recalculate_bbox(&frames);
+        let header = NblHeader {
+            version: NBL_VERSION,
+            target_fps: 60,
+            total_frames: 1,
+            texture_count: 0,
+            attributes: 0x03,
+            bbox_min,
+            bbox_max,
+        };
+        match self.player.save_file(&output, &header, &[], &frames) {
+            Ok(_) => self.open_nbl(&output),
+            Err(e) => self.error_msg = Some(format!("Save Failed: {}", e)),
+        }
+    }
+
+    pub fn project_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(&NebulaProject {
+            multimedia: &self.multimedia,
+            creator: &self.creator,
+            camera: &self.camera,
+            edit: &self.edit,
+            nbl_path: self.player.file_path.clone(),
+            expr_version: crate::particleex::EXPR_VERSION,
+        })
+    }
+
+    pub fn write_project(&self, path: &std::path::Path) -> anyhow::Result<()> {
+        fs::write(path, self.project_json()?)?;
+        Ok(())
+    }
+
+    pub fn apply_project(&mut self, project: NebulaProject) {
+        self.multimedia = project.multimedia;
+        self.multimedia.is_processing = false;
+        self.multimedia.processing_progress = None;
+        self.creator = project.creator;
+        self.camera = project.camera;
+        self.edit = project.edit;
+
+        if project.expr_version < crate::particleex::EXPR_VERSION {
+            let version = project.expr_version;
+            let mut events = Vec::new();
+            for expr in [
+                &mut self.multimedia.velocity_expr,
+                &mut self.multimedia.color_expr,
+                &mut self.creator.velocity_expr,
+                &mut self.edit.resample_expr,
+            ] {
+                let (migrated, applied) = crate::particleex::migrate_expression(expr, version);
+                *expr = migrated;
+                // Every field goes through the same migrations; log them once.
+                events = applied;
+            }
+            self.multimedia.expr_log.lock().unwrap().extend(events);
+        }
+
+        if let Some(path) = project.nbl_path.filter(|p| p.exists()) {
+            self.open_nbl(&path);
+        }
+    }
+
+    pub fn write_expression_recipe(&self, path: &std::path::Path) -> anyhow::Result<()> {
+        let recipe = ExpressionRecipe {
+            velocity_expr: self.multimedia.velocity_expr.clone(),
+            macros: self.expression_macros.clone(),
+        };
+        fs::write(path, serde_json::to_string_pretty(&recipe)?)?;
+        Ok(())
+    }
+
+    /// Reads a `.pex` file and checks that its expression parses once its
+    /// own macros are expanded.
+    pub fn read_expression_recipe(path: &std::path::Path) -> anyhow::Result<ExpressionRecipe> {
+        let recipe: ExpressionRecipe = serde_json::from_str(&fs::read_to_string(path)?)?;
+        let expanded = crate::particleex::expand_macros(&recipe.velocity_expr, &recipe.macros);
+        crate::particleex::check_expr(&expanded).map_err(anyhow::Error::msg)?;
+        Ok(recipe)
+    }
+
+    pub fn handle_save_project(&mut self) {
+        if let Some(path) = rfd::FileDialog::new()
+            .add_filter("Nebula Project", &["nbproj"][..])
+            .set_file_name("project.nbproj")
+            .save_file()
+        {
+            match self.write_project(&path) {
+                Ok(_) => self.error_msg = None,
+                Err(e) => {
+                    self.error_msg = Some(format!("{}: {}", self.i18n.tr("project_save_failed"), e))
+                }
+            }
+        }
+    }
+
+    pub fn handle_open_project(&mut self) {
+        if let Some(path) = rfd::FileDialog::new()
+            .add_filter("Nebula Project", &["nbproj"][..])
+            .pick_file()
+        {
+            match read_project(&path) {
+                Ok(project) => {
                     self.error_msg = None;
-                    self.mode = AppMode::Preview;
+                    self.apply_project(project);
+                }
+                Err(e) => {
+                    self.error_msg = Some(format!("{}: {}", self.i18n.tr("project_load_failed"), e))
                 }
-                Err(e) => self.error_msg = Some(format!("Load Failed: {}", e)),
             }
         }
     }
@@ -725,22 +1800,37 @@ impl NebulaToolsApp {
     }
 
     pub fn calculate_view_matrix(&self) -> [f32; 16] {
+        crate::math::look_at(self.camera_eye(), self.camera.target, [0.0, 1.0, 0.0])
+    }
+
+    fn camera_eye(&self) -> [f32; 3] {
         let cos_p = self.camera.pitch.cos();
         let sin_p = self.camera.pitch.sin();
         let cos_y = self.camera.yaw.cos();
         let sin_y = self.camera.yaw.sin();
-        let eye = [
+        [
             self.camera.target[0] + self.camera.distance * cos_p * sin_y,
             self.camera.target[1] + self.camera.distance * sin_p,
             self.camera.target[2] + self.camera.distance * cos_p * cos_y,
-        ];
-        crate::math::look_at(eye, self.camera.target, [0.0, 1.0, 0.0])
+        ]
     }
 
     pub fn calculate_projection_matrix(&self, aspect: f32) -> [f32; 16] {
         crate::math::perspective(45.0f32.to_radians(), aspect, 0.1, 1000000.0)
     }
 
+    /// Texture atlases referenced by `tex_id` in the current workflow's viewport.
+    fn viewport_textures(&self) -> Vec<TextureEntry> {
+        match self.mode {
+            AppMode::Preview => self.player.textures.clone(),
+            AppMode::Creator => build_texture_entries(&self.creator.texture_animation.textures),
+            AppMode::Multimedia => {
+                build_texture_entries(&self.multimedia.texture_animation.textures)
+            }
+            AppMode::Edit | AppMode::Particleex => Vec::new(),
+        }
+    }
+
     /// Shared 3D viewport rendering (used by both preview and creator).
     pub fn paint_3d_viewport(
         &mut self,
@@ -748,9 +1838,27 @@ impl NebulaToolsApp {
         ctx: &egui::Context,
         particles_data: &[f32],
     ) {
+        self.paint_3d_viewport_layers(ui, ctx, particles_data, &[], &[], &[]);
+    }
+
+    /// Like `paint_3d_viewport`, with an overlay layer drawn on top, a
+    /// highlight layer drawn as enlarged halos behind the particles and
+    /// trail line segments for `RenderMode::Trail`.
+    /// Returns the viewport response so callers can handle picking and place
+    /// extra overlay widgets.
+    pub fn paint_3d_viewport_layers(
+        &mut self,
+        ui: &mut egui::Ui,
+        ctx: &egui::Context,
+        particles_data: &[f32],
+        overlay_data: &[f32],
+        highlight_data: &[f32],
+        trail_data: &[f32],
+    ) -> egui::Response {
         let (rect, response) =
             ui.allocate_exact_size(ui.available_size(), egui::Sense::click_and_drag());
-        if response.dragged_by(egui::PointerButton::Primary) {
+        let lasso = self.lasso_active && self.mode == AppMode::Preview;
+        if response.dragged_by(egui::PointerButton::Primary) && !lasso {
             let d = response.drag_delta();
             self.camera.yaw -= d.x * 0.01;
             self.camera.pitch += d.y * 0.01;
@@ -764,27 +1872,54 @@ impl NebulaToolsApp {
         let mvp = self.calculate_mvp(aspect);
         let renderer_ref = self.renderer.clone();
         let show_grid = self.show_grid;
+        let [bg_r, bg_g, bg_b, bg_a] = self.viewport_bg_color;
+        let msaa_samples = self.msaa_samples;
+        let point_shape = self.point_shape;
+        let render_mode = self.render_mode;
         let rect_height = rect.height();
         let data = particles_data.to_vec();
+        let overlay = overlay_data.to_vec();
+        let highlight = highlight_data.to_vec();
+        let trails = trail_data.to_vec();
+        let textures = self.viewport_textures();
+        let timings = self.debug_timings.clone();
 
         let fov_y = 45.0f32.to_radians();
         let focal_length = 1.0 / (fov_y / 2.0).tan();
 
         let callback = egui_glow::CallbackFn::new(move |info, painter| {
+            let _timing = TimingGuard::new(&timings, |t| &mut t.render_ms);
             let mut lock = renderer_ref.lock().unwrap();
             if lock.is_none() {
                 *lock = Some(crate::renderer::ParticleRenderer::new(painter.gl()));
             }
-            if let Some(r) = lock.as_ref() {
+            if let Some(r) = lock.as_mut() {
+                r.set_textures(painter.gl(), &textures);
+                r.set_point_shape(painter.gl(), point_shape);
+                r.set_render_mode(render_mode);
                 let physical_height = rect_height * info.pixels_per_point;
                 let scaling = (focal_length * physical_height) / 2.0;
+                let vp = info.viewport_in_pixels();
+                let viewport = [vp.left_px, vp.from_bottom_px, vp.width_px, vp.height_px];
                 unsafe {
-                    painter.gl().clear_color(0.0, 0.0, 0.0, 1.0);
-                    r.paint(painter.gl(), mvp, &data, scaling, show_grid);
+                    painter.gl().clear_color(bg_r, bg_g, bg_b, bg_a);
+                    let msaa = r.begin_msaa(painter.gl(), msaa_samples, viewport);
+                    r.paint(painter.gl(), mvp, &data, &highlight, scaling, show_grid);
+                    if let Some(us) = r.measure_render_time(painter.gl()) {
+                        timings.lock().unwrap().gpu_render_us = Some(us);
+                    }
+                    r.paint_trails(painter.gl(), mvp, &trails);
+                    if !overlay.is_empty() {
+                        r.paint_overlay(painter.gl(), mvp, &overlay, scaling);
+                    }
+                    if msaa {
+                        r.end_msaa(painter.gl(), painter.intermediate_fbo(), viewport);
+                    }
                 }
             }
         });
-        ui.painter().rect_filled(rect, 0.0, egui::Color32::BLACK);
+        let bg = egui::Rgba::from_rgba_unmultiplied(bg_r, bg_g, bg_b, bg_a);
+        ui.painter().rect_filled(rect, 0.0, bg);
         ui.painter().add(egui::PaintCallback {
             rect,
             callback: Arc::new(callback),
@@ -814,7 +1949,7 @@ impl NebulaToolsApp {
         );
 
         // Particle count overlay (below FPS)
-        let particle_count = particles_data.len() / 8;
+        let particle_count = particles_data.len() / FLOATS_PER_PARTICLE;
         let particle_pos = rect.left_top() + egui::vec2(10.0, 30.0);
         painter.text(
             particle_pos,
@@ -823,11 +1958,99 @@ impl NebulaToolsApp {
             egui::FontId::proportional(16.0),
             egui::Color32::from_white_alpha(180),
         );
+
+        // Background colour picker (top-right)
+        let picker_rect = egui::Rect::from_min_size(
+            rect.right_top() + egui::vec2(-50.0, 10.0),
+            egui::vec2(40.0, 20.0),
+        );
+        ui.put(picker_rect, |ui: &mut egui::Ui| {
+            ui.color_edit_button_rgba_unmultiplied(&mut self.viewport_bg_color)
+                .on_hover_text(self.i18n.tr("viewport_background"))
+        });
+
+        // MSAA quality (left of the colour picker)
+        let msaa_rect = egui::Rect::from_min_size(
+            rect.right_top() + egui::vec2(-150.0, 10.0),
+            egui::vec2(90.0, 20.0),
+        );
+        let msaa_label = |n: u8| match n {
+            1 => "MSAA Off".to_string(),
+            n => format!("MSAA {}x", n),
+        };
+        ui.put(msaa_rect, |ui: &mut egui::Ui| {
+            egui::ComboBox::from_id_source("viewport_msaa")
+                .width(80.0)
+                .selected_text(msaa_label(self.msaa_samples))
+                .show_ui(ui, |ui| {
+                    for n in [1, 2, 4, 8] {
+                        ui.selectable_value(&mut self.msaa_samples, n, msaa_label(n));
+                    }
+                })
+                .response
+                .on_hover_text(self.i18n.tr("msaa_hint"))
+        });
+        response
+    }
+
+    fn show_debug_overlay_window(&mut self, ctx: &egui::Context) {
+        let timings = *self.debug_timings.lock().unwrap();
+        let rows = [
+            ("debug_compile", timings.compile_ms),
+            ("debug_render", timings.render_ms),
+            ("debug_decode", timings.decode_ms),
+            ("debug_ui", timings.ui_ms),
+        ];
+        egui::Window::new(self.i18n.tr("debug_overlay"))
+            .open(&mut self.show_debug_overlay)
+            .default_width(280.0)
+            .show(ctx, |ui| {
+                for (key, ms) in rows {
+                    ui.label(format!("{}: {:.2} ms", self.i18n.tr(key), ms));
+                }
+                if let Some(us) = timings.gpu_render_us {
+                    ui.label(format!(
+                        "{}: {:.1} µs",
+                        self.i18n.tr("debug_gpu_render"),
+                        us
+                    ));
+                }
+                let bars = rows
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (key, ms))| {
+                        egui_plot::Bar::new(i as f64, *ms as f64).name(self.i18n.tr(key))
+                    })
+                    .collect();
+                egui_plot::Plot::new("debug_timings_plot")
+                    .height(140.0)
+                    .allow_scroll(false)
+                    .allow_drag(false)
+                    .allow_zoom(false)
+                    .show_x(false)
+                    .include_y(0.0)
+                    .show(ui, |plot_ui| {
+                        plot_ui.bar_chart(egui_plot::BarChart::new(bars).width(0.6));
+                    });
+            });
     }
 }
 
 impl eframe::App for NebulaToolsApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let _timing = TimingGuard::new(&self.debug_timings, |t| &mut t.ui_ms);
+        if ctx.input_mut(|i| {
+            i.consume_shortcut(&egui::KeyboardShortcut::new(
+                egui::Modifiers::COMMAND,
+                egui::Key::D,
+            ))
+        }) {
+            self.show_debug_overlay = !self.show_debug_overlay;
+        }
+        if self.show_debug_overlay {
+            self.show_debug_overlay_window(ctx);
+        }
+
         if self.player.header.is_none()
             && self.mode != AppMode::Particleex
             && self.mode != AppMode::Multimedia
@@ -847,7 +2070,10 @@ impl eframe::App for NebulaToolsApp {
                     self.player.frame_timer -= frame_dur;
                     let next_frame = self.player.current_frame_idx + 1;
                     if (next_frame as u32) < header.total_frames {
+                        let _timing = TimingGuard::new(&self.debug_timings, |t| &mut t.decode_ms);
+                        let lookahead = header.target_fps.max(1) as u32;
                         let _ = self.player.seek_to(next_frame as u32);
+                        self.player.prefetch_frames(next_frame as u32, lookahead);
                     } else {
                         self.player.is_playing = false;
                     }
@@ -866,6 +2092,15 @@ impl eframe::App for NebulaToolsApp {
                 ui.separator();
 
                 ui.menu_button(self.i18n.tr("file"), |ui| {
+                    if ui.button(self.i18n.tr("open_project")).clicked() {
+                        self.handle_open_project();
+                        ui.close_menu();
+                    }
+                    if ui.button(self.i18n.tr("save_project")).clicked() {
+                        self.handle_save_project();
+                        ui.close_menu();
+                    }
+                    ui.separator();
                     if self.mode == AppMode::Multimedia {
                     } else if self.mode == AppMode::Creator {
                         if ui.button(self.i18n.tr("export_nbl")).clicked() {
@@ -882,6 +2117,35 @@ impl eframe::App for NebulaToolsApp {
                             self.handle_import();
                             ui.close_menu();
                         }
+                        if ui.button(self.i18n.tr("import_ply")).clicked() {
+                            self.handle_import_ply();
+                            ui.close_menu();
+                        }
+                        if self.player.header.is_some() {
+                            ui.menu_button(self.i18n.tr("export"), |ui| {
+                                if ui.button(self.i18n.tr("export_e57")).clicked() {
+                                    self.handle_export_frame(
+                                        "E57",
+                                        "e57",
+                                        crate::player::export_e57_frame,
+                                    );
+                                    ui.close_menu();
+                                }
+                                if ui.button(self.i18n.tr("export_unity_prefab")).clicked() {
+                                    self.handle_export_frame(
+                                        "Unity Prefab",
+                                        "prefab",
+                                        crate::player::export_unity_prefab_frame,
+                                    );
+                                    ui.close_menu();
+                                }
+                            });
+                        }
+                    }
+                    ui.separator();
+                    if ui.button(self.i18n.tr("upgrade_nbl_version")).clicked() {
+                        ui.close_menu();
+                        self.handle_upgrade_nbl();
                     }
                 });
 
@@ -912,7 +2176,9 @@ impl eframe::App for NebulaToolsApp {
                     ui.separator();
                 }
 
-                // 2. File-specific modes (only shown if a file is loaded)
+                // 2. Workflow tabs. Edit/Preview need a loaded file, the generators
+                //    are always reachable. Each mode keeps its own state, so switching
+                //    back and forth does not reset anything.
                 if self.player.header.is_some() {
                     ui.selectable_value(&mut self.mode, AppMode::Edit, self.i18n.tr("edit_mode"));
                     ui.selectable_value(
@@ -920,22 +2186,22 @@ impl eframe::App for NebulaToolsApp {
                         AppMode::Preview,
                         self.i18n.tr("preview_mode"),
                     );
-                    ui.separator();
                 }
+                ui.selectable_value(
+                    &mut self.mode,
+                    AppMode::Creator,
+                    format!("✨ {}", self.i18n.tr("creator_mode")),
+                );
+                ui.selectable_value(
+                    &mut self.mode,
+                    AppMode::Multimedia,
+                    format!("📺 {}", self.i18n.tr("multimedia_mode")),
+                );
+                ui.separator();
 
                 // 3. Current active Tool Mode display (Non-switchable while inside)
                 if self.mode == AppMode::Particleex {
                     ui.label(egui::RichText::new("🔧 Particleex").strong());
-                } else if self.mode == AppMode::Multimedia {
-                    ui.label(
-                        egui::RichText::new(format!("📺 {}", self.i18n.tr("multimedia_mode")))
-                            .strong(),
-                    );
-                } else if self.mode == AppMode::Creator {
-                    ui.label(
-                        egui::RichText::new(format!("✨ {}", self.i18n.tr("creator_mode")))
-                            .strong(),
-                    );
                 }
 
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
@@ -961,6 +2227,15 @@ impl eframe::App for NebulaToolsApp {
             AppMode::Multimedia => self.show_multimedia_workflow(ctx),
             AppMode::Creator => self.show_creator_workflow(ctx),
         }
+
+        // Holding off until the offered autosave is recovered or dismissed
+        // keeps this session from overwriting it.
+        if self.recoverable_autosave.is_none()
+            && self.last_autosave.elapsed() >= self.autosave_interval
+        {
+            self.last_autosave = Instant::now();
+            let _ = self.write_project(&autosave_path());
+        }
     }
 
     fn on_exit(&mut self, gl: Option<&glow::Context>) {
@@ -968,6 +2243,9 @@ impl eframe::App for NebulaToolsApp {
             if let Some(renderer) = self.renderer.lock().unwrap().take() {
                 renderer.destroy(gl);
             }
+            if let Some(renderer) = self.extent_renderer.lock().unwrap().take() {
+                renderer.destroy(gl);
+            }
         }
     }
 }
@@ -1,6 +1,9 @@
-use super::app::{build_texture_entries, CreatorPreset, NebulaToolsApp};
-use crate::player::{recalculate_bbox, NblHeader, Particle};
-use eframe::egui;
+use super::app::{
+    build_texture_entries, CreatorPreset, EmissionMode, NebulaToolsApp, PlaybackLoopMode,
+    SubEmitterConfig,
+};
+use crate::player::{recalculate_bbox, NblHeader, Particle, NBL_VERSION};
+use eframe::{egui, egui_glow};
 
 fn apply_euler_rotation(mut x: f32, mut y: f32, mut z: f32, rot: [f32; 3]) -> (f32, f32, f32) {
     let (sx, cx) = rot[0].to_radians().sin_cos();
@@ -28,6 +31,125 @@ fn apply_euler_rotation(mut x: f32, mut y: f32, mut z: f32, rot: [f32; 3]) -> (f
     (x, y, z)
 }
 
+/// Triangles of a Wavefront OBJ file. Only `v` and `f` lines are read;
+/// polygons are split into fans and negative (relative) indices are allowed.
+fn load_obj_triangles(path: &std::path::Path) -> anyhow::Result<Vec<[[f32; 3]; 3]>> {
+    let text = std::fs::read_to_string(path)?;
+    let mut vertices: Vec<[f32; 3]> = Vec::new();
+    let mut triangles = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let mut fields = line.split_whitespace();
+        match fields.next() {
+            Some("v") => {
+                let mut v = [0.0f32; 3];
+                for c in v.iter_mut() {
+                    *c = fields
+                        .next()
+                        .and_then(|s| s.parse().ok())
+                        .ok_or_else(|| anyhow::anyhow!("line {}: bad vertex", n + 1))?;
+                }
+                vertices.push(v);
+            }
+            Some("f") => {
+                let face = fields
+                    .map(|f| {
+                        let index: i64 = f.split('/').next().unwrap_or("").parse()?;
+                        let resolved = if index < 0 {
+                            vertices.len() as i64 + index
+                        } else {
+                            index - 1
+                        };
+                        vertices
+                            .get(usize::try_from(resolved)?)
+                            .copied()
+                            .ok_or_else(|| anyhow::anyhow!("vertex {} out of range", index))
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()
+                    .map_err(|e| anyhow::anyhow!("line {}: {}", n + 1, e))?;
+                for i in 1..face.len().saturating_sub(1) {
+                    triangles.push([face[0], face[i], face[i + 1]]);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(triangles)
+}
+
+/// Area-weighted random points on a triangle mesh.
+struct MeshSampler {
+    triangles: Vec<[[f32; 3]; 3]>,
+    /// Running total of triangle areas, for picking a triangle by area.
+    cumulative_area: Vec<f32>,
+}
+
+impl MeshSampler {
+    fn new(triangles: Vec<[[f32; 3]; 3]>) -> anyhow::Result<Self> {
+        let mut total = 0.0;
+        let cumulative_area: Vec<f32> = triangles
+            .iter()
+            .map(|[a, b, c]| {
+                total += triangle_normal(*a, *b, *c).1;
+                total
+            })
+            .collect();
+        if total <= 0.0 {
+            anyhow::bail!("mesh has no triangles with area");
+        }
+        Ok(Self {
+            triangles,
+            cumulative_area,
+        })
+    }
+
+    /// A uniformly distributed surface point and its triangle's unit normal.
+    fn sample(&self, rng: &mut impl rand::Rng) -> ([f32; 3], [f32; 3]) {
+        let total = *self.cumulative_area.last().unwrap();
+        let target = rng.gen::<f32>() * total;
+        let i = self
+            .cumulative_area
+            .partition_point(|&area| area <= target)
+            .min(self.triangles.len() - 1);
+        let [a, b, c] = self.triangles[i];
+        let (mut u, mut v) = (rng.gen::<f32>(), rng.gen::<f32>());
+        if u + v > 1.0 {
+            (u, v) = (1.0 - u, 1.0 - v);
+        }
+        let pos = std::array::from_fn(|k| a[k] + (b[k] - a[k]) * u + (c[k] - a[k]) * v);
+        (pos, triangle_normal(a, b, c).0)
+    }
+}
+
+/// A particle being simulated by the mesh emitter.
+struct LiveParticle {
+    particle: Particle,
+    velocity: [f32; 3],
+    gravity: [f32; 3],
+    age: f32,
+    lifetime: f32,
+    /// Whether sub-emitters spawn from this particle.
+    emits: bool,
+}
+
+fn random_unit_vector(rng: &mut impl rand::Rng) -> [f32; 3] {
+    loop {
+        let v: [f32; 3] = std::array::from_fn(|_| rng.gen_range(-1.0..1.0));
+        let len_sq = crate::math::dot(v, v);
+        if len_sq > 1e-6 && len_sq <= 1.0 {
+            return crate::math::normalize(v);
+        }
+    }
+}
+
+/// Unit normal (counter-clockwise winding) and area of a triangle.
+fn triangle_normal(a: [f32; 3], b: [f32; 3], c: [f32; 3]) -> ([f32; 3], f32) {
+    let ab = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+    let ac = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+    let n = crate::math::cross(ab, ac);
+    let area = 0.5 * crate::math::dot(n, n).sqrt();
+    (crate::math::normalize(n), area)
+}
+
 impl NebulaToolsApp {
     pub(crate) fn show_creator_workflow(&mut self, ctx: &egui::Context) {
         egui::SidePanel::left("creator_side_panel")
@@ -46,6 +168,7 @@ impl NebulaToolsApp {
                                 CreatorPreset::Butterfly => {
                                     format!("🦋 {}", self.i18n.tr("butterfly"))
                                 }
+                                CreatorPreset::Mesh => format!("🔺 {}", self.i18n.tr("cr_mesh")),
                             };
                             egui::ComboBox::from_id_source("creator_preset_combo")
                                 .selected_text(selected_text)
@@ -55,12 +178,20 @@ impl NebulaToolsApp {
                                         CreatorPreset::Butterfly,
                                         format!("🦋 {}", self.i18n.tr("butterfly")),
                                     );
+                                    ui.selectable_value(
+                                        &mut self.creator.selected_preset,
+                                        CreatorPreset::Mesh,
+                                        format!("🔺 {}", self.i18n.tr("cr_mesh")),
+                                    );
                                 });
                         });
 
                         ui.add_space(10.0);
 
                         match self.creator.selected_preset {
+                            CreatorPreset::Mesh => {
+                                ui.group(|ui: &mut egui::Ui| self.ui_mesh_settings(ui));
+                            }
                             CreatorPreset::Butterfly => {
                                 // === Basic Settings ===
                                 ui.group(|ui: &mut egui::Ui| {
@@ -365,6 +496,11 @@ impl NebulaToolsApp {
 
                                 ui.add_space(6.0);
 
+                                // === Path ===
+                                ui.group(|ui: &mut egui::Ui| self.ui_follow_path(ui));
+
+                                ui.add_space(6.0);
+
                                 // === Velocity Expression ===
                                 ui.group(|ui: &mut egui::Ui| {
                                     ui.label(
@@ -372,6 +508,17 @@ impl NebulaToolsApp {
                                     );
                                     let editor_id =
                                         ui.make_persistent_id("creator_velocity_editor");
+                                    let lints = super::multimedia_ui::expression_lints(
+                                        &self.creator.velocity_expr,
+                                        &self.expression_macros,
+                                    );
+                                    let mut layouter =
+                                        |ui: &egui::Ui, text: &str, wrap_width: f32| {
+                                            let job = super::multimedia_ui::lint_layout_job(
+                                                ui, text, &lints, wrap_width,
+                                            );
+                                            ui.fonts(|f| f.layout_job(job))
+                                        };
                                     egui::Frame::canvas(ui.style()).show(ui, |ui| {
                                         ui.add(
                                             egui::TextEdit::multiline(
@@ -382,9 +529,11 @@ impl NebulaToolsApp {
                                             .desired_width(f32::INFINITY)
                                             .desired_rows(3)
                                             .lock_focus(true)
+                                            .layouter(&mut layouter)
                                             .hint_text("vx = cos(t*0.1); vy = sin(t*0.1); ..."),
                                         );
                                     });
+                                    super::multimedia_ui::lint_messages(ui, &lints, &self.i18n);
                                     ui.add_space(4.0);
                                     ui.collapsing(
                                         self.i18n.tr("expr_help"),
@@ -438,6 +587,18 @@ impl NebulaToolsApp {
                                                     }
                                                 });
                                             });
+                                            ui.add_space(4.0);
+                                            ui.group(|ui: &mut egui::Ui| {
+                                                ui.label(
+                                                    egui::RichText::new(self.i18n.tr("macros"))
+                                                        .strong(),
+                                                );
+                                                super::multimedia_ui::macro_buttons(
+                                                    ui,
+                                                    &self.expression_macros,
+                                                    &mut self.creator.velocity_expr,
+                                                );
+                                            });
                                         },
                                     );
                                 });
@@ -485,13 +646,44 @@ impl NebulaToolsApp {
                             )
                             .clicked()
                         {
-                            self.generate_butterfly_preset();
+                            match self.creator.selected_preset {
+                                CreatorPreset::Butterfly => self.generate_butterfly_preset(),
+                                CreatorPreset::Mesh => self.generate_mesh_preset(),
+                            }
                         }
 
+                        ui.add_space(6.0);
+                        ui.horizontal(|ui| {
+                            if ui
+                                .add_enabled(
+                                    self.creator.preview_frames.is_some(),
+                                    egui::Button::new(self.i18n.tr("export_layers")),
+                                )
+                                .on_hover_text(self.i18n.tr("export_layers_hint"))
+                                .clicked()
+                            {
+                                if let Some(path) = rfd::FileDialog::new()
+                                    .set_file_name("butterfly")
+                                    .save_file()
+                                {
+                                    self.export_creator_layers(&path);
+                                }
+                            }
+                            if ui.button(self.i18n.tr("merge_layers")).clicked() {
+                                self.merge_creator_layers();
+                            }
+                        });
+
                         if let Some(msg) = &self.creator.status_msg {
                             ui.add_space(10.0);
                             ui.label(egui::RichText::new(msg).color(egui::Color32::GREEN));
                         }
+
+                        if self.creator.preview_frames.is_some() {
+                            ui.add_space(10.0);
+                            ui.label(self.i18n.tr("extent_view"));
+                            self.ui_extent_view(ui);
+                        }
                     });
             });
 
@@ -508,7 +700,7 @@ impl NebulaToolsApp {
 
                 let render_data = if let Some(frames) = &self.creator.preview_frames {
                     if let Some(frame_data) = frames.get(frame_idx) {
-                        Some(self.prepare_render_data_from(frame_data))
+                        Some(self.prepare_render_data_from(frame_data, frame_idx))
                     } else {
                         None
                     }
@@ -525,8 +717,14 @@ impl NebulaToolsApp {
                         let frame_dur = 1.0 / self.creator.target_fps as f32;
                         if self.creator.preview_timer >= frame_dur {
                             self.creator.preview_timer -= frame_dur;
-                            self.creator.preview_frame_idx =
-                                (self.creator.preview_frame_idx + 1) % frames_len as i32;
+                            match self.creator.loop_mode.advance(
+                                self.creator.preview_frame_idx,
+                                &mut self.creator.preview_direction,
+                                frames_len as i32,
+                            ) {
+                                Some(next) => self.creator.preview_frame_idx = next,
+                                None => self.creator.preview_playing = false,
+                            }
                         }
                         ctx.request_repaint();
                     }
@@ -544,8 +742,36 @@ impl NebulaToolsApp {
                             .clicked()
                         {
                             self.creator.preview_playing = !self.creator.preview_playing;
+                            let at_end = self.creator.preview_frame_idx + 1 >= frames_len as i32;
+                            if self.creator.preview_playing
+                                && self.creator.loop_mode == PlaybackLoopMode::Once
+                                && at_end
+                            {
+                                self.creator.preview_frame_idx = 0;
+                            }
                         }
 
+                        let loop_label = |mode: PlaybackLoopMode| match mode {
+                            PlaybackLoopMode::Loop => self.i18n.tr("loop_mode_loop"),
+                            PlaybackLoopMode::Once => self.i18n.tr("loop_mode_once"),
+                            PlaybackLoopMode::PingPong => self.i18n.tr("loop_mode_ping_pong"),
+                        };
+                        egui::ComboBox::from_id_source("creator_loop_mode")
+                            .selected_text(loop_label(self.creator.loop_mode))
+                            .show_ui(ui, |ui| {
+                                for mode in [
+                                    PlaybackLoopMode::Loop,
+                                    PlaybackLoopMode::Once,
+                                    PlaybackLoopMode::PingPong,
+                                ] {
+                                    ui.selectable_value(
+                                        &mut self.creator.loop_mode,
+                                        mode,
+                                        loop_label(mode),
+                                    );
+                                }
+                            });
+
                         let mut idx = self.creator.preview_frame_idx as usize;
                         if ui
                             .add(
@@ -569,6 +795,443 @@ impl NebulaToolsApp {
         });
     }
 
+    /// Small top-down wireframe of where the preview lives: the extent of the
+    /// whole animation (grey), the current frame's box (orange) and its
+    /// bounding ring (cyan), plus a cross at the origin.
+    fn ui_extent_view(&mut self, ui: &mut egui::Ui) {
+        let (Some(frames), Some((min, max))) =
+            (&self.creator.preview_frames, self.creator.preview_bbox)
+        else {
+            return;
+        };
+        let current = frames
+            .get(self.creator.preview_frame_idx.max(0) as usize)
+            .map(|f| recalculate_bbox(std::slice::from_ref(f)))
+            .unwrap_or((min, max));
+
+        let (rect, _) = ui.allocate_exact_size(egui::vec2(200.0, 150.0), egui::Sense::hover());
+        let aspect = rect.width() / rect.height();
+        let center = [(min[0] + max[0]) / 2.0, (min[2] + max[2]) / 2.0];
+        let half_h = ((max[0] - min[0]) / 2.0 / aspect)
+            .max((max[2] - min[2]) / 2.0)
+            .max(0.5)
+            * 1.15;
+        let half_w = half_h * aspect;
+        // Looking straight down -Y with -Z up on screen.
+        let view = crate::math::look_at(
+            [center[0], max[1] + 1.0, center[1]],
+            [center[0], max[1], center[1]],
+            [0.0, 0.0, -1.0],
+        );
+        let proj = crate::math::orthographic(-half_w, half_w, -half_h, half_h, 0.1, 2.0);
+        let mvp = crate::math::multiply_matrices(proj, view);
+
+        let y = max[1];
+        let mut lines = Vec::new();
+        let mut segment = |a: [f32; 2], b: [f32; 2], color: [f32; 4]| {
+            lines.extend_from_slice(&[a[0], y, a[1]]);
+            lines.extend_from_slice(&color);
+            lines.extend_from_slice(&[b[0], y, b[1]]);
+            lines.extend_from_slice(&color);
+        };
+        for ((lo, hi), color) in [
+            ((min, max), [0.6, 0.6, 0.6, 0.8]),
+            (current, [1.0, 0.6, 0.2, 1.0]),
+        ] {
+            let corners = [
+                [lo[0], lo[2]],
+                [hi[0], lo[2]],
+                [hi[0], hi[2]],
+                [lo[0], hi[2]],
+            ];
+            for i in 0..4 {
+                segment(corners[i], corners[(i + 1) % 4], color);
+            }
+        }
+
+        let (lo, hi) = current;
+        let ring_center = [(lo[0] + hi[0]) / 2.0, (lo[2] + hi[2]) / 2.0];
+        let ring_radius = (hi[0] - lo[0]).hypot(hi[2] - lo[2]) / 2.0;
+        let ring_point = |i: usize| {
+            let a = i as f32 / 48.0 * std::f32::consts::TAU;
+            [
+                ring_center[0] + ring_radius * a.cos(),
+                ring_center[1] + ring_radius * a.sin(),
+            ]
+        };
+        for i in 0..48 {
+            segment(ring_point(i), ring_point(i + 1), [0.3, 0.85, 1.0, 0.9]);
+        }
+        let tick = half_h * 0.08;
+        segment([-tick, 0.0], [tick, 0.0], [1.0, 0.3, 0.3, 1.0]);
+        segment([0.0, -tick], [0.0, tick], [0.3, 0.3, 1.0, 1.0]);
+
+        let renderer_ref = self.extent_renderer.clone();
+        let callback = egui_glow::CallbackFn::new(move |_info, painter| {
+            let mut lock = renderer_ref.lock().unwrap();
+            let r =
+                lock.get_or_insert_with(|| crate::renderer::WireframeRenderer::new(painter.gl()));
+            unsafe { r.paint(painter.gl(), mvp, &lines) };
+        });
+        ui.painter()
+            .rect_filled(rect, 4.0, egui::Color32::from_gray(16));
+        ui.painter().add(egui::PaintCallback {
+            rect,
+            callback: std::sync::Arc::new(callback),
+        });
+    }
+
+    fn ui_mesh_settings(&mut self, ui: &mut egui::Ui) {
+        ui.label(egui::RichText::new(self.i18n.tr("cr_mesh_settings")).strong());
+        ui.horizontal(|ui| {
+            if ui.button(self.i18n.tr("cr_load_mesh")).clicked() {
+                if let Some(path) = rfd::FileDialog::new()
+                    .add_filter("Wavefront OBJ", &["obj"])
+                    .pick_file()
+                {
+                    self.creator.mesh_path = Some(path.to_string_lossy().into_owned());
+                }
+            }
+            match &self.creator.mesh_path {
+                Some(path) => ui.label(
+                    std::path::Path::new(path)
+                        .file_name()
+                        .map(|n| n.to_string_lossy().into_owned())
+                        .unwrap_or_else(|| path.clone()),
+                ),
+                None => ui.weak(self.i18n.tr("cr_no_mesh")),
+            };
+        });
+        ui.horizontal(|ui| {
+            let triggered = matches!(self.creator.mesh_emission, EmissionMode::OnEvent(_));
+            if ui
+                .radio(!triggered, self.i18n.tr("cr_emit_continuous"))
+                .clicked()
+            {
+                self.creator.mesh_emission = EmissionMode::Continuous;
+            }
+            if ui
+                .radio(triggered, self.i18n.tr("cr_emit_triggered"))
+                .on_hover_text(self.i18n.tr("cr_emit_triggered_hint"))
+                .clicked()
+                && !triggered
+            {
+                self.creator.mesh_emission =
+                    EmissionMode::OnEvent(EmissionMode::DEFAULT_EVENT_EXPR.to_string());
+            }
+        });
+        match &mut self.creator.mesh_emission {
+            EmissionMode::Continuous => {
+                ui.add(
+                    egui::Slider::new(&mut self.creator.mesh_emit_rate, 1..=5000)
+                        .logarithmic(true)
+                        .text(self.i18n.tr("cr_emit_rate")),
+                );
+            }
+            EmissionMode::OnEvent(expr) => {
+                ui.add(
+                    egui::TextEdit::singleline(expr)
+                        .font(egui::TextStyle::Monospace)
+                        .desired_width(f32::INFINITY),
+                );
+                ui.add(
+                    egui::Slider::new(&mut self.creator.mesh_burst_count, 1..=50000)
+                        .logarithmic(true)
+                        .text(self.i18n.tr("cr_burst_count")),
+                );
+            }
+        }
+        ui.add(
+            egui::Slider::new(&mut self.creator.mesh_emit_speed, 0.0..=10.0)
+                .text(self.i18n.tr("cr_emit_speed")),
+        );
+        ui.add(
+            egui::Slider::new(&mut self.creator.mesh_lifetime, 0.1..=10.0)
+                .text(self.i18n.tr("cr_particle_lifetime")),
+        );
+        ui.add(
+            egui::Slider::new(&mut self.creator.point_size, 0.01..=1.0)
+                .text(self.i18n.tr("point_size")),
+        );
+        ui.horizontal(|ui| {
+            ui.label(format!("{}:", self.i18n.tr("color")));
+            ui.color_edit_button_rgb(&mut self.creator.mesh_color);
+        });
+        ui.horizontal(|ui| {
+            ui.label(self.i18n.tr("cr_max_particles"));
+            ui.add(
+                egui::DragValue::new(&mut self.creator.max_particles)
+                    .speed(1000.0)
+                    .clamp_range(1..=10_000_000),
+            );
+        });
+
+        egui::CollapsingHeader::new(format!(
+            "{} ({})",
+            self.i18n.tr("cr_sub_emitters"),
+            self.creator.mesh_sub_emitters.len()
+        ))
+        .default_open(false)
+        .show(ui, |ui| {
+            ui.small(self.i18n.tr("cr_sub_emitters_hint"));
+            let mut remove = None;
+            for (i, sub) in self.creator.mesh_sub_emitters.iter_mut().enumerate() {
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.strong(format!("#{}", i + 1));
+                    ui.color_edit_button_rgb(&mut sub.color);
+                    if ui
+                        .small_button("🗑")
+                        .on_hover_text(self.i18n.tr("remove"))
+                        .clicked()
+                    {
+                        remove = Some(i);
+                    }
+                });
+                ui.add(
+                    egui::Slider::new(&mut sub.rate, 0.0..=100.0)
+                        .logarithmic(true)
+                        .text(self.i18n.tr("cr_sub_rate")),
+                );
+                ui.add(
+                    egui::Slider::new(&mut sub.speed, 0.0..=10.0)
+                        .text(self.i18n.tr("cr_sub_speed")),
+                );
+                ui.add(
+                    egui::Slider::new(&mut sub.lifetime, 0.1..=10.0)
+                        .text(self.i18n.tr("cr_particle_lifetime")),
+                );
+                ui.add(
+                    egui::Slider::new(&mut sub.point_size, 0.01..=1.0)
+                        .text(self.i18n.tr("point_size")),
+                );
+                ui.horizontal(|ui| {
+                    ui.label(self.i18n.tr("cr_trail_gravity"));
+                    for (value, axis) in sub.gravity.iter_mut().zip(["X: ", "Y: ", "Z: "]) {
+                        ui.add(egui::DragValue::new(value).speed(0.01).prefix(axis));
+                    }
+                });
+            }
+            if let Some(i) = remove {
+                self.creator.mesh_sub_emitters.remove(i);
+            }
+            if ui.button(self.i18n.tr("cr_sub_emitter_add")).clicked() {
+                self.creator
+                    .mesh_sub_emitters
+                    .push(SubEmitterConfig::default());
+            }
+        });
+    }
+
+    /// Spawn `mesh_emit_rate` particles per frame at random points on the
+    /// mesh surface, each moving along its triangle's normal and fading out
+    /// over `mesh_lifetime`.
+    fn generate_mesh_preset(&mut self) {
+        let Some(path) = self.creator.mesh_path.clone() else {
+            self.creator.status_msg = Some(self.i18n.tr("cr_no_mesh").to_string());
+            return;
+        };
+        let sampler =
+            match load_obj_triangles(std::path::Path::new(&path)).and_then(MeshSampler::new) {
+                Ok(sampler) => sampler,
+                Err(e) => {
+                    self.creator.status_msg = Some(format!("❌ {}", e));
+                    return;
+                }
+            };
+
+        let target_fps = self.creator.target_fps;
+        let total_frames = (self.creator.duration_secs * target_fps as f32) as u32;
+        let dt = 1.0 / target_fps as f32;
+        let rotation = self.creator.rotation;
+        let speed = self.creator.mesh_emit_speed;
+        let lifetime = self.creator.mesh_lifetime.max(dt);
+        let c = self.creator.mesh_color;
+        let color = [
+            (c[0] * 255.0) as u8,
+            (c[1] * 255.0) as u8,
+            (c[2] * 255.0) as u8,
+        ];
+
+        let max_particles = self.creator.max_particles;
+        let event_stmts = match &self.creator.mesh_emission {
+            EmissionMode::Continuous => None,
+            EmissionMode::OnEvent(expr) => {
+                let expr = crate::particleex::expand_macros(expr, &self.expression_macros);
+                match crate::particleex::compile_expr(&expr) {
+                    Some(stmts) => Some(stmts),
+                    None => {
+                        self.creator.status_msg =
+                            Some(format!("❌ {}", self.i18n.tr("cr_event_expr_invalid")));
+                        return;
+                    }
+                }
+            }
+        };
+        let mut event_ctx = crate::particleex::ExprContext::new();
+        let sub_emitters = &self.creator.mesh_sub_emitters;
+
+        use rand::Rng;
+        let mut rng = rand::thread_rng();
+        let mut live: Vec<LiveParticle> = Vec::new();
+        let mut next_id: i32 = 0;
+        let mut frames = Vec::with_capacity(total_frames as usize);
+        for f in 0..total_frames {
+            let spawn_count = match &event_stmts {
+                None => self.creator.mesh_emit_rate,
+                Some(stmts) => {
+                    use crate::particleex::Value;
+                    event_ctx.set_frame(f as i64);
+                    event_ctx.set("t", Value::Num(f as f64 * dt as f64));
+                    event_ctx.set("count", Value::Num(live.len() as f64));
+                    event_ctx.set("emit", Value::Num(0.0));
+                    crate::particleex::exec_stmts(stmts, &mut event_ctx);
+                    if event_ctx.get("emit").as_num() >= 1.0 {
+                        self.creator.mesh_burst_count
+                    } else {
+                        0
+                    }
+                }
+            };
+            for _ in 0..spawn_count {
+                if live.len() >= max_particles {
+                    break;
+                }
+                let (pos, normal) = sampler.sample(&mut rng);
+                let (px, py, pz) = apply_euler_rotation(pos[0], pos[1], pos[2], rotation);
+                let (nx, ny, nz) = apply_euler_rotation(normal[0], normal[1], normal[2], rotation);
+                live.push(LiveParticle {
+                    particle: Particle {
+                        id: next_id,
+                        pos: [px, py, pz],
+                        color: [color[0], color[1], color[2], 255],
+                        size: self.creator.point_size,
+                        tex_id: 0,
+                        seq_index: 0,
+                    },
+                    velocity: [nx * speed, ny * speed, nz * speed],
+                    gravity: [0.0; 3],
+                    age: 0.0,
+                    lifetime,
+                    emits: true,
+                });
+                next_id = next_id.wrapping_add(1);
+            }
+
+            let parents = live.len();
+            'spawn: for parent in 0..parents {
+                if !live[parent].emits {
+                    continue;
+                }
+                let origin = live[parent].particle.pos;
+                for sub in sub_emitters {
+                    let expected = sub.rate * dt;
+                    let mut count = expected.floor() as u32;
+                    if rng.gen::<f32>() < expected.fract() {
+                        count += 1;
+                    }
+                    for _ in 0..count {
+                        if live.len() >= max_particles {
+                            break 'spawn;
+                        }
+                        let dir = random_unit_vector(&mut rng);
+                        live.push(LiveParticle {
+                            particle: Particle {
+                                id: next_id,
+                                pos: origin,
+                                color: [
+                                    (sub.color[0] * 255.0) as u8,
+                                    (sub.color[1] * 255.0) as u8,
+                                    (sub.color[2] * 255.0) as u8,
+                                    255,
+                                ],
+                                size: sub.point_size,
+                                tex_id: 0,
+                                seq_index: 0,
+                            },
+                            velocity: dir.map(|d| d * sub.speed),
+                            gravity: sub.gravity,
+                            age: 0.0,
+                            lifetime: sub.lifetime.max(dt),
+                            emits: false,
+                        });
+                        next_id = next_id.wrapping_add(1);
+                    }
+                }
+            }
+
+            for lp in live.iter_mut() {
+                lp.age += dt;
+                for k in 0..3 {
+                    lp.velocity[k] += lp.gravity[k] * dt;
+                    lp.particle.pos[k] += lp.velocity[k] * dt;
+                }
+                let fade = (1.0 - lp.age / lp.lifetime).clamp(0.0, 1.0);
+                lp.particle.color[3] = (fade * 255.0) as u8;
+            }
+            live.retain(|lp| lp.age < lp.lifetime);
+            frames.push(live.iter().map(|lp| lp.particle.clone()).collect());
+        }
+
+        self.apply_texture_animation_to_frames(
+            &mut frames,
+            &self.creator.texture_animation.textures,
+            self.creator.texture_animation.texture_interval,
+        );
+        self.creator.trail_id_start = i32::MAX;
+        self.creator.preview_bbox = Some(recalculate_bbox(&frames));
+        self.creator.preview_frames = Some(frames);
+        self.creator.preview_frame_idx = 0;
+        self.creator.preview_playing = true;
+        self.creator.status_msg = Some(self.i18n.tr("gen_success").to_string());
+    }
+
+    fn ui_follow_path(&mut self, ui: &mut egui::Ui) {
+        let mut enabled = self.creator.follow_path.is_some();
+        if ui
+            .checkbox(&mut enabled, self.i18n.tr("cr_follow_path"))
+            .on_hover_text(self.i18n.tr("cr_follow_path_hint"))
+            .changed()
+        {
+            self.creator.follow_path =
+                enabled.then(|| vec![[0.0, 0.0, 0.0], [2.0, 1.0, 0.0], [4.0, 0.0, 0.0]]);
+        }
+        let Some(points) = &mut self.creator.follow_path else {
+            return;
+        };
+        egui::CollapsingHeader::new(format!(
+            "{} ({})",
+            self.i18n.tr("cr_path_points"),
+            points.len()
+        ))
+        .default_open(true)
+        .show(ui, |ui| {
+            let mut remove = None;
+            for (i, point) in points.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{}.", i + 1));
+                    for (value, axis) in point.iter_mut().zip(["X: ", "Y: ", "Z: "]) {
+                        ui.add(egui::DragValue::new(value).speed(0.05).prefix(axis));
+                    }
+                    if ui
+                        .small_button("🗑")
+                        .on_hover_text(self.i18n.tr("remove"))
+                        .clicked()
+                    {
+                        remove = Some(i);
+                    }
+                });
+            }
+            if let Some(i) = remove {
+                points.remove(i);
+            }
+            if ui.button(self.i18n.tr("cr_path_add")).clicked() {
+                let last = points.last().copied().unwrap_or([0.0; 3]);
+                points.push([last[0] + 1.0, last[1], last[2]]);
+            }
+        });
+    }
+
     fn generate_butterfly_preset(&mut self) {
         let target_fps = self.creator.target_fps;
         let total_frames = (self.creator.duration_secs * target_fps as f32) as u32;
@@ -598,7 +1261,9 @@ impl NebulaToolsApp {
         let trail_opacity = self.creator.trail_opacity;
 
         // Velocity expression
-        let stmts = crate::particleex::compile_expr(&self.creator.velocity_expr);
+        let velocity_expr =
+            crate::particleex::expand_macros(&self.creator.velocity_expr, &self.expression_macros);
+        let stmts = crate::particleex::compile_expr(&velocity_expr);
 
         // Particle distribution:
         //  ~60% upper wing, ~20% lower wing, ~10% body, ~5% antennae, ~5% wing edge
@@ -867,8 +1532,19 @@ impl NebulaToolsApp {
                 }
             }
 
+            // ── Move along the path; trails are sampled from these positions ──
+            if let Some(path) = &self.creator.follow_path {
+                let offset = crate::math::catmull_rom(path, f as f32 / total_frames.max(1) as f32);
+                for p in particles.iter_mut() {
+                    p.pos[0] += offset[0];
+                    p.pos[1] += offset[1];
+                    p.pos[2] += offset[2];
+                }
+            }
+
             // Save base particles (without trails) for trail sourcing
             base_frames.push(particles.clone());
+            self.creator.trail_id_start = pid;
 
             // ── Add Trail Particles (from previous frames' BASE particles only) ──
             if trail_enabled && trail_frames > 0 && f > 0 {
@@ -918,12 +1594,84 @@ impl NebulaToolsApp {
             &self.creator.texture_animation.textures,
             self.creator.texture_animation.texture_interval,
         );
+        self.creator.preview_bbox = Some(recalculate_bbox(&frames));
         self.creator.preview_frames = Some(frames);
         self.creator.preview_frame_idx = 0;
         self.creator.preview_playing = true;
         self.creator.status_msg = Some(self.i18n.tr("gen_success").to_string());
     }
 
+    /// Write each layer of the creator output to `{prefix}_layer{i}.nbl`, all
+    /// with the same FPS and frame count. The creator has one emitter, so the
+    /// layers are the butterfly itself and, when enabled, its trail.
+    fn export_creator_layers(&mut self, path_prefix: &std::path::Path) {
+        let Some(frames) = &self.creator.preview_frames else {
+            return;
+        };
+        let split = self.creator.trail_id_start;
+        let (base, trail): (Vec<Vec<Particle>>, Vec<Vec<Particle>>) = frames
+            .iter()
+            .map(|f| f.iter().cloned().partition(|p| p.id < split))
+            .unzip();
+        let mut layers = vec![base];
+        if trail.iter().any(|f| !f.is_empty()) {
+            layers.push(trail);
+        }
+
+        let textures = build_texture_entries(&self.creator.texture_animation.textures);
+        let stem = path_prefix.with_extension("");
+        for (i, layer) in layers.iter().enumerate() {
+            let path = std::path::PathBuf::from(format!("{}_layer{}.nbl", stem.display(), i));
+            let (bbox_min, bbox_max) = recalculate_bbox(layer);
+            let header = NblHeader {
+                version: NBL_VERSION,
+                target_fps: self.creator.target_fps,
+                total_frames: layer.len() as u32,
+                texture_count: textures.len() as u16,
+                attributes: 3,
+                bbox_min,
+                bbox_max,
+            };
+            if let Err(e) = self.player.save_file(&path, &header, &textures, layer) {
+                self.creator.status_msg = Some(format!("{}{}", self.i18n.tr("export_failed"), e));
+                return;
+            }
+        }
+        self.creator.status_msg = Some(format!(
+            "{} ({} {})",
+            self.i18n.tr("export_success"),
+            layers.len(),
+            self.i18n.tr("layers")
+        ));
+    }
+
+    /// Pick several NBL layers and load their overlay as the creator preview,
+    /// ready to be exported as a single file.
+    fn merge_creator_layers(&mut self) {
+        let Some(paths) = rfd::FileDialog::new()
+            .add_filter("Nebula", &["nbl"][..])
+            .pick_files()
+        else {
+            return;
+        };
+        match crate::player::merge_nbl_layers(&paths) {
+            Ok((fps, textures, frames)) => {
+                self.creator.target_fps = fps;
+                self.creator.texture_animation.textures =
+                    textures.into_iter().map(|t| t.path).collect();
+                self.creator.trail_id_start = i32::MAX;
+                self.creator.preview_bbox = Some(recalculate_bbox(&frames));
+                self.creator.preview_frames = Some(frames);
+                self.creator.preview_frame_idx = 0;
+                self.creator.preview_playing = true;
+                self.creator.status_msg = Some(self.i18n.tr("layers_merged").to_string());
+            }
+            Err(e) => {
+                self.creator.status_msg = Some(format!("{}{}", self.i18n.tr("export_failed"), e))
+            }
+        }
+    }
+
     pub(crate) fn export_creator_nbl(&mut self) {
         if let Some(frames) = &self.creator.preview_frames {
             if let Some(path) = rfd::FileDialog::new()
@@ -946,7 +1694,7 @@ impl NebulaToolsApp {
 
                 let textures = build_texture_entries(&self.creator.texture_animation.textures);
                 let header = NblHeader {
-                    version: 1,
+                    version: NBL_VERSION,
                     target_fps: self.creator.target_fps,
                     total_frames: frames.len() as u32,
                     texture_count: textures.len() as u16,
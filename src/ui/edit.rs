@@ -1,4 +1,4 @@
-use super::app::NebulaToolsApp;
+use super::app::{NebulaToolsApp, VoxelEstimate};
 use crate::player;
 use eframe::egui;
 use std::sync::{Arc, Mutex};
@@ -36,6 +36,9 @@ impl NebulaToolsApp {
                     (crate::ui::app::EditTool::Transform, "edit_transform"),
                     (crate::ui::app::EditTool::Trim, "edit_trim"),
                     (crate::ui::app::EditTool::Compress, "edit_compress"),
+                    (crate::ui::app::EditTool::Deflate, "edit_deflate"),
+                    (crate::ui::app::EditTool::Voxelise, "edit_voxelise"),
+                    (crate::ui::app::EditTool::Smooth, "edit_smooth"),
                 ];
 
                 for (tool, lang_key) in tools {
@@ -74,6 +77,9 @@ impl NebulaToolsApp {
                     crate::ui::app::EditTool::Transform => self.i18n.tr("edit_transform"),
                     crate::ui::app::EditTool::Trim => self.i18n.tr("edit_trim"),
                     crate::ui::app::EditTool::Compress => self.i18n.tr("edit_compress"),
+                    crate::ui::app::EditTool::Deflate => self.i18n.tr("edit_deflate"),
+                    crate::ui::app::EditTool::Voxelise => self.i18n.tr("edit_voxelise"),
+                    crate::ui::app::EditTool::Smooth => self.i18n.tr("edit_smooth"),
                 };
                 ui.label(egui::RichText::new(title).size(26.0).strong().color(ACCENT));
                 ui.add_space(6.0);
@@ -92,6 +98,9 @@ impl NebulaToolsApp {
                         crate::ui::app::EditTool::Transform => self.ui_transform_params(ui),
                         crate::ui::app::EditTool::Trim => self.ui_trim_params(ui),
                         crate::ui::app::EditTool::Compress => self.ui_compress_params(ui),
+                        crate::ui::app::EditTool::Deflate => self.ui_deflate_params(ui),
+                        crate::ui::app::EditTool::Voxelise => self.ui_voxelise_params(ui),
+                        crate::ui::app::EditTool::Smooth => self.ui_smooth_params(ui),
                     });
 
                 self.ui_progress_bar(ui);
@@ -511,6 +520,20 @@ impl NebulaToolsApp {
                         .max_decimals(4),
                 );
                 ui.end_row();
+
+                ui.label(egui::RichText::new(self.i18n.tr("color_temperature")).strong());
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::Slider::new(&mut self.edit.color_temperature, 1000.0..=12000.0)
+                            .step_by(100.0),
+                    );
+                    let [r, g, b] = player::kelvin_to_rgb(self.edit.color_temperature);
+                    let (rect, _) =
+                        ui.allocate_exact_size(egui::vec2(18.0, 18.0), egui::Sense::hover());
+                    ui.painter()
+                        .rect_filled(rect, 3.0, egui::Color32::from_rgb(r, g, b));
+                });
+                ui.end_row();
             });
 
         ui.add_space(20.0);
@@ -529,6 +552,7 @@ impl NebulaToolsApp {
             self.start_export(player::EditAction::AdjustColor(
                 self.edit.brightness,
                 self.edit.opacity,
+                self.edit.color_temperature,
             ));
         }
     }
@@ -584,6 +608,178 @@ impl NebulaToolsApp {
                 self.edit.pos_scale,
             ));
         }
+
+        ui.add_space(16.0);
+        ui.separator();
+        ui.add_space(8.0);
+        ui.label(
+            egui::RichText::new(self.i18n.tr("edit_jitter"))
+                .strong()
+                .size(16.0),
+        );
+        ui.label(
+            egui::RichText::new(self.i18n.tr("edit_jitter_desc"))
+                .color(HINT_COLOR)
+                .size(13.0),
+        );
+        ui.add_space(8.0);
+        egui::Grid::new("jitter_grid")
+            .num_columns(2)
+            .spacing([16.0, 12.0])
+            .show(ui, |ui| {
+                ui.label(egui::RichText::new(self.i18n.tr("jitter_amplitude")).strong());
+                ui.add(
+                    egui::Slider::new(&mut self.edit.jitter_amplitude, 0.0..=1.0).logarithmic(true),
+                );
+                ui.end_row();
+
+                ui.label(egui::RichText::new(self.i18n.tr("jitter_seed")).strong());
+                ui.add(egui::DragValue::new(&mut self.edit.jitter_seed));
+                ui.end_row();
+            });
+        ui.add_space(8.0);
+        if ui
+            .add_sized(
+                [ui.available_width().min(200.0), 32.0],
+                egui::Button::new(
+                    egui::RichText::new(format!("▶ {}", self.i18n.tr("export_nbl")))
+                        .strong()
+                        .size(15.0),
+                ),
+            )
+            .clicked()
+        {
+            self.start_export(player::EditAction::Jitter(
+                self.edit.jitter_amplitude,
+                self.edit.jitter_seed,
+            ));
+        }
+
+        ui.add_space(16.0);
+        ui.separator();
+        ui.add_space(8.0);
+        ui.label(
+            egui::RichText::new(self.i18n.tr("edit_wind"))
+                .strong()
+                .size(16.0),
+        );
+        ui.label(
+            egui::RichText::new(self.i18n.tr("edit_wind_desc"))
+                .color(HINT_COLOR)
+                .size(13.0),
+        );
+        ui.add_space(8.0);
+        egui::Grid::new("wind_grid")
+            .num_columns(2)
+            .spacing([16.0, 12.0])
+            .show(ui, |ui| {
+                ui.label(egui::RichText::new(self.i18n.tr("wind_direction")).strong());
+                ui.horizontal(|ui| {
+                    ui.label("X:");
+                    ui.add(egui::Slider::new(
+                        &mut self.edit.wind_direction[0],
+                        -1.0..=1.0,
+                    ));
+                    ui.label("Z:");
+                    ui.add(egui::Slider::new(
+                        &mut self.edit.wind_direction[1],
+                        -1.0..=1.0,
+                    ));
+                });
+                ui.end_row();
+
+                ui.label(egui::RichText::new(self.i18n.tr("wind_strength")).strong());
+                ui.add(
+                    egui::Slider::new(&mut self.edit.wind_strength, 0.0..=1.0).logarithmic(true),
+                );
+                ui.end_row();
+
+                ui.label(egui::RichText::new(self.i18n.tr("wind_turbulence")).strong());
+                ui.add(egui::Slider::new(&mut self.edit.wind_turbulence, 0.0..=2.0));
+                ui.end_row();
+            });
+        ui.add_space(8.0);
+        if ui
+            .add_sized(
+                [ui.available_width().min(200.0), 32.0],
+                egui::Button::new(
+                    egui::RichText::new(format!("▶ {}", self.i18n.tr("export_nbl")))
+                        .strong()
+                        .size(15.0),
+                ),
+            )
+            .clicked()
+        {
+            self.start_export(player::EditAction::Wind(
+                self.edit.wind_direction,
+                self.edit.wind_strength,
+                self.edit.wind_turbulence,
+            ));
+        }
+
+        ui.add_space(16.0);
+        ui.separator();
+        ui.add_space(8.0);
+        ui.label(
+            egui::RichText::new(self.i18n.tr("edit_gravity_well"))
+                .strong()
+                .size(16.0),
+        );
+        ui.label(
+            egui::RichText::new(self.i18n.tr("edit_gravity_well_desc"))
+                .color(HINT_COLOR)
+                .size(13.0),
+        );
+        ui.add_space(8.0);
+        egui::Grid::new("gravity_well_grid")
+            .num_columns(2)
+            .spacing([16.0, 12.0])
+            .show(ui, |ui| {
+                ui.label(egui::RichText::new(self.i18n.tr("gravity_center")).strong());
+                ui.horizontal(|ui| {
+                    ui.label("X:");
+                    ui.add(egui::DragValue::new(&mut self.edit.gravity_center[0]).speed(0.1));
+                    ui.label("Y:");
+                    ui.add(egui::DragValue::new(&mut self.edit.gravity_center[1]).speed(0.1));
+                    ui.label("Z:");
+                    ui.add(egui::DragValue::new(&mut self.edit.gravity_center[2]).speed(0.1));
+                });
+                ui.end_row();
+
+                ui.label(egui::RichText::new(self.i18n.tr("gravity_strength")).strong());
+                ui.add(
+                    egui::DragValue::new(&mut self.edit.gravity_strength)
+                        .speed(0.001)
+                        .max_decimals(4),
+                );
+                ui.end_row();
+
+                ui.label(egui::RichText::new(self.i18n.tr("gravity_falloff")).strong());
+                ui.add(
+                    egui::DragValue::new(&mut self.edit.gravity_falloff)
+                        .clamp_range(0.0..=4.0)
+                        .speed(0.01),
+                );
+                ui.end_row();
+            });
+        ui.add_space(8.0);
+        if ui
+            .add_sized(
+                [ui.available_width().min(200.0), 32.0],
+                egui::Button::new(
+                    egui::RichText::new(format!("▶ {}", self.i18n.tr("export_nbl")))
+                        .strong()
+                        .size(15.0),
+                ),
+            )
+            .clicked()
+        {
+            self.start_export(player::EditAction::GravityWell(
+                self.edit.gravity_center,
+                self.edit.gravity_strength,
+                self.edit.gravity_falloff,
+            ));
+        }
     }
 
     fn ui_trim_params(&mut self, ui: &mut egui::Ui) {
@@ -612,20 +808,29 @@ impl NebulaToolsApp {
             .spacing([16.0, 12.0])
             .show(ui, |ui| {
                 ui.label(egui::RichText::new(self.i18n.tr("trim_start")).strong());
-                ui.add(
-                    egui::DragValue::new(&mut self.edit.trim_start)
-                        .clamp_range(0..=max_frame)
-                        .speed(1.0),
-                );
+                let start_changed = ui
+                    .add(
+                        egui::DragValue::new(&mut self.edit.trim_start)
+                            .clamp_range(0..=max_frame)
+                            .speed(1.0),
+                    )
+                    .changed();
                 ui.end_row();
 
                 ui.label(egui::RichText::new(self.i18n.tr("trim_end")).strong());
-                ui.add(
-                    egui::DragValue::new(&mut self.edit.trim_end)
-                        .clamp_range(0..=max_frame)
-                        .speed(1.0),
-                );
+                let end_changed = ui
+                    .add(
+                        egui::DragValue::new(&mut self.edit.trim_end)
+                            .clamp_range(0..=max_frame)
+                            .speed(1.0),
+                    )
+                    .changed();
                 ui.end_row();
+
+                // A stale preview would show the wrong range
+                if start_changed || end_changed {
+                    self.edit.trim_preview = None;
+                }
             });
 
         let start = self.edit.trim_start as usize;
@@ -656,6 +861,24 @@ impl NebulaToolsApp {
             );
         }
 
+        ui.add_space(12.0);
+        if ui
+            .button(format!("👁 {}", self.i18n.tr("trim_preview")))
+            .clicked()
+        {
+            match self
+                .player
+                .decode_frame_range(self.edit.trim_start, self.edit.trim_end + 1)
+            {
+                Ok(frames) => {
+                    self.edit.trim_preview = Some(frames);
+                    self.edit.trim_preview_frame = 0;
+                }
+                Err(e) => self.edit.status_msg = Some(format!("❌ {}", e)),
+            }
+        }
+        self.ui_trim_preview(ui);
+
         ui.add_space(20.0);
         ui.add_space(20.0);
         if ui
@@ -674,6 +897,84 @@ impl NebulaToolsApp {
                 self.edit.trim_end,
             ));
         }
+
+        ui.add_space(16.0);
+        ui.separator();
+        ui.add_space(8.0);
+        ui.label(
+            egui::RichText::new(self.i18n.tr("edit_resample"))
+                .strong()
+                .size(16.0),
+        );
+        ui.label(
+            egui::RichText::new(self.i18n.tr("edit_resample_desc"))
+                .color(HINT_COLOR)
+                .size(13.0),
+        );
+        ui.add_space(8.0);
+        if self.edit.resample_frames == 0 {
+            self.edit.resample_frames = max_frame as usize + 1;
+        }
+        let compiles = crate::particleex::compile_expr(&self.edit.resample_expr).is_some();
+        egui::Grid::new("resample_grid")
+            .num_columns(2)
+            .spacing([16.0, 12.0])
+            .show(ui, |ui| {
+                ui.label(egui::RichText::new(self.i18n.tr("resample_expr")).strong());
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.edit.resample_expr)
+                        .font(egui::TextStyle::Monospace)
+                        .text_color_opt(
+                            (!compiles).then_some(egui::Color32::from_rgb(255, 100, 100)),
+                        ),
+                );
+                ui.end_row();
+
+                ui.label(egui::RichText::new(self.i18n.tr("resample_frames")).strong());
+                ui.add(
+                    egui::DragValue::new(&mut self.edit.resample_frames)
+                        .clamp_range(1..=1_000_000)
+                        .speed(1.0),
+                );
+                ui.end_row();
+            });
+        ui.add_space(8.0);
+        if ui
+            .add_enabled(
+                compiles,
+                egui::Button::new(
+                    egui::RichText::new(format!("▶ {}", self.i18n.tr("export_nbl")))
+                        .strong()
+                        .size(15.0),
+                )
+                .min_size(egui::vec2(ui.available_width().min(200.0), 32.0)),
+            )
+            .clicked()
+        {
+            self.start_export(player::EditAction::Resample(self.edit.resample_frames));
+        }
+    }
+
+    /// Scrubbable viewport over the decoded trim range.
+    fn ui_trim_preview(&mut self, ui: &mut egui::Ui) {
+        let Some(frames) = &self.edit.trim_preview else {
+            return;
+        };
+        if frames.is_empty() {
+            return;
+        }
+        let last = frames.len() - 1;
+        let idx = self.edit.trim_preview_frame.min(last);
+        let data = self.prepare_render_data_from(&frames[idx], idx);
+
+        ui.add_space(8.0);
+        ui.add(
+            egui::Slider::new(&mut self.edit.trim_preview_frame, 0..=last)
+                .text(self.i18n.tr("frame")),
+        );
+        let ctx = ui.ctx().clone();
+        let size = egui::vec2(ui.available_width(), 280.0);
+        ui.allocate_ui(size, |ui| self.paint_3d_viewport(ui, &ctx, &data));
     }
 
     /// Decode all frames on demand. Returns true if frames are available.
@@ -724,16 +1025,64 @@ impl NebulaToolsApp {
                 });
                 ui.end_row();
 
+                // Block compression
+                ui.label(egui::RichText::new(self.i18n.tr("compress_algorithm")).strong());
+                ui.vertical(|ui| {
+                    let current = self.edit.compress_algorithm;
+                    egui::ComboBox::from_id_source("compress_algorithm_combo")
+                        .selected_text(match current {
+                            player::CompressAlgorithm::Zstd(_) => "Zstd",
+                            player::CompressAlgorithm::Lz4 => "LZ4",
+                        })
+                        .show_ui(ui, |ui| {
+                            let zstd_level = match current {
+                                player::CompressAlgorithm::Zstd(level) => level,
+                                player::CompressAlgorithm::Lz4 => 1,
+                            };
+                            ui.selectable_value(
+                                &mut self.edit.compress_algorithm,
+                                player::CompressAlgorithm::Zstd(zstd_level),
+                                "Zstd",
+                            );
+                            ui.selectable_value(
+                                &mut self.edit.compress_algorithm,
+                                player::CompressAlgorithm::Lz4,
+                                "LZ4",
+                            );
+                        });
+                    ui.label(
+                        egui::RichText::new(self.i18n.tr("compress_algorithm_desc"))
+                            .color(HINT_COLOR)
+                            .size(11.0),
+                    );
+                });
+                ui.end_row();
+
                 // Zstd level
-                ui.label(egui::RichText::new(self.i18n.tr("compress_zstd_level")).strong());
+                if let player::CompressAlgorithm::Zstd(level) = &mut self.edit.compress_algorithm {
+                    ui.label(egui::RichText::new(self.i18n.tr("compress_zstd_level")).strong());
+                    ui.vertical(|ui| {
+                        ui.add(egui::DragValue::new(level).clamp_range(1..=5).speed(1.0));
+                        ui.label(
+                            egui::RichText::new(self.i18n.tr("compress_zstd_level_desc"))
+                                .color(HINT_COLOR)
+                                .size(11.0),
+                        );
+                    });
+                    ui.end_row();
+                }
+
+                // Linear motion
+                ui.label(egui::RichText::new(self.i18n.tr("compress_linear_tolerance")).strong());
                 ui.vertical(|ui| {
                     ui.add(
-                        egui::DragValue::new(&mut self.edit.compress_zstd_level)
-                            .clamp_range(1..=5)
-                            .speed(1.0),
+                        egui::DragValue::new(&mut self.edit.compress_linear_tolerance)
+                            .clamp_range(0.0..=1.0)
+                            .speed(0.001)
+                            .max_decimals(3),
                     );
                     ui.label(
-                        egui::RichText::new(self.i18n.tr("compress_zstd_level_desc"))
+                        egui::RichText::new(self.i18n.tr("compress_linear_tolerance_desc"))
                             .color(HINT_COLOR)
                             .size(11.0),
                     );
@@ -756,6 +1105,181 @@ impl NebulaToolsApp {
         {
             self.start_export(player::EditAction::Compress(
                 self.edit.compress_keyframe_interval,
+                self.edit.compress_linear_tolerance,
+            ));
+        }
+    }
+
+    fn ui_deflate_params(&mut self, ui: &mut egui::Ui) {
+        ui.label(
+            egui::RichText::new(self.i18n.tr("edit_deflate_desc"))
+                .color(HINT_COLOR)
+                .size(14.0),
+        );
+        ui.add_space(16.0);
+
+        egui::Grid::new("deflate_grid")
+            .num_columns(2)
+            .spacing([16.0, 12.0])
+            .show(ui, |ui| {
+                ui.label(egui::RichText::new(self.i18n.tr("deflate_radius")).strong());
+                ui.add(
+                    egui::DragValue::new(&mut self.edit.deflate_radius)
+                        .clamp_range(0.0..=10.0)
+                        .speed(0.001)
+                        .max_decimals(4),
+                );
+                ui.end_row();
+            });
+
+        ui.add_space(20.0);
+        ui.add_space(20.0);
+        if ui
+            .add_sized(
+                [ui.available_width().min(200.0), 32.0],
+                egui::Button::new(
+                    egui::RichText::new(format!("▶ {}", self.i18n.tr("export_nbl")))
+                        .strong()
+                        .size(15.0),
+                ),
+            )
+            .clicked()
+        {
+            self.start_export(player::EditAction::Deflate(self.edit.deflate_radius));
+        }
+    }
+
+    fn ui_voxelise_params(&mut self, ui: &mut egui::Ui) {
+        ui.label(
+            egui::RichText::new(self.i18n.tr("edit_voxelise_desc"))
+                .color(HINT_COLOR)
+                .size(14.0),
+        );
+        ui.add_space(16.0);
+
+        egui::Grid::new("voxelise_grid")
+            .num_columns(2)
+            .spacing([16.0, 12.0])
+            .show(ui, |ui| {
+                ui.label(egui::RichText::new(self.i18n.tr("voxel_grid_size")).strong());
+                ui.add(
+                    egui::DragValue::new(&mut self.edit.voxel_grid_size)
+                        .clamp_range(0.001..=10.0)
+                        .speed(0.001)
+                        .max_decimals(4),
+                );
+                ui.end_row();
+            });
+
+        // Estimate on the current frame only; decoding everything would stall the UI
+        let grid_size = self.edit.voxel_grid_size;
+        let frame = self.player.current_frame_idx;
+        let before = self.player.particles.len();
+        let estimate = match self.edit.voxel_estimate {
+            Some(e) if e.grid_size == grid_size && e.frame == frame && e.before == before => e,
+            _ => {
+                let current: Vec<player::Particle> =
+                    self.player.particles.values().cloned().collect();
+                let e = VoxelEstimate {
+                    grid_size,
+                    frame,
+                    before,
+                    after: player::merge_voxel_duplicates(current, grid_size).len(),
+                };
+                self.edit.voxel_estimate = Some(e);
+                e
+            }
+        };
+        let after = estimate.after;
+        ui.add_space(8.0);
+        ui.label(
+            egui::RichText::new(format!(
+                "{}: {} → {}",
+                self.i18n.tr("voxel_estimated_count"),
+                before,
+                after
+            ))
+            .color(PREVIEW_COLOR)
+            .size(13.0),
+        );
+
+        ui.add_space(20.0);
+        ui.add_space(20.0);
+        if ui
+            .add_sized(
+                [ui.available_width().min(200.0), 32.0],
+                egui::Button::new(
+                    egui::RichText::new(format!("▶ {}", self.i18n.tr("export_nbl")))
+                        .strong()
+                        .size(15.0),
+                ),
+            )
+            .clicked()
+        {
+            self.start_export(player::EditAction::Voxelise(self.edit.voxel_grid_size));
+        }
+
+        ui.add_space(16.0);
+        ui.separator();
+        ui.add_space(8.0);
+        ui.label(
+            egui::RichText::new(self.i18n.tr("edit_deduplicate_desc"))
+                .color(HINT_COLOR)
+                .size(13.0),
+        );
+        ui.add_space(8.0);
+        if ui
+            .add_sized(
+                [ui.available_width().min(200.0), 32.0],
+                egui::Button::new(
+                    egui::RichText::new(format!("▶ {}", self.i18n.tr("edit_deduplicate")))
+                        .strong()
+                        .size(15.0),
+                ),
+            )
+            .clicked()
+        {
+            self.start_export(player::EditAction::Deduplicate);
+        }
+    }
+
+    fn ui_smooth_params(&mut self, ui: &mut egui::Ui) {
+        ui.label(
+            egui::RichText::new(self.i18n.tr("edit_smooth_desc"))
+                .color(HINT_COLOR)
+                .size(14.0),
+        );
+        ui.add_space(16.0);
+
+        egui::Grid::new("smooth_grid")
+            .num_columns(2)
+            .spacing([16.0, 12.0])
+            .show(ui, |ui| {
+                ui.label(egui::RichText::new(self.i18n.tr("smooth_passes")).strong());
+                ui.add(egui::Slider::new(&mut self.edit.smooth_passes, 1..=20));
+                ui.end_row();
+
+                ui.label(egui::RichText::new(self.i18n.tr("smooth_weight")).strong());
+                ui.add(egui::Slider::new(&mut self.edit.smooth_weight, 0.0..=1.0));
+                ui.end_row();
+            });
+
+        ui.add_space(20.0);
+        ui.add_space(20.0);
+        if ui
+            .add_sized(
+                [ui.available_width().min(200.0), 32.0],
+                egui::Button::new(
+                    egui::RichText::new(format!("▶ {}", self.i18n.tr("export_nbl")))
+                        .strong()
+                        .size(15.0),
+                ),
+            )
+            .clicked()
+        {
+            self.start_export(player::EditAction::Smooth(
+                self.edit.smooth_passes,
+                self.edit.smooth_weight,
             ));
         }
     }
@@ -769,10 +1293,11 @@ impl NebulaToolsApp {
                 p.is_done,
                 p.error.clone(),
                 p.start_time,
+                p.removed_particles,
             )
         });
 
-        if let Some((current, total, is_done, error, start_time)) = progress_state {
+        if let Some((current, total, is_done, error, start_time, removed)) = progress_state {
             if !is_done && error.is_none() {
                 ui.add_space(10.0);
                 ui.separator();
@@ -833,7 +1358,15 @@ impl NebulaToolsApp {
             // Done or Error logic
             if is_done {
                 self.edit.compress_progress = None;
-                self.edit.status_msg = Some(self.i18n.tr("apply_success").to_string());
+                let mut msg = self.i18n.tr("apply_success").to_string();
+                if removed > 0 {
+                    msg.push_str(&format!(
+                        " ({} {})",
+                        self.i18n.tr("removed_particles"),
+                        removed
+                    ));
+                }
+                self.edit.status_msg = Some(msg);
             } else if let Some(err) = error {
                 self.edit.compress_progress = None;
                 self.edit.status_msg = Some(format!("❌ {}", err));
@@ -863,26 +1396,66 @@ impl NebulaToolsApp {
             .set_file_name("export.nbl")
             .save_file()
         {
+            // Writing over the file open for playback would truncate it under
+            // the player's memory map.
+            if let Err(e) = player::ensure_not_mapped(&output_path) {
+                self.edit.status_msg = Some(format!("❌ {}", e));
+                return;
+            }
             let progress = Arc::new(Mutex::new(player::CompressProgress {
                 total_frames: 0,
                 current_frame: 0,
                 is_done: false,
                 error: None,
                 start_time: std::time::Instant::now(),
+                removed_particles: 0,
             }));
             self.edit.compress_progress = Some(progress.clone());
 
-            // Use level 3 as broad default, can optimize later
-            let zstd_level = self.edit.compress_zstd_level.clamp(1, 22);
+            let resample_expr = self.edit.resample_expr.clone();
+            // Only the compress export takes its block compression from the
+            // panel; every other edit writes Zstd like `save_file` does.
+            let algorithm = match (&action, self.edit.compress_algorithm) {
+                (player::EditAction::Compress(..), player::CompressAlgorithm::Zstd(level)) => {
+                    player::CompressAlgorithm::Zstd(level.clamp(1, 22))
+                }
+                (player::EditAction::Compress(..), other) => other,
+                _ => player::CompressAlgorithm::Zstd(3),
+            };
 
             std::thread::spawn(move || {
-                if let Err(e) = player::streaming_edit(
-                    source_path,
-                    output_path,
-                    action,
-                    zstd_level,
-                    progress.clone(),
-                ) {
+                let result = match action {
+                    player::EditAction::Smooth(passes, weight) => player::smooth_nbl_file(
+                        source_path,
+                        output_path,
+                        passes,
+                        weight,
+                        progress.clone(),
+                    ),
+                    player::EditAction::Wind(direction, strength, turbulence) => {
+                        player::rewrite_nbl_file(source_path, output_path, progress.clone(), |f| {
+                            player::edit_apply_wind(f, direction, strength, turbulence)
+                        })
+                    }
+                    player::EditAction::GravityWell(center, strength, falloff) => {
+                        player::rewrite_nbl_file(source_path, output_path, progress.clone(), |f| {
+                            player::edit_gravity_well(f, center, strength, falloff)
+                        })
+                    }
+                    player::EditAction::Resample(total_out) => {
+                        player::rewrite_nbl_file(source_path, output_path, progress.clone(), |f| {
+                            *f = player::edit_resample_frames(f, &resample_expr, total_out)
+                        })
+                    }
+                    _ => player::streaming_edit(
+                        source_path,
+                        output_path,
+                        action,
+                        algorithm,
+                        progress.clone(),
+                    ),
+                };
+                if let Err(e) = result {
                     if let Ok(mut p) = progress.lock() {
                         p.error = Some(format!("{}", e));
                     }
@@ -0,0 +1,309 @@
+use crate::i18n::I18nManager;
+use eframe::egui;
+
+/// A ready-made expression snippet. `category` and `description` are i18n
+/// keys; descriptions may wrap variable names in backticks.
+pub struct FormulaEntry {
+    pub name: &'static str,
+    pub category: &'static str,
+    pub tags: &'static [&'static str],
+    pub code: &'static str,
+    pub description: &'static str,
+}
+
+pub const FORMULAS: &[FormulaEntry] = &[
+    FormulaEntry {
+        name: "Orbit (Y axis)",
+        category: "formula_cat_motion",
+        tags: &["orbit", "rotate", "spin", "circle"],
+        code: "vx = -z * 0.02; vz = x * 0.02",
+        description: "formula_orbit_y_desc",
+    },
+    FormulaEntry {
+        name: "Orbit (X axis)",
+        category: "formula_cat_motion",
+        tags: &["orbit", "rotate", "spin", "circle"],
+        code: "vy = -z * 0.02; vz = y * 0.02",
+        description: "formula_orbit_x_desc",
+    },
+    FormulaEntry {
+        name: "Spiral Out",
+        category: "formula_cat_motion",
+        tags: &["spiral", "orbit", "expand", "galaxy"],
+        code: "vx = -z * 0.02 + x * 0.005; vz = x * 0.02 + z * 0.005",
+        description: "formula_spiral_desc",
+    },
+    FormulaEntry {
+        name: "Rising Vortex",
+        category: "formula_cat_motion",
+        tags: &["vortex", "tornado", "swirl", "rise"],
+        code: "vx = -z * 0.03; vz = x * 0.03; vy = 0.01",
+        description: "formula_vortex_desc",
+    },
+    FormulaEntry {
+        name: "Drift",
+        category: "formula_cat_motion",
+        tags: &["drift", "move", "linear", "scroll"],
+        code: "vx = 0.01; vy = 0.005",
+        description: "formula_drift_desc",
+    },
+    FormulaEntry {
+        name: "Explode",
+        category: "formula_cat_motion",
+        tags: &["explode", "burst", "expand", "radial"],
+        code: "vx = x * 0.05; vy = y * 0.05; vz = z * 0.05",
+        description: "formula_explode_desc",
+    },
+    FormulaEntry {
+        name: "Implode",
+        category: "formula_cat_motion",
+        tags: &["implode", "collapse", "shrink", "radial"],
+        code: "vx = -x * 0.05; vy = -y * 0.05; vz = -z * 0.05",
+        description: "formula_implode_desc",
+    },
+    FormulaEntry {
+        name: "Jitter",
+        category: "formula_cat_motion",
+        tags: &["random", "noise", "shake", "jitter"],
+        code: "vx = (random() - 0.5) * 0.02; vy = (random() - 0.5) * 0.02; vz = (random() - 0.5) * 0.02",
+        description: "formula_jitter_desc",
+    },
+    FormulaEntry {
+        name: "Turbulence",
+        category: "formula_cat_motion",
+        tags: &["turbulence", "flow", "noise", "smoke"],
+        code: "vx = sin(y * 3 + t) * 0.01; vy = cos(z * 3 + t) * 0.01; vz = sin(x * 3 + t) * 0.01",
+        description: "formula_turbulence_desc",
+    },
+    FormulaEntry {
+        name: "Sine Wave",
+        category: "formula_cat_waves",
+        tags: &["wave", "sine", "oscillate"],
+        code: "vy = sin(x * 4 + t * 3) * 0.01",
+        description: "formula_sine_wave_desc",
+    },
+    FormulaEntry {
+        name: "Ripple",
+        category: "formula_cat_waves",
+        tags: &["ripple", "water", "radial", "wave"],
+        code: "vy = sin(hypot(x, z) * 6 - t * 4) * 0.01",
+        description: "formula_ripple_desc",
+    },
+    FormulaEntry {
+        name: "Wave Interference",
+        category: "formula_cat_waves",
+        tags: &["interference", "ripple", "two source", "wave"],
+        code: "vy = (sin(hypot(x - 1, z) * 6 - t * 4) + sin(hypot(x + 1, z) * 6 - t * 4)) * 0.005",
+        description: "formula_interference_desc",
+    },
+    FormulaEntry {
+        name: "Breathing",
+        category: "formula_cat_waves",
+        tags: &["pulse", "breathe", "scale", "heartbeat"],
+        code: "vx = x * sin(t * 2) * 0.01; vy = y * sin(t * 2) * 0.01; vz = z * sin(t * 2) * 0.01",
+        description: "formula_breathing_desc",
+    },
+    FormulaEntry {
+        name: "Flag Wave",
+        category: "formula_cat_waves",
+        tags: &["flag", "cloth", "wave", "banner"],
+        code: "vz = sin(x * 3 - t * 5) * 0.01 * (x + 1)",
+        description: "formula_flag_desc",
+    },
+    FormulaEntry {
+        name: "Phase by ID",
+        category: "formula_cat_waves",
+        tags: &["id", "phase", "offset", "stagger"],
+        code: "vy = sin(t * 3 + id * 0.1) * 0.01",
+        description: "formula_phase_id_desc",
+    },
+    FormulaEntry {
+        name: "Gravity",
+        category: "formula_cat_physics",
+        tags: &["gravity", "fall", "drop"],
+        code: "vy = -0.02 * t",
+        description: "formula_gravity_desc",
+    },
+    FormulaEntry {
+        name: "Fall to Floor",
+        category: "formula_cat_physics",
+        tags: &["floor", "fall", "ground", "land"],
+        code: "vy = -0.02 * (y > -1)",
+        description: "formula_floor_desc",
+    },
+    FormulaEntry {
+        name: "Damped Bounce",
+        category: "formula_cat_physics",
+        tags: &["spring", "bounce", "damping", "decay"],
+        code: "vy = cos(t * 6) * exp(-t * 0.8) * 0.03",
+        description: "formula_damped_desc",
+    },
+    FormulaEntry {
+        name: "Gusting Wind",
+        category: "formula_cat_physics",
+        tags: &["wind", "gust", "blow"],
+        code: "vx = 0.01 * (1 + sin(t * 0.5 + z))",
+        description: "formula_wind_desc",
+    },
+    FormulaEntry {
+        name: "Attractor",
+        category: "formula_cat_physics",
+        tags: &["attract", "target", "magnet", "gather"],
+        code: "vx = (1 - x) * 0.03; vy = (1 - y) * 0.03; vz = -z * 0.03",
+        description: "formula_attractor_desc",
+    },
+    FormulaEntry {
+        name: "Repel",
+        category: "formula_cat_physics",
+        tags: &["repel", "push", "magnet", "scatter"],
+        code: "vx = x / (hypot(hypot(x, y), z) + 0.1) * 0.01; vy = y / (hypot(hypot(x, y), z) + 0.1) * 0.01; vz = z / (hypot(hypot(x, y), z) + 0.1) * 0.01",
+        description: "formula_repel_desc",
+    },
+    FormulaEntry {
+        name: "Rainbow Cycle",
+        category: "formula_cat_color",
+        tags: &["rainbow", "hue", "cycle", "colour", "color"],
+        code: "cr = 0.5 + 0.5 * sin(t * 2); cg = 0.5 + 0.5 * sin(t * 2 + 2.094); cb = 0.5 + 0.5 * sin(t * 2 + 4.189)",
+        description: "formula_rainbow_desc",
+    },
+    FormulaEntry {
+        name: "Height Gradient",
+        category: "formula_cat_color",
+        tags: &["gradient", "height", "colour", "color"],
+        code: "cr = clamp(y, 0, 1); cg = 0.3; cb = clamp(-y, 0, 1)",
+        description: "formula_height_gradient_desc",
+    },
+    FormulaEntry {
+        name: "Colour Pulse",
+        category: "formula_cat_color",
+        tags: &["pulse", "glow", "colour", "color"],
+        code: "cr = cr * (0.75 + 0.25 * sin(t * 6))",
+        description: "formula_colour_pulse_desc",
+    },
+    FormulaEntry {
+        name: "Flicker",
+        category: "formula_cat_color",
+        tags: &["flicker", "sparkle", "alpha", "random"],
+        code: "alpha = 0.5 + 0.5 * random()",
+        description: "formula_flicker_desc",
+    },
+    FormulaEntry {
+        name: "Fade Out",
+        category: "formula_cat_color",
+        tags: &["fade", "alpha", "vanish"],
+        code: "alpha = clamp(1 - t * 0.5, 0, 1)",
+        description: "formula_fade_out_desc",
+    },
+    FormulaEntry {
+        name: "Distance Fade",
+        category: "formula_cat_color",
+        tags: &["fade", "alpha", "distance", "vignette"],
+        code: "alpha = clamp(1 - hypot(hypot(x, y), z) * 0.3, 0, 1)",
+        description: "formula_distance_fade_desc",
+    },
+    FormulaEntry {
+        name: "Size Pulse",
+        category: "formula_cat_lifecycle",
+        tags: &["size", "pulse", "twinkle"],
+        code: "mpsize = 0.05 * (1 + 0.5 * sin(t * 4 + id))",
+        description: "formula_size_pulse_desc",
+    },
+    FormulaEntry {
+        name: "Kill Below Floor",
+        category: "formula_cat_lifecycle",
+        tags: &["destroy", "floor", "cull", "remove"],
+        code: "destroy = y < -2",
+        description: "formula_kill_floor_desc",
+    },
+    FormulaEntry {
+        name: "Random Cull",
+        category: "formula_cat_lifecycle",
+        tags: &["destroy", "random", "thin", "remove"],
+        code: "destroy = random() < 0.01",
+        description: "formula_random_cull_desc",
+    },
+    FormulaEntry {
+        name: "Timed Dissolve",
+        category: "formula_cat_lifecycle",
+        tags: &["destroy", "dissolve", "disintegrate", "time"],
+        code: "destroy = random() < t * 0.002",
+        description: "formula_dissolve_desc",
+    },
+];
+
+impl FormulaEntry {
+    /// Case-insensitive match of every whitespace-separated term against the
+    /// name, tags, code and the translated category/description.
+    pub fn matches(&self, query: &str, i18n: &I18nManager) -> bool {
+        let haystack = format!(
+            "{} {} {} {} {}",
+            self.name,
+            self.tags.join(" "),
+            self.code,
+            i18n.tr(self.category),
+            i18n.tr(self.description)
+        )
+        .to_lowercase();
+        query
+            .to_lowercase()
+            .split_whitespace()
+            .all(|term| haystack.contains(term))
+    }
+}
+
+/// Lays out a translated description, rendering `backticked` spans in the
+/// monospace font.
+pub fn description_job(text: &str, ui: &egui::Ui) -> egui::text::LayoutJob {
+    let mut job = egui::text::LayoutJob::default();
+    let color = ui.visuals().weak_text_color();
+    let body = egui::TextStyle::Small.resolve(ui.style());
+    let code = egui::FontId::monospace(body.size);
+    for (i, part) in text.split('`').enumerate() {
+        let font_id = if i % 2 == 1 {
+            code.clone()
+        } else {
+            body.clone()
+        };
+        job.append(
+            part,
+            0.0,
+            egui::TextFormat {
+                font_id,
+                color,
+                ..Default::default()
+            },
+        );
+    }
+    job
+}
+
+/// Searchable snippet list; returns the code of the entry clicked this frame.
+pub fn ui(ui: &mut egui::Ui, i18n: &I18nManager, query: &mut String) -> Option<&'static str> {
+    let mut picked = None;
+    ui.add(
+        egui::TextEdit::singleline(query)
+            .hint_text(i18n.tr("formula_search"))
+            .desired_width(f32::INFINITY),
+    );
+    egui::ScrollArea::vertical()
+        .id_source("formula_library")
+        .max_height(220.0)
+        .show(ui, |ui| {
+            let mut last_category = "";
+            for entry in FORMULAS.iter().filter(|e| e.matches(query, i18n)) {
+                if entry.category != last_category {
+                    last_category = entry.category;
+                    ui.label(egui::RichText::new(i18n.tr(entry.category)).strong());
+                }
+                if ui
+                    .button(entry.name)
+                    .on_hover_text(egui::RichText::new(entry.code).monospace())
+                    .clicked()
+                {
+                    picked = Some(entry.code);
+                }
+                ui.label(description_job(i18n.tr(entry.description), ui));
+            }
+        });
+    picked
+}
@@ -1,7 +1,9 @@
 pub mod app;
 mod creator_ui;
 mod edit;
+mod formula_library;
 mod multimedia_ui;
+mod node_editor;
 mod particleex_ui;
 mod preview;
 mod welcome;
@@ -1,8 +1,12 @@
-use crate::player::{NblHeader, Particle};
+use crate::i18n::I18nManager;
+use crate::player::{NblHeader, Particle, NBL_VERSION};
 use crate::ui::app::{
-    build_texture_entries, MultimediaThreadProgress, MultimediaThreadStatus, NebulaToolsApp,
+    build_texture_entries, AttractMode, BirthDelayMode, FontVariant, MultimediaState,
+    MultimediaThreadProgress, MultimediaThreadStatus, NebulaToolsApp, PalettePair, TextColorMode,
+    TextDirection, TextRenderMode, TilingMode, TimingGuard,
 };
-use ab_glyph::{Font, PxScale, ScaleFont};
+use crate::ui::node_editor;
+use ab_glyph::{Font, PxScale, ScaleFont, VariableFont};
 use eframe::egui;
 use image::{DynamicImage, GenericImageView};
 use std::io::Read;
@@ -10,6 +14,413 @@ use std::process::{Command, Stdio};
 use std::sync::{Arc, Mutex};
 
 const HIGH_PARTICLE_WARNING_THRESHOLD: usize = 100_000;
+/// Swatches shown per palette under the export quantisation option.
+const PALETTE_SWATCHES: usize = 64;
+
+/// Rasterise `text` (one line per `\n`) in white onto a padded transparent canvas.
+/// Also returns the cell `[x0, y0, x1, y1]` of every non-whitespace character,
+/// in reading order, for per-character colouring. Vertical text uses the
+/// font's vertical advances where it defines them, else the line height.
+/// `letter_spacing` and `extra_line_gap` are extra pixels at `font_size`
+/// after each glyph and between lines; negative values tighten the layout.
+/// Characters `font` has no glyph for are drawn with the first of
+/// `fallbacks` that has one; the last value counts them.
+fn render_text_image<F: Font>(
+    font: &F,
+    fallbacks: &[ab_glyph::FontRef<'static>],
+    text: &str,
+    font_size: f32,
+    direction: TextDirection,
+    letter_spacing: f32,
+    extra_line_gap: f32,
+) -> (image::RgbaImage, Vec<[u32; 4]>, usize) {
+    let px_scale = PxScale::from(font_size);
+    let scale_font = font.as_scaled(px_scale);
+    // Index into `fallbacks` of the font drawing `ch`, `None` for `font`.
+    // A missing glyph maps to `.notdef` (id 0) or has no advance.
+    let fallback_for = |ch: char| -> Option<usize> {
+        let id = scale_font.glyph_id(ch);
+        if ch.is_whitespace() || (id.0 != 0 && scale_font.h_advance(id) != 0.0) {
+            return None;
+        }
+        fallbacks.iter().position(|f| f.glyph_id(ch).0 != 0)
+    };
+    let h_advance = |ch: char, source: Option<usize>| match source {
+        Some(i) => {
+            let f = fallbacks[i].as_scaled(px_scale);
+            f.h_advance(f.glyph_id(ch))
+        }
+        None => scale_font.h_advance(scale_font.glyph_id(ch)),
+    };
+    let lines: Vec<&str> = text.lines().collect();
+
+    let ascent = scale_font.ascent().ceil() as u32;
+    let descent = scale_font.descent().floor() as i32;
+    let line_height = (ascent as i32 - descent).abs() as u32;
+    let line_gap = ((line_height as f32 * 0.2).ceil() + extra_line_gap).max(0.0) as u32;
+    let vertical = direction == TextDirection::TopToBottom;
+
+    // Character, pen offset, advance and `fallback_for` font of a glyph.
+    type LaidOutGlyph = (char, f32, f32, Option<usize>);
+    // Pen offset along the line of every glyph, in visual order, plus the
+    // line's total length.
+    let layout_line = |line: &str| -> (Vec<LaidOutGlyph>, f32) {
+        let mut chars: Vec<char> = line.chars().collect();
+        if direction == TextDirection::RightToLeft {
+            chars.reverse();
+        }
+        let mut pen = 0.0;
+        let mut prev_glyph: Option<ab_glyph::GlyphId> = None;
+        let mut glyphs = Vec::with_capacity(chars.len());
+        for ch in chars {
+            let glyph_id = scale_font.glyph_id(ch);
+            let source = fallback_for(ch);
+            let advance = if vertical {
+                let v = match source {
+                    Some(_) => 0.0,
+                    None => scale_font.v_advance(glyph_id),
+                };
+                if v > 0.0 {
+                    v
+                } else {
+                    line_height as f32
+                }
+            } else if source.is_some() {
+                prev_glyph = None;
+                h_advance(ch, source)
+            } else {
+                if let Some(prev) = prev_glyph {
+                    pen += scale_font.kern(prev, glyph_id);
+                }
+                scale_font.h_advance(glyph_id)
+            };
+            glyphs.push((ch, pen, advance, source));
+            pen += advance + letter_spacing;
+            if source.is_none() {
+                prev_glyph = Some(glyph_id);
+            }
+        }
+        (glyphs, pen.max(0.0))
+    };
+    let laid_out: Vec<_> = lines.iter().map(|line| layout_line(line)).collect();
+    let max_len = laid_out
+        .iter()
+        .map(|(_, len)| len.ceil() as u32)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let stacked =
+        (lines.len() as u32 * line_height) + ((lines.len() as u32).saturating_sub(1) * line_gap);
+
+    let pad = (font_size as u32).max(1);
+    let (canvas_w, canvas_h) = if vertical {
+        (stacked + pad * 4, max_len + pad * 4)
+    } else {
+        (max_len + pad * 4, stacked + pad * 4)
+    };
+    let mut text_img = image::RgbaImage::new(canvas_w, canvas_h);
+    let mut cells = Vec::new();
+    let mut fallback_count = 0;
+
+    let origin = (pad * 2) as f32;
+    for (i, (glyphs, len)) in laid_out.iter().enumerate() {
+        let across = (i as u32 * (line_height + line_gap)) as f32;
+        let mut line_cells = Vec::new();
+        for &(ch, pen, advance, source) in glyphs {
+            let (x, y, cell) = if vertical {
+                // First line in the rightmost column.
+                let col_x = canvas_w as f32 - origin - across - line_height as f32;
+                let glyph_w = h_advance(ch, source);
+                let x = col_x + (line_height as f32 - glyph_w) / 2.0;
+                let y = origin + pen;
+                let cell = [
+                    col_x as u32,
+                    y as u32,
+                    (col_x + (line_height + line_gap) as f32) as u32,
+                    (y + advance).ceil() as u32,
+                ];
+                (x, y, cell)
+            } else {
+                let align = if direction == TextDirection::RightToLeft {
+                    max_len as f32 - len
+                } else {
+                    0.0
+                };
+                let x = origin + align + pen;
+                let y = origin + across;
+                let cell = [
+                    x as u32,
+                    y as u32,
+                    (x + advance).ceil() as u32,
+                    y as u32 + line_height + line_gap,
+                ];
+                (x, y, cell)
+            };
+            if ch.is_whitespace() {
+                continue;
+            }
+            line_cells.push(cell);
+            let white = image::Rgba([255, 255, 255, 255]);
+            let (x, y, s) = (x as i32, y as i32, ch.to_string());
+            match source {
+                Some(i) => {
+                    fallback_count += 1;
+                    imageproc::drawing::draw_text_mut(
+                        &mut text_img,
+                        white,
+                        x,
+                        y,
+                        px_scale,
+                        &fallbacks[i],
+                        &s,
+                    );
+                }
+                None => imageproc::drawing::draw_text_mut(
+                    &mut text_img,
+                    white,
+                    x,
+                    y,
+                    px_scale,
+                    font,
+                    &s,
+                ),
+            }
+        }
+        if direction == TextDirection::RightToLeft {
+            line_cells.reverse();
+        }
+        cells.extend(line_cells);
+    }
+    (text_img, cells, fallback_count)
+}
+
+/// egui's built-in proportional and emoji fonts, used by `render_text_image`
+/// for characters the selected font lacks. They are already linked into the
+/// binary for the UI, so no separate fallback font has to ship.
+fn fallback_fonts() -> Vec<ab_glyph::FontRef<'static>> {
+    let defaults = egui::FontDefinitions::default();
+    ["Ubuntu-Light", "NotoEmoji-Regular", "emoji-icon-font"]
+        .iter()
+        .filter_map(|name| match &defaults.font_data.get(*name)?.font {
+            std::borrow::Cow::Borrowed(bytes) => ab_glyph::FontRef::try_from_slice(bytes).ok(),
+            std::borrow::Cow::Owned(_) => None,
+        })
+        .collect()
+}
+
+/// Replace the glyph alpha with its outline for the stroke modes: the alpha
+/// mask is dilated by the stroke width and XORed with the original coverage.
+fn apply_text_render_mode(img: &mut image::RgbaImage, mode: TextRenderMode) {
+    let Some(width) = mode.stroke_width() else {
+        return;
+    };
+    let fill = image::GrayImage::from_fn(img.width(), img.height(), |x, y| {
+        image::Luma([img.get_pixel(x, y)[3]])
+    });
+    let k = width.round().clamp(1.0, 255.0) as u8;
+    let dilated = imageproc::morphology::dilate(&fill, imageproc::distance_transform::Norm::L2, k);
+    for (x, y, pixel) in img.enumerate_pixels_mut() {
+        let inside = fill.get_pixel(x, y)[0] > 0;
+        let grown = dilated.get_pixel(x, y)[0];
+        pixel[3] = match mode {
+            TextRenderMode::Stroke(_) if inside => 0,
+            _ => grown,
+        };
+    }
+}
+
+/// Keep only boundary pixels: the glyph coverage (alpha at least half) XOR
+/// its erosion by one pixel. Unlike the stroke modes this needs nothing from
+/// the font and works on whatever `render_text_image` drew.
+fn apply_outline_only(img: &mut image::RgbaImage) {
+    let inside = image::GrayImage::from_fn(img.width(), img.height(), |x, y| {
+        image::Luma([u8::from(img.get_pixel(x, y)[3] >= 128) * 255])
+    });
+    let eroded =
+        imageproc::morphology::erode(&inside, imageproc::distance_transform::Norm::LInf, 1);
+    for (x, y, pixel) in img.enumerate_pixels_mut() {
+        let border = inside.get_pixel(x, y)[0] != eroded.get_pixel(x, y)[0];
+        pixel[3] = if border { 255 } else { 0 };
+    }
+}
+
+/// Bounding box `[min_x, min_y, max_x, max_y]` of all glyph cells, the
+/// extent the text gradients run across.
+fn text_cells_bbox(cells: &[[u32; 4]]) -> [u32; 4] {
+    cells.iter().fold([u32::MAX, u32::MAX, 0, 0], |b, c| {
+        [
+            b[0].min(c[0]),
+            b[1].min(c[1]),
+            b[2].max(c[2]),
+            b[3].max(c[3]),
+        ]
+    })
+}
+
+/// Colour of a text-mode particle at canvas pixel (`x`, `y`). `bbox` is
+/// `text_cells_bbox(cells)`, computed once by the caller.
+fn text_pixel_color(
+    state: &MultimediaState,
+    cells: &[[u32; 4]],
+    bbox: [u32; 4],
+    x: u32,
+    y: u32,
+    alpha: u8,
+) -> [u8; 4] {
+    let lerp = |t: f32| {
+        let t = t.clamp(0.0, 1.0);
+        let c = |i: usize| {
+            (state.color_start[i] as f32
+                + (state.color_end[i] as f32 - state.color_start[i] as f32) * t)
+                .round() as u8
+        };
+        [c(0), c(1), c(2), alpha]
+    };
+    let ratio = |v: u32, lo: u32, hi: u32| {
+        if hi > lo {
+            v.saturating_sub(lo) as f32 / (hi - lo) as f32
+        } else {
+            0.0
+        }
+    };
+
+    match state.text_color_mode {
+        TextColorMode::Uniform => {
+            let [r, g, b] = state.color_start;
+            [r, g, b, alpha]
+        }
+        TextColorMode::GradientX => lerp(ratio(x, bbox[0], bbox[2])),
+        TextColorMode::GradientY => lerp(ratio(y, bbox[1], bbox[3])),
+        TextColorMode::PerChar => {
+            // Glyph ink can overhang its advance box; fall back to the nearest
+            // cell on the same line.
+            let index = cells
+                .iter()
+                .position(|c| x >= c[0] && x < c[2] && y >= c[1] && y < c[3])
+                .or_else(|| {
+                    cells
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, c)| y >= c[1] && y < c[3])
+                        .min_by_key(|(_, c)| x.abs_diff((c[0] + c[2]) / 2))
+                        .map(|(i, _)| i)
+                })
+                .unwrap_or(0);
+            let [r, g, b] = if state.text_palette.is_empty() {
+                state.color_start
+            } else {
+                state.text_palette[index % state.text_palette.len()]
+            };
+            [r, g, b, alpha]
+        }
+    }
+}
+
+/// Read the font bytes for a `font_name`, which is either a file path or
+/// `system://<family>` for an installed family.
+fn load_font_data(font_name: &str) -> Option<Vec<u8>> {
+    if let Some(family) = font_name.strip_prefix("system://") {
+        use font_kit::family_name::FamilyName;
+        use font_kit::properties::Properties;
+        let handle = font_kit::source::SystemSource::new()
+            .select_best_match(&[FamilyName::Title(family.to_string())], &Properties::new())
+            .ok()?;
+        match handle {
+            font_kit::handle::Handle::Path { path, .. } => std::fs::read(&path).ok(),
+            font_kit::handle::Handle::Memory { bytes, .. } => Some(bytes.to_vec()),
+        }
+    } else if !font_name.is_empty() {
+        std::fs::read(font_name).ok()
+    } else {
+        None
+    }
+}
+
+/// Subfamily name and per-axis coordinates of a variable font instance.
+type NamedInstance = (String, Vec<([u8; 4], f32)>);
+
+/// Named instances from a variable font's `fvar` table.
+fn named_instances(data: &[u8]) -> Vec<NamedInstance> {
+    let Ok(face) = ttf_parser::Face::parse(data, 0) else {
+        return Vec::new();
+    };
+    let Some(fvar) = face.raw_face().table(ttf_parser::Tag::from_bytes(b"fvar")) else {
+        return Vec::new();
+    };
+    let u16_at = |o: usize| fvar.get(o..o + 2).map(|b| u16::from_be_bytes([b[0], b[1]]));
+    let header = [4, 8, 10, 12, 14].map(u16_at);
+    let [Some(axes_offset), Some(axis_count), Some(axis_size), Some(count), Some(size)] = header
+    else {
+        return Vec::new();
+    };
+    let tags: Vec<[u8; 4]> = face
+        .variation_axes()
+        .into_iter()
+        .map(|a| a.tag.to_bytes())
+        .collect();
+    let name_of = |id: u16| {
+        face.names()
+            .into_iter()
+            .find(|n| n.name_id == id && n.is_unicode())
+            .and_then(|n| n.to_string())
+    };
+
+    // Instance records follow the axis records: subfamilyNameID, flags,
+    // then one 16.16 fixed coordinate per axis.
+    let first = axes_offset as usize + axis_count as usize * axis_size as usize;
+    (0..count as usize)
+        .filter_map(|i| {
+            let record = fvar.get(first + i * size as usize..)?;
+            let name = name_of(u16::from_be_bytes([*record.first()?, *record.get(1)?]))?;
+            let coordinates = tags
+                .iter()
+                .enumerate()
+                .map(|(k, tag)| {
+                    let b = record.get(4 + k * 4..8 + k * 4)?;
+                    let fixed = i32::from_be_bytes([b[0], b[1], b[2], b[3]]);
+                    Some((*tag, fixed as f32 / 65536.0))
+                })
+                .collect::<Option<Vec<_>>>()?;
+            Some((name, coordinates))
+        })
+        .collect()
+}
+
+/// List the selectable variants of a font: every face of a system family
+/// (via font_kit), and for variable fonts the named instances in its `fvar`
+/// table.
+fn list_font_variants(font_name: &str) -> Vec<FontVariant> {
+    let mut variants = Vec::new();
+    if let Some(family) = font_name.strip_prefix("system://") {
+        if let Ok(handles) = font_kit::source::SystemSource::new().select_family_by_name(family) {
+            for handle in handles.fonts() {
+                let font_kit::handle::Handle::Path { path, font_index } = handle else {
+                    continue;
+                };
+                if let Ok(font) = handle.load() {
+                    variants.push(FontVariant {
+                        name: font.full_name(),
+                        font_index: *font_index,
+                        coordinates: Vec::new(),
+                        path: Some(path.clone()),
+                    });
+                }
+            }
+        }
+    }
+
+    if let Some(fd) = load_font_data(font_name) {
+        for (name, coordinates) in named_instances(&fd) {
+            variants.push(FontVariant {
+                name,
+                font_index: 0,
+                coordinates,
+                path: None,
+            });
+        }
+    }
+    variants
+}
 
 fn apply_euler_rotation(mut x: f32, mut y: f32, mut z: f32, rot: [f32; 3]) -> (f32, f32, f32) {
     let (sx, cx) = rot[0].to_radians().sin_cos();
@@ -62,6 +473,10 @@ struct VideoParticleGenerator {
     point_size: f32,
     target_fps: u16,
     frame_count: usize,
+    /// Per-pixel colours fed to `stmts` instead of the video's, when set.
+    fixed_colors: Option<Vec<[u8; 4]>>,
+    /// Colour particles with the pixel's luminance on all channels.
+    grayscale: bool,
 }
 
 impl VideoParticleGenerator {
@@ -134,14 +549,38 @@ impl VideoParticleGenerator {
             point_size,
             target_fps,
             frame_count: start_frame as usize,
+            fixed_colors: None,
+            grayscale: false,
         }
     }
 
+    /// Colour every pixel once from `stmts` at frame 0 and use that instead of
+    /// the video's colours; the video then only decides placement.
+    fn override_colors_from_expression(&mut self) {
+        let Some(stmts) = &self.stmts else {
+            return;
+        };
+        let mut particles: Vec<Particle> = self
+            .screen_pixels
+            .iter()
+            .map(|sp| Particle {
+                id: sp.id,
+                pos: [sp.px, sp.py, sp.pz],
+                color: [255; 4],
+                size: self.point_size,
+                tex_id: 0,
+                seq_index: 0,
+            })
+            .collect();
+        apply_color_expression(&mut particles, stmts);
+        self.fixed_colors = Some(particles.iter().map(|p| p.color).collect());
+    }
+
     fn next_frame(&mut self, buffer: &[u8]) -> Vec<Particle> {
         let mut frame_particles = Vec::with_capacity(self.screen_pixels.len());
         let t = self.frame_count as f64 / self.target_fps as f64;
 
-        for sp in &mut self.screen_pixels {
+        for (i, sp) in self.screen_pixels.iter_mut().enumerate() {
             let r = buffer[sp.idx];
             let g = buffer[sp.idx + 1];
             let b = buffer[sp.idx + 2];
@@ -149,7 +588,16 @@ impl VideoParticleGenerator {
             if luma < self.brightness_threshold {
                 continue;
             }
+            let [r, g, b, a] = match &self.fixed_colors {
+                Some(colors) => colors[i],
+                None if self.grayscale => {
+                    let l = (luma * 255.0).round() as u8;
+                    [l, l, l, 255]
+                }
+                None => [r, g, b, 255],
+            };
 
+            self.pex_ctx.set_frame(self.frame_count as i64);
             self.pex_ctx.set("t", crate::particleex::Value::Num(t));
             self.pex_ctx
                 .set("x", crate::particleex::Value::Num((sp.px + sp.ox) as f64));
@@ -164,7 +612,7 @@ impl VideoParticleGenerator {
             self.pex_ctx
                 .set("cb", crate::particleex::Value::Num(b as f64 / 255.0));
             self.pex_ctx
-                .set("alpha", crate::particleex::Value::Num(1.0));
+                .set("alpha", crate::particleex::Value::Num(a as f64 / 255.0));
             self.pex_ctx.set(
                 "mpsize",
                 crate::particleex::Value::Num(self.point_size as f64),
@@ -261,6 +709,46 @@ fn probe_video_info(media_path: &str) -> anyhow::Result<VideoProbeInfo> {
     })
 }
 
+/// Size of a `width`×`height` video after resizing by `scale`, as passed to
+/// ffmpeg's `scale` filter.
+fn scaled_video_size(width: u32, height: u32, scale: f32) -> [u32; 2] {
+    let scale = scale.clamp(0.1, 1.0);
+    [
+        ((width as f32 * scale).round() as u32).max(1),
+        ((height as f32 * scale).round() as u32).max(1),
+    ]
+}
+
+/// ffmpeg `-vf` chain: `scale`, then the optional greyscale and invert filters.
+fn video_filter_chain(scale: String, grayscale: bool, invert: bool) -> String {
+    let mut chain = scale;
+    if grayscale {
+        chain.push_str(",hue=s=0");
+    }
+    if invert {
+        chain.push_str(",negate");
+    }
+    chain
+}
+
+/// Start and end of the trimmed video in seconds; `end <= 0` means the end
+/// of the video.
+fn resolve_video_trim(start: f32, end: f32, duration: f32) -> anyhow::Result<(f32, f32)> {
+    let end = if end <= 0.0 { duration } else { end };
+    if start < 0.0 || end > duration {
+        anyhow::bail!(
+            "trim {:.2}s–{:.2}s is outside the video (0–{:.2}s)",
+            start,
+            end,
+            duration
+        );
+    }
+    if end <= start {
+        anyhow::bail!("trim end {:.2}s must be after start {:.2}s", end, start);
+    }
+    Ok((start, end))
+}
+
 fn split_frame_ranges(total_frames: u32, chunk_count: usize) -> Vec<(u32, u32)> {
     if total_frames == 0 || chunk_count == 0 {
         return Vec::new();
@@ -282,6 +770,618 @@ fn split_frame_ranges(total_frames: u32, chunk_count: usize) -> Vec<(u32, u32)>
     ranges
 }
 
+/// Uniformly distributed point inside a sphere of `radius` at the origin.
+fn random_in_sphere(rng: &mut impl rand::Rng, radius: f32) -> [f32; 3] {
+    loop {
+        let p: [f32; 3] = [
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+        ];
+        if p[0] * p[0] + p[1] * p[1] + p[2] * p[2] <= 1.0 {
+            return p.map(|v| v * radius);
+        }
+    }
+}
+
+/// Vector field image stretched over the source's XY extent in world units.
+struct FlowField {
+    img: image::RgbImage,
+    half_extent: [f32; 2],
+    strength: f32,
+}
+
+impl FlowField {
+    /// Velocity at the XY projection of `pos`; positions outside the extent
+    /// are clamped to the nearest edge pixel.
+    fn sample(&self, pos: [f32; 3]) -> [f32; 2] {
+        let (w, h) = self.img.dimensions();
+        let u = (pos[0] / self.half_extent[0].max(f32::EPSILON) + 1.0) * 0.5;
+        let v = (1.0 - pos[1] / self.half_extent[1].max(f32::EPSILON)) * 0.5;
+        let x = ((u * w as f32) as u32).min(w - 1);
+        let y = ((v.max(0.0) * h as f32) as u32).min(h - 1);
+        let px = self.img.get_pixel(x, y);
+        let axis = |c: u8| (c as f32 - 128.0) / 127.0 * self.strength;
+        [axis(px[0]), axis(px[1])]
+    }
+}
+
+/// Placement gate for image mode, resampled to the source size: the mask's
+/// alpha, or its luminance when the image has no alpha channel.
+fn load_placement_mask(mask: image::DynamicImage, width: u32, height: u32) -> image::GrayImage {
+    let gate = if mask.color().has_alpha() {
+        let rgba = mask.to_rgba8();
+        image::GrayImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+            image::Luma([rgba.get_pixel(x, y)[3]])
+        })
+    } else {
+        mask.to_luma8()
+    };
+    image::imageops::resize(&gate, width, height, image::imageops::FilterType::Nearest)
+}
+
+/// Shrink `img` with Lanczos3 so neither side exceeds `max_dimension`,
+/// returning the original size when it did. 0 leaves the image alone.
+fn limit_source_size(img: DynamicImage, max_dimension: u32) -> (DynamicImage, Option<[u32; 2]>) {
+    let (width, height) = img.dimensions();
+    if max_dimension == 0 || width.max(height) <= max_dimension {
+        return (img, None);
+    }
+    let scale = max_dimension as f64 / width.max(height) as f64;
+    let w = ((width as f64 * scale).round() as u32).max(1);
+    let h = ((height as f64 * scale).round() as u32).max(1);
+    let resized = image::imageops::resize(&img, w, h, image::imageops::FilterType::Lanczos3);
+    (DynamicImage::ImageRgba8(resized), Some([width, height]))
+}
+
+/// Per-cell keep probabilities that even out particle density over a
+/// 32×32 pixel grid.
+struct DensityEqualiser {
+    cols: u32,
+    keep: Vec<f32>,
+}
+
+impl DensityEqualiser {
+    const CELL: u32 = 32;
+
+    /// `passes` says whether a source pixel would spawn particles. The
+    /// target density is read off the CDF of non-empty cell counts at
+    /// `SPARSE_QUANTILE`, so a few near-empty cells don't thin out everything.
+    fn new(width: u32, height: u32, strength: f32, passes: impl Fn(u32, u32) -> bool) -> Self {
+        const SPARSE_QUANTILE: f32 = 0.1;
+        let cols = width.div_ceil(Self::CELL);
+        let rows = height.div_ceil(Self::CELL);
+        let mut counts = vec![0u32; (cols * rows) as usize];
+        for y in 0..height {
+            for x in 0..width {
+                if passes(x, y) {
+                    counts[((y / Self::CELL) * cols + x / Self::CELL) as usize] += 1;
+                }
+            }
+        }
+        let mut sorted: Vec<u32> = counts.iter().copied().filter(|&c| c > 0).collect();
+        sorted.sort_unstable();
+        let target = sorted
+            .get(((sorted.len() as f32 * SPARSE_QUANTILE) as usize).min(sorted.len().max(1) - 1))
+            .copied()
+            .unwrap_or(0) as f32;
+        let strength = strength.clamp(0.0, 1.0);
+        let keep = counts
+            .iter()
+            .map(|&c| {
+                let full = (target / c.max(1) as f32).min(1.0);
+                1.0 + (full - 1.0) * strength
+            })
+            .collect();
+        Self { cols, keep }
+    }
+
+    fn keep_probability(&self, x: u32, y: u32) -> f32 {
+        self.keep[((y / Self::CELL) * self.cols + x / Self::CELL) as usize]
+    }
+}
+
+/// Frame each particle appears on, from 0 up to `delay * total_frames`,
+/// ordered by `mode`. All zero for `BirthDelayMode::None`.
+fn birth_frames(
+    particles: &[Particle],
+    mode: BirthDelayMode,
+    delay: f32,
+    total_frames: usize,
+    rng: &mut impl rand::Rng,
+) -> Vec<usize> {
+    let key = |p: &Particle| match mode {
+        BirthDelayMode::ByXPosition => p.pos[0],
+        BirthDelayMode::ByDistance => crate::math::dot(p.pos, p.pos).sqrt(),
+        BirthDelayMode::None | BirthDelayMode::Random => 0.0,
+    };
+    let (lo, hi) = particles
+        .iter()
+        .map(key)
+        .fold((f32::MAX, f32::MIN), |(lo, hi), k| (lo.min(k), hi.max(k)));
+    let span = delay.clamp(0.0, 1.0) * total_frames as f32;
+    particles
+        .iter()
+        .map(|p| {
+            let norm = match mode {
+                BirthDelayMode::None => 0.0,
+                BirthDelayMode::Random => rng.gen::<f32>(),
+                _ if hi > lo => (key(p) - lo) / (hi - lo),
+                _ => 0.0,
+            };
+            (norm * span).round() as usize
+        })
+        .collect()
+}
+
+/// Recolour placed particles by running `stmts` with `x, y, z, id` and
+/// `t = 0`; `cr, cg, cb, alpha` start at white and are read back afterwards.
+fn apply_color_expression(particles: &mut [Particle], stmts: &[crate::particleex::Stmt]) {
+    use crate::particleex::Value;
+    let mut ctx = crate::particleex::ExprContext::new();
+    let channel = |v: Value| (v.as_num().clamp(0.0, 1.0) * 255.0) as u8;
+    for p in particles.iter_mut() {
+        ctx.set("x", Value::Num(p.pos[0] as f64));
+        ctx.set("y", Value::Num(p.pos[1] as f64));
+        ctx.set("z", Value::Num(p.pos[2] as f64));
+        ctx.set("id", Value::Num(p.id as f64));
+        ctx.set("t", Value::Num(0.0));
+        for name in ["cr", "cg", "cb", "alpha"] {
+            ctx.set(name, Value::Num(1.0));
+        }
+        crate::particleex::exec_stmts(stmts, &mut ctx);
+        p.color = [
+            channel(ctx.get("cr")),
+            channel(ctx.get("cg")),
+            channel(ctx.get("cb")),
+            channel(ctx.get("alpha")),
+        ];
+    }
+}
+
+type ColorCounts = std::collections::HashMap<[u8; 3], usize>;
+type ColorRemap = std::collections::HashMap<[u8; 3], [u8; 3]>;
+
+fn count_colors<'a>(particles: impl IntoIterator<Item = &'a Particle>) -> ColorCounts {
+    let mut counts = ColorCounts::new();
+    for p in particles {
+        *counts
+            .entry([p.color[0], p.color[1], p.color[2]])
+            .or_insert(0) += 1;
+    }
+    counts
+}
+
+/// Reduce the particles' colours to at most `k` by k-means over the unique
+/// RGB values (weighted by how often each occurs). Alpha is left untouched.
+fn limit_palette(particles: &mut [Particle], k: usize) {
+    let remap = palette_remap(&count_colors(particles.iter()), k);
+    for p in particles.iter_mut() {
+        if let Some(m) = remap.get(&[p.color[0], p.color[1], p.color[2]]) {
+            p.color[..3].copy_from_slice(m);
+        }
+    }
+}
+
+/// `limit_palette` over every frame at once, so all frames share one palette.
+/// Returns the most common colours before and the palette after, for display.
+fn quantize_frames(frames: &mut [Vec<Particle>], k: usize) -> PalettePair {
+    let counts = count_colors(frames.iter().flatten());
+    let remap = palette_remap(&counts, k);
+    for p in frames.iter_mut().flatten() {
+        if let Some(m) = remap.get(&[p.color[0], p.color[1], p.color[2]]) {
+            p.color[..3].copy_from_slice(m);
+        }
+    }
+    let mut before: Vec<([u8; 3], usize)> = counts.into_iter().collect();
+    before.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    let after = count_colors(frames.iter().flatten());
+    let mut after: Vec<([u8; 3], usize)> = after.into_iter().collect();
+    after.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    (
+        before.into_iter().map(|(c, _)| c).collect(),
+        after.into_iter().map(|(c, _)| c).collect(),
+    )
+}
+
+/// Nearest k-means centroid for every colour in `counts`; empty when there
+/// are already at most `k` colours.
+fn palette_remap(counts: &ColorCounts, k: usize) -> ColorRemap {
+    let k = k.max(1);
+    if counts.len() <= k {
+        return ColorRemap::new();
+    }
+    let colors: Vec<([f32; 3], f32)> = counts
+        .iter()
+        .map(|(c, &n)| ([c[0] as f32, c[1] as f32, c[2] as f32], n as f32))
+        .collect();
+    let dist2 = |a: [f32; 3], b: [f32; 3]| {
+        (a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)
+    };
+    let nearest = |centroids: &[[f32; 3]], c: [f32; 3]| {
+        (0..centroids.len())
+            .min_by(|&a, &b| dist2(centroids[a], c).total_cmp(&dist2(centroids[b], c)))
+            .unwrap_or(0)
+    };
+
+    // Farthest-point seeding starting from the most common colour keeps the
+    // result deterministic and spreads the initial centroids out.
+    let mut centroids = vec![colors
+        .iter()
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|c| c.0)
+        .unwrap_or_default()];
+    // Distance of every colour to its nearest centroid so far, updated as
+    // centroids are added.
+    let mut closest: Vec<f32> = colors
+        .iter()
+        .map(|(c, _)| dist2(centroids[0], *c))
+        .collect();
+    while centroids.len() < k {
+        let far = closest
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(i, _)| colors[i].0)
+            .unwrap_or_default();
+        for (d, (c, _)) in closest.iter_mut().zip(&colors) {
+            *d = d.min(dist2(far, *c));
+        }
+        centroids.push(far);
+    }
+
+    for _ in 0..16 {
+        let mut sums = vec![([0.0f32; 3], 0.0f32); k];
+        for (c, n) in &colors {
+            let (sum, weight) = &mut sums[nearest(&centroids, *c)];
+            for i in 0..3 {
+                sum[i] += c[i] * n;
+            }
+            *weight += n;
+        }
+        let mut moved = false;
+        for (centroid, (sum, weight)) in centroids.iter_mut().zip(&sums) {
+            if *weight > 0.0 {
+                let next = [sum[0] / weight, sum[1] / weight, sum[2] / weight];
+                moved |= dist2(next, *centroid) > 0.25;
+                *centroid = next;
+            }
+        }
+        if !moved {
+            break;
+        }
+    }
+
+    colors
+        .iter()
+        .map(|(c, _)| {
+            let m = centroids[nearest(&centroids, *c)];
+            (
+                [c[0] as u8, c[1] as u8, c[2] as u8],
+                [m[0].round() as u8, m[1].round() as u8, m[2].round() as u8],
+            )
+        })
+        .collect()
+}
+
+/// A row of small colour squares, at most `PALETTE_SWATCHES` of them.
+fn palette_swatches(ui: &mut egui::Ui, colors: &[[u8; 3]]) {
+    ui.horizontal_wrapped(|ui| {
+        ui.spacing_mut().item_spacing = egui::vec2(1.0, 1.0);
+        for c in colors.iter().take(PALETTE_SWATCHES) {
+            let (rect, _) = ui.allocate_exact_size(egui::vec2(8.0, 8.0), egui::Sense::hover());
+            ui.painter()
+                .rect_filled(rect, 0.0, egui::Color32::from_rgb(c[0], c[1], c[2]));
+        }
+        if colors.len() > PALETTE_SWATCHES {
+            ui.small(format!("+{}", colors.len() - PALETTE_SWATCHES));
+        }
+    });
+}
+
+/// Loaded native plugins and the expression functions each one provides.
+fn plugins_panel(ui: &mut egui::Ui, i18n: &I18nManager) {
+    let Some(registry) = crate::plugins::registry() else {
+        return;
+    };
+    if registry.plugins.is_empty() && registry.errors.is_empty() {
+        ui.small(i18n.tr("plugins_none"));
+    }
+    for plugin in &registry.plugins {
+        ui.label(egui::RichText::new(&plugin.name).strong())
+            .on_hover_text(plugin.path.display().to_string());
+        ui.horizontal_wrapped(|ui| {
+            for func in &plugin.functions {
+                ui.label(egui::RichText::new(format!("{}()", func)).monospace());
+            }
+        });
+    }
+    for err in &registry.errors {
+        ui.colored_label(egui::Color32::from_rgb(255, 100, 100), err);
+    }
+}
+
+/// Replaces the selection (or inserts at the caret) of the `TextEdit` with
+/// `id`, falling back to appending when it has never been focused. `snippet`
+/// is a whole statement list, so `; ` separators are added where it would
+/// otherwise run into the neighbouring statements.
+fn insert_at_cursor(ctx: &egui::Context, id: egui::Id, text: &mut String, snippet: &str) {
+    let mut state = egui::TextEdit::load_state(ctx, id).unwrap_or_default();
+    let len = text.chars().count();
+    let (start, end) = state
+        .cursor
+        .char_range()
+        .map(|r| {
+            let (a, b) = (r.primary.index.min(len), r.secondary.index.min(len));
+            (a.min(b), a.max(b))
+        })
+        .unwrap_or((len, len));
+    let byte = |idx: usize| text.char_indices().nth(idx).map_or(text.len(), |(b, _)| b);
+    let range = byte(start)..byte(end);
+    let needs_separator = |c: Option<char>| c.is_some_and(|c| c != ';');
+    let mut insert = String::new();
+    if needs_separator(text[..range.start].trim_end().chars().next_back()) {
+        insert.push_str("; ");
+    }
+    insert.push_str(snippet);
+    if needs_separator(text[range.end..].trim_start().chars().next()) {
+        insert.push_str("; ");
+    }
+    text.replace_range(range, &insert);
+    let caret = egui::text::CCursor::new(start + insert.chars().count());
+    state
+        .cursor
+        .set_char_range(Some(egui::text::CCursorRange::one(caret)));
+    egui::TextEdit::store_state(ctx, id, state);
+}
+
+/// Buttons for each defined macro that append a `$name(arg1, ...)` call to
+/// `expr`, with one placeholder per parameter the body uses.
+pub(crate) fn macro_buttons(
+    ui: &mut egui::Ui,
+    macros: &std::collections::HashMap<String, String>,
+    expr: &mut String,
+) {
+    let mut names: Vec<&String> = macros.keys().collect();
+    names.sort();
+    ui.horizontal_wrapped(|ui| {
+        for name in names {
+            let params = crate::particleex::macro_param_count(&macros[name]);
+            let args: Vec<String> = (1..=params).map(|k| format!("arg{}", k)).collect();
+            let call = format!("${}({})", name, args.join(", "));
+            if ui
+                .button(egui::RichText::new(&call).monospace())
+                .on_hover_text(&macros[name])
+                .clicked()
+            {
+                expr.push_str(&call);
+            }
+        }
+    });
+}
+
+const LINT_COLOR: egui::Color32 = egui::Color32::from_rgb(230, 190, 40);
+
+/// `particleex::lint` of an editor's text after macro expansion; empty while
+/// it does not parse.
+pub(crate) fn expression_lints(
+    src: &str,
+    macros: &std::collections::HashMap<String, String>,
+) -> Vec<crate::particleex::LintWarning> {
+    let expanded = crate::particleex::expand_macros(src, macros);
+    if crate::particleex::check_expr(&expanded).is_err() {
+        return Vec::new();
+    }
+    crate::particleex::compile_expr(&expanded)
+        .map(|stmts| crate::particleex::lint(&stmts))
+        .unwrap_or_default()
+}
+
+/// Layouter for an expression `TextEdit` that underlines the `;`-separated
+/// statements named by `lints`. Statement indices count the expanded text,
+/// so they can drift when a macro body holds several statements.
+pub(crate) fn lint_layout_job(
+    ui: &egui::Ui,
+    text: &str,
+    lints: &[crate::particleex::LintWarning],
+    wrap_width: f32,
+) -> egui::text::LayoutJob {
+    let font_id = egui::TextStyle::Monospace.resolve(ui.style());
+    let color = ui
+        .visuals()
+        .override_text_color
+        .unwrap_or_else(|| ui.visuals().widgets.inactive.text_color());
+    let mut job = egui::text::LayoutJob::default();
+    job.wrap.max_width = wrap_width;
+    for (i, stmt) in text.split_inclusive(';').enumerate() {
+        let underline = if lints.iter().any(|l| l.stmts.contains(&i)) {
+            egui::Stroke::new(1.5, LINT_COLOR)
+        } else {
+            egui::Stroke::NONE
+        };
+        job.append(
+            stmt,
+            0.0,
+            egui::TextFormat {
+                font_id: font_id.clone(),
+                color,
+                underline,
+                ..Default::default()
+            },
+        );
+    }
+    job
+}
+
+pub(crate) fn lint_messages(
+    ui: &mut egui::Ui,
+    lints: &[crate::particleex::LintWarning],
+    i18n: &I18nManager,
+) {
+    use crate::particleex::LintKind;
+    for lint in lints {
+        let message = match lint.kind {
+            LintKind::ScaledIdentity(k) => i18n
+                .tr("lint_scaled_identity")
+                .replace("{k}", &k.to_string()),
+            LintKind::AfterDestroy => i18n.tr("lint_after_destroy").to_string(),
+        };
+        ui.label(
+            egui::RichText::new(format!("⚠ {}", message))
+                .color(LINT_COLOR)
+                .small(),
+        );
+    }
+}
+
+/// Software-rasterise one frame as round splats over `background`, seen
+/// through `mvp`. Splat sizes follow the GL point shader so exports match the
+/// viewport; a translucent background is kept in the alpha channel.
+fn rasterize_frame(
+    particles: &[Particle],
+    mvp: [f32; 16],
+    width: u32,
+    height: u32,
+    background: [f32; 4],
+) -> image::RgbaImage {
+    let focal_length = 1.0 / (45.0f32.to_radians() / 2.0).tan();
+    let scaling = focal_length * height as f32 / 2.0;
+    let mut splats: Vec<(f32, f32, f32, f32, [u8; 4])> = particles
+        .iter()
+        .filter(|p| p.color[3] > 0)
+        .filter_map(|p| {
+            let ndc = crate::math::project_to_ndc(mvp, p.pos)?;
+            let w = mvp[3] * p.pos[0] + mvp[7] * p.pos[1] + mvp[11] * p.pos[2] + mvp[15];
+            let sx = (ndc[0] * 0.5 + 0.5) * width as f32;
+            let sy = (0.5 - ndc[1] * 0.5) * height as f32;
+            let radius = ((p.size * scaling) / w).max(1.2) / 2.0;
+            Some((w, sx, sy, radius, p.color))
+        })
+        .collect();
+    // Far to near, so closer particles blend over farther ones.
+    splats.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let bg = background.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8);
+    let mut img = image::RgbaImage::from_pixel(width, height, image::Rgba(bg));
+    for (_, sx, sy, radius, color) in splats {
+        let x0 = (sx - radius).floor().max(0.0) as u32;
+        let y0 = (sy - radius).floor().max(0.0) as u32;
+        let x1 = ((sx + radius).ceil() as i64).min(width as i64);
+        let y1 = ((sy + radius).ceil() as i64).min(height as i64);
+        let alpha = color[3] as f32 / 255.0;
+        for py in y0..y1.max(0) as u32 {
+            for px in x0..x1.max(0) as u32 {
+                let dx = px as f32 + 0.5 - sx;
+                let dy = py as f32 + 0.5 - sy;
+                if dx * dx + dy * dy > radius * radius {
+                    continue;
+                }
+                // Porter-Duff "over", so splats on a clear background keep
+                // their own coverage in the alpha channel.
+                let dst = img.get_pixel_mut(px, py);
+                let dst_alpha = dst.0[3] as f32 / 255.0;
+                let out_alpha = alpha + dst_alpha * (1.0 - alpha);
+                if out_alpha <= 0.0 {
+                    continue;
+                }
+                for (d, &s) in dst.0[..3].iter_mut().zip(&color[..3]) {
+                    let blended = s as f32 * alpha + *d as f32 * dst_alpha * (1.0 - alpha);
+                    *d = (blended / out_alpha).round() as u8;
+                }
+                dst.0[3] = (out_alpha * 255.0).round() as u8;
+            }
+        }
+    }
+    img
+}
+
+/// Render every frame through `mvp` and write a looping GIF. Each frame is
+/// quantised to its own 256-colour palette with NeuQuant. GIF has no partial
+/// transparency, so the background is always drawn opaque.
+fn export_gif(
+    frames: &[Vec<Particle>],
+    [width, height]: [u32; 2],
+    path: &std::path::Path,
+    fps: u16,
+    mvp: [f32; 16],
+    background: [f32; 4],
+    progress: &Mutex<f32>,
+) -> anyhow::Result<()> {
+    let background = [background[0], background[1], background[2], 1.0];
+    let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    let mut encoder = gif::Encoder::new(file, width as u16, height as u16, &[])?;
+    encoder.set_repeat(gif::Repeat::Infinite)?;
+    // GIF delays are in hundredths of a second.
+    let delay = (100.0 / fps.max(1) as f32).round().max(1.0) as u16;
+
+    for (idx, particles) in frames.iter().enumerate() {
+        let img = rasterize_frame(particles, mvp, width, height, background);
+        let quant = color_quant::NeuQuant::new(10, 256, img.as_raw());
+        let indices: Vec<u8> = img
+            .as_raw()
+            .chunks_exact(4)
+            .map(|px| quant.index_of(px) as u8)
+            .collect();
+        let frame = gif::Frame {
+            width: width as u16,
+            height: height as u16,
+            delay,
+            palette: Some(quant.color_map_rgb()),
+            buffer: std::borrow::Cow::Owned(indices),
+            ..Default::default()
+        };
+        encoder.write_frame(&frame)?;
+        if let Ok(mut p) = progress.lock() {
+            *p = (idx + 1) as f32 / frames.len() as f32;
+        }
+    }
+    Ok(())
+}
+
+/// Largest atlas side `export_spritesheet` will allocate.
+const MAX_SPRITESHEET_SIZE: u64 = 16384;
+
+/// Render every frame through `mvp` and stitch them row-major into an RGBA
+/// PNG atlas of `cols` columns.
+fn export_spritesheet(
+    frames: &[Vec<Particle>],
+    [width, height]: [u32; 2],
+    cols: u32,
+    path: &std::path::Path,
+    mvp: [f32; 16],
+    background: [f32; 4],
+    progress: &Mutex<f32>,
+) -> anyhow::Result<()> {
+    let cols = cols.clamp(1, frames.len().max(1) as u32);
+    let rows = (frames.len() as u32).div_ceil(cols).max(1);
+    let (sheet_w, sheet_h) = (width as u64 * cols as u64, height as u64 * rows as u64);
+    if sheet_w > MAX_SPRITESHEET_SIZE || sheet_h > MAX_SPRITESHEET_SIZE {
+        return Err(anyhow::anyhow!(
+            "Spritesheet would be {}x{}, larger than the {}x{} limit; lower the image size or adjust the column count",
+            sheet_w,
+            sheet_h,
+            MAX_SPRITESHEET_SIZE,
+            MAX_SPRITESHEET_SIZE
+        ));
+    }
+    let mut sheet = image::RgbaImage::new(width * cols, height * rows);
+    for (idx, particles) in frames.iter().enumerate() {
+        let img = rasterize_frame(particles, mvp, width, height, background);
+        let (col, row) = (idx as u32 % cols, idx as u32 / cols);
+        image::imageops::replace(
+            &mut sheet,
+            &img,
+            (col * width) as i64,
+            (row * height) as i64,
+        );
+        if let Ok(mut p) = progress.lock() {
+            *p = (idx + 1) as f32 / frames.len() as f32;
+        }
+    }
+    sheet.save(path)?;
+    Ok(())
+}
+
 impl NebulaToolsApp {
     pub fn show_multimedia_workflow(&mut self, ctx: &egui::Context) {
         egui::SidePanel::left("multimedia_left_panel")
@@ -364,6 +1464,16 @@ impl NebulaToolsApp {
                                             .clamp_range(0.0..=f32::MAX),
                                     );
                                 });
+                                ui.horizontal(|ui| {
+                                    ui.label(self.i18n.tr("outro_hold_frames"))
+                                        .on_hover_text(self.i18n.tr("outro_hold_frames_hint"));
+                                    ui.add(
+                                        egui::DragValue::new(
+                                            &mut self.multimedia.outro_hold_frames,
+                                        )
+                                        .speed(1.0),
+                                    );
+                                });
                                 // Outro Preset
                                 let cur_outro =
                                     self.i18n.tr(self.multimedia.outro_preset.i18n_key());
@@ -474,6 +1584,24 @@ impl NebulaToolsApp {
                                     egui::RichText::new(self.i18n.tr("source_preview")).strong(),
                                 );
                                 ui.add(egui::Image::new(tex).max_width(ui.available_width()));
+                                let [w, h] = tex.size().map(|s| s as u32);
+                                ui.label(format!(
+                                    "{}: {} × {}",
+                                    self.i18n.tr("source_resolution"),
+                                    w,
+                                    h
+                                ));
+                                if let Some([ow, oh]) = self.multimedia.source_downscaled_from {
+                                    ui.label(
+                                        egui::RichText::new(format!(
+                                            "📐 {} {} × {}",
+                                            self.i18n.tr("downscaled_from"),
+                                            ow,
+                                            oh
+                                        ))
+                                        .weak(),
+                                    );
+                                }
                             });
                         }
                     });
@@ -498,24 +1626,84 @@ impl NebulaToolsApp {
                         self.multimedia.preview_playing = false;
                         self.multimedia.preview_frame_idx = 0;
                     }
+                    if ui
+                        .button(self.i18n.tr("fullscreen_preview"))
+                        .on_hover_text(self.i18n.tr("fullscreen_preview_hint"))
+                        .clicked()
+                    {
+                        self.multimedia.fullscreen_preview = true;
+                    }
 
                     if let Some(frames) = &self.multimedia.preview_frames {
                         ui.add_space(16.0);
+                        let last_frame = frames.len() as i32 - 1;
                         let mut frame_idx = self.multimedia.preview_frame_idx;
-                        if ui
-                            .add(
-                                egui::Slider::new(&mut frame_idx, 0..=(frames.len() as i32 - 1))
-                                    .text(self.i18n.tr("frame_label")),
-                            )
-                            .changed()
-                        {
+                        // Value and label are added separately so the response
+                        // rect covers only the rail, for hover lookup.
+                        let slider = ui.add(
+                            egui::Slider::new(&mut frame_idx, 0..=last_frame).show_value(false),
+                        );
+                        ui.add(egui::DragValue::new(&mut frame_idx).clamp_range(0..=last_frame));
+                        ui.label(self.i18n.tr("frame_label"));
+                        if slider.clicked() || frame_idx != self.multimedia.preview_frame_idx {
                             self.multimedia.preview_frame_idx = frame_idx;
                             self.multimedia.preview_playing = false; // pause when scrubbing
                         }
+                        if let Some(pos) = slider.hover_pos() {
+                            self.show_frame_hover_preview(ctx, slider.rect, pos, last_frame);
+                        }
                     }
+
+                    ui.add_space(16.0);
+                    ui.add(
+                        egui::Slider::new(&mut self.multimedia.preview_fps, 1..=240)
+                            .text(self.i18n.tr("preview_speed"))
+                            .suffix(" fps"),
+                    );
+
+                    ui.add_space(16.0);
+                    ui.label(self.i18n.tr("preview_step"));
+                    ui.add(
+                        egui::DragValue::new(&mut self.multimedia.preview_step)
+                            .clamp_range(-16..=16)
+                            .speed(0.1),
+                    );
                 });
+                ui.horizontal(|ui| {
+                    ui.label(self.i18n.tr("export_size"));
+                    let size = &mut self.multimedia.export_image_size;
+                    ui.add(egui::DragValue::new(&mut size[0]).clamp_range(16..=4096));
+                    ui.label("×");
+                    ui.add(egui::DragValue::new(&mut size[1]).clamp_range(16..=4096));
+                    let can_export = self.multimedia.preview_frames.is_some()
+                        && self.multimedia.image_export.is_none();
+                    if ui
+                        .add_enabled(can_export, egui::Button::new(self.i18n.tr("export_gif")))
+                        .clicked()
+                    {
+                        self.start_gif_export();
+                    }
+                    ui.add_space(16.0);
+                    ui.label(self.i18n.tr("spritesheet_columns"));
+                    ui.add(
+                        egui::DragValue::new(&mut self.multimedia.spritesheet_cols)
+                            .clamp_range(1..=256),
+                    );
+                    if ui
+                        .add_enabled(
+                            can_export,
+                            egui::Button::new(self.i18n.tr("export_spritesheet")),
+                        )
+                        .clicked()
+                    {
+                        self.start_spritesheet_export();
+                    }
+                });
+                ui.add_space(4.0);
             });
 
+        self.show_image_export_progress(ctx);
+
         // Central Canvas
         egui::CentralPanel::default().show(ctx, |ui| {
             if self.multimedia.is_processing {
@@ -634,11 +1822,234 @@ impl NebulaToolsApp {
                 });
             } else {
                 let particles_data = self.prepare_render_data_from_multimedia(ctx);
-                self.paint_3d_viewport(ui, ctx, &particles_data);
+                let response =
+                    self.paint_3d_viewport_layers(ui, ctx, &particles_data, &[], &[], &[]);
+                self.paint_particle_count_sparkline(ui, response.rect);
+            }
+        });
+
+        if self.multimedia.fullscreen_preview {
+            self.show_multimedia_fullscreen(ctx);
+        }
+    }
+
+    /// Render the current preview frame alone in a fullscreen window, closed
+    /// with Escape. Playback is advanced by the main window only.
+    fn show_multimedia_fullscreen(&mut self, ctx: &egui::Context) {
+        ctx.show_viewport_immediate(
+            egui::ViewportId::from_hash_of("multimedia_fullscreen"),
+            egui::ViewportBuilder::default()
+                .with_title(self.i18n.tr("fullscreen_preview"))
+                .with_fullscreen(true),
+            |ctx, _class| {
+                let close = ctx
+                    .input(|i| i.key_pressed(egui::Key::Escape) || i.viewport().close_requested());
+                if close {
+                    self.multimedia.fullscreen_preview = false;
+                    return;
+                }
+                let data = match &self.multimedia.preview_frames {
+                    Some(frames) if !frames.is_empty() => {
+                        let idx = (self.multimedia.preview_frame_idx.max(0) as usize)
+                            .min(frames.len() - 1);
+                        self.prepare_render_data_from(&frames[idx], idx)
+                    }
+                    _ => Vec::new(),
+                };
+                egui::CentralPanel::default()
+                    .frame(egui::Frame::none())
+                    .show(ctx, |ui| self.paint_3d_viewport(ui, ctx, &data));
+                if self.multimedia.preview_playing {
+                    ctx.request_repaint();
+                }
+            },
+        );
+    }
+
+    /// Ask for a destination and render the preview frames to a GIF on a
+    /// background thread, viewed through the current camera.
+    fn start_gif_export(&mut self) {
+        let Some(frames) = self.multimedia.preview_frames.clone() else {
+            return;
+        };
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("GIF", &["gif"])
+            .set_file_name("multimedia_preview.gif")
+            .save_file()
+        else {
+            return;
+        };
+        let size @ [width, height] = self.multimedia.export_image_size;
+        let mvp = self.calculate_mvp(width as f32 / height as f32);
+        let background = self.viewport_bg_color;
+        let fps = self.multimedia.preview_fps;
+        let progress = Arc::new(Mutex::new(0.0f32));
+        let result = Arc::new(Mutex::new(None));
+        self.multimedia.image_export = Some((progress.clone(), result.clone()));
+
+        std::thread::spawn(move || {
+            let outcome = export_gif(&frames, size, &path, fps, mvp, background, &progress)
+                .map(|_| path.to_string_lossy().to_string())
+                .map_err(|e| e.to_string());
+            if let Ok(mut slot) = result.lock() {
+                *slot = Some(outcome);
             }
         });
     }
 
+    /// Ask for a destination and render the preview frames into a PNG
+    /// spritesheet on a background thread.
+    fn start_spritesheet_export(&mut self) {
+        let Some(frames) = self.multimedia.preview_frames.clone() else {
+            return;
+        };
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("PNG", &["png"])
+            .set_file_name("multimedia_spritesheet.png")
+            .save_file()
+        else {
+            return;
+        };
+        let size @ [width, height] = self.multimedia.export_image_size;
+        let mvp = self.calculate_mvp(width as f32 / height as f32);
+        let background = self.viewport_bg_color;
+        let cols = self.multimedia.spritesheet_cols;
+        let progress = Arc::new(Mutex::new(0.0f32));
+        let result = Arc::new(Mutex::new(None));
+        self.multimedia.image_export = Some((progress.clone(), result.clone()));
+
+        std::thread::spawn(move || {
+            let outcome =
+                export_spritesheet(&frames, size, cols, &path, mvp, background, &progress)
+                    .map(|_| path.to_string_lossy().to_string())
+                    .map_err(|e| e.to_string());
+            if let Ok(mut slot) = result.lock() {
+                *slot = Some(outcome);
+            }
+        });
+    }
+
+    /// Modal progress window for a running image export; reports the result
+    /// in the status line once the worker finishes.
+    fn show_image_export_progress(&mut self, ctx: &egui::Context) {
+        let Some((progress, result)) = &self.multimedia.image_export else {
+            return;
+        };
+        let finished = result.lock().ok().and_then(|mut r| r.take());
+        if let Some(outcome) = finished {
+            self.multimedia.status_msg = Some(match outcome {
+                Ok(path) => format!("{}: {}", self.i18n.tr("export_image_done"), path),
+                Err(e) => format!("Export Failed: {}", e),
+            });
+            self.multimedia.image_export = None;
+            return;
+        }
+        let pct = progress.lock().map(|p| *p).unwrap_or(0.0);
+
+        egui::Area::new(egui::Id::new("image_export_blocker"))
+            .order(egui::Order::Foreground)
+            .fixed_pos(egui::Pos2::ZERO)
+            .show(ctx, |ui| {
+                let screen = ctx.screen_rect();
+                ui.allocate_rect(screen, egui::Sense::click_and_drag());
+                ui.painter()
+                    .rect_filled(screen, 0.0, egui::Color32::from_black_alpha(120));
+            });
+        // Above the blocker, which swallows input to the rest of the UI.
+        egui::Area::new(egui::Id::new("image_export_progress"))
+            .order(egui::Order::Tooltip)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                egui::Frame::window(ui.style()).show(ui, |ui| {
+                    ui.label(egui::RichText::new(self.i18n.tr("exporting_image")).strong());
+                    ui.add_space(6.0);
+                    ui.add(
+                        egui::ProgressBar::new(pct)
+                            .show_percentage()
+                            .desired_width(300.0),
+                    );
+                });
+            });
+        ctx.request_repaint();
+    }
+
+    /// Tooltip above the frame slider with the hovered frame index and its
+    /// particle count.
+    fn show_frame_hover_preview(
+        &self,
+        ctx: &egui::Context,
+        rail: egui::Rect,
+        pos: egui::Pos2,
+        last_frame: i32,
+    ) {
+        // Same handle inset egui uses for the slider rail.
+        let inset = rail.height() / 2.5;
+        let t = ((pos.x - rail.left() - inset) / (rail.width() - 2.0 * inset)).clamp(0.0, 1.0);
+        let frame = (t * last_frame as f32).round() as usize;
+        let count = self.multimedia.preview_counts.get(frame).copied();
+
+        egui::Area::new(egui::Id::new("frame_hover_preview"))
+            .order(egui::Order::Tooltip)
+            .fixed_pos(egui::pos2(pos.x, rail.top() - 6.0))
+            .pivot(egui::Align2::CENTER_BOTTOM)
+            .interactable(false)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.label(format!("{} {}", self.i18n.tr("frame_label"), frame));
+                    if let Some(count) = count {
+                        ui.label(format!("{}: {}", self.i18n.tr("particle_count"), count));
+                    }
+                });
+            });
+    }
+
+    /// Bar sparkline of visible particles per preview frame in the top-right
+    /// corner of the canvas, with the current frame in yellow.
+    fn paint_particle_count_sparkline(&self, ui: &egui::Ui, rect: egui::Rect) {
+        let counts = &self.multimedia.preview_counts;
+        if counts.len() < 2 {
+            return;
+        }
+        let size = egui::vec2(200.0, 48.0);
+        let chart =
+            egui::Rect::from_min_size(rect.right_top() + egui::vec2(-size.x - 14.0, 14.0), size);
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(chart.expand(4.0), 4.0, egui::Color32::from_black_alpha(150));
+
+        let max = counts.iter().copied().max().unwrap_or(0).max(1);
+        let current = self.multimedia.preview_frame_idx.max(0) as usize;
+        // Bucket frames so there is at most one bar per point.
+        let n = counts.len();
+        let bars = n.min(size.x as usize);
+        let bar_width = size.x / bars as f32;
+        for b in 0..bars {
+            let start = b * n / bars;
+            let end = ((b + 1) * n / bars).max(start + 1);
+            let count = counts[start..end].iter().copied().max().unwrap_or(0);
+            let x = chart.left() + (b as f32 + 0.5) * bar_width;
+            let h = (count as f32 / max as f32 * size.y).max(1.0);
+            let color = if (start..end).contains(&current) {
+                egui::Color32::YELLOW
+            } else {
+                egui::Color32::from_rgb(120, 180, 255)
+            };
+            painter.line_segment(
+                [
+                    egui::pos2(x, chart.bottom()),
+                    egui::pos2(x, chart.bottom() - h),
+                ],
+                egui::Stroke::new((bar_width - 0.5).max(1.0), color),
+            );
+        }
+        painter.text(
+            chart.left_top(),
+            egui::Align2::LEFT_TOP,
+            format!("{}", max),
+            egui::FontId::proportional(10.0),
+            egui::Color32::from_white_alpha(160),
+        );
+    }
+
     fn show_text_ui(&mut self, ui: &mut egui::Ui) {
         ui.label(self.i18n.tr("text_input"));
         ui.add(
@@ -653,13 +2064,141 @@ impl NebulaToolsApp {
             ui.add(egui::DragValue::new(&mut self.multimedia.font_size).speed(1.0));
         });
 
+        ui.horizontal(|ui| {
+            ui.label(self.i18n.tr("letter_spacing"));
+            ui.add(egui::DragValue::new(&mut self.multimedia.letter_spacing).speed(0.5));
+            ui.label(self.i18n.tr("line_spacing"));
+            ui.add(egui::DragValue::new(&mut self.multimedia.extra_line_gap).speed(0.5));
+        });
+
+        ui.horizontal(|ui| {
+            let mode = &mut self.multimedia.text_render_mode;
+            let width = mode.stroke_width().unwrap_or(4.0);
+            if ui
+                .radio(*mode == TextRenderMode::Fill, self.i18n.tr("text_fill"))
+                .clicked()
+            {
+                *mode = TextRenderMode::Fill;
+            }
+            if ui
+                .radio(
+                    matches!(mode, TextRenderMode::Stroke(_)),
+                    self.i18n.tr("text_stroke"),
+                )
+                .clicked()
+            {
+                *mode = TextRenderMode::Stroke(width);
+            }
+            if ui
+                .radio(
+                    matches!(mode, TextRenderMode::FillAndStroke(_)),
+                    self.i18n.tr("text_fill_and_stroke"),
+                )
+                .clicked()
+            {
+                *mode = TextRenderMode::FillAndStroke(width);
+            }
+            if let TextRenderMode::Stroke(w) | TextRenderMode::FillAndStroke(w) = mode {
+                ui.label(self.i18n.tr("stroke_width"));
+                ui.add(egui::DragValue::new(w).speed(0.5).clamp_range(1.0..=255.0));
+            }
+        });
+        ui.checkbox(
+            &mut self.multimedia.text_outline_only,
+            self.i18n.tr("text_outline_only"),
+        )
+        .on_hover_text(self.i18n.tr("text_outline_only_hint"));
+
+        ui.horizontal(|ui| {
+            ui.label(self.i18n.tr("text_direction"));
+            let direction_label = |direction: TextDirection| match direction {
+                TextDirection::LeftToRight => self.i18n.tr("text_dir_ltr"),
+                TextDirection::RightToLeft => self.i18n.tr("text_dir_rtl"),
+                TextDirection::TopToBottom => self.i18n.tr("text_dir_ttb"),
+            };
+            egui::ComboBox::from_id_source("mm_text_direction")
+                .selected_text(direction_label(self.multimedia.text_direction))
+                .show_ui(ui, |ui| {
+                    for direction in [
+                        TextDirection::LeftToRight,
+                        TextDirection::RightToLeft,
+                        TextDirection::TopToBottom,
+                    ] {
+                        ui.selectable_value(
+                            &mut self.multimedia.text_direction,
+                            direction,
+                            direction_label(direction),
+                        );
+                    }
+                });
+        });
+
+        ui.horizontal(|ui| {
+            ui.label(self.i18n.tr("text_color_mode"));
+            let mode_label = |mode: TextColorMode| match mode {
+                TextColorMode::Uniform => self.i18n.tr("text_color_uniform"),
+                TextColorMode::GradientX => self.i18n.tr("text_color_gradient_x"),
+                TextColorMode::GradientY => self.i18n.tr("text_color_gradient_y"),
+                TextColorMode::PerChar => self.i18n.tr("text_color_per_char"),
+            };
+            egui::ComboBox::from_id_source("mm_text_color_mode")
+                .selected_text(mode_label(self.multimedia.text_color_mode))
+                .show_ui(ui, |ui| {
+                    for mode in [
+                        TextColorMode::Uniform,
+                        TextColorMode::GradientX,
+                        TextColorMode::GradientY,
+                        TextColorMode::PerChar,
+                    ] {
+                        ui.selectable_value(
+                            &mut self.multimedia.text_color_mode,
+                            mode,
+                            mode_label(mode),
+                        );
+                    }
+                });
+        });
+
+        ui.horizontal(|ui| match self.multimedia.text_color_mode {
+            TextColorMode::Uniform => {
+                ui.color_edit_button_srgb(&mut self.multimedia.color_start);
+            }
+            TextColorMode::GradientX | TextColorMode::GradientY => {
+                ui.color_edit_button_srgb(&mut self.multimedia.color_start);
+                ui.label("→");
+                ui.color_edit_button_srgb(&mut self.multimedia.color_end);
+            }
+            TextColorMode::PerChar => {
+                let mut remove = None;
+                for (i, color) in self.multimedia.text_palette.iter_mut().enumerate() {
+                    if ui.color_edit_button_srgb(color).secondary_clicked() {
+                        remove = Some(i);
+                    }
+                }
+                if let Some(i) = remove {
+                    self.multimedia.text_palette.remove(i);
+                }
+                if ui.small_button("+").clicked() {
+                    self.multimedia.text_palette.push([255, 255, 255]);
+                }
+            }
+        });
+        if self.multimedia.text_color_mode == TextColorMode::PerChar {
+            ui.label(
+                egui::RichText::new(self.i18n.tr("text_palette_hint"))
+                    .weak()
+                    .size(11.0),
+            );
+        }
+
         ui.horizontal(|ui| {
             if ui.button(self.i18n.tr("load_font")).clicked() {
                 if let Some(path) = rfd::FileDialog::new()
-                    .add_filter("Fonts", &["ttf", "otf"])
+                    .add_filter("Fonts", &["ttf", "otf", "ttc", "otc"])
                     .pick_file()
                 {
                     self.multimedia.font_name = path.to_string_lossy().to_string();
+                    self.refresh_font_variants();
                 }
             }
             if !self.multimedia.font_name.is_empty()
@@ -680,6 +2219,7 @@ impl NebulaToolsApp {
         });
 
         if !self.multimedia.system_fonts.is_empty() {
+            let mut font_changed = false;
             egui::ComboBox::from_label(self.i18n.tr("load_system_font"))
                 .selected_text(if self.multimedia.font_name.starts_with("system://") {
                     self.multimedia.font_name.replace("system://", "")
@@ -696,12 +2236,40 @@ impl NebulaToolsApp {
                             .clicked()
                         {
                             self.multimedia.font_name = format!("system://{}", font);
+                            font_changed = true;
                         }
                     }
                 });
+            if font_changed {
+                self.refresh_font_variants();
+            }
+        }
+
+        if self.multimedia.font_variants.len() > 1 {
+            egui::ComboBox::from_label(self.i18n.tr("font_variant"))
+                .selected_text(
+                    self.multimedia
+                        .font_variant
+                        .as_ref()
+                        .map_or("-", |v| v.name.as_str()),
+                )
+                .show_ui(ui, |ui| {
+                    for variant in &self.multimedia.font_variants {
+                        ui.selectable_value(
+                            &mut self.multimedia.font_variant,
+                            Some(variant.clone()),
+                            &variant.name,
+                        );
+                    }
+                });
         }
     }
 
+    fn refresh_font_variants(&mut self) {
+        self.multimedia.font_variants = list_font_variants(&self.multimedia.font_name);
+        self.multimedia.font_variant = None;
+    }
+
     fn count_particles(&self, w: u32, h: u32, density: f32) -> usize {
         let total = (w * h) as f32;
         let factor = if density < 1.0 {
@@ -738,7 +2306,8 @@ impl NebulaToolsApp {
             }
             1 => {
                 let [w, h] = self.multimedia.last_source_size.unwrap_or([1920, 1080]);
-                self.count_particles(w, h, density)
+                let (nx, ny) = self.multimedia.tiling.counts();
+                self.count_particles(w * nx, h * ny, density)
             }
             2 => {
                 let [w, h] = self.multimedia.last_source_size.unwrap_or([1280, 720]);
@@ -782,6 +2351,35 @@ impl NebulaToolsApp {
                 );
             });
 
+            if self.multimedia.mode != 2 {
+                ui.horizontal(|ui| {
+                    ui.checkbox(
+                        &mut self.multimedia.grid_snap_output,
+                        self.i18n.tr("grid_snap"),
+                    )
+                    .on_hover_text(self.i18n.tr("grid_snap_hint"));
+                    if self.multimedia.grid_snap_output {
+                        ui.add(
+                            egui::DragValue::new(&mut self.multimedia.grid_snap)
+                                .speed(0.01)
+                                .max_decimals(6)
+                                .clamp_range(0.000001..=f32::MAX),
+                        );
+                        if let Some((cells, particles)) = self.multimedia.grid_snap_cells {
+                            ui.label(
+                                egui::RichText::new(format!(
+                                    "≈ {} / {} {}",
+                                    cells,
+                                    particles,
+                                    self.i18n.tr("grid_snap_cells")
+                                ))
+                                .weak(),
+                            );
+                        }
+                    }
+                });
+            }
+
             ui.horizontal(|ui| {
                 ui.label(self.i18n.tr("brightness_threshold"));
                 ui.add(egui::Slider::new(
@@ -839,29 +2437,306 @@ impl NebulaToolsApp {
             });
 
             ui.horizontal(|ui| {
-                ui.label(self.i18n.tr("duration_s"));
-                ui.add(egui::DragValue::new(&mut self.multimedia.duration_secs).speed(0.1));
+                let mut enabled = self.multimedia.palette_limit.is_some();
+                if ui
+                    .checkbox(&mut enabled, self.i18n.tr("limit_palette"))
+                    .changed()
+                {
+                    self.multimedia.palette_limit = enabled.then_some(16);
+                }
+                if let Some(limit) = &mut self.multimedia.palette_limit {
+                    ui.add(egui::DragValue::new(limit).clamp_range(1..=256));
+                }
+            });
+
+            ui.horizontal(|ui| {
+                ui.checkbox(
+                    &mut self.multimedia.quantize_on_export,
+                    self.i18n.tr("quantize_on_export"),
+                )
+                .on_hover_text(self.i18n.tr("quantize_on_export_hint"));
+                if self.multimedia.quantize_on_export {
+                    ui.add(
+                        egui::DragValue::new(&mut self.multimedia.quantize_k).clamp_range(1..=4096),
+                    );
+                }
+            });
+            if self.multimedia.quantize_on_export {
+                if let Some((before, after)) = &self.multimedia.quantize_palettes {
+                    ui.small(format!(
+                        "{} ({})",
+                        self.i18n.tr("palette_before"),
+                        before.len()
+                    ));
+                    palette_swatches(ui, before);
+                    ui.small(format!(
+                        "{} ({})",
+                        self.i18n.tr("palette_after"),
+                        after.len()
+                    ));
+                    palette_swatches(ui, after);
+                }
+            }
+
+            ui.checkbox(
+                &mut self.multimedia.color_from_expr,
+                self.i18n.tr("color_from_expr"),
+            )
+            .on_hover_text(self.i18n.tr("color_from_expr_hint"));
+            ui.checkbox(
+                &mut self.multimedia.override_color,
+                self.i18n.tr("override_color"),
+            )
+            .on_hover_text(self.i18n.tr("override_color_hint"));
+            if self.multimedia.color_from_expr {
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.multimedia.color_expr)
+                        .font(egui::TextStyle::Monospace)
+                        .desired_width(f32::INFINITY),
+                );
+            }
+
+            ui.horizontal(|ui| {
+                if ui
+                    .button(self.i18n.tr("load_flow_field"))
+                    .on_hover_text(self.i18n.tr("flow_field_hint"))
+                    .clicked()
+                {
+                    if let Some(path) = rfd::FileDialog::new()
+                        .add_filter("Images", &["png", "jpg", "jpeg", "webp"])
+                        .pick_file()
+                    {
+                        self.multimedia.flow_field_path = Some(path.to_string_lossy().to_string());
+                    }
+                }
+                if let Some(path) = &self.multimedia.flow_field_path {
+                    ui.label(
+                        std::path::Path::new(path)
+                            .file_name()
+                            .unwrap_or_default()
+                            .to_string_lossy(),
+                    );
+                    if ui.small_button("x").clicked() {
+                        self.multimedia.flow_field_path = None;
+                    }
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label(self.i18n.tr("attract_mode"));
+                let label = |mode: AttractMode| match mode {
+                    AttractMode::None => self.i18n.tr("attract_none"),
+                    AttractMode::ToSource => self.i18n.tr("attract_to_source"),
+                };
+                egui::ComboBox::from_id_source("attract_mode")
+                    .selected_text(label(self.multimedia.attract_mode))
+                    .show_ui(ui, |ui| {
+                        for mode in [AttractMode::None, AttractMode::ToSource] {
+                            ui.selectable_value(
+                                &mut self.multimedia.attract_mode,
+                                mode,
+                                label(mode),
+                            );
+                        }
+                    });
+                if self.multimedia.attract_mode == AttractMode::ToSource {
+                    ui.add(
+                        egui::DragValue::new(&mut self.multimedia.attract_strength)
+                            .speed(0.005)
+                            .clamp_range(0.0..=1.0),
+                    );
+                }
+            });
+
+            ui.horizontal(|ui| {
+                ui.label(self.i18n.tr("birth_delay_mode"))
+                    .on_hover_text(self.i18n.tr("birth_delay_hint"));
+                let label = |mode: BirthDelayMode| match mode {
+                    BirthDelayMode::None => self.i18n.tr("birth_delay_none"),
+                    BirthDelayMode::ByXPosition => self.i18n.tr("birth_delay_by_x"),
+                    BirthDelayMode::ByDistance => self.i18n.tr("birth_delay_by_distance"),
+                    BirthDelayMode::Random => self.i18n.tr("birth_delay_random"),
+                };
+                egui::ComboBox::from_id_source("birth_delay_mode")
+                    .selected_text(label(self.multimedia.birth_delay_mode))
+                    .show_ui(ui, |ui| {
+                        for mode in [
+                            BirthDelayMode::None,
+                            BirthDelayMode::ByXPosition,
+                            BirthDelayMode::ByDistance,
+                            BirthDelayMode::Random,
+                        ] {
+                            ui.selectable_value(
+                                &mut self.multimedia.birth_delay_mode,
+                                mode,
+                                label(mode),
+                            );
+                        }
+                    });
+                if self.multimedia.birth_delay_mode != BirthDelayMode::None {
+                    ui.add(
+                        egui::Slider::new(&mut self.multimedia.particle_birth_delay, 0.0..=1.0)
+                            .text(self.i18n.tr("birth_delay")),
+                    );
+                }
+            });
+
+            if self.multimedia.flow_field_path.is_some() {
+                ui.horizontal(|ui| {
+                    ui.label(self.i18n.tr("flow_field_strength"));
+                    ui.add(
+                        egui::DragValue::new(&mut self.multimedia.flow_field_strength).speed(0.001),
+                    );
+                });
+            }
+
+            ui.horizontal(|ui| {
+                ui.label(self.i18n.tr("duration_s"));
+                ui.add(egui::DragValue::new(&mut self.multimedia.duration_secs).speed(0.1));
+            });
+        });
+    }
+
+    fn show_image_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if ui.button(self.i18n.tr("select_image")).clicked() {
+                if let Some(path) = rfd::FileDialog::new()
+                    .add_filter("Images", &["png", "jpg", "jpeg", "webp"])
+                    .pick_file()
+                {
+                    self.multimedia.media_path = Some(path.to_string_lossy().to_string());
+                }
+            }
+            if let Some(path) = &self.multimedia.media_path {
+                ui.label(
+                    std::path::Path::new(path)
+                        .file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy(),
+                );
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label(self.i18n.tr("max_source_dimension"))
+                .on_hover_text(self.i18n.tr("max_source_dimension_hint"));
+            ui.add(
+                egui::DragValue::new(&mut self.multimedia.max_source_dimension)
+                    .clamp_range(0..=16384)
+                    .speed(16.0)
+                    .suffix(" px"),
+            );
+        });
+
+        ui.horizontal(|ui| {
+            ui.checkbox(
+                &mut self.multimedia.density_equalise,
+                self.i18n.tr("density_equalise"),
+            )
+            .on_hover_text(self.i18n.tr("density_equalise_hint"));
+            if self.multimedia.density_equalise {
+                ui.add(
+                    egui::Slider::new(&mut self.multimedia.equalise_strength, 0.0..=1.0)
+                        .text(self.i18n.tr("equalise_strength")),
+                );
+            }
+        });
+
+        ui.horizontal(|ui| {
+            if ui.button(self.i18n.tr("load_depth_map")).clicked() {
+                if let Some(path) = rfd::FileDialog::new()
+                    .add_filter("Images", &["png", "jpg", "jpeg", "webp"])
+                    .pick_file()
+                {
+                    self.multimedia.depth_map_path = Some(path.to_string_lossy().to_string());
+                }
+            }
+            if let Some(path) = &self.multimedia.depth_map_path {
+                ui.label(
+                    std::path::Path::new(path)
+                        .file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy(),
+                );
+                if ui.small_button("x").clicked() {
+                    self.multimedia.depth_map_path = None;
+                }
+            }
+        });
+        if self.multimedia.depth_map_path.is_some() {
+            ui.horizontal(|ui| {
+                ui.label(self.i18n.tr("depth_scale"));
+                ui.add(egui::DragValue::new(&mut self.multimedia.depth_scale).speed(0.01));
             });
-        });
-    }
+        }
 
-    fn show_image_ui(&mut self, ui: &mut egui::Ui) {
         ui.horizontal(|ui| {
-            if ui.button(self.i18n.tr("select_image")).clicked() {
+            if ui
+                .button(self.i18n.tr("load_mask_image"))
+                .on_hover_text(self.i18n.tr("mask_image_hint"))
+                .clicked()
+            {
                 if let Some(path) = rfd::FileDialog::new()
                     .add_filter("Images", &["png", "jpg", "jpeg", "webp"])
                     .pick_file()
                 {
-                    self.multimedia.media_path = Some(path.to_string_lossy().to_string());
+                    self.multimedia.mask_path = Some(path.to_string_lossy().to_string());
                 }
             }
-            if let Some(path) = &self.multimedia.media_path {
+            if let Some(path) = &self.multimedia.mask_path {
                 ui.label(
                     std::path::Path::new(path)
                         .file_name()
                         .unwrap_or_default()
                         .to_string_lossy(),
                 );
+                if ui.small_button("x").clicked() {
+                    self.multimedia.mask_path = None;
+                }
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label(self.i18n.tr("tiling"));
+            let tiling = &mut self.multimedia.tiling;
+            let (mut nx, mut ny) = match *tiling {
+                TilingMode::None => (2, 2),
+                TilingMode::Repeat(nx, ny) | TilingMode::Mirror(nx, ny) => (nx, ny),
+            };
+            let selected = match tiling {
+                TilingMode::None => self.i18n.tr("tiling_none"),
+                TilingMode::Repeat(..) => self.i18n.tr("tiling_repeat"),
+                TilingMode::Mirror(..) => self.i18n.tr("tiling_mirror"),
+            };
+            egui::ComboBox::from_id_source("mm_tiling")
+                .selected_text(selected)
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(tiling, TilingMode::None, self.i18n.tr("tiling_none"));
+                    ui.selectable_value(
+                        tiling,
+                        TilingMode::Repeat(nx, ny),
+                        self.i18n.tr("tiling_repeat"),
+                    );
+                    ui.selectable_value(
+                        tiling,
+                        TilingMode::Mirror(nx, ny),
+                        self.i18n.tr("tiling_mirror"),
+                    );
+                });
+            if *tiling != TilingMode::None {
+                ui.add(
+                    egui::DragValue::new(&mut nx)
+                        .clamp_range(1..=64)
+                        .prefix("x "),
+                );
+                ui.add(
+                    egui::DragValue::new(&mut ny)
+                        .clamp_range(1..=64)
+                        .prefix("y "),
+                );
+                *tiling = match *tiling {
+                    TilingMode::Mirror(..) => TilingMode::Mirror(nx, ny),
+                    _ => TilingMode::Repeat(nx, ny),
+                };
             }
         });
     }
@@ -875,9 +2750,19 @@ impl NebulaToolsApp {
                 {
                     self.multimedia.media_path = Some(path.to_string_lossy().to_string());
                     self.multimedia.last_source_size = None;
+                    self.multimedia.video_native_size = None;
+                    self.multimedia.video_duration = None;
+                    self.multimedia.video_start_sec = 0.0;
+                    self.multimedia.video_end_sec = 0.0;
                     match probe_video_info(&path.to_string_lossy()) {
                         Ok(info) => {
-                            self.multimedia.last_source_size = Some([info.width, info.height]);
+                            self.multimedia.video_native_size = Some([info.width, info.height]);
+                            self.multimedia.last_source_size = Some(scaled_video_size(
+                                info.width,
+                                info.height,
+                                self.multimedia.video_scale,
+                            ));
+                            self.multimedia.video_duration = Some(info.duration);
                         }
                         Err(e) => {
                             self.multimedia.status_msg = Some(format!("Video probe failed: {}", e));
@@ -895,6 +2780,74 @@ impl NebulaToolsApp {
             }
         });
 
+        ui.horizontal(|ui| {
+            let changed = ui
+                .add(
+                    egui::Slider::new(&mut self.multimedia.video_scale, 0.1..=1.0)
+                        .text(self.i18n.tr("video_scale")),
+                )
+                .on_hover_text(self.i18n.tr("video_scale_hint"))
+                .changed();
+            if let Some([w, h]) = self.multimedia.video_native_size {
+                let scaled = scaled_video_size(w, h, self.multimedia.video_scale);
+                if changed {
+                    self.multimedia.last_source_size = Some(scaled);
+                }
+                ui.weak(format!("{}×{}", scaled[0], scaled[1]));
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.checkbox(
+                &mut self.multimedia.video_grayscale,
+                self.i18n.tr("video_grayscale"),
+            );
+            ui.checkbox(
+                &mut self.multimedia.video_invert,
+                self.i18n.tr("video_invert"),
+            );
+        });
+
+        ui.horizontal(|ui| {
+            ui.label(self.i18n.tr("video_trim"));
+            ui.add(
+                egui::DragValue::new(&mut self.multimedia.video_start_sec)
+                    .speed(0.1)
+                    .clamp_range(0.0..=f32::MAX)
+                    .suffix(" s"),
+            );
+            ui.label("–");
+            ui.add(
+                egui::DragValue::new(&mut self.multimedia.video_end_sec)
+                    .speed(0.1)
+                    .clamp_range(0.0..=f32::MAX)
+                    .suffix(" s"),
+            )
+            .on_hover_text(self.i18n.tr("video_trim_end_hint"));
+        });
+        if let Some(duration) = self.multimedia.video_duration {
+            match resolve_video_trim(
+                self.multimedia.video_start_sec,
+                self.multimedia.video_end_sec,
+                duration,
+            ) {
+                Ok((start, end)) => {
+                    let frames = ((end - start) * self.multimedia.target_fps as f32).ceil();
+                    ui.small(format!(
+                        "{}: {:.2}s / {:.2}s ({} {})",
+                        self.i18n.tr("video_trimmed_duration"),
+                        end - start,
+                        duration,
+                        frames,
+                        self.i18n.tr("video_trim_frames")
+                    ));
+                }
+                Err(e) => {
+                    ui.colored_label(egui::Color32::from_rgb(255, 80, 80), e.to_string());
+                }
+            }
+        }
+
         ui.horizontal(|ui| {
             ui.label(self.i18n.tr("export_threads"));
             ui.add(
@@ -928,24 +2881,24 @@ impl NebulaToolsApp {
         }
 
         if self.multimedia.preview_playing {
-            let dt = 1.0 / self.multimedia.target_fps as f32;
+            let dt = 1.0 / self.multimedia.preview_fps.max(1) as f32;
             let actual_dt = ctx.input(|i| i.stable_dt);
             self.multimedia.preview_timer += actual_dt;
             if self.multimedia.preview_timer >= dt {
                 self.multimedia.preview_timer -= dt;
-                self.multimedia.preview_frame_idx += 1;
-                if self.multimedia.preview_frame_idx >= frames.len() as i32 {
-                    self.multimedia.preview_frame_idx = 0; // loop
-                }
+                // Wraps around in both directions (loop)
+                self.multimedia.preview_frame_idx = (self.multimedia.preview_frame_idx
+                    + self.multimedia.preview_step)
+                    .rem_euclid(frames.len() as i32);
             }
             ctx.request_repaint();
         }
 
         let idx = (self.multimedia.preview_frame_idx as usize).min(frames.len() - 1);
-        self.prepare_render_data_from(&frames[idx])
+        self.prepare_render_data_from(&frames[idx], idx)
     }
 
-    fn load_preview_frames_from_nbl(
+    pub(crate) fn load_preview_frames_from_nbl(
         &mut self,
         path: &std::path::Path,
     ) -> anyhow::Result<Vec<Vec<Particle>>> {
@@ -974,7 +2927,7 @@ impl NebulaToolsApp {
         let (bbox_min, bbox_max) = crate::player::recalculate_bbox(frames);
         let textures = build_texture_entries(&self.multimedia.texture_animation.textures);
         let header = NblHeader {
-            version: 1,
+            version: NBL_VERSION,
             target_fps: self.multimedia.target_fps,
             total_frames: frames.len() as u32,
             texture_count: textures.len() as u16,
@@ -1012,6 +2965,10 @@ impl NebulaToolsApp {
         if let Some(path) = saved_path {
             self.multimedia.preview_output_path = Some(path.to_string_lossy().to_string());
         }
+        self.multimedia.preview_counts = frames
+            .iter()
+            .map(|f| f.iter().filter(|p| p.color[3] > 0).count())
+            .collect();
         self.multimedia.preview_frames = Some(frames);
         self.multimedia.status_msg = Some(format!(
             "{}: {}",
@@ -1024,9 +2981,11 @@ impl NebulaToolsApp {
         self.multimedia.preview_playing = true;
         self.multimedia.preview_frame_idx = 0;
         self.multimedia.preview_timer = 0.0;
+        self.multimedia.preview_fps = self.multimedia.target_fps;
     }
 
     fn compile_multimedia_preview(&mut self, ctx: &egui::Context, source_only: bool) {
+        let _timing = TimingGuard::new(&self.debug_timings, |t| &mut t.compile_ms);
         self.multimedia.status_msg = Some(
             if source_only {
                 self.i18n.tr("multimedia_refreshing_source")
@@ -1038,6 +2997,7 @@ impl NebulaToolsApp {
 
         let mode = self.multimedia.mode;
         self.multimedia.source_image_preview = None;
+        self.multimedia.source_downscaled_from = None;
 
         let preview_path = if source_only {
             None
@@ -1054,79 +3014,55 @@ impl NebulaToolsApp {
         };
 
         let mut img: Option<DynamicImage> = None;
+        let mut text_cells: Vec<[u32; 4]> = Vec::new();
+        let mut font_fallback_chars = 0;
 
         if mode == 0 {
-            let text = &self.multimedia.text_input;
-            let mut font_data = None;
-
-            if self.multimedia.font_name.starts_with("system://") {
-                let family = &self.multimedia.font_name[9..];
-                use font_kit::family_name::FamilyName;
-                use font_kit::properties::Properties;
-                if let Ok(handle) = font_kit::source::SystemSource::new()
-                    .select_best_match(&[FamilyName::Title(family.to_string())], &Properties::new())
-                {
-                    font_data = match handle {
-                        font_kit::handle::Handle::Path { path, .. } => std::fs::read(&path).ok(),
-                        font_kit::handle::Handle::Memory { bytes, .. } => Some(bytes.to_vec()),
-                    };
-                }
-            } else if !self.multimedia.font_name.is_empty() {
-                font_data = std::fs::read(&self.multimedia.font_name).ok();
-            }
+            let variant = self.multimedia.font_variant.clone();
+            let font_data = match variant.as_ref().and_then(|v| v.path.as_ref()) {
+                Some(path) => std::fs::read(path).ok(),
+                None => load_font_data(&self.multimedia.font_name),
+            };
 
             if let Some(fd) = font_data {
-                if let Ok(font_ref) = ab_glyph::FontRef::try_from_slice(&fd) {
-                    let px_scale = PxScale::from(self.multimedia.font_size);
-                    let scale_font = font_ref.as_scaled(px_scale);
-                    let lines: Vec<&str> = text.lines().collect();
-
-                    let ascent = scale_font.ascent().ceil() as u32;
-                    let descent = scale_font.descent().floor() as i32;
-                    let line_height = (ascent as i32 - descent).abs() as u32;
-                    let line_gap: u32 = (line_height as f32 * 0.2).ceil() as u32;
-
-                    let mut max_w: u32 = 1;
-                    for line in &lines {
-                        let mut w: f32 = 0.0;
-                        let mut prev_glyph: Option<ab_glyph::GlyphId> = None;
-                        for ch in line.chars() {
-                            let glyph_id = scale_font.glyph_id(ch);
-                            if let Some(prev) = prev_glyph {
-                                w += scale_font.kern(prev, glyph_id);
-                            }
-                            w += scale_font.h_advance(glyph_id);
-                            prev_glyph = Some(glyph_id);
+                let index = variant.as_ref().map_or(0, |v| v.font_index);
+                let coordinates = variant.map(|v| v.coordinates).unwrap_or_default();
+                let text = &self.multimedia.text_input;
+                let font_size = self.multimedia.font_size;
+                let direction = self.multimedia.text_direction;
+                let letter_spacing = self.multimedia.letter_spacing;
+                let extra_line_gap = self.multimedia.extra_line_gap;
+                let fallbacks = fallback_fonts();
+                let rendered = ab_glyph::FontRef::try_from_slice_and_index(&fd, index)
+                    .ok()
+                    .map(|mut font| {
+                        for (tag, value) in &coordinates {
+                            font.set_variation(tag, *value);
                         }
-                        max_w = max_w.max(w.ceil() as u32);
-                    }
-
-                    let pad = (self.multimedia.font_size as u32).max(1);
-                    let canvas_w = max_w + pad * 4;
-                    let canvas_h = (lines.len() as u32 * line_height)
-                        + ((lines.len() as u32).saturating_sub(1) * line_gap)
-                        + pad * 4;
-                    let mut text_img = image::RgbaImage::new(canvas_w, canvas_h);
-
-                    let mut cur_y = (pad * 2) as i32;
-                    for line in lines {
-                        if !line.is_empty() {
-                            imageproc::drawing::draw_text_mut(
-                                &mut text_img,
-                                image::Rgba([255, 255, 255, 255]),
-                                (pad * 2) as i32,
-                                cur_y,
-                                px_scale,
-                                &font_ref,
-                                line,
-                            );
+                        render_text_image(
+                            &font,
+                            &fallbacks,
+                            text,
+                            font_size,
+                            direction,
+                            letter_spacing,
+                            extra_line_gap,
+                        )
+                    });
+                match rendered {
+                    Some((mut text_img, cells, fallback_count)) => {
+                        apply_text_render_mode(&mut text_img, self.multimedia.text_render_mode);
+                        if self.multimedia.text_outline_only {
+                            apply_outline_only(&mut text_img);
                         }
-                        cur_y += (line_height + line_gap) as i32;
+                        img = Some(DynamicImage::ImageRgba8(text_img));
+                        text_cells = cells;
+                        font_fallback_chars = fallback_count;
+                    }
+                    None => {
+                        self.multimedia.status_msg = Some("Failed to parse font".into());
+                        return;
                     }
-                    img = Some(DynamicImage::ImageRgba8(text_img));
-                } else {
-                    self.multimedia.status_msg = Some("Failed to parse font".into());
-                    return;
                 }
             } else {
                 self.multimedia.status_msg = Some("No valid Font selected/found".into());
@@ -1135,6 +3071,9 @@ impl NebulaToolsApp {
         } else if mode == 1 {
             if let Some(path) = &self.multimedia.media_path {
                 if let Ok(loaded) = image::open(path) {
+                    let (loaded, downscaled_from) =
+                        limit_source_size(loaded, self.multimedia.max_source_dimension);
+                    self.multimedia.source_downscaled_from = downscaled_from;
                     img = Some(loaded);
                 } else {
                     self.multimedia.status_msg = Some("Failed to load Image".into());
@@ -1145,12 +3084,21 @@ impl NebulaToolsApp {
                 return;
             }
         } else if mode == 2 {
-            // Video Mode: Extract first frame for source preview
+            // Video Mode: Extract first frame of the trimmed range for source preview
             if let Some(path) = &self.multimedia.media_path {
+                let scale = self.multimedia.video_scale.clamp(0.1, 1.0);
                 let output = Command::new("ffmpeg")
                     .args([
+                        "-ss",
+                        &format!("{:.6}", self.multimedia.video_start_sec),
                         "-i",
                         path,
+                        "-vf",
+                        &video_filter_chain(
+                            format!("scale=iw*{0}:ih*{0}", scale),
+                            self.multimedia.video_grayscale,
+                            self.multimedia.video_invert,
+                        ),
                         "-vframes",
                         "1",
                         "-f",
@@ -1206,14 +3154,42 @@ impl NebulaToolsApp {
             if source_only {
                 self.multimedia.status_msg =
                     Some(self.i18n.tr("multimedia_source_preview_updated").to_string());
+                self.note_font_fallback(font_fallback_chars);
                 return;
             }
 
             let mut base_particles = Vec::new();
             let (width, height) = img.dimensions();
             self.multimedia.last_source_size = Some([width, height]);
-            let cx = width as f32 / 2.0;
-            let cy = height as f32 / 2.0;
+            let tiling = if mode == 1 {
+                self.multimedia.tiling
+            } else {
+                TilingMode::None
+            };
+            let (tiles_x, tiles_y) = tiling.counts();
+            let depth_map = match (mode, &self.multimedia.depth_map_path) {
+                (1, Some(path)) => match image::open(path) {
+                    Ok(depth) => Some(depth.to_luma8()),
+                    Err(_) => {
+                        self.multimedia.status_msg = Some("Failed to load Depth Map".into());
+                        return;
+                    }
+                },
+                _ => None,
+            };
+            let mask = match (mode, &self.multimedia.mask_path) {
+                (1, Some(path)) => match image::open(path) {
+                    Ok(mask) => Some(load_placement_mask(mask, width, height)),
+                    Err(_) => {
+                        self.multimedia.status_msg = Some("Failed to load Mask Image".into());
+                        return;
+                    }
+                },
+                _ => None,
+            };
+            let depth_scale = self.multimedia.depth_scale;
+            let cx = (width * tiles_x) as f32 / 2.0;
+            let cy = (height * tiles_y) as f32 / 2.0;
             let dist_scale = self.multimedia.particle_size;
             let density = self.multimedia.density.max(0.000001);
 
@@ -1226,29 +3202,65 @@ impl NebulaToolsApp {
             };
             use rand::Rng;
             let mut rng = rand::thread_rng();
+            let grid = (self.multimedia.grid_snap_output && self.multimedia.grid_snap > 0.0)
+                .then_some(self.multimedia.grid_snap);
+            let mut grid_cells = std::collections::HashSet::new();
+
+            let text_bbox = text_cells_bbox(&text_cells);
+            let threshold = self.multimedia.brightness_threshold;
+            let image_pixel_passes = |sx: u32, sy: u32, pixel: image::Rgba<u8>| {
+                let luma =
+                    (pixel[0] as f32 * 0.299 + pixel[1] as f32 * 0.587 + pixel[2] as f32 * 0.114)
+                        / 255.0;
+                let masked_in = mask.as_ref().is_none_or(|m| m.get_pixel(sx, sy)[0] > 0);
+                pixel[3] != 0 && luma >= threshold && masked_in
+            };
+            let equaliser = (mode != 0 && self.multimedia.density_equalise).then(|| {
+                DensityEqualiser::new(
+                    width,
+                    height,
+                    self.multimedia.equalise_strength,
+                    |sx, sy| image_pixel_passes(sx, sy, img.get_pixel(sx, sy)),
+                )
+            });
 
-            for y in 0..height {
-                for x in 0..width {
-                    let pixel = img.get_pixel(x, y);
+            for y in 0..height * tiles_y {
+                for x in 0..width * tiles_x {
+                    let (sx, sy) = tiling.source_coord(x, y, width, height);
+                    let pixel = img.get_pixel(sx, sy);
+                    // Depth map may differ in size from the source; sample proportionally.
+                    let depth = depth_map.as_ref().map_or(0.0, |d| {
+                        let dx = (sx as u64 * d.width() as u64 / width as u64) as u32;
+                        let dy = (sy as u64 * d.height() as u64 / height as u64) as u32;
+                        d.get_pixel(dx, dy)[0] as f32 / 255.0 * depth_scale
+                    });
 
                     let is_filtered = if mode == 0 {
                         pixel[3] < 128
                     } else {
-                        let luma = (pixel[0] as f32 * 0.299
-                            + pixel[1] as f32 * 0.587
-                            + pixel[2] as f32 * 0.114)
-                            / 255.0;
-                        pixel[3] == 0 || luma < self.multimedia.brightness_threshold
+                        !image_pixel_passes(sx, sy, pixel)
                     };
 
                     if is_filtered {
                         continue;
                     }
 
+                    if let Some(eq) = &equaliser {
+                        if rng.gen::<f32>() >= eq.keep_probability(sx, sy) {
+                            continue;
+                        }
+                    }
+
                     if mode != 0 && density < 1.0 && rng.gen::<f32>() > density {
                         continue;
                     }
 
+                    let color = if mode == 0 {
+                        text_pixel_color(&self.multimedia, &text_cells, text_bbox, sx, sy, pixel[3])
+                    } else {
+                        [pixel[0], pixel[1], pixel[2], pixel[3]]
+                    };
+
                     for c in 0..copies_per_pixel {
                         let jx = if c == 0 {
                             0.0
@@ -1260,14 +3272,19 @@ impl NebulaToolsApp {
                         } else {
                             rng.gen_range(-0.5..0.5)
                         };
-                        let px = (x as f32 + jx - cx) * dist_scale;
-                        let py = -(y as f32 + jy - cy) * dist_scale;
+                        let mut px = (x as f32 + jx - cx) * dist_scale;
+                        let mut py = -(y as f32 + jy - cy) * dist_scale;
+                        if let Some(grid) = grid {
+                            let cell = ((px / grid).round(), (py / grid).round());
+                            (px, py) = (cell.0 * grid, cell.1 * grid);
+                            grid_cells.insert((cell.0 as i64, cell.1 as i64));
+                        }
                         let (px, py, pz) =
-                            apply_euler_rotation(px, py, 0.0, self.multimedia.rotation);
+                            apply_euler_rotation(px, py, depth, self.multimedia.rotation);
                         base_particles.push(Particle {
                             id,
                             pos: [px, py, pz],
-                            color: [pixel[0], pixel[1], pixel[2], pixel[3]],
+                            color,
                             size: self.multimedia.point_size,
                             tex_id: 0,
                             seq_index: 0,
@@ -1277,24 +3294,104 @@ impl NebulaToolsApp {
                 }
             }
 
-            let total_frames =
-                (self.multimedia.duration_secs * self.multimedia.target_fps as f32) as usize;
+            self.multimedia.grid_snap_cells =
+                grid.map(|_| (grid_cells.len(), base_particles.len()));
+
+            if self.multimedia.override_color {
+                let velocity_expr = crate::particleex::expand_macros(
+                    &self.multimedia.velocity_expr,
+                    &self.expression_macros,
+                );
+                if let Some(stmts) = crate::particleex::compile_expr(&velocity_expr) {
+                    apply_color_expression(&mut base_particles, &stmts);
+                }
+            }
+
+            if self.multimedia.color_from_expr {
+                let color_expr = crate::particleex::expand_macros(
+                    &self.multimedia.color_expr,
+                    &self.expression_macros,
+                );
+                if let Some(stmts) = crate::particleex::compile_expr(&color_expr) {
+                    apply_color_expression(&mut base_particles, &stmts);
+                }
+            }
+
+            if let Some(limit) = self.multimedia.palette_limit {
+                limit_palette(&mut base_particles, limit);
+            }
+
+            let hold_frames = self.multimedia.outro_hold_frames as usize;
+            let total_frames = (self.multimedia.duration_secs * self.multimedia.target_fps as f32)
+                as usize
+                + hold_frames;
             let intro_frames =
                 (self.multimedia.intro_duration * self.multimedia.target_fps as f32) as usize;
             let outro_frames =
                 (self.multimedia.outro_duration * self.multimedia.target_fps as f32) as usize;
 
-            let stmts = crate::particleex::compile_expr(&self.multimedia.velocity_expr);
+            let velocity_expr = crate::particleex::expand_macros(
+                &self.multimedia.velocity_expr,
+                &self.expression_macros,
+            );
+            let stmts = crate::particleex::compile_expr(&velocity_expr);
 
             let mut frames = Vec::with_capacity(total_frames);
             let mut runtime_particles = base_particles.clone();
+            let attract = self.multimedia.attract_mode == AttractMode::ToSource;
+            if attract {
+                let radius = cx.max(cy) * dist_scale;
+                for p in runtime_particles.iter_mut() {
+                    p.pos = random_in_sphere(&mut rng, radius);
+                }
+            }
+
+            let flow_field = match &self.multimedia.flow_field_path {
+                Some(path) => match image::open(path) {
+                    Ok(img) => Some(FlowField {
+                        img: img.to_rgb8(),
+                        half_extent: [cx * dist_scale, cy * dist_scale],
+                        strength: self.multimedia.flow_field_strength,
+                    }),
+                    Err(_) => {
+                        self.multimedia.status_msg = Some("Failed to load Flow Field".into());
+                        return;
+                    }
+                },
+                None => None,
+            };
+
+            let births = birth_frames(
+                &base_particles,
+                self.multimedia.birth_delay_mode,
+                self.multimedia.particle_birth_delay,
+                total_frames - hold_frames,
+                &mut rng,
+            );
+            let hold_start = total_frames.saturating_sub(outro_frames + hold_frames);
+            let hold_range = hold_start..hold_start + hold_frames;
 
             let mut pex_ctx = crate::particleex::ExprContext::new();
+            self.multimedia.expr_log.lock().unwrap().clear();
+            pex_ctx.log = Some(self.multimedia.expr_log.clone());
 
             for f_idx in 0..total_frames {
-                let t = f_idx as f64 / self.multimedia.target_fps as f64;
+                if hold_range.contains(&f_idx) {
+                    if let Some(last) = frames.last().cloned() {
+                        frames.push(last);
+                        continue;
+                    }
+                }
+                // The simulation is paused during the hold.
+                let sim_idx = if f_idx >= hold_range.end {
+                    f_idx - hold_frames
+                } else {
+                    f_idx
+                };
+                let t = sim_idx as f64 / self.multimedia.target_fps as f64;
+                pex_ctx.set_frame(sim_idx as i64);
 
-                for p in runtime_particles.iter_mut() {
+                for (p, rest) in runtime_particles.iter_mut().zip(&base_particles) {
                     pex_ctx.set("t", crate::particleex::Value::Num(t));
                     pex_ctx.set("x", crate::particleex::Value::Num(p.pos[0] as f64));
                     pex_ctx.set("y", crate::particleex::Value::Num(p.pos[1] as f64));
@@ -1329,9 +3426,20 @@ impl NebulaToolsApp {
                         p.color[3] = 0;
                     }
 
-                    let vx = pex_ctx.get("vx").as_num() as f32;
-                    let vy = pex_ctx.get("vy").as_num() as f32;
-                    let vz = pex_ctx.get("vz").as_num() as f32;
+                    let mut vx = pex_ctx.get("vx").as_num() as f32;
+                    let mut vy = pex_ctx.get("vy").as_num() as f32;
+                    let mut vz = pex_ctx.get("vz").as_num() as f32;
+                    if attract {
+                        let strength = self.multimedia.attract_strength;
+                        vx += (rest.pos[0] - p.pos[0]) * strength;
+                        vy += (rest.pos[1] - p.pos[1]) * strength;
+                        vz += (rest.pos[2] - p.pos[2]) * strength;
+                    }
+                    if let Some(field) = &flow_field {
+                        let [fx, fy] = field.sample(p.pos);
+                        vx += fx;
+                        vy += fy;
+                    }
 
                     p.pos[0] += vx;
                     p.pos[1] += vy;
@@ -1470,16 +3578,27 @@ impl NebulaToolsApp {
                     }
                 }
 
+                for (p, &birth) in frame_particles.iter_mut().zip(&births) {
+                    if f_idx < birth {
+                        p.color[3] = 0;
+                    }
+                }
+
                 frames.push(frame_particles);
             }
 
             if let Some(preview_path) = preview_path.as_deref() {
+                if self.multimedia.quantize_on_export {
+                    self.multimedia.quantize_palettes =
+                        Some(quantize_frames(&mut frames, self.multimedia.quantize_k));
+                }
                 match self.save_preview_frames_to_nbl(preview_path, &frames) {
                     Ok(preview_frames) => {
                         self.finalize_multimedia_preview_from_frames(
                             preview_frames,
                             Some(preview_path),
                         );
+                        self.note_font_fallback(font_fallback_chars);
                     }
                     Err(e) => {
                         self.multimedia.status_msg = Some(format!(
@@ -1493,6 +3612,19 @@ impl NebulaToolsApp {
         }
     }
 
+    /// Append to `status_msg` how many characters were drawn with a
+    /// fallback font.
+    fn note_font_fallback(&mut self, count: usize) {
+        if count == 0 {
+            return;
+        }
+        let note = format!("{} {}", count, self.i18n.tr("font_fallback_chars"));
+        self.multimedia.status_msg = Some(match self.multimedia.status_msg.take() {
+            Some(msg) => format!("{} ({})", msg, note),
+            None => note,
+        });
+    }
+
     fn compile_video_preview_via_nbl(
         &mut self,
         ctx: &egui::Context,
@@ -1521,7 +3653,22 @@ impl NebulaToolsApp {
         let particle_size = self.multimedia.particle_size;
         let point_size = self.multimedia.point_size;
         let rotation = self.multimedia.rotation;
-        let velocity_expr = self.multimedia.velocity_expr.clone();
+        let override_color = self.multimedia.override_color;
+        let video_scale = self.multimedia.video_scale;
+        let (grayscale, invert) = (
+            self.multimedia.video_grayscale,
+            self.multimedia.video_invert,
+        );
+        let (trim_start, trim_end) = (
+            self.multimedia.video_start_sec,
+            self.multimedia.video_end_sec,
+        );
+        let velocity_expr = crate::particleex::expand_macros(
+            &self.multimedia.velocity_expr,
+            &self.expression_macros,
+        );
+        let expr_log = self.multimedia.expr_log.clone();
+        expr_log.lock().unwrap().clear();
         let export_threads = self.multimedia.export_threads.max(1);
 
         let shared_progress = Arc::new(Mutex::new(0.0f32));
@@ -1553,8 +3700,20 @@ impl NebulaToolsApp {
                     return;
                 }
             };
-            let total_frames = (probe.duration * target_fps as f32).ceil().max(1.0) as u32;
-            let frame_size = (probe.width * probe.height * 3) as usize;
+            let (trim_start, trim_end) =
+                match resolve_video_trim(trim_start, trim_end, probe.duration) {
+                    Ok(trim) => trim,
+                    Err(e) => {
+                        *status_clone.lock().unwrap() = Some(format!("Invalid video trim: {}", e));
+                        *done_clone.lock().unwrap() = true;
+                        ctx_clone.request_repaint();
+                        return;
+                    }
+                };
+            let trimmed_secs = trim_end - trim_start;
+            let total_frames = (trimmed_secs * target_fps as f32).ceil().max(1.0) as u32;
+            let [width, height] = scaled_video_size(probe.width, probe.height, video_scale);
+            let frame_size = (width * height * 3) as usize;
             let ranges = split_frame_ranges(total_frames, export_threads);
             let keyframe_interval = target_fps.max(1) as u32;
 
@@ -1575,6 +3734,7 @@ impl NebulaToolsApp {
             for (worker_idx, (start_frame, end_frame)) in ranges.iter().copied().enumerate() {
                 let media_path = media_path.clone();
                 let velocity_expr = velocity_expr.clone();
+                let expr_log = expr_log.clone();
                 let thread_progress = shared_threads.clone();
                 let shared_progress_worker = shared_progress.clone();
                 let ctx_worker = ctx_clone.clone();
@@ -1590,9 +3750,20 @@ impl NebulaToolsApp {
                         let child = Command::new("ffmpeg")
                             .args([
                                 "-ss",
-                                &format!("{:.6}", start_frame as f64 / target_fps as f64),
+                                &format!(
+                                    "{:.6}",
+                                    trim_start as f64 + start_frame as f64 / target_fps as f64
+                                ),
+                                "-to",
+                                &format!("{:.6}", trim_end),
                                 "-i",
                                 &media_path,
+                                "-vf",
+                                &video_filter_chain(
+                                    format!("scale={}:{}", width, height),
+                                    grayscale,
+                                    invert,
+                                ),
                                 "-frames:v",
                                 &(end_frame - start_frame).to_string(),
                                 "-f",
@@ -1613,8 +3784,8 @@ impl NebulaToolsApp {
                         let mut stdout = child.stdout.take().expect("Failed to open stdout");
                         let mut buffer = vec![0u8; frame_size];
                         let mut generator = VideoParticleGenerator::new(
-                            probe.width,
-                            probe.height,
+                            width,
+                            height,
                             target_fps,
                             density,
                             brightness_threshold,
@@ -1624,6 +3795,11 @@ impl NebulaToolsApp {
                             &velocity_expr,
                             start_frame,
                         );
+                        generator.grayscale = grayscale;
+                        if override_color {
+                            generator.override_colors_from_expression();
+                        }
+                        generator.pex_ctx.log = Some(expr_log.clone());
                         let player = crate::player::PlayerState::default();
 
                         let mut provider = |frame_idx: u32| -> anyhow::Result<Vec<Particle>> {
@@ -1740,7 +3916,7 @@ impl NebulaToolsApp {
             }
 
             let header = NblHeader {
-                version: 1,
+                version: NBL_VERSION,
                 target_fps,
                 total_frames,
                 texture_count: 0,
@@ -1774,10 +3950,199 @@ impl NebulaToolsApp {
         });
     }
 
+    /// Save/load buttons for `.pex` expression files, plus the confirmation
+    /// preview of a file that was just loaded.
+    fn ui_expression_file(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if ui.button(self.i18n.tr("save_expression")).clicked() {
+                if let Some(path) = rfd::FileDialog::new()
+                    .add_filter("Particle Expression", &["pex"][..])
+                    .set_file_name("effect.pex")
+                    .save_file()
+                {
+                    if self.multimedia.format_on_save {
+                        self.multimedia.velocity_expr =
+                            crate::particleex::format_expr(&self.multimedia.velocity_expr);
+                    }
+                    self.multimedia.status_msg = Some(match self.write_expression_recipe(&path) {
+                        Ok(_) => format!("{} {}", self.i18n.tr("expression_saved"), path.display()),
+                        Err(e) => format!("{}: {}", self.i18n.tr("expression_save_failed"), e),
+                    });
+                }
+            }
+            if ui.button(self.i18n.tr("load_expression")).clicked() {
+                if let Some(path) = rfd::FileDialog::new()
+                    .add_filter("Particle Expression", &["pex"][..])
+                    .pick_file()
+                {
+                    match Self::read_expression_recipe(&path) {
+                        Ok(recipe) => self.multimedia.pending_recipe = Some(recipe),
+                        Err(e) => {
+                            self.multimedia.status_msg =
+                                Some(format!("{}: {}", self.i18n.tr("expression_load_failed"), e))
+                        }
+                    }
+                }
+            }
+        });
+
+        let Some(recipe) = &self.multimedia.pending_recipe else {
+            return;
+        };
+        let mut apply = false;
+        let mut discard = false;
+        ui.group(|ui| {
+            egui::ScrollArea::vertical()
+                .id_source("pex_preview")
+                .max_height(120.0)
+                .show(ui, |ui| {
+                    ui.label(egui::RichText::new(&recipe.velocity_expr).monospace());
+                    let mut names: Vec<&String> = recipe.macros.keys().collect();
+                    names.sort();
+                    for name in names {
+                        ui.label(
+                            egui::RichText::new(format!("${} = {}", name, recipe.macros[name]))
+                                .monospace()
+                                .weak(),
+                        );
+                    }
+                });
+            ui.horizontal(|ui| {
+                apply = ui.button(self.i18n.tr("expression_apply")).clicked();
+                discard = ui.button(self.i18n.tr("expression_discard")).clicked();
+            });
+        });
+        if apply {
+            if let Some(recipe) = self.multimedia.pending_recipe.take() {
+                self.multimedia.velocity_expr = recipe.velocity_expr;
+                self.expression_macros.extend(recipe.macros);
+            }
+        } else if discard {
+            self.multimedia.pending_recipe = None;
+        }
+    }
+
+    /// Define, edit and delete `$name` expression macros.
+    fn ui_macro_panel(&mut self, ui: &mut egui::Ui) {
+        ui.small(self.i18n.tr("macros_desc"));
+        let mut names: Vec<String> = self.expression_macros.keys().cloned().collect();
+        names.sort();
+        let mut remove = None;
+        for name in names {
+            ui.horizontal(|ui| {
+                ui.label(egui::RichText::new(format!("${}", name)).monospace());
+                if let Some(body) = self.expression_macros.get_mut(&name) {
+                    ui.add(
+                        egui::TextEdit::singleline(body)
+                            .font(egui::TextStyle::Monospace)
+                            .desired_width(ui.available_width() - 30.0),
+                    );
+                }
+                if ui.small_button("🗑").clicked() {
+                    remove = Some(name.clone());
+                }
+            });
+        }
+        if let Some(name) = remove {
+            self.expression_macros.remove(&name);
+        }
+        ui.horizontal(|ui| {
+            let (name, body) = &mut self.macro_draft;
+            ui.add(
+                egui::TextEdit::singleline(name)
+                    .desired_width(70.0)
+                    .hint_text(self.i18n.tr("macro_name")),
+            );
+            ui.add(
+                egui::TextEdit::singleline(body)
+                    .font(egui::TextStyle::Monospace)
+                    .desired_width(ui.available_width() - 40.0)
+                    .hint_text("$1 * cos(t)"),
+            );
+            let valid =
+                !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if ui.add_enabled(valid, egui::Button::new("➕")).clicked() {
+                self.expression_macros
+                    .insert(std::mem::take(name), std::mem::take(body));
+            }
+        });
+    }
+
+    /// Node-graph alternative to the text box; "Apply" replaces the script
+    /// with the graph's statements.
+    fn show_expression_node_editor(&mut self, ui: &mut egui::Ui) {
+        self.multimedia
+            .expr_graph
+            .ui(ui, self.i18n.tr("node_add"), self.i18n.tr("node_delete"));
+        let compiled = node_editor::graph_to_stmts(&self.multimedia.expr_graph);
+        ui.horizontal(|ui| {
+            let ready = matches!(&compiled, Ok(stmts) if !stmts.is_empty());
+            if ui
+                .add_enabled(ready, egui::Button::new(self.i18n.tr("node_apply")))
+                .clicked()
+            {
+                if let Ok(stmts) = &compiled {
+                    self.multimedia.velocity_expr = node_editor::stmts_to_source(stmts);
+                }
+            }
+            match &compiled {
+                Ok(stmts) if stmts.is_empty() => {
+                    ui.label(egui::RichText::new(self.i18n.tr("node_no_outputs")).weak());
+                }
+                Ok(stmts) => {
+                    ui.label(format!(
+                        "✔ {} {}",
+                        stmts.len(),
+                        self.i18n.tr("node_statements")
+                    ));
+                }
+                Err(e) => {
+                    ui.colored_label(egui::Color32::RED, e);
+                }
+            }
+        });
+    }
+
     fn show_expression_editor(&mut self, ui: &mut egui::Ui) {
         ui.vertical(|ui| {
-            ui.label(self.i18n.tr("velocity_expr"));
+            ui.horizontal(|ui| {
+                ui.label(self.i18n.tr("velocity_expr"));
+                ui.selectable_value(
+                    &mut self.multimedia.expr_node_mode,
+                    false,
+                    self.i18n.tr("expr_text_tab"),
+                );
+                ui.selectable_value(
+                    &mut self.multimedia.expr_node_mode,
+                    true,
+                    self.i18n.tr("expr_nodes_tab"),
+                );
+            });
+            if self.multimedia.expr_node_mode {
+                self.show_expression_node_editor(ui);
+                return;
+            }
+            self.ui_expression_file(ui);
+            ui.horizontal(|ui| {
+                if ui
+                    .button(self.i18n.tr("format_expr"))
+                    .on_hover_text(self.i18n.tr("format_expr_hint"))
+                    .clicked()
+                {
+                    self.multimedia.velocity_expr =
+                        crate::particleex::format_expr(&self.multimedia.velocity_expr);
+                }
+                ui.checkbox(
+                    &mut self.multimedia.format_on_save,
+                    self.i18n.tr("format_on_save"),
+                );
+            });
             let editor_id = ui.make_persistent_id("velocity_script_editor");
+            let lints = expression_lints(&self.multimedia.velocity_expr, &self.expression_macros);
+            let mut layouter = |ui: &egui::Ui, text: &str, wrap_width: f32| {
+                let job = lint_layout_job(ui, text, &lints, wrap_width);
+                ui.fonts(|f| f.layout_job(job))
+            };
             egui::Frame::canvas(ui.style()).show(ui, |ui| {
                 ui.add(
                     egui::TextEdit::multiline(&mut self.multimedia.velocity_expr)
@@ -1786,11 +4151,28 @@ impl NebulaToolsApp {
                         .desired_width(f32::INFINITY)
                         .desired_rows(4)
                         .lock_focus(true)
+                        .layouter(&mut layouter)
                         .hint_text("vx = cos(t*0.1); vy = sin(t*0.1); ..."),
                 );
             });
+            lint_messages(ui, &lints, &self.i18n);
 
             ui.add_space(4.0);
+            ui.collapsing(self.i18n.tr("formula_library"), |ui| {
+                if let Some(code) =
+                    super::formula_library::ui(ui, &self.i18n, &mut self.multimedia.formula_query)
+                {
+                    insert_at_cursor(
+                        ui.ctx(),
+                        editor_id,
+                        &mut self.multimedia.velocity_expr,
+                        code,
+                    );
+                }
+            });
+            ui.collapsing(self.i18n.tr("plugins"), |ui| {
+                plugins_panel(ui, &self.i18n);
+            });
             ui.collapsing(self.i18n.tr("expr_help"), |ui| {
                 ui.small(self.i18n.tr("expr_funcs_desc"));
                 ui.add_space(4.0);
@@ -1801,7 +4183,7 @@ impl NebulaToolsApp {
                     ui.horizontal_wrapped(|ui| {
                         let vars = [
                             "x", "y", "z", "vx", "vy", "vz", "id", "cr", "cg", "cb", "alpha",
-                            "mpsize", "t",
+                            "mpsize", "t", "frame",
                         ];
                         for v in vars {
                             if ui.button(egui::RichText::new(v).monospace()).clicked() {
@@ -1816,7 +4198,7 @@ impl NebulaToolsApp {
                     ui.horizontal_wrapped(|ui| {
                         let funcs = [
                             "sin()", "cos()", "tan()", "abs()", "random()", "pow()", "sqrt()",
-                            "lerp()", "clamp()",
+                            "lerp()", "clamp()", "print()",
                         ];
                         for f in funcs {
                             if ui.button(egui::RichText::new(f).monospace()).clicked() {
@@ -1825,7 +4207,67 @@ impl NebulaToolsApp {
                         }
                     });
                 });
+                ui.add_space(4.0);
+                ui.group(|ui| {
+                    ui.label(egui::RichText::new(self.i18n.tr("macros")).strong());
+                    macro_buttons(
+                        ui,
+                        &self.expression_macros,
+                        &mut self.multimedia.velocity_expr,
+                    );
+                });
+            });
+
+            ui.collapsing(self.i18n.tr("macros"), |ui| {
+                self.ui_macro_panel(ui);
             });
+
+            let events = self.multimedia.expr_log.lock().unwrap().clone();
+            let (failures, prints): (Vec<_>, Vec<_>) = events.iter().partition(|e| {
+                matches!(
+                    e,
+                    crate::particleex::ExprLogEvent::ExprAssertionFailed { .. }
+                )
+            });
+            ui.collapsing(self.i18n.tr("debug_log"), |ui| {
+                if prints.is_empty() {
+                    ui.small(self.i18n.tr("debug_log_empty"));
+                }
+                egui::ScrollArea::vertical()
+                    .id_source("expr_debug_log")
+                    .max_height(150.0)
+                    .stick_to_bottom(true)
+                    .show(ui, |ui| {
+                        for event in prints {
+                            ui.label(egui::RichText::new(event.to_string()).monospace().small());
+                        }
+                    });
+            });
+            let header = if failures.is_empty() {
+                self.i18n.tr("assertion_results").to_string()
+            } else {
+                format!("{} ({})", self.i18n.tr("assertion_results"), failures.len())
+            };
+            egui::CollapsingHeader::new(header)
+                .id_source("expr_assertions_header")
+                .show(ui, |ui| {
+                    if failures.is_empty() {
+                        ui.small(self.i18n.tr("assertions_passed"));
+                    }
+                    egui::ScrollArea::vertical()
+                        .id_source("expr_assertions")
+                        .max_height(150.0)
+                        .show(ui, |ui| {
+                            for event in failures {
+                                ui.label(
+                                    egui::RichText::new(event.to_string())
+                                        .monospace()
+                                        .small()
+                                        .color(ui.visuals().error_fg_color),
+                                );
+                            }
+                        });
+                });
         });
     }
 }
@@ -0,0 +1,498 @@
+use crate::particleex::{op_symbol, BinOp, Expr, Stmt};
+use eframe::egui;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// Scalar built-in functions offered as nodes, with their argument count.
+/// Matrix builders (`translate`, `rotate`, ...) stay text-only since node
+/// outputs are single numbers.
+const BUILTIN_FUNCTIONS: &[(&str, usize)] = &[
+    ("sin", 1),
+    ("cos", 1),
+    ("tan", 1),
+    ("asin", 1),
+    ("acos", 1),
+    ("atan", 1),
+    ("atan2", 2),
+    ("sinh", 1),
+    ("cosh", 1),
+    ("tanh", 1),
+    ("exp", 1),
+    ("log", 1),
+    ("log10", 1),
+    ("expm1", 1),
+    ("log1p", 1),
+    ("pow", 2),
+    ("sqrt", 1),
+    ("cbrt", 1),
+    ("hypot", 2),
+    ("ceil", 1),
+    ("floor", 1),
+    ("round", 1),
+    ("max", 2),
+    ("min", 2),
+    ("abs", 1),
+    ("signum", 1),
+    ("random", 0),
+    ("toRadians", 1),
+    ("toDegrees", 1),
+    ("clamp", 3),
+    ("lerp", 3),
+    ("lerpInt", 3),
+    ("floorMod", 2),
+    ("fma", 3),
+    ("copySign", 2),
+    ("getExponent", 1),
+    ("nextUp", 1),
+    ("nextDown", 1),
+    ("scalb", 2),
+];
+
+/// Per-particle variables a Variable node can read.
+const INPUT_VARIABLES: &[&str] = &[
+    "x", "y", "z", "vx", "vy", "vz", "id", "cr", "cg", "cb", "alpha", "mpsize", "t",
+];
+
+/// Variables an Output node can assign.
+const OUTPUT_TARGETS: &[&str] = &["vx", "vy", "vz", "cr", "cg", "cb", "alpha"];
+
+const MATH_OPS: &[BinOp] = &[
+    BinOp::Add,
+    BinOp::Sub,
+    BinOp::Mul,
+    BinOp::Div,
+    BinOp::Mod,
+    BinOp::Pow,
+];
+
+const NODE_WIDTH: f32 = 110.0;
+const HEADER_HEIGHT: f32 = 22.0;
+const PIN_SPACING: f32 = 18.0;
+const PIN_RADIUS: f32 = 5.0;
+
+#[derive(Debug, Clone)]
+pub enum ExprNodeKind {
+    Constant(f64),
+    Variable(&'static str),
+    Math(BinOp),
+    Function(&'static str, usize),
+    Output(&'static str),
+}
+
+impl ExprNodeKind {
+    fn input_count(&self) -> usize {
+        match self {
+            ExprNodeKind::Constant(_) | ExprNodeKind::Variable(_) => 0,
+            ExprNodeKind::Math(_) => 2,
+            ExprNodeKind::Function(_, arity) => *arity,
+            ExprNodeKind::Output(_) => 1,
+        }
+    }
+
+    fn title(&self) -> String {
+        match self {
+            ExprNodeKind::Constant(_) => "const".to_string(),
+            ExprNodeKind::Variable(_) => "var".to_string(),
+            ExprNodeKind::Math(op) => op_symbol(*op).to_string(),
+            ExprNodeKind::Function(name, _) => format!("{}()", name),
+            ExprNodeKind::Output(target) => format!("→ {}", target),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ExprNode {
+    pub id: usize,
+    pub kind: ExprNodeKind,
+    /// Top-left corner relative to the canvas.
+    pub pos: egui::Pos2,
+    /// Source node id feeding each input pin.
+    pub inputs: Vec<Option<usize>>,
+}
+
+impl ExprNode {
+    fn rect(&self, origin: egui::Vec2) -> egui::Rect {
+        let body = match self.kind {
+            ExprNodeKind::Constant(_) | ExprNodeKind::Variable(_) => 26.0,
+            _ => self.inputs.len().max(1) as f32 * PIN_SPACING + 4.0,
+        };
+        egui::Rect::from_min_size(
+            self.pos + origin,
+            egui::vec2(NODE_WIDTH, HEADER_HEIGHT + body),
+        )
+    }
+
+    fn input_pin(&self, origin: egui::Vec2, slot: usize) -> egui::Pos2 {
+        let rect = self.rect(origin);
+        egui::pos2(
+            rect.left(),
+            rect.top() + HEADER_HEIGHT + PIN_SPACING * (slot as f32 + 0.5),
+        )
+    }
+
+    fn output_pin(&self, origin: egui::Vec2) -> egui::Pos2 {
+        let rect = self.rect(origin);
+        egui::pos2(rect.right(), rect.top() + HEADER_HEIGHT / 2.0)
+    }
+}
+
+/// A velocity/colour expression drawn as a graph of nodes. Output nodes
+/// assign to particle variables; `graph_to_stmts` turns the graph into the
+/// same statements the text editor compiles to.
+#[derive(Debug, Clone, Default)]
+pub struct ExprGraph {
+    pub nodes: Vec<ExprNode>,
+    next_id: usize,
+    /// Node whose output pin was clicked and awaits an input pin.
+    pending_link: Option<usize>,
+}
+
+impl ExprGraph {
+    pub fn add_node(&mut self, kind: ExprNodeKind, pos: egui::Pos2) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        let inputs = vec![None; kind.input_count()];
+        self.nodes.push(ExprNode {
+            id,
+            kind,
+            pos,
+            inputs,
+        });
+        id
+    }
+
+    pub fn remove_node(&mut self, id: usize) {
+        self.nodes.retain(|n| n.id != id);
+        for node in &mut self.nodes {
+            for input in &mut node.inputs {
+                if *input == Some(id) {
+                    *input = None;
+                }
+            }
+        }
+        if self.pending_link == Some(id) {
+            self.pending_link = None;
+        }
+    }
+
+    /// Draw the graph on a canvas with an "add node" menu above it. Drag node
+    /// headers to move them, click an output pin and then an input pin to
+    /// connect, click a connected input pin to disconnect, and right-click a
+    /// header to delete.
+    pub fn ui(&mut self, ui: &mut egui::Ui, add_label: &str, delete_label: &str) {
+        let spawn = egui::pos2(12.0, 12.0 + (self.nodes.len() % 8) as f32 * 14.0);
+        ui.menu_button(add_label, |ui| {
+            let mut added = None;
+            if ui.button("const").clicked() {
+                added = Some(ExprNodeKind::Constant(1.0));
+            }
+            if ui.button("var").clicked() {
+                added = Some(ExprNodeKind::Variable("t"));
+            }
+            ui.menu_button("math", |ui| {
+                for &op in MATH_OPS {
+                    if ui.button(op_symbol(op)).clicked() {
+                        added = Some(ExprNodeKind::Math(op));
+                    }
+                }
+            });
+            ui.menu_button("fn", |ui| {
+                egui::ScrollArea::vertical()
+                    .max_height(240.0)
+                    .show(ui, |ui| {
+                        for &(name, arity) in BUILTIN_FUNCTIONS {
+                            if ui.button(format!("{}()", name)).clicked() {
+                                added = Some(ExprNodeKind::Function(name, arity));
+                            }
+                        }
+                    });
+            });
+            ui.menu_button("output", |ui| {
+                for &target in OUTPUT_TARGETS {
+                    if ui.button(target).clicked() {
+                        added = Some(ExprNodeKind::Output(target));
+                    }
+                }
+            });
+            if let Some(kind) = added {
+                self.add_node(kind, spawn);
+                ui.close_menu();
+            }
+        });
+
+        let (canvas, canvas_response) = ui.allocate_exact_size(
+            egui::vec2(ui.available_width(), 320.0),
+            egui::Sense::click(),
+        );
+        let origin = canvas.min.to_vec2();
+        let painter = ui.painter_at(canvas);
+        painter.rect_filled(canvas, 4.0, egui::Color32::from_gray(20));
+
+        let positions: HashMap<usize, egui::Pos2> = self
+            .nodes
+            .iter()
+            .map(|n| (n.id, n.output_pin(origin)))
+            .collect();
+        let link_stroke = egui::Stroke::new(2.0, egui::Color32::from_rgb(120, 180, 255));
+        for node in &self.nodes {
+            for (slot, source) in node.inputs.iter().enumerate() {
+                if let Some(from) = source.and_then(|id| positions.get(&id)) {
+                    draw_link(&painter, *from, node.input_pin(origin, slot), link_stroke);
+                }
+            }
+        }
+        if let Some(from) = self.pending_link.and_then(|id| positions.get(&id)) {
+            if let Some(pointer) = ui.ctx().pointer_hover_pos() {
+                draw_link(&painter, *from, pointer, link_stroke);
+            }
+        }
+
+        let mut delete = None;
+        for i in 0..self.nodes.len() {
+            let id = self.nodes[i].id;
+            let rect = self.nodes[i].rect(origin);
+            let header = egui::Rect::from_min_size(rect.min, egui::vec2(NODE_WIDTH, HEADER_HEIGHT));
+            let header_response = ui.interact(
+                header,
+                ui.id().with(("expr_node", id)),
+                egui::Sense::click_and_drag(),
+            );
+            if header_response.dragged() {
+                self.nodes[i].pos += header_response.drag_delta();
+            }
+            header_response.context_menu(|ui| {
+                if ui.button(delete_label).clicked() {
+                    delete = Some(id);
+                    ui.close_menu();
+                }
+            });
+
+            let fill = match self.nodes[i].kind {
+                ExprNodeKind::Output(_) => egui::Color32::from_rgb(70, 50, 40),
+                _ => egui::Color32::from_gray(45),
+            };
+            painter.rect_filled(rect, 4.0, fill);
+            painter.rect_stroke(rect, 4.0, egui::Stroke::new(1.0, egui::Color32::GRAY));
+            painter.text(
+                header.left_center() + egui::vec2(6.0, 0.0),
+                egui::Align2::LEFT_CENTER,
+                self.nodes[i].kind.title(),
+                egui::FontId::monospace(12.0),
+                egui::Color32::WHITE,
+            );
+
+            let body = egui::Rect::from_min_max(
+                rect.min + egui::vec2(6.0, HEADER_HEIGHT),
+                rect.max - egui::vec2(6.0, 4.0),
+            );
+            match &mut self.nodes[i].kind {
+                ExprNodeKind::Constant(value) => {
+                    ui.put(body, egui::DragValue::new(value).speed(0.01));
+                }
+                ExprNodeKind::Variable(name) => {
+                    ui.put(body, |ui: &mut egui::Ui| {
+                        egui::ComboBox::from_id_source(("expr_node_var", id))
+                            .width(body.width())
+                            .selected_text(*name)
+                            .show_ui(ui, |ui| {
+                                for &var in INPUT_VARIABLES {
+                                    ui.selectable_value(name, var, var);
+                                }
+                            })
+                            .response
+                    });
+                }
+                _ => {}
+            }
+
+            for slot in 0..self.nodes[i].inputs.len() {
+                let pin = self.nodes[i].input_pin(origin, slot);
+                let pin_response = ui.interact(
+                    egui::Rect::from_center_size(pin, egui::Vec2::splat(PIN_RADIUS * 3.0)),
+                    ui.id().with(("expr_node_in", id, slot)),
+                    egui::Sense::click(),
+                );
+                if pin_response.clicked() {
+                    self.nodes[i].inputs[slot] = self.pending_link.take();
+                }
+                let connected = self.nodes[i].inputs[slot].is_some();
+                draw_pin(&painter, pin, connected, pin_response.hovered());
+            }
+            if !matches!(self.nodes[i].kind, ExprNodeKind::Output(_)) {
+                let pin = self.nodes[i].output_pin(origin);
+                let pin_response = ui.interact(
+                    egui::Rect::from_center_size(pin, egui::Vec2::splat(PIN_RADIUS * 3.0)),
+                    ui.id().with(("expr_node_out", id)),
+                    egui::Sense::click(),
+                );
+                if pin_response.clicked() {
+                    self.pending_link = Some(id);
+                }
+                let active = self.pending_link == Some(id);
+                draw_pin(&painter, pin, active, pin_response.hovered());
+            }
+        }
+        if let Some(id) = delete {
+            self.remove_node(id);
+        }
+        if canvas_response.clicked() {
+            self.pending_link = None;
+        }
+    }
+}
+
+fn draw_link(painter: &egui::Painter, from: egui::Pos2, to: egui::Pos2, stroke: egui::Stroke) {
+    let bend = ((to.x - from.x).abs() * 0.5).max(30.0);
+    painter.add(egui::epaint::CubicBezierShape::from_points_stroke(
+        [
+            from,
+            from + egui::vec2(bend, 0.0),
+            to - egui::vec2(bend, 0.0),
+            to,
+        ],
+        false,
+        egui::Color32::TRANSPARENT,
+        stroke,
+    ));
+}
+
+fn draw_pin(painter: &egui::Painter, pos: egui::Pos2, filled: bool, hovered: bool) {
+    let color = if hovered {
+        egui::Color32::YELLOW
+    } else {
+        egui::Color32::from_rgb(120, 180, 255)
+    };
+    if filled {
+        painter.circle_filled(pos, PIN_RADIUS, color);
+    } else {
+        painter.circle_stroke(pos, PIN_RADIUS, egui::Stroke::new(1.5, color));
+    }
+}
+
+/// Temporary variable holding the value of an intermediate node.
+fn temp_var(id: usize) -> String {
+    format!("_n{}", id)
+}
+
+/// Turn the graph into statements, visiting nodes in topological order.
+/// Every math/function node feeding an output is evaluated once into a
+/// temporary (`_n<id>`), so shared inputs such as `random()` keep a single
+/// value; outputs then assign their target from it. Nodes that reach no
+/// output are ignored.
+pub fn graph_to_stmts(graph: &ExprGraph) -> Result<Vec<Stmt>, String> {
+    let by_id: HashMap<usize, &ExprNode> = graph.nodes.iter().map(|n| (n.id, n)).collect();
+
+    // Everything upstream of an output node.
+    let mut live = HashSet::new();
+    let mut stack: Vec<usize> = graph
+        .nodes
+        .iter()
+        .filter(|n| matches!(n.kind, ExprNodeKind::Output(_)))
+        .map(|n| n.id)
+        .collect();
+    while let Some(id) = stack.pop() {
+        if !live.insert(id) {
+            continue;
+        }
+        let node = by_id[&id];
+        for (slot, input) in node.inputs.iter().enumerate() {
+            match input {
+                Some(source) if by_id.contains_key(source) => stack.push(*source),
+                _ => {
+                    return Err(format!(
+                        "'{}' input {} is not connected",
+                        node.kind.title(),
+                        slot + 1
+                    ))
+                }
+            }
+        }
+    }
+
+    // Kahn's algorithm over the live subgraph.
+    let mut pending: HashMap<usize, usize> = live
+        .iter()
+        .map(|&id| (id, by_id[&id].inputs.len()))
+        .collect();
+    let mut consumers: HashMap<usize, Vec<usize>> = HashMap::new();
+    for &id in &live {
+        for source in by_id[&id].inputs.iter().flatten() {
+            consumers.entry(*source).or_default().push(id);
+        }
+    }
+    let mut ready: VecDeque<usize> = graph
+        .nodes
+        .iter()
+        .filter(|n| pending.get(&n.id) == Some(&0))
+        .map(|n| n.id)
+        .collect();
+    let mut order = Vec::with_capacity(live.len());
+    while let Some(id) = ready.pop_front() {
+        order.push(id);
+        for &consumer in consumers.get(&id).into_iter().flatten() {
+            let count = pending.get_mut(&consumer).unwrap();
+            *count -= 1;
+            if *count == 0 {
+                ready.push_back(consumer);
+            }
+        }
+    }
+    if order.len() != live.len() {
+        return Err("the node graph contains a cycle".to_string());
+    }
+
+    let operand = |id: usize| match &by_id[&id].kind {
+        ExprNodeKind::Constant(value) => Expr::Num(*value),
+        ExprNodeKind::Variable(name) => Expr::Var(name.to_string()),
+        _ => Expr::Var(temp_var(id)),
+    };
+    let mut stmts = Vec::new();
+    for id in order {
+        let node = by_id[&id];
+        let args: Vec<Expr> = node.inputs.iter().flatten().map(|&s| operand(s)).collect();
+        let (target, value) = match &node.kind {
+            ExprNodeKind::Constant(_) | ExprNodeKind::Variable(_) => continue,
+            ExprNodeKind::Math(op) => {
+                let mut args = args.into_iter();
+                let (lhs, rhs) = (args.next().unwrap(), args.next().unwrap());
+                (temp_var(id), Expr::BinOp(Box::new(lhs), *op, Box::new(rhs)))
+            }
+            ExprNodeKind::Function(name, _) => (temp_var(id), Expr::Call(name.to_string(), args)),
+            ExprNodeKind::Output(target) => (target.to_string(), args.into_iter().next().unwrap()),
+        };
+        stmts.push(Stmt::ExprStmt(Expr::Assign(target, Box::new(value))));
+    }
+    Ok(stmts)
+}
+
+/// Source text for statements produced by `graph_to_stmts`, one per line,
+/// so the graph can be handed back to the text editor.
+pub fn stmts_to_source(stmts: &[Stmt]) -> String {
+    stmts
+        .iter()
+        .map(|Stmt::ExprStmt(expr)| format!("{};", expr_source(expr)))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn expr_source(expr: &Expr) -> String {
+    match expr {
+        Expr::Num(n) => format!("{}", n),
+        Expr::Var(name) => name.clone(),
+        Expr::BinOp(lhs, op, rhs) => format!(
+            "({} {} {})",
+            expr_source(lhs),
+            op_symbol(*op),
+            expr_source(rhs)
+        ),
+        Expr::UnaryNeg(e) => format!("-{}", expr_source(e)),
+        Expr::UnaryNot(e) => format!("!{}", expr_source(e)),
+        Expr::Call(name, args) => format!(
+            "{}({})",
+            name,
+            args.iter().map(expr_source).collect::<Vec<_>>().join(", ")
+        ),
+        Expr::Index(base, idx) => format!("{}[{}]", expr_source(base), expr_source(idx)),
+        Expr::Assign(name, value) => format!("{} = {}", name, expr_source(value)),
+        Expr::Conditional(..) | Expr::MatrixBuilder(_) | Expr::MultiAssign(..) => String::new(),
+    }
+}
@@ -1,9 +1,17 @@
-use super::app::{build_texture_entries, NebulaToolsApp, PexCommandEntry};
+use super::app::{
+    build_texture_entries, LiveTestState, NebulaToolsApp, PexCommandEntry, TimingGuard,
+};
+use crate::i18n::I18nManager;
 use crate::particleex::{
     self, CompileEntry, ParticleexCommand, ParticleexCommandFormat, ParticleexEditorMode,
 };
-use crate::player::{self, NblHeader};
+use crate::player::{self, NblHeader, NBL_VERSION};
 use eframe::egui;
+use std::time::{Duration, Instant};
+
+const LIVE_TEST_DEBOUNCE: Duration = Duration::from_millis(100);
+/// 0–5 s at 60 fps.
+const LIVE_TEST_FRAMES: u32 = 300;
 
 impl NebulaToolsApp {
     pub(crate) fn show_particleex_workflow(&mut self, ctx: &egui::Context) {
@@ -54,6 +62,8 @@ impl NebulaToolsApp {
             }
         }
 
+        self.show_particleex_status_bar(ctx);
+
         egui::SidePanel::left("particleex_side")
             .resizable(true)
             .default_width(420.0)
@@ -73,6 +83,28 @@ impl NebulaToolsApp {
                             }
                         });
                     });
+                    ui.horizontal(|ui| {
+                        if ui
+                            .selectable_label(!self.pex.text_view, self.i18n.tr("pex_view_cards"))
+                            .clicked()
+                        {
+                            self.pex.text_view = false;
+                        }
+                        if ui
+                            .selectable_label(self.pex.text_view, self.i18n.tr("pex_view_text"))
+                            .clicked()
+                            && !self.pex.text_view
+                        {
+                            self.pex.raw_text = self
+                                .pex
+                                .entries
+                                .iter()
+                                .map(|e| e.command.as_str())
+                                .collect::<Vec<_>>()
+                                .join("\n");
+                            self.pex.text_view = true;
+                        }
+                    });
                     ui.separator();
 
                     if self.pex.show_help {
@@ -130,205 +162,244 @@ impl NebulaToolsApp {
                         ui.separator();
                     }
 
-                    let entry_count = self.pex.entries.len();
-                    let mut remove_idx: Option<usize> = None;
-
-                    for i in 0..entry_count {
-                        self.sync_entry_from_text_if_needed(i);
-                        ui.add_space(8.0);
-                        let entry_id = format!("pex_entry_{}", i);
-                        egui::Frame::none()
-                            .fill(ui.visuals().faint_bg_color)
-                            .inner_margin(10.0)
-                            .rounding(8.0)
-                            .stroke(egui::Stroke::new(
-                                1.0,
-                                ui.visuals().widgets.noninteractive.bg_stroke.color,
-                            ))
-                            .show(ui, |ui| {
-                                ui.horizontal(|ui| {
-                                    ui.checkbox(&mut self.pex.entries[i].enabled, "");
-                                    ui.label(
-                                        egui::RichText::new(format!("#{}", i + 1))
-                                            .strong()
-                                            .size(14.0),
-                                    );
-
-                                    if let Some(model) = &self.pex.entries[i].wizard_model {
+                    if self.pex.text_view {
+                        self.show_particleex_raw_editor(ui);
+                    } else {
+                        let entry_count = self.pex.entries.len();
+                        let mut remove_idx: Option<usize> = None;
+
+                        for i in 0..entry_count {
+                            self.sync_entry_from_text_if_needed(i);
+                            ui.add_space(8.0);
+                            let entry_id = format!("pex_entry_{}", i);
+                            egui::Frame::none()
+                                .fill(ui.visuals().faint_bg_color)
+                                .inner_margin(10.0)
+                                .rounding(8.0)
+                                .stroke(egui::Stroke::new(
+                                    1.0,
+                                    ui.visuals().widgets.noninteractive.bg_stroke.color,
+                                ))
+                                .show(ui, |ui| {
+                                    ui.horizontal(|ui| {
+                                        ui.checkbox(&mut self.pex.entries[i].enabled, "");
                                         ui.label(
-                                            egui::RichText::new(model.format_label())
-                                                .small()
-                                                .monospace(),
+                                            egui::RichText::new(format!("#{}", i + 1))
+                                                .strong()
+                                                .size(14.0),
                                         );
-                                    }
 
-                                    let status = self.entry_validation_text(i);
-                                    let status_color = if status.starts_with('✅') {
-                                        egui::Color32::from_rgb(80, 200, 80)
-                                    } else {
-                                        egui::Color32::from_rgb(255, 100, 100)
-                                    };
-                                    ui.add(
-                                        egui::Label::new(
-                                            egui::RichText::new(status).color(status_color),
-                                        )
-                                        .wrap(true),
-                                    );
+                                        if let Some(model) = &self.pex.entries[i].wizard_model {
+                                            ui.label(
+                                                egui::RichText::new(model.format_label())
+                                                    .small()
+                                                    .monospace(),
+                                            );
+                                        }
+
+                                        let status = self.entry_validation_text(i);
+                                        let status_color = if status.starts_with('✅') {
+                                            egui::Color32::from_rgb(80, 200, 80)
+                                        } else {
+                                            egui::Color32::from_rgb(255, 100, 100)
+                                        };
+                                        ui.add(
+                                            egui::Label::new(
+                                                egui::RichText::new(status).color(status_color),
+                                            )
+                                            .wrap(true),
+                                        );
 
-                                    ui.with_layout(
-                                        egui::Layout::right_to_left(egui::Align::Center),
-                                        |ui| {
-                                            if entry_count > 1 {
+                                        ui.with_layout(
+                                            egui::Layout::right_to_left(egui::Align::Center),
+                                            |ui| {
+                                                if entry_count > 1 {
+                                                    if ui
+                                                        .small_button("🗑")
+                                                        .on_hover_text(self.i18n.tr("remove"))
+                                                        .clicked()
+                                                    {
+                                                        self.pex.confirm_delete = Some(i);
+                                                    }
+                                                }
                                                 if ui
-                                                    .small_button("🗑")
-                                                    .on_hover_text(self.i18n.tr("remove"))
+                                                    .small_button("⛶")
+                                                    .on_hover_text(self.i18n.tr("fullscreen"))
                                                     .clicked()
                                                 {
-                                                    self.pex.confirm_delete = Some(i);
+                                                    self.pex.fullscreen_entry = Some(i);
                                                 }
-                                            }
-                                            if ui
-                                                .small_button("⛶")
-                                                .on_hover_text(self.i18n.tr("fullscreen"))
-                                                .clicked()
-                                            {
-                                                self.pex.fullscreen_entry = Some(i);
-                                            }
-                                        },
-                                    );
-                                });
-
-                                ui.add_space(6.0);
-                                ui.label(
-                                    egui::RichText::new(self.i18n.tr("pex_command_preview"))
-                                        .strong(),
-                                );
-                                let mut preview_text = self.pex.entries[i].command.clone();
-                                ui.add(
-                                    egui::TextEdit::multiline(&mut preview_text)
-                                        .desired_width(f32::INFINITY)
-                                        .desired_rows(3)
-                                        .interactive(false)
-                                        .code_editor(),
-                                );
-
-                                ui.add_space(6.0);
-                                egui::Grid::new(format!("{}_grid", entry_id))
-                                    .num_columns(2)
-                                    .spacing([8.0, 4.0])
-                                    .show(ui, |ui| {
-                                        ui.label(self.i18n.tr("pex_start_tick"));
-                                        ui.add(
-                                            egui::DragValue::new(
-                                                &mut self.pex.entries[i].start_tick,
-                                            )
-                                            .speed(1.0)
-                                            .clamp_range(0.0..=100000.0_f32)
-                                            .suffix(" tick"),
+                                            },
                                         );
-                                        ui.end_row();
+                                    });
 
-                                        ui.label(self.i18n.tr("pex_position"));
-                                        ui.horizontal(|ui| {
-                                            ui.add(
-                                                egui::DragValue::new(
-                                                    &mut self.pex.entries[i].position[0],
-                                                )
-                                                .speed(0.1)
-                                                .prefix("X:"),
-                                            );
+                                    ui.add_space(6.0);
+                                    ui.label(
+                                        egui::RichText::new(self.i18n.tr("pex_command_preview"))
+                                            .strong(),
+                                    );
+                                    let mut preview_text = self.pex.entries[i].command.clone();
+                                    ui.add(
+                                        egui::TextEdit::multiline(&mut preview_text)
+                                            .desired_width(f32::INFINITY)
+                                            .desired_rows(3)
+                                            .interactive(false)
+                                            .code_editor(),
+                                    );
+
+                                    ui.add_space(6.0);
+                                    egui::Grid::new(format!("{}_grid", entry_id))
+                                        .num_columns(2)
+                                        .spacing([8.0, 4.0])
+                                        .show(ui, |ui| {
+                                            ui.label(self.i18n.tr("pex_start_tick"));
                                             ui.add(
                                                 egui::DragValue::new(
-                                                    &mut self.pex.entries[i].position[1],
+                                                    &mut self.pex.entries[i].start_tick,
                                                 )
-                                                .speed(0.1)
-                                                .prefix("Y:"),
+                                                .speed(1.0)
+                                                .clamp_range(0.0..=100000.0_f32)
+                                                .suffix(" tick"),
                                             );
+                                            ui.end_row();
+
+                                            ui.label(self.i18n.tr("pex_position"));
+                                            ui.horizontal(|ui| {
+                                                ui.add(
+                                                    egui::DragValue::new(
+                                                        &mut self.pex.entries[i].position[0],
+                                                    )
+                                                    .speed(0.1)
+                                                    .prefix("X:"),
+                                                );
+                                                ui.add(
+                                                    egui::DragValue::new(
+                                                        &mut self.pex.entries[i].position[1],
+                                                    )
+                                                    .speed(0.1)
+                                                    .prefix("Y:"),
+                                                );
+                                                ui.add(
+                                                    egui::DragValue::new(
+                                                        &mut self.pex.entries[i].position[2],
+                                                    )
+                                                    .speed(0.1)
+                                                    .prefix("Z:"),
+                                                );
+                                            });
+                                            ui.end_row();
+
+                                            ui.label(self.i18n.tr("pex_duration"));
                                             ui.add(
                                                 egui::DragValue::new(
-                                                    &mut self.pex.entries[i].position[2],
+                                                    &mut self.pex.entries[i].duration_override,
                                                 )
-                                                .speed(0.1)
-                                                .prefix("Z:"),
+                                                .speed(1.0)
+                                                .clamp_range(0.0..=100000.0_f32)
+                                                .suffix(" tick"),
                                             );
+                                            ui.end_row();
+
+                                            ui.label(self.i18n.tr("pex_tick_rate"));
+                                            ui.horizontal(|ui| {
+                                                let rate =
+                                                    &mut self.pex.entries[i].tick_rate_override;
+                                                let mut custom = rate.is_some();
+                                                if ui.checkbox(&mut custom, "").changed() {
+                                                    *rate = custom.then_some(20.0);
+                                                }
+                                                match rate {
+                                                    Some(rate) => {
+                                                        ui.add(
+                                                            egui::DragValue::new(rate)
+                                                                .speed(1.0)
+                                                                .clamp_range(1.0..=1000.0_f32)
+                                                                .suffix(" tick/s"),
+                                                        );
+                                                    }
+                                                    None => {
+                                                        ui.label("20 tick/s");
+                                                    }
+                                                }
+                                            });
+                                            ui.end_row();
                                         });
-                                        ui.end_row();
 
-                                        ui.label(self.i18n.tr("pex_duration"));
-                                        ui.add(
-                                            egui::DragValue::new(
-                                                &mut self.pex.entries[i].duration_override,
-                                            )
-                                            .speed(1.0)
-                                            .clamp_range(0.0..=100000.0_f32)
-                                            .suffix(" tick"),
-                                        );
-                                        ui.end_row();
-                                    });
-
-                                ui.add_space(6.0);
-                                ui.separator();
-                                ui.add_space(4.0);
-
-                                let entry = &mut self.pex.entries[i];
-                                Self::show_texture_animation_editor(
-                                    ui,
-                                    self.i18n.tr("pex_texture_animation"),
-                                    self.i18n.tr("pex_texture_interval"),
-                                    self.i18n.tr("pex_texture_sequence"),
-                                    self.i18n.tr("pex_add_texture"),
-                                    self.i18n.tr("pex_reset_default_textures"),
-                                    &mut entry.textures,
-                                    &mut entry.texture_interval,
-                                );
-                            });
-                    }
+                                    ui.add_space(6.0);
+                                    ui.separator();
+                                    ui.add_space(4.0);
+
+                                    let entry = &mut self.pex.entries[i];
+                                    Self::show_texture_animation_editor(
+                                        ui,
+                                        self.i18n.tr("pex_texture_animation"),
+                                        self.i18n.tr("pex_texture_interval"),
+                                        self.i18n.tr("pex_texture_sequence"),
+                                        self.i18n.tr("pex_add_texture"),
+                                        self.i18n.tr("pex_reset_default_textures"),
+                                        &mut entry.textures,
+                                        &mut entry.texture_interval,
+                                    );
+                                });
+                        }
 
-                    if let Some(idx) = self.pex.confirm_delete {
-                        egui::Window::new(self.i18n.tr("pex_confirm_delete_title"))
-                            .collapsible(false)
-                            .resizable(false)
-                            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
-                            .show(ctx, |ui| {
-                                ui.label(self.i18n.tr("pex_confirm_delete_msg"));
-                                ui.add_space(8.0);
-                                ui.horizontal(|ui| {
-                                    if ui.button(self.i18n.tr("yes")).clicked() {
-                                        remove_idx = Some(idx);
-                                        self.pex.confirm_delete = None;
-                                    }
-                                    if ui.button(self.i18n.tr("no")).clicked() {
-                                        self.pex.confirm_delete = None;
-                                    }
+                        if let Some(idx) = self.pex.confirm_delete {
+                            egui::Window::new(self.i18n.tr("pex_confirm_delete_title"))
+                                .collapsible(false)
+                                .resizable(false)
+                                .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+                                .show(ctx, |ui| {
+                                    ui.label(self.i18n.tr("pex_confirm_delete_msg"));
+                                    ui.add_space(8.0);
+                                    ui.horizontal(|ui| {
+                                        if ui.button(self.i18n.tr("yes")).clicked() {
+                                            remove_idx = Some(idx);
+                                            self.pex.confirm_delete = None;
+                                        }
+                                        if ui.button(self.i18n.tr("no")).clicked() {
+                                            self.pex.confirm_delete = None;
+                                        }
+                                    });
                                 });
-                            });
-                    }
+                        }
 
-                    if let Some(idx) = remove_idx {
-                        self.pex.entries.remove(idx);
-                        self.pex.preview_frames = None;
-                    }
+                        if let Some(idx) = remove_idx {
+                            self.pex.entries.remove(idx);
+                            self.pex.preview_frames = None;
+                        }
 
-                    ui.add_space(12.0);
-                    if ui
-                        .add_sized(
-                            [ui.available_width(), 32.0],
-                            egui::Button::new(
-                                egui::RichText::new(format!(
-                                    "➕ {}",
-                                    self.i18n.tr("pex_add_command")
-                                ))
-                                .size(14.0),
-                            ),
-                        )
-                        .clicked()
-                    {
-                        self.pex.entries.push(PexCommandEntry::default());
+                        ui.add_space(12.0);
+                        if ui
+                            .add_sized(
+                                [ui.available_width(), 32.0],
+                                egui::Button::new(
+                                    egui::RichText::new(format!(
+                                        "➕ {}",
+                                        self.i18n.tr("pex_add_command")
+                                    ))
+                                    .size(14.0),
+                                ),
+                            )
+                            .clicked()
+                        {
+                            self.pex.entries.push(PexCommandEntry::default());
+                        }
                     }
 
                     ui.add_space(16.0);
                     ui.separator();
 
+                    ui.add_space(8.0);
+                    ui.horizontal(|ui| {
+                        ui.label(self.i18n.tr("pex_subdivisions"))
+                            .on_hover_text(self.i18n.tr("pex_subdivisions_desc"));
+                        ui.add(
+                            egui::DragValue::new(&mut self.pex.subdivisions)
+                                .clamp_range(1..=particleex::MAX_SUBDIVISIONS),
+                        );
+                        ui.label(format!("→ {} fps", 60 * self.pex.subdivisions));
+                    });
+
                     ui.add_space(8.0);
                     if ui
                         .add_sized(
@@ -463,7 +534,7 @@ impl NebulaToolsApp {
 
         let particles_data = if let Some(ref frames) = self.pex.preview_frames {
             let idx = (self.pex.preview_frame_idx as usize).min(frames.len().saturating_sub(1));
-            self.prepare_render_data_from(&frames[idx])
+            self.prepare_render_data_from(&frames[idx], idx)
         } else {
             vec![]
         };
@@ -567,6 +638,7 @@ impl NebulaToolsApp {
         let Some(model) = entry.wizard_model.as_mut() else {
             return;
         };
+        let live_test = &mut self.pex.live_test;
 
         let mut wizard_changed = false;
         egui::ScrollArea::vertical().show(ui, |ui| {
@@ -736,6 +808,12 @@ impl NebulaToolsApp {
                 }
             });
 
+            ui.add_space(8.0);
+            ui.group(|ui| {
+                let expr = model.speed_expr.as_deref().unwrap_or_default();
+                show_live_test(ui, live_test, expr, &self.i18n);
+            });
+
             ui.add_space(8.0);
             ui.group(|ui| {
                 ui.label(egui::RichText::new(self.i18n.tr("pex_command_preview")).strong());
@@ -755,6 +833,99 @@ impl NebulaToolsApp {
         }
     }
 
+    /// Plain-text view of all commands, one per line.
+    fn show_particleex_raw_editor(&mut self, ui: &mut egui::Ui) {
+        ui.add_space(8.0);
+        let response = ui.add(
+            egui::TextEdit::multiline(&mut self.pex.raw_text)
+                .desired_width(f32::INFINITY)
+                .desired_rows(24)
+                .code_editor()
+                .hint_text(self.i18n.tr("pex_raw_hint")),
+        );
+        if response.changed() {
+            self.sync_entries_from_raw_text();
+        }
+    }
+
+    /// Give the `i`-th non-empty line of the text view to entry `i`, adding
+    /// default entries for new lines and dropping entries past the last one.
+    /// Entries whose command is unchanged keep their wizard state.
+    fn sync_entries_from_raw_text(&mut self) {
+        let lines: Vec<&str> = self
+            .pex
+            .raw_text
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .collect();
+        self.pex
+            .entries
+            .resize_with(lines.len().max(1), PexCommandEntry::default);
+        for (i, entry) in self.pex.entries.iter_mut().enumerate() {
+            let line = lines.get(i).copied().unwrap_or_default();
+            if entry.command != line {
+                entry.command = line.to_string();
+                entry.editor_mode = ParticleexEditorMode::Text;
+                entry.wizard_model = None;
+                entry.parse_error = None;
+            }
+        }
+        // The compiled preview no longer matches the commands.
+        self.pex.preview_frames = None;
+    }
+
+    /// Frame count and duration of the compiled preview, and the parse errors
+    /// of the enabled commands.
+    fn show_particleex_status_bar(&mut self, ctx: &egui::Context) {
+        let errors: Vec<String> = (0..self.pex.entries.len())
+            .filter(|&i| {
+                self.pex.entries[i].enabled && !self.pex.entries[i].command.trim().is_empty()
+            })
+            .filter_map(|i| {
+                let status = self.entry_validation_text(i);
+                (!status.starts_with('✅'))
+                    .then(|| format!("#{} {}", i + 1, status.trim_start_matches("❌ ")))
+            })
+            .collect();
+        let (frame_count, duration) = match &self.pex.preview_frames {
+            Some(frames) => (
+                frames.len(),
+                frames.len() as f64 / self.pex.preview_fps as f64,
+            ),
+            None => (0, 0.0),
+        };
+
+        egui::TopBottomPanel::bottom("pex_status").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(format!("{}: {}", self.i18n.tr("total_frames"), frame_count));
+                ui.separator();
+                ui.label(format!("{}: {:.2}s", self.i18n.tr("duration"), duration));
+                ui.separator();
+                match errors.first() {
+                    None => {
+                        ui.colored_label(
+                            egui::Color32::from_rgb(80, 200, 80),
+                            self.i18n.tr("pex_no_parse_errors"),
+                        );
+                    }
+                    Some(first) => {
+                        ui.colored_label(
+                            egui::Color32::from_rgb(255, 100, 100),
+                            format!(
+                                "{} ({}): {}",
+                                self.i18n.tr("pex_parse_errors"),
+                                errors.len(),
+                                first
+                            ),
+                        )
+                        .on_hover_text(errors.join("\n"));
+                    }
+                }
+            });
+        });
+    }
+
     fn sync_entry_from_text_if_needed(&mut self, index: usize) {
         let entry = &mut self.pex.entries[index];
         if entry.wizard_model.is_none() {
@@ -772,21 +943,23 @@ impl NebulaToolsApp {
 
     fn entry_validation_text(&self, index: usize) -> String {
         let entry = &self.pex.entries[index];
+        let time_scale = entry.time_scale_override();
         if let Some(model) = &entry.wizard_model {
-            if let Ok(info) = particleex::validate_command_model(model) {
+            if let Ok(info) = particleex::validate_command_model(model, time_scale) {
                 return info;
             }
         }
         if let Some(err) = &entry.parse_error {
             return format!("❌ {}", err);
         }
-        match particleex::validate_command(&entry.command) {
+        match particleex::validate_command(&entry.command, time_scale) {
             Ok(info) => info,
             Err(err) => err,
         }
     }
 
     fn compile_particleex(&mut self) {
+        let _timing = TimingGuard::new(&self.debug_timings, |t| &mut t.compile_ms);
         let entries: Vec<CompileEntry> = self
             .pex
             .entries
@@ -803,6 +976,7 @@ impl NebulaToolsApp {
                 duration_override: e.duration_override as f64,
                 textures: e.textures.clone(),
                 texture_interval: e.texture_interval,
+                time_scale_override: e.time_scale_override(),
             })
             .collect();
 
@@ -811,7 +985,7 @@ impl NebulaToolsApp {
             return;
         }
 
-        match particleex::compile_entries(&entries) {
+        match particleex::compile_entries(&entries, self.pex.subdivisions) {
             Ok((frames, fps, textures)) => {
                 let frame_count = frames.len();
                 let duration = frame_count as f64 / fps as f64;
@@ -852,7 +1026,7 @@ impl NebulaToolsApp {
 
         let (bbox_min, bbox_max) = player::recalculate_bbox(&frames);
         let header = NblHeader {
-            version: 1,
+            version: NBL_VERSION,
             target_fps: self.pex.preview_fps,
             total_frames: frames.len() as u32,
             texture_count: textures.len() as u16,
@@ -877,3 +1051,50 @@ impl NebulaToolsApp {
         }
     }
 }
+
+/// Plot x(t), y(t), z(t) of a single test particle driven by `expr`,
+/// re-simulating 100 ms after the last edit.
+fn show_live_test(ui: &mut egui::Ui, live: &mut LiveTestState, expr: &str, i18n: &I18nManager) {
+    let latest = live.pending.as_ref().map_or(&live.source, |(s, _)| s);
+    if latest != expr {
+        live.pending = Some((expr.to_string(), Instant::now()));
+    }
+    if let Some((src, at)) = &live.pending {
+        if at.elapsed() >= LIVE_TEST_DEBOUNCE {
+            match particleex::simulate_test_particle(src, LIVE_TEST_FRAMES) {
+                Ok(trace) => {
+                    live.trace = trace;
+                    live.error = None;
+                }
+                Err(e) => live.error = Some(e),
+            }
+            live.source = src.clone();
+            live.pending = None;
+        } else {
+            ui.ctx().request_repaint_after(LIVE_TEST_DEBOUNCE);
+        }
+    }
+
+    ui.label(egui::RichText::new(i18n.tr("pex_live_test")).strong());
+    ui.label(egui::RichText::new(i18n.tr("pex_live_test_desc")).weak());
+    if let Some(err) = &live.error {
+        ui.colored_label(egui::Color32::from_rgb(255, 100, 100), err);
+    }
+
+    let axis = |k: usize| -> Vec<[f64; 2]> {
+        live.trace
+            .iter()
+            .enumerate()
+            .map(|(f, p)| [f as f64 / 60.0, p[k]])
+            .collect()
+    };
+    egui_plot::Plot::new("pex_live_test_plot")
+        .height(160.0)
+        .legend(egui_plot::Legend::default())
+        .allow_scroll(false)
+        .show(ui, |plot_ui| {
+            plot_ui.line(egui_plot::Line::new(axis(0)).name("x"));
+            plot_ui.line(egui_plot::Line::new(axis(1)).name("y"));
+            plot_ui.line(egui_plot::Line::new(axis(2)).name("z"));
+        });
+}
@@ -1,5 +1,11 @@
-use super::app::NebulaToolsApp;
+use super::app::{NebulaToolsApp, PlaybackMode, SortMode};
+use crate::player::{self, Particle, PlayerState};
+use crate::renderer::{PointShape, RenderMode, FLOATS_PER_PARTICLE};
 use eframe::egui;
+use std::sync::{Arc, Mutex};
+
+/// Rows listed in the Motion Analytics table.
+const MOTION_TABLE_ROWS: usize = 100;
 
 impl NebulaToolsApp {
     pub(crate) fn show_preview_workflow(&mut self, ctx: &egui::Context) {
@@ -125,6 +131,36 @@ impl NebulaToolsApp {
                         self.i18n.tr("particle_count"),
                         self.player.particles.len()
                     ));
+
+                    ui.add_space(10.0);
+                    ui.separator();
+                    egui::CollapsingHeader::new(self.i18n.tr("display_settings"))
+                        .default_open(true)
+                        .show(ui, |ui| {
+                            self.ui_point_shape(ui);
+                            self.ui_render_mode(ui);
+                            self.ui_sort_mode(ui);
+                            self.ui_prefetch_threads(ui);
+                        });
+                    ui.horizontal(|ui| {
+                        ui.checkbox(
+                            &mut self.show_velocity_arrows,
+                            self.i18n.tr("velocity_arrows"),
+                        );
+                        ui.label(self.i18n.tr("scale"));
+                        ui.add(
+                            egui::DragValue::new(&mut self.velocity_scale)
+                                .speed(0.1)
+                                .clamp_range(0.0..=1000.0),
+                        );
+                    });
+                    ui.add_space(6.0);
+                    self.ui_compare_section(ui);
+                    self.ui_selection_section(ui);
+                    self.ui_textures_section(ui);
+                    self.ui_frame_stats_section(ui);
+                    self.ui_motion_section(ui);
+                    self.ui_properties_section(ui);
                 }
 
                 if let Some(err) = &self.error_msg {
@@ -163,6 +199,7 @@ impl NebulaToolsApp {
 
                         ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                             ui.checkbox(&mut self.show_grid, "Grid");
+                            ui.toggle_value(&mut self.lasso_active, self.i18n.tr("lasso"));
                             ui.add_space(8.0);
                             ui.label(format!("/ {}", max_frame));
 
@@ -205,12 +242,1064 @@ impl NebulaToolsApp {
             });
 
         // --- Central Panel ---
-        let particles_data = self.prepare_render_data();
+        self.sync_compare_frame();
+        let particles = self.viewport_particles();
+        let frame = self.player.current_frame_idx.max(0) as usize;
+        let particles_data = self.prepare_render_data_from(&particles, frame);
+        let particles_data = self.blend_motion_frames(particles_data);
+        if self.compare.frame_diff {
+            self.update_frame_diff(&particles);
+        }
+        let overlay_data = self.prepare_compare_overlay();
+        let highlight_data = self.prepare_selection_highlight(&particles);
+        let trail_data = self.prepare_trail_lines(&particles);
         egui::CentralPanel::default().show(ctx, |ui| {
-            self.paint_3d_viewport(ui, ctx, &particles_data);
+            let response = self.paint_3d_viewport_layers(
+                ui,
+                ctx,
+                &particles_data,
+                &overlay_data,
+                &highlight_data,
+                &trail_data,
+            );
+            let rect = response.rect;
+
+            if response.clicked() {
+                if let Some(pos) = response.interact_pointer_pos() {
+                    let shift = ui.input(|i| i.modifiers.shift);
+                    self.pick_particle(&particles, rect, pos, shift);
+                }
+            }
+
+            // Lasso selection
+            if self.lasso_active {
+                if response.dragged_by(egui::PointerButton::Primary) {
+                    if let Some(pos) = response.interact_pointer_pos() {
+                        if self
+                            .lasso_points
+                            .last()
+                            .is_none_or(|last| last.distance(pos) > 2.0)
+                        {
+                            self.lasso_points.push(pos);
+                        }
+                    }
+                }
+                if response.drag_stopped() {
+                    self.select_in_lasso(&particles, rect);
+                    self.lasso_points.clear();
+                }
+                if self.lasso_points.len() > 1 {
+                    let mut path = self.lasso_points.clone();
+                    path.push(path[0]);
+                    ui.painter_at(rect).extend(egui::Shape::dashed_line(
+                        &path,
+                        egui::Stroke::new(1.5, egui::Color32::from_rgb(255, 215, 50)),
+                        6.0,
+                        4.0,
+                    ));
+                }
+            } else {
+                self.lasso_points.clear();
+            }
 
             // Extra particle count overlay
             // (FPS is handled inside paint_3d_viewport)
+
+            if self.show_velocity_arrows {
+                self.draw_velocity_arrows(ui, &particles, rect);
+            }
+            if self.compare.frame_diff {
+                self.draw_frame_diff_lines(ui, rect);
+            }
+
+            // Motion blur toggle (bottom-left)
+            let blur_rect = egui::Rect::from_min_size(
+                rect.left_bottom() + egui::vec2(10.0, -30.0),
+                egui::vec2(260.0, 20.0),
+            );
+            ui.put(blur_rect, |ui: &mut egui::Ui| {
+                ui.horizontal(|ui| {
+                    let mut enabled = self.playback_mode != PlaybackMode::Normal;
+                    if ui
+                        .checkbox(&mut enabled, self.i18n.tr("motion_blur"))
+                        .changed()
+                    {
+                        self.playback_mode = if enabled {
+                            PlaybackMode::FrameBlend(4)
+                        } else {
+                            PlaybackMode::Normal
+                        };
+                    }
+                    if let PlaybackMode::FrameBlend(samples) = &mut self.playback_mode {
+                        ui.label(self.i18n.tr("samples"));
+                        ui.add(egui::DragValue::new(samples).clamp_range(2..=16));
+                    }
+                })
+                .response
+            });
+
+            // Diff toggle (only when a comparison file is loaded), below the
+            // background colour picker and MSAA selector
+            if self.compare.secondary_player.is_some() {
+                let toggle_rect = egui::Rect::from_min_size(
+                    rect.right_top() + egui::vec2(-70.0, 35.0),
+                    egui::vec2(60.0, 20.0),
+                );
+                ui.put(
+                    toggle_rect,
+                    egui::Checkbox::new(&mut self.compare.enabled, "Diff"),
+                );
+            }
+        });
+    }
+
+    fn ui_point_shape(&mut self, ui: &mut egui::Ui) {
+        let shape_label = |shape: PointShape| match shape {
+            PointShape::Circle => self.i18n.tr("shape_circle"),
+            PointShape::Square => self.i18n.tr("shape_square"),
+            PointShape::Diamond => self.i18n.tr("shape_diamond"),
+            PointShape::Star(_) => self.i18n.tr("shape_star"),
+            PointShape::Cross => self.i18n.tr("shape_cross"),
+        };
+        ui.horizontal(|ui| {
+            ui.label(self.i18n.tr("point_shape"));
+            egui::ComboBox::from_id_source("point_shape")
+                .selected_text(shape_label(self.point_shape))
+                .show_ui(ui, |ui| {
+                    for shape in [
+                        PointShape::Circle,
+                        PointShape::Square,
+                        PointShape::Diamond,
+                        PointShape::Star(5),
+                        PointShape::Cross,
+                    ] {
+                        let selected = std::mem::discriminant(&self.point_shape)
+                            == std::mem::discriminant(&shape);
+                        if ui.selectable_label(selected, shape_label(shape)).clicked() && !selected
+                        {
+                            self.point_shape = shape;
+                        }
+                    }
+                });
+            if let PointShape::Star(points) = &mut self.point_shape {
+                ui.add(egui::DragValue::new(points).clamp_range(3..=12));
+            }
+        });
+    }
+
+    fn ui_sort_mode(&mut self, ui: &mut egui::Ui) {
+        let label = |mode: SortMode| match mode {
+            SortMode::None => self.i18n.tr("sort_none"),
+            SortMode::FrontToBack => self.i18n.tr("sort_front_to_back"),
+            SortMode::BackToFront => self.i18n.tr("sort_back_to_front"),
+            SortMode::Random => self.i18n.tr("sort_random"),
+            SortMode::Hue => self.i18n.tr("sort_hue"),
+            SortMode::Brightness => self.i18n.tr("sort_brightness"),
+            SortMode::ZDepth => self.i18n.tr("sort_z_depth"),
+        };
+        ui.horizontal(|ui| {
+            ui.label(self.i18n.tr("sort_mode"));
+            egui::ComboBox::from_id_source("sort_mode")
+                .selected_text(label(self.sort_mode))
+                .show_ui(ui, |ui| {
+                    for mode in [
+                        SortMode::None,
+                        SortMode::FrontToBack,
+                        SortMode::BackToFront,
+                        SortMode::Random,
+                        SortMode::Hue,
+                        SortMode::Brightness,
+                        SortMode::ZDepth,
+                    ] {
+                        ui.selectable_value(&mut self.sort_mode, mode, label(mode));
+                    }
+                });
+        });
+    }
+
+    fn ui_prefetch_threads(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label(self.i18n.tr("prefetch_threads"));
+            let changed = ui
+                .add(egui::DragValue::new(&mut self.config.prefetch_threads).clamp_range(0..=8))
+                .on_hover_text(self.i18n.tr("prefetch_threads_desc"))
+                .changed();
+            if changed {
+                self.player.prefetch_threads = self.config.prefetch_threads;
+                self.save_config();
+            }
+        });
+    }
+
+    fn ui_render_mode(&mut self, ui: &mut egui::Ui) {
+        let heatmap = RenderMode::HeatMap {
+            low_color: [0.1, 0.2, 1.0, 1.0],
+            high_color: [1.0, 0.2, 0.1, 1.0],
+            radius: 1.0,
+        };
+        let trail = RenderMode::Trail {
+            history_frames: 8,
+            tail_alpha_factor: 0.7,
+        };
+        ui.horizontal(|ui| {
+            ui.label(self.i18n.tr("render_mode"));
+            let is_heatmap = matches!(self.render_mode, RenderMode::HeatMap { .. });
+            let is_trail = matches!(self.render_mode, RenderMode::Trail { .. });
+            egui::ComboBox::from_id_source("render_mode")
+                .selected_text(if is_heatmap {
+                    self.i18n.tr("heatmap")
+                } else if is_trail {
+                    self.i18n.tr("render_trail")
+                } else {
+                    self.i18n.tr("render_normal")
+                })
+                .show_ui(ui, |ui| {
+                    if ui
+                        .selectable_label(
+                            self.render_mode == RenderMode::Normal,
+                            self.i18n.tr("render_normal"),
+                        )
+                        .clicked()
+                    {
+                        self.render_mode = RenderMode::Normal;
+                    }
+                    if ui
+                        .selectable_label(is_heatmap, self.i18n.tr("heatmap"))
+                        .clicked()
+                        && !is_heatmap
+                    {
+                        self.render_mode = heatmap;
+                    }
+                    if ui
+                        .selectable_label(is_trail, self.i18n.tr("render_trail"))
+                        .clicked()
+                        && !is_trail
+                    {
+                        self.render_mode = trail;
+                    }
+                });
+        });
+        if let RenderMode::HeatMap {
+            low_color,
+            high_color,
+            radius,
+        } = &mut self.render_mode
+        {
+            ui.horizontal(|ui| {
+                ui.color_edit_button_rgba_unmultiplied(low_color);
+                ui.label("→");
+                ui.color_edit_button_rgba_unmultiplied(high_color);
+                ui.label(self.i18n.tr("radius"));
+                ui.add(
+                    egui::DragValue::new(radius)
+                        .speed(0.05)
+                        .clamp_range(0.01..=1000.0),
+                );
+            });
+        }
+        if let RenderMode::Trail {
+            history_frames,
+            tail_alpha_factor,
+        } = &mut self.render_mode
+        {
+            ui.horizontal(|ui| {
+                ui.label(self.i18n.tr("trail_history"));
+                ui.add(egui::DragValue::new(history_frames).clamp_range(1..=64));
+                ui.label(self.i18n.tr("trail_fade"));
+                ui.add(
+                    egui::DragValue::new(tail_alpha_factor)
+                        .speed(0.01)
+                        .clamp_range(0.0..=1.0),
+                );
+            });
+        }
+    }
+
+    fn ui_compare_section(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new(self.i18n.tr("compare"))
+            .default_open(false)
+            .show(ui, |ui| {
+                ui.label(egui::RichText::new(self.i18n.tr("compare_desc")).weak());
+                ui.horizontal(|ui| {
+                    if ui.button(self.i18n.tr("compare_load")).clicked() {
+                        self.handle_load_compare();
+                    }
+                    if self.compare.secondary_player.is_some()
+                        && ui.button(self.i18n.tr("compare_clear")).clicked()
+                    {
+                        self.compare.secondary_player = None;
+                        self.compare.secondary_path = None;
+                    }
+                });
+                if let Some(path) = &self.compare.secondary_path {
+                    ui.label(
+                        egui::RichText::new(
+                            path.file_name()
+                                .map(|n| n.to_string_lossy().into_owned())
+                                .unwrap_or_default(),
+                        )
+                        .monospace(),
+                    );
+                }
+                ui.horizontal(|ui| {
+                    ui.label(self.i18n.tr("compare_color"));
+                    ui.color_edit_button_srgba_unmultiplied(&mut self.compare.overlay_color);
+                });
+                ui.separator();
+                ui.checkbox(
+                    &mut self.compare.frame_diff,
+                    self.i18n.tr("compare_adjacent"),
+                )
+                .on_hover_text(self.i18n.tr("compare_adjacent_hint"));
+                if let Some((_, diff)) = self
+                    .compare
+                    .frame_diff_cache
+                    .as_ref()
+                    .filter(|_| self.compare.frame_diff)
+                {
+                    ui.horizontal(|ui| {
+                        ui.colored_label(FRAME_DIFF_ADDED, format!("+{}", diff.added.len()));
+                        ui.colored_label(FRAME_DIFF_REMOVED, format!("-{}", diff.removed.len()));
+                        ui.colored_label(FRAME_DIFF_MOVED, format!("~{}", diff.moved.len()));
+                    });
+                }
+            });
+    }
+
+    fn ui_frame_stats_section(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new(self.i18n.tr("frame_stats"))
+            .default_open(false)
+            .show(ui, |ui| {
+                let frame = self.player.current_frame_idx.max(0) as u32;
+                if let Ok(stats) = self.player.particle_statistics(frame) {
+                    let v3 = |v: [f32; 3]| format!("({:.2}, {:.2}, {:.2})", v[0], v[1], v[2]);
+                    egui::Grid::new("frame_stats_grid")
+                        .num_columns(2)
+                        .spacing([8.0, 4.0])
+                        .show(ui, |ui| {
+                            ui.label(self.i18n.tr("particle_count"));
+                            ui.label(stats.particle_count.to_string());
+                            ui.end_row();
+                            ui.label(self.i18n.tr("bbox"));
+                            ui.label(format!("{} → {}", v3(stats.bbox_min), v3(stats.bbox_max)));
+                            ui.end_row();
+                            ui.label(self.i18n.tr("mean_pos"));
+                            ui.label(v3(stats.mean_pos));
+                            ui.end_row();
+                            ui.label(self.i18n.tr("mean_size"));
+                            ui.label(format!("{:.4}", stats.mean_size));
+                            ui.end_row();
+                            ui.label(self.i18n.tr("unique_colors"));
+                            ui.label(stats.unique_colors.to_string());
+                            ui.end_row();
+                        });
+                }
+
+                ui.add_space(4.0);
+                if ui.button(self.i18n.tr("analyse_all_frames")).clicked() {
+                    self.animation_stats = self.player.animation_statistics();
+                }
+                if !self.animation_stats.is_empty() {
+                    let counts = self.animation_stats.iter().map(|s| s.particle_count);
+                    let peak = counts.clone().max().unwrap_or(0);
+                    let mean = counts.sum::<usize>() as f32 / self.animation_stats.len() as f32;
+                    ui.label(format!(
+                        "{}: {}  {}: {:.0}",
+                        self.i18n.tr("peak_particles"),
+                        peak,
+                        self.i18n.tr("mean_particles"),
+                        mean
+                    ));
+                }
+            });
+    }
+
+    /// Compute `motion_stats` on a worker thread; `ui_motion_section` shows
+    /// its progress and picks up the result.
+    fn start_motion_analysis(&mut self) {
+        let progress = Arc::new(Mutex::new(0.0f32));
+        let result = Arc::new(Mutex::new(None));
+        self.motion_job = Some((progress.clone(), result.clone()));
+        // Frames the edit workflow already decoded save a re-read.
+        let frames = self.edit.decoded_frames.clone();
+        let path = self.player.file_path.clone();
+        std::thread::spawn(move || {
+            let stats = match (frames, path) {
+                (Some(frames), _) => player::compute_motion_stats(&frames),
+                (None, Some(path)) => player::file_motion_stats(&path, &progress),
+                (None, None) => Vec::new(),
+            };
+            if let Ok(mut slot) = result.lock() {
+                *slot = Some(stats);
+            }
         });
     }
+
+    /// Per-particle path length and speed over the whole file, longest paths
+    /// first. Clicking an id selects that particle in the viewport.
+    fn ui_motion_section(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new(self.i18n.tr("motion_analytics"))
+            .default_open(false)
+            .show(ui, |ui| {
+                if let Some((progress, result)) = &self.motion_job {
+                    if let Some(stats) = result.lock().ok().and_then(|mut r| r.take()) {
+                        self.motion_stats = stats;
+                        self.motion_job = None;
+                    } else {
+                        let pct = progress.lock().map(|p| *p).unwrap_or(0.0);
+                        ui.add(egui::ProgressBar::new(pct).show_percentage().animate(true));
+                        ui.ctx().request_repaint();
+                        return;
+                    }
+                }
+                if ui.button(self.i18n.tr("analyse_motion")).clicked() {
+                    self.start_motion_analysis();
+                }
+                if self.motion_stats.is_empty() {
+                    return;
+                }
+                let fps = self
+                    .player
+                    .header
+                    .as_ref()
+                    .map_or(1.0, |h| h.target_fps.max(1) as f32);
+                let total: f32 = self.motion_stats.iter().map(|s| s.total_distance).sum();
+                ui.label(format!(
+                    "{}: {}  {}: {:.2}",
+                    self.i18n.tr("motion_tracks"),
+                    self.motion_stats.len(),
+                    self.i18n.tr("motion_mean_distance"),
+                    total / self.motion_stats.len() as f32
+                ));
+                egui::ScrollArea::vertical()
+                    .id_source("motion_stats_scroll")
+                    .max_height(200.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("motion_stats_grid")
+                            .num_columns(5)
+                            .striped(true)
+                            .spacing([8.0, 2.0])
+                            .show(ui, |ui| {
+                                ui.strong("id");
+                                ui.strong(self.i18n.tr("motion_distance"));
+                                ui.strong(self.i18n.tr("motion_max_speed"));
+                                ui.strong(self.i18n.tr("motion_avg_speed"));
+                                ui.strong(self.i18n.tr("motion_lifetime"));
+                                ui.end_row();
+                                for stats in self.motion_stats.iter().take(MOTION_TABLE_ROWS) {
+                                    let selected = self.selected_ids.contains(&stats.id);
+                                    if ui
+                                        .selectable_label(selected, stats.id.to_string())
+                                        .clicked()
+                                    {
+                                        self.selected_ids.clear();
+                                        self.selected_ids.insert(stats.id);
+                                    }
+                                    ui.label(format!("{:.2}", stats.total_distance));
+                                    ui.label(format!("{:.2}", stats.max_speed * fps));
+                                    ui.label(format!("{:.2}", stats.avg_speed * fps));
+                                    ui.label(stats.lifetime_frames.to_string());
+                                    ui.end_row();
+                                }
+                            });
+                    });
+            });
+    }
+
+    /// Atlas textures of the loaded file, with a thumbnail each and a button
+    /// to point an entry at a different image. The renderer re-uploads the
+    /// atlas on the next frame; the NBL file itself is not rewritten.
+    fn ui_textures_section(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new(self.i18n.tr("textures_panel"))
+            .default_open(false)
+            .show(ui, |ui| {
+                if self.player.textures.is_empty() {
+                    ui.label(egui::RichText::new(self.i18n.tr("no_textures")).weak());
+                    return;
+                }
+                let mut replace = None;
+                for (i, tex) in self.player.textures.iter().enumerate() {
+                    let thumb = self
+                        .texture_thumbnails
+                        .entry(tex.path.clone())
+                        .or_insert_with(|| load_thumbnail(ui.ctx(), &tex.path));
+                    ui.horizontal(|ui| {
+                        match thumb {
+                            Some(handle) => {
+                                ui.add(egui::Image::new(&*handle).max_size(egui::vec2(32.0, 32.0)));
+                            }
+                            None => {
+                                ui.add_sized([32.0, 32.0], egui::Label::new("?"));
+                            }
+                        }
+                        ui.vertical(|ui| {
+                            ui.label(egui::RichText::new(&tex.path).monospace())
+                                .on_hover_text(&tex.path);
+                            ui.horizontal(|ui| {
+                                ui.label(format!("[{}] {}×{}", i, tex.rows, tex.cols));
+                                if ui.button(self.i18n.tr("replace_texture")).clicked() {
+                                    replace = Some(i);
+                                }
+                            });
+                        });
+                    });
+                }
+                if let Some(i) = replace {
+                    if let Some(path) = rfd::FileDialog::new()
+                        .add_filter("Image", &["png", "jpg", "jpeg"])
+                        .pick_file()
+                    {
+                        self.player.textures[i].path = path.to_string_lossy().to_string();
+                    }
+                }
+            });
+    }
+
+    /// Author, description and tags from the file's `.nbmeta` sidecar.
+    fn ui_properties_section(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new(self.i18n.tr("properties"))
+            .default_open(false)
+            .show(ui, |ui| {
+                let meta = &mut self.nbl_meta;
+                egui::Grid::new("properties_grid")
+                    .num_columns(2)
+                    .spacing([8.0, 4.0])
+                    .show(ui, |ui| {
+                        ui.label(self.i18n.tr("author"));
+                        ui.text_edit_singleline(&mut meta.author);
+                        ui.end_row();
+                        ui.label(self.i18n.tr("meta_description"));
+                        ui.add(egui::TextEdit::multiline(&mut meta.description).desired_rows(3));
+                        ui.end_row();
+                        ui.label(self.i18n.tr("meta_tags"));
+                        ui.text_edit_singleline(&mut self.nbl_meta_tags)
+                            .on_hover_text(self.i18n.tr("meta_tags_hint"));
+                        ui.end_row();
+                        ui.label(self.i18n.tr("meta_created_at"));
+                        ui.label(&meta.created_at);
+                        ui.end_row();
+                    });
+
+                ui.add_space(4.0);
+                let Some(path) = self.player.file_path.clone() else {
+                    return;
+                };
+                if ui.button(self.i18n.tr("meta_save")).clicked() {
+                    meta.tags = self
+                        .nbl_meta_tags
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|t| !t.is_empty())
+                        .map(str::to_string)
+                        .collect();
+                    if meta.created_at.is_empty() {
+                        meta.created_at = crate::player::utc_timestamp_now();
+                    }
+                    if let Err(e) = crate::player::save_meta(&path, meta) {
+                        self.error_msg = Some(format!("Save Failed: {}", e));
+                    }
+                }
+            });
+    }
+
+    fn ui_selection_section(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new(self.i18n.tr("transform_selection"))
+            .default_open(false)
+            .show(ui, |ui| {
+                ui.label(egui::RichText::new(self.i18n.tr("transform_selection_desc")).weak());
+                ui.horizontal(|ui| {
+                    ui.label(format!(
+                        "{}: {}",
+                        self.i18n.tr("selected_count"),
+                        self.selected_ids.len()
+                    ));
+                    if ui.button(self.i18n.tr("clear_selection")).clicked() {
+                        self.selected_ids.clear();
+                    }
+                });
+
+                egui::Grid::new("selection_transform_grid")
+                    .num_columns(2)
+                    .spacing([8.0, 4.0])
+                    .show(ui, |ui| {
+                        ui.label(self.i18n.tr("translate_offset"));
+                        ui.horizontal(|ui| {
+                            for v in &mut self.edit.selection_translate {
+                                ui.add(egui::DragValue::new(v).speed(0.05));
+                            }
+                        });
+                        ui.end_row();
+
+                        ui.label(self.i18n.tr("cr_rotation"));
+                        ui.horizontal(|ui| {
+                            for v in &mut self.edit.selection_rotate {
+                                ui.add(egui::DragValue::new(v).speed(1.0).suffix("°"));
+                            }
+                        });
+                        ui.end_row();
+
+                        ui.label(self.i18n.tr("position_scale"));
+                        ui.add(
+                            egui::DragValue::new(&mut self.edit.selection_scale)
+                                .speed(0.01)
+                                .clamp_range(0.01..=100.0),
+                        );
+                        ui.end_row();
+                    });
+
+                ui.horizontal(|ui| {
+                    let can_apply = !self.selected_ids.is_empty();
+                    if ui
+                        .add_enabled(
+                            can_apply,
+                            egui::Button::new(self.i18n.tr("apply_selection")),
+                        )
+                        .clicked()
+                    {
+                        self.apply_selection_transform();
+                    }
+                    if self.edit.decoded_frames.is_some() {
+                        if ui.button(self.i18n.tr("save_edited")).clicked() {
+                            self.save_decoded_frames();
+                        }
+                        if ui.button(self.i18n.tr("discard_edits")).clicked() {
+                            self.edit.decoded_frames = None;
+                        }
+                    }
+                });
+            });
+    }
+
+    /// In `Trail` mode, line segments from each particle of `current` back
+    /// through its positions in up to `history_frames` earlier frames, in
+    /// the line vertex layout. A particle's trail stops at the first frame
+    /// it is missing from. The history is reset on jumps, as for
+    /// `blend_motion_frames`.
+    fn prepare_trail_lines(&mut self, current: &[Particle]) -> Vec<f32> {
+        let RenderMode::Trail {
+            history_frames,
+            tail_alpha_factor,
+        } = self.render_mode
+        else {
+            self.trail_history.clear();
+            return Vec::new();
+        };
+        let frame = self.player.current_frame_idx;
+        match self.trail_history.back() {
+            Some((last, _)) if *last == frame => {
+                self.trail_history.pop_back();
+            }
+            Some((last, _)) if *last + 1 == frame => {}
+            _ => self.trail_history.clear(),
+        }
+        self.trail_history
+            .push_back((frame, current.iter().map(|p| (p.id, p.pos)).collect()));
+        while self.trail_history.len() > history_frames.max(1) as usize + 1 {
+            self.trail_history.pop_front();
+        }
+
+        let mut lines = Vec::new();
+        for p in current {
+            let [r, g, b, a] = p.color.map(|c| c as f32 / 255.0);
+            let mut from = p.pos;
+            let mut alpha = a;
+            // Newest to oldest, skipping the current frame itself.
+            for (_, positions) in self.trail_history.iter().rev().skip(1) {
+                let Some(&to) = positions.get(&p.id) else {
+                    break;
+                };
+                let next_alpha = alpha * tail_alpha_factor;
+                lines.extend_from_slice(&[from[0], from[1], from[2], r, g, b, alpha]);
+                lines.extend_from_slice(&[to[0], to[1], to[2], r, g, b, next_alpha]);
+                from = to;
+                alpha = next_alpha;
+            }
+        }
+        lines
+    }
+
+    /// Particles of the current frame, taken from the in-memory edit if one
+    /// exists so that selection transforms are visible straight away.
+    fn viewport_particles(&self) -> Vec<Particle> {
+        let frame = self.player.current_frame_idx.max(0) as usize;
+        match self.edit.decoded_frames.as_ref().and_then(|f| f.get(frame)) {
+            Some(frame) => frame.clone(),
+            None => self.player.particles.values().cloned().collect(),
+        }
+    }
+
+    /// In `FrameBlend(n)` mode, draw the previous `n` frames behind `current`
+    /// with alpha falling off as `1/n`, `1/n * (1 - 1/n)`, … The history is
+    /// reset whenever playback jumps instead of advancing by one frame.
+    fn blend_motion_frames(&mut self, current: Vec<f32>) -> Vec<f32> {
+        let PlaybackMode::FrameBlend(n) = self.playback_mode else {
+            self.blend_history.clear();
+            return current;
+        };
+        let frame = self.player.current_frame_idx;
+        match self.blend_history.back() {
+            Some((last, _)) if *last == frame => {
+                self.blend_history.pop_back();
+            }
+            Some((last, _)) if *last + 1 == frame => {}
+            _ => self.blend_history.clear(),
+        }
+        self.blend_history.push_back((frame, current));
+        let n = n.max(1) as usize;
+        while self.blend_history.len() > n + 1 {
+            self.blend_history.pop_front();
+        }
+
+        let decay = 1.0 - 1.0 / n as f32;
+        let trail = self.blend_history.len() - 1;
+        let mut data = Vec::with_capacity(self.blend_history.iter().map(|(_, d)| d.len()).sum());
+        for (i, (_, frame_data)) in self.blend_history.iter().enumerate() {
+            // 0 for the current frame, counting up into the past.
+            let age = trail - i;
+            let weight = if age == 0 {
+                1.0
+            } else {
+                decay.powi(age as i32 - 1) / n as f32
+            };
+            for p in frame_data.chunks_exact(FLOATS_PER_PARTICLE) {
+                data.extend_from_slice(p);
+                let alpha = data.len() - FLOATS_PER_PARTICLE + 6;
+                data[alpha] *= weight;
+            }
+        }
+        data
+    }
+
+    /// Toggle the particle nearest to `pos` (in screen space) in the selection.
+    /// Without shift the selection is replaced; clicking empty space clears it.
+    fn pick_particle(
+        &mut self,
+        particles: &[Particle],
+        rect: egui::Rect,
+        pos: egui::Pos2,
+        shift: bool,
+    ) {
+        const PICK_RADIUS: f32 = 8.0;
+        let mvp = self.calculate_mvp(rect.width() / rect.height());
+        let mut best: Option<(i32, f32)> = None;
+        for p in particles {
+            let Some((screen, depth)) = project_to_screen(mvp, rect, p.pos) else {
+                continue;
+            };
+            if screen.distance(pos) > PICK_RADIUS {
+                continue;
+            }
+            if best.is_none_or(|(_, d)| depth < d) {
+                best = Some((p.id, depth));
+            }
+        }
+
+        let Some((id, _)) = best else {
+            if !shift {
+                self.selected_ids.clear();
+            }
+            return;
+        };
+        if shift {
+            if !self.selected_ids.remove(&id) {
+                self.selected_ids.insert(id);
+            }
+        } else {
+            let was_only = self.selected_ids.len() == 1 && self.selected_ids.contains(&id);
+            self.selected_ids.clear();
+            if !was_only {
+                self.selected_ids.insert(id);
+            }
+        }
+    }
+
+    /// Add every particle whose screen position falls inside the lasso.
+    fn select_in_lasso(&mut self, particles: &[Particle], rect: egui::Rect) {
+        if self.lasso_points.len() < 3 {
+            return;
+        }
+        let mvp = self.calculate_mvp(rect.width() / rect.height());
+        for p in particles {
+            if let Some((screen, _)) = project_to_screen(mvp, rect, p.pos) {
+                if point_in_polygon(screen, &self.lasso_points) {
+                    self.selected_ids.insert(p.id);
+                }
+            }
+        }
+    }
+
+    /// Draw an arrow from each particle towards its position in the next
+    /// frame, scaled by `velocity_scale`. Only the first
+    /// `MAX_VELOCITY_ARROWS` are drawn.
+    fn draw_velocity_arrows(&mut self, ui: &egui::Ui, particles: &[Particle], rect: egui::Rect) {
+        let frame = self.player.current_frame_idx;
+        if self.velocity_next.as_ref().map(|(f, _)| *f) != Some(frame) {
+            let next_idx = (frame + 1).max(0) as usize;
+            let next = match self.edit.decoded_frames.as_ref() {
+                Some(frames) => frames.get(next_idx).cloned().unwrap_or_default(),
+                None => self.player.peek_frame(next_idx as u32).unwrap_or_default(),
+            };
+            let positions = next.into_iter().map(|p| (p.id, p.pos)).collect();
+            self.velocity_next = Some((frame, positions));
+        }
+        let Some((_, next)) = &self.velocity_next else {
+            return;
+        };
+
+        let mvp = self.calculate_mvp(rect.width() / rect.height());
+        let painter = ui.painter_at(rect);
+        let stroke = egui::Stroke::new(1.0, egui::Color32::from_rgb(80, 220, 255));
+        let mut drawn = 0;
+        let mut skipped = 0;
+        for p in particles {
+            let Some(to) = next.get(&p.id) else {
+                continue;
+            };
+            let delta = [to[0] - p.pos[0], to[1] - p.pos[1], to[2] - p.pos[2]];
+            if delta == [0.0; 3] {
+                continue;
+            }
+            if drawn >= MAX_VELOCITY_ARROWS {
+                skipped += 1;
+                continue;
+            }
+            let end = [
+                p.pos[0] + delta[0] * self.velocity_scale,
+                p.pos[1] + delta[1] * self.velocity_scale,
+                p.pos[2] + delta[2] * self.velocity_scale,
+            ];
+            if let (Some((a, _)), Some((b, _))) = (
+                project_to_screen(mvp, rect, p.pos),
+                project_to_screen(mvp, rect, end),
+            ) {
+                painter.arrow(a, b - a, stroke);
+                drawn += 1;
+            }
+        }
+        if skipped > 0 {
+            painter.text(
+                rect.left_top() + egui::vec2(10.0, 50.0),
+                egui::Align2::LEFT_TOP,
+                format!("{} ({})", self.i18n.tr("too_many_arrows"), skipped),
+                egui::FontId::proportional(14.0),
+                egui::Color32::from_rgb(255, 200, 80),
+            );
+        }
+    }
+
+    fn prepare_selection_highlight(&self, particles: &[Particle]) -> Vec<f32> {
+        if self.selected_ids.is_empty() {
+            return Vec::new();
+        }
+        let mut data = Vec::with_capacity(self.selected_ids.len() * FLOATS_PER_PARTICLE);
+        for p in particles
+            .iter()
+            .filter(|p| self.selected_ids.contains(&p.id))
+        {
+            data.extend_from_slice(&p.pos);
+            data.extend_from_slice(&[1.0, 0.85, 0.2, 1.0]);
+            data.extend_from_slice(&[p.size, p.tex_id as f32, p.seq_index as f32]);
+        }
+        data
+    }
+
+    /// Apply the selection transform to every frame, decoding the file into
+    /// `edit.decoded_frames` first if needed.
+    fn apply_selection_transform(&mut self) {
+        if self.edit.decoded_frames.is_none() {
+            let Some(path) = self.player.file_path.clone() else {
+                return;
+            };
+            match self.load_preview_frames_from_nbl(&path) {
+                Ok(frames) => self.edit.decoded_frames = Some(frames),
+                Err(e) => {
+                    self.error_msg = Some(format!("Load Failed: {}", e));
+                    return;
+                }
+            }
+        }
+        let Some(frames) = self.edit.decoded_frames.as_mut() else {
+            return;
+        };
+        player::transform_selection(
+            frames,
+            &self.selected_ids,
+            self.edit.selection_translate,
+            self.edit.selection_rotate,
+            self.edit.selection_scale,
+        );
+        if let Some(mut header) = self.player.header.clone() {
+            let (bbox_min, bbox_max) = player::recalculate_bbox(frames);
+            header.bbox_min = bbox_min;
+            header.bbox_max = bbox_max;
+            header.total_frames = frames.len() as u32;
+            self.edit.edited_header = Some(header);
+        }
+    }
+
+    fn save_decoded_frames(&mut self) {
+        let (Some(frames), Some(header)) = (&self.edit.decoded_frames, &self.edit.edited_header)
+        else {
+            return;
+        };
+        if let Some(path) = rfd::FileDialog::new()
+            .add_filter("Nebula", &["nbl"][..])
+            .set_file_name("export.nbl")
+            .save_file()
+        {
+            match self
+                .player
+                .save_file(&path, header, &self.player.textures, frames)
+            {
+                Ok(_) => self.error_msg = None,
+                Err(e) => self.error_msg = Some(format!("Save Failed: {}", e)),
+            }
+        }
+    }
+
+    fn handle_load_compare(&mut self) {
+        if let Some(path) = rfd::FileDialog::new()
+            .add_filter("Nebula", &["nbl"][..])
+            .pick_file()
+        {
+            let mut secondary = PlayerState::default();
+            match secondary.load_file_mmap(&path) {
+                Ok(_) => {
+                    self.compare.secondary_player = Some(secondary);
+                    self.compare.secondary_path = Some(path);
+                    self.compare.enabled = true;
+                    self.error_msg = None;
+                }
+                Err(e) => self.error_msg = Some(format!("Load Failed: {}", e)),
+            }
+        }
+    }
+
+    /// Keep the comparison file on the same frame as the primary one.
+    fn sync_compare_frame(&mut self) {
+        let target = self.player.current_frame_idx.max(0) as u32;
+        if let Some(secondary) = &mut self.compare.secondary_player {
+            let last = secondary
+                .header
+                .as_ref()
+                .map_or(0, |h| h.total_frames.saturating_sub(1));
+            let target = target.min(last);
+            if secondary.current_frame_idx != target as i32 {
+                let _ = secondary.seek_to(target);
+            }
+        }
+    }
+
+    /// Secondary particles with their colour replaced by the overlay colour,
+    /// followed by the adjacent-frame diff when that is enabled.
+    fn prepare_compare_overlay(&self) -> Vec<f32> {
+        let mut data = Vec::new();
+        let mut push = |p: &Particle, [r, g, b, a]: [f32; 4]| {
+            data.extend_from_slice(&p.pos);
+            data.extend_from_slice(&[r, g, b, a]);
+            data.extend_from_slice(&[p.size, p.tex_id as f32, p.seq_index as f32]);
+        };
+        if let Some(secondary) = self.compare.secondary_player.as_ref() {
+            if self.compare.enabled {
+                let [r, g, b, a] = self.compare.overlay_color.map(|c| c as f32 / 255.0);
+                for p in secondary.particles.values() {
+                    push(p, [r, g, b, a * p.color[3] as f32 / 255.0]);
+                }
+            }
+        }
+        if let Some((_, diff)) = self
+            .compare
+            .frame_diff_cache
+            .as_ref()
+            .filter(|_| self.compare.frame_diff)
+        {
+            let rgba = |c: egui::Color32| egui::Rgba::from(c).to_array();
+            for p in &diff.added {
+                push(p, rgba(FRAME_DIFF_ADDED));
+            }
+            for p in &diff.removed {
+                push(p, rgba(FRAME_DIFF_REMOVED));
+            }
+            for (_, p) in &diff.moved {
+                push(p, rgba(FRAME_DIFF_MOVED));
+            }
+        }
+        data
+    }
+
+    /// Diff the viewport particles against the previous frame, once per frame.
+    fn update_frame_diff(&mut self, particles: &[Particle]) {
+        let frame = self.player.current_frame_idx;
+        if self.compare.frame_diff_cache.as_ref().map(|(f, _)| *f) == Some(frame) {
+            return;
+        }
+        let previous = if frame <= 0 {
+            Vec::new()
+        } else {
+            let prev_idx = (frame - 1) as usize;
+            match self.edit.decoded_frames.as_ref() {
+                Some(frames) => frames.get(prev_idx).cloned().unwrap_or_default(),
+                None => self.player.peek_frame(prev_idx as u32).unwrap_or_default(),
+            }
+        };
+        let diff = crate::player::compare_frames(&previous, particles);
+        self.compare.frame_diff_cache = Some((frame, diff));
+    }
+
+    /// Old-to-new lines for particles that moved since the previous frame.
+    fn draw_frame_diff_lines(&self, ui: &egui::Ui, rect: egui::Rect) {
+        let Some((_, diff)) = &self.compare.frame_diff_cache else {
+            return;
+        };
+        let mvp = self.calculate_mvp(rect.width() / rect.height());
+        let painter = ui.painter_at(rect);
+        let stroke = egui::Stroke::new(1.0, FRAME_DIFF_MOVED);
+        for (old, new) in diff.moved.iter().take(MAX_VELOCITY_ARROWS) {
+            if let (Some((a, _)), Some((b, _))) = (
+                project_to_screen(mvp, rect, old.pos),
+                project_to_screen(mvp, rect, new.pos),
+            ) {
+                painter.line_segment([a, b], stroke);
+            }
+        }
+    }
+}
+
+/// Small GPU texture of the image at `path` for the Textures panel.
+fn load_thumbnail(ctx: &egui::Context, path: &str) -> Option<egui::TextureHandle> {
+    let img = image::open(path).ok()?.thumbnail(64, 64).to_rgba8();
+    let size = [img.width() as usize, img.height() as usize];
+    let color = egui::ColorImage::from_rgba_unmultiplied(size, img.as_raw());
+    Some(ctx.load_texture(format!("texture_thumb:{}", path), color, Default::default()))
+}
+
+const FRAME_DIFF_ADDED: egui::Color32 = egui::Color32::from_rgb(80, 220, 80);
+const FRAME_DIFF_REMOVED: egui::Color32 = egui::Color32::from_rgb(230, 60, 60);
+const FRAME_DIFF_MOVED: egui::Color32 = egui::Color32::from_rgb(240, 210, 60);
+
+/// Screen position and NDC depth of a world-space point inside `rect`.
+fn project_to_screen(mvp: [f32; 16], rect: egui::Rect, pos: [f32; 3]) -> Option<(egui::Pos2, f32)> {
+    let ndc = crate::math::project_to_ndc(mvp, pos)?;
+    let screen = egui::pos2(
+        rect.left() + (ndc[0] + 1.0) * 0.5 * rect.width(),
+        rect.top() + (1.0 - ndc[1]) * 0.5 * rect.height(),
+    );
+    Some((screen, ndc[2]))
+}
+
+/// Upper bound on velocity arrows (and frame-diff lines) drawn per frame.
+const MAX_VELOCITY_ARROWS: usize = 5000;
+
+/// Even-odd rule point-in-polygon test.
+fn point_in_polygon(p: egui::Pos2, polygon: &[egui::Pos2]) -> bool {
+    let mut inside = false;
+    let mut j = polygon.len() - 1;
+    for i in 0..polygon.len() {
+        let (a, b) = (polygon[i], polygon[j]);
+        if (a.y > p.y) != (b.y > p.y) && p.x < (b.x - a.x) * (p.y - a.y) / (b.y - a.y) + a.x {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
 }
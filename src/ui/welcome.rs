@@ -1,4 +1,4 @@
-use super::app::{AppMode, NebulaToolsApp};
+use super::app::{read_project, AppMode, NebulaToolsApp};
 use eframe::egui;
 
 impl NebulaToolsApp {
@@ -33,6 +33,34 @@ impl NebulaToolsApp {
                     .strong(),
                 );
                 ui.add_space(40.0);
+                if let Some(path) = self.recoverable_autosave.clone() {
+                    ui.horizontal(|ui| {
+                        let total_width = 460.0;
+                        ui.add_space((ui.available_width() - total_width).max(0.0) / 2.0);
+                        ui.label(
+                            egui::RichText::new(self.i18n.tr("recover_project_prompt"))
+                                .size(16.0)
+                                .color(egui::Color32::from_rgb(255, 200, 50)),
+                        );
+                        if ui.button(self.i18n.tr("recover")).clicked() {
+                            self.recoverable_autosave = None;
+                            match read_project(&path) {
+                                Ok(project) => self.apply_project(project),
+                                Err(e) => {
+                                    self.error_msg = Some(format!(
+                                        "{}: {}",
+                                        self.i18n.tr("project_load_failed"),
+                                        e
+                                    ))
+                                }
+                            }
+                        }
+                        if ui.button(self.i18n.tr("dismiss")).clicked() {
+                            self.recoverable_autosave = None;
+                        }
+                    });
+                    ui.add_space(20.0);
+                }
                 ui.horizontal(|ui| {
                     let total_width = 860.0;
                     ui.add_space((ui.available_width() - total_width) / 2.0);
@@ -0,0 +1,48 @@
+//! `wasm-bindgen` entry points so the expression engine can run in a web
+//! worker. Track generation and the UI are not exposed.
+use crate::particleex::{self, ExprContext, Stmt, Value};
+use wasm_bindgen::prelude::*;
+
+/// A compiled expression, reusable across `eval_expr` calls.
+#[wasm_bindgen]
+pub struct CompiledExpr {
+    stmts: Vec<Stmt>,
+}
+
+/// Wraps `particleex::compile_expr`; throws on empty or unparsable source.
+#[wasm_bindgen]
+pub fn compile_expr(src: &str) -> Result<CompiledExpr, JsError> {
+    particleex::check_expr(src).map_err(|e| JsError::new(&e))?;
+    particleex::compile_expr(src)
+        .map(|stmts| CompiledExpr { stmts })
+        .ok_or_else(|| JsError::new("empty expression"))
+}
+
+/// Wraps `particleex::exec_stmts`: sets `names[i] = values[i]`, runs the
+/// expression and returns the final value of every name, in order.
+#[wasm_bindgen]
+pub fn eval_expr(expr: &CompiledExpr, names: Vec<String>, values: Vec<f64>) -> Vec<f64> {
+    let mut ctx = ExprContext::new();
+    for (name, value) in names.iter().zip(values) {
+        ctx.set(name, Value::Num(value));
+    }
+    particleex::exec_stmts(&expr.stmts, &mut ctx);
+    names.iter().map(|name| ctx.get(name).as_num()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evaluates_compiled_expressions() {
+        let Ok(expr) = compile_expr("vx = x * 2; vy = vx + 1") else {
+            panic!("expression should compile");
+        };
+        let names = vec!["x".to_string(), "vx".to_string(), "vy".to_string()];
+        assert_eq!(
+            eval_expr(&expr, names, vec![1.5, 0.0, 0.0]),
+            vec![1.5, 3.0, 4.0]
+        );
+    }
+}